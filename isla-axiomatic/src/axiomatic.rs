@@ -37,7 +37,12 @@ use std::fmt;
 use isla_lib::concrete::BV;
 use isla_lib::config::ISAConfig;
 use isla_lib::ir::{Name, SharedState, Val};
-use isla_lib::smt::{EvPath, Event};
+use isla_lib::primop::smt_value;
+use isla_lib::simplify::renumber_event;
+use isla_lib::smt::smtlib::{Def, Exp, Ty};
+use isla_lib::smt::{EvPath, Event, Sym};
+
+use crate::sexp::{InterpretError, SexpVal};
 
 pub type ThreadId = usize;
 
@@ -84,21 +89,34 @@ fn increment_index(index: &mut [usize], max_index: &[usize], carry: usize) -> bo
     }
 }
 
-impl<'ev, B: BV> Iterator for Candidates<'ev, B> {
-    type Item = Vec<&'ev [Event<B>]>;
+/// A candidate execution, with the index of the path taken by each
+/// thread
+pub type CandidateWithPaths<'ev, B> = (Vec<usize>, Vec<&'ev [Event<B>]>);
 
-    fn next(&mut self) -> Option<Self::Item> {
+impl<'ev, B: BV> Candidates<'ev, B> {
+    /// Like `next`, but also returns the index of the path taken by
+    /// each thread in the candidate.
+    pub fn next_with_paths(&mut self) -> Option<CandidateWithPaths<'ev, B>> {
         if self.out_of_bounds {
             None
         } else {
+            let paths = self.index.clone();
             let mut result = Vec::with_capacity(self.threads.len());
             self.threads.iter().zip(self.index.iter()).for_each(|(thread, i)| result.push(thread[*i].as_ref()));
             self.out_of_bounds = increment_index(&mut self.index, &self.max_index, 0);
-            Some(result)
+            Some((paths, result))
         }
     }
 }
 
+impl<'ev, B: BV> Iterator for Candidates<'ev, B> {
+    type Item = Vec<&'ev [Event<B>]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_paths().map(|(_, candidate)| candidate)
+    }
+}
+
 pub struct Pairs<'a, A> {
     index: (usize, usize),
     slice: &'a [A],
@@ -147,6 +165,12 @@ pub struct AxEvent<'a, B> {
     pub base: &'a Event<B>,
     /// Is the event an instruction fetch (i.e. base is ReadMem with an ifetch read_kind)
     pub is_ifetch: bool,
+    /// Is the event a read made by a translation table walk (i.e.
+    /// base is ReadMem with the read_kind from the MMU config)
+    pub is_translate: bool,
+    /// For events in a combined candidate (see `SymbolicCandidates`),
+    /// a boolean variable that is true if the event's path is chosen
+    pub guard: Option<Sym>,
 }
 
 impl<'a, B: BV> AxEvent<'a, B> {
@@ -172,12 +196,21 @@ impl<'a, B: BV> AxEvent<'a, B> {
             _ => None,
         }
     }
+
+    /// The number of bytes accessed by a memory read or write
+    pub fn bytes(&self) -> Option<u32> {
+        match self.base {
+            Event::ReadMem { bytes, .. } | Event::WriteMem { bytes, .. } => Some(*bytes),
+            _ => None,
+        }
+    }
 }
 
 pub mod relations {
     use std::collections::HashMap;
 
     use isla_lib::concrete::BV;
+    use isla_lib::smt::{Event, ExceptionKind};
 
     use super::AxEvent;
     use crate::footprint_analysis::{addr_dep, ctrl_dep, data_dep, rmw_dep, Footprint};
@@ -187,7 +220,11 @@ pub mod relations {
     }
 
     pub fn is_read<B: BV>(ev: &AxEvent<B>) -> bool {
-        !ev.is_ifetch && ev.base.is_memory_read()
+        !ev.is_ifetch && !ev.is_translate && ev.base.is_memory_read()
+    }
+
+    pub fn is_translate<B: BV>(ev: &AxEvent<B>) -> bool {
+        ev.is_translate
     }
 
     pub fn is_barrier<B: BV>(ev: &AxEvent<B>) -> bool {
@@ -202,6 +239,22 @@ pub mod relations {
         ev.base.is_cache_op()
     }
 
+    pub fn is_exception_entry<B: BV>(ev: &AxEvent<B>) -> bool {
+        matches!(ev.base, Event::Exception(ExceptionKind::Taken))
+    }
+
+    pub fn is_exception_return<B: BV>(ev: &AxEvent<B>) -> bool {
+        matches!(ev.base, Event::Exception(ExceptionKind::Return))
+    }
+
+    pub fn is_tag_read<B: BV>(ev: &AxEvent<B>) -> bool {
+        matches!(ev.base, Event::ReadTag { .. })
+    }
+
+    pub fn is_tag_write<B: BV>(ev: &AxEvent<B>) -> bool {
+        matches!(ev.base, Event::WriteTag { .. })
+    }
+
     // TODO:
     pub fn amo<B: BV>(_ev1: &AxEvent<B>, _ev2: &AxEvent<B>) -> bool {
         false
@@ -324,6 +377,11 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
         };
 
         let rk_ifetch = shared_state.enum_member(isa_config.ifetch_read_kind).expect("Invalid ifetch read kind");
+        let rk_translate = isa_config
+            .mmu
+            .as_ref()
+            .and_then(|mmu| mmu.read_kind)
+            .map(|rk| shared_state.enum_member(rk).expect("Invalid translation read kind"));
 
         for (tid, thread) in candidate.iter().enumerate() {
             for (po, cycle) in thread.split(|ev| ev.is_cycle()).skip(1).enumerate() {
@@ -357,6 +415,15 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
                         Event::CacheOp { .. } => {
                             cycle_events.push((tid, eid, format!("C{}_{}_{}", po, eid, tid), event, false))
                         }
+                        Event::Exception(_) => {
+                            cycle_events.push((tid, eid, format!("E{}_{}_{}", po, eid, tid), event, false))
+                        }
+                        Event::ReadTag { .. } => {
+                            cycle_events.push((tid, eid, format!("RT{}_{}_{}", po, eid, tid), event, false))
+                        }
+                        Event::WriteTag { .. } => {
+                            cycle_events.push((tid, eid, format!("WT{}_{}_{}", po, eid, tid), event, false))
+                        }
                         Event::WriteReg(reg, _, val) => {
                             exec.final_writes.insert((*reg, tid), val);
                         }
@@ -375,6 +442,8 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
                             name,
                             base: ev,
                             is_ifetch,
+                            is_translate: matches!(rk_translate, Some(rk) if ev.has_read_kind(rk)),
+                            guard: None,
                         })
                     } else if !ev.has_read_kind(rk_ifetch) {
                         // Unless we have a single failing ifetch
@@ -388,6 +457,225 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
     }
 }
 
+/// All the paths through each thread of a litmus test combined into
+/// a single candidate execution, where the path taken by each thread
+/// is chosen by the SMT solver rather than enumerated.
+///
+/// Each path is treated as a separate thread of the combined
+/// candidate, and is renumbered so its variables are disjoint from
+/// every other path. Each path has a boolean guard variable which is
+/// true when the path is chosen, and the assertions the path makes
+/// only need to hold when it is. `smt_of_candidate` guards the events
+/// of each path in the same way, so events from paths which are not
+/// chosen take no part in the execution.
+pub struct SymbolicCandidates<B> {
+    paths: Vec<EvPath<B>>,
+    /// The thread each path belongs to, and its index within that
+    /// thread's paths
+    path_threads: Vec<(ThreadId, usize)>,
+    guards: Vec<Sym>,
+    final_values: HashMap<(Name, ThreadId), Val<B>>,
+    num_threads: usize,
+    num_candidates: usize,
+}
+
+/// Guard an expression by a boolean variable
+fn guarded(guard: Sym, exp: Exp) -> Exp {
+    Exp::Or(Box::new(Exp::Not(Box::new(Exp::Var(guard)))), Box::new(exp))
+}
+
+/// The types of the variables and functions declared in a path
+#[allow(clippy::type_complexity)]
+fn path_types<B: BV>(path: &[Event<B>]) -> (HashMap<Sym, Ty>, HashMap<Sym, (Vec<Ty>, Ty)>) {
+    let mut tcx = HashMap::new();
+    let mut ftcx = HashMap::new();
+    for event in path {
+        match event {
+            Event::Smt(Def::DeclareConst(v, ty)) => {
+                tcx.insert(*v, ty.clone());
+            }
+            Event::Smt(Def::DeclareFun(v, arg_tys, result_ty)) => {
+                ftcx.insert(*v, (arg_tys.clone(), result_ty.clone()));
+            }
+            Event::Smt(Def::DefineConst(v, exp)) => {
+                if let Some(ty) = exp.infer(&tcx, &ftcx) {
+                    tcx.insert(*v, ty);
+                }
+            }
+            _ => (),
+        }
+    }
+    (tcx, ftcx)
+}
+
+/// The value of a register which a path does not write, as used for
+/// final register values when candidates are enumerated (see
+/// `smt_events::register_value`).
+fn unwritten(width: u32) -> Exp {
+    if width <= 64 {
+        Exp::Bits64(0xDEAD & (u64::MAX >> (64 - width)), width)
+    } else {
+        Exp::ZeroExtend(width - 64, Box::new(Exp::Bits64(0xDEAD, 64)))
+    }
+}
+
+impl<B: BV> SymbolicCandidates<B> {
+    /// Combine the paths through each thread. The final values of
+    /// the registers in `final_regs`, which should be those used in
+    /// the final assertion, are defined by the path chosen for their
+    /// thread.
+    pub fn new(threads: &[Vec<EvPath<B>>], final_regs: &[(Name, ThreadId)]) -> Result<Self, String> {
+        let num_paths: usize = threads.iter().map(Vec::len).sum();
+        // Variables in path p are renumbered to id * total + p, so the
+        // ids id * total + num_paths are free for our own variables.
+        let total = num_paths as u32 + 1;
+        let extra = |i: usize| Sym::from_u32(i as u32 * total + num_paths as u32);
+
+        let guards: Vec<Sym> = (0..num_paths).map(extra).collect();
+        let mut paths: Vec<EvPath<B>> = Vec::with_capacity(num_paths);
+        let mut path_threads = Vec::with_capacity(num_paths);
+        let mut final_values = HashMap::new();
+
+        for (tid, thread) in threads.iter().enumerate() {
+            let first = paths.len();
+            for (i, path) in thread.iter().enumerate() {
+                let p = paths.len();
+                let mut events = vec![Event::Smt(Def::DeclareConst(guards[p], Ty::Bool))];
+                for event in path {
+                    let mut event = event.clone();
+                    renumber_event(&mut event, p as u32, total);
+                    if let Event::Smt(Def::Assert(exp)) = event {
+                        event = Event::Smt(Def::Assert(guarded(guards[p], exp)))
+                    }
+                    events.push(event)
+                }
+                paths.push(events);
+                path_threads.push((tid, i))
+            }
+
+            if first == paths.len() {
+                continue;
+            }
+            let thread_guards = &guards[first..paths.len()];
+
+            let mut constraints = Vec::new();
+            // Exactly one path must be chosen for each thread
+            let some_path = thread_guards
+                .iter()
+                .fold(Exp::Bool(false), |exp, guard| Exp::Or(Box::new(Exp::Var(*guard)), Box::new(exp)));
+            constraints.push(Def::Assert(some_path));
+            for (i, guard1) in thread_guards.iter().enumerate() {
+                for guard2 in &thread_guards[i + 1..] {
+                    constraints.push(Def::Assert(Exp::Not(Box::new(Exp::And(
+                        Box::new(Exp::Var(*guard1)),
+                        Box::new(Exp::Var(*guard2)),
+                    )))))
+                }
+            }
+
+            for (n, (reg, _)) in final_regs.iter().enumerate().filter(|(_, (_, reg_tid))| *reg_tid == tid) {
+                let mut values = Vec::new();
+                for path in &paths[first..] {
+                    // As in `ExecutionInfo::from`, only writes after the first cycle are counted
+                    let value = path
+                        .split(|ev| ev.is_cycle())
+                        .skip(1)
+                        .flatten()
+                        .filter_map(|ev| match ev {
+                            Event::WriteReg(r, _, value) if r == reg => Some(value),
+                            _ => None,
+                        })
+                        .last();
+                    values.push(match value {
+                        Some(value) => {
+                            let exp = smt_value(value).map_err(|e| format!("Final value of register: {}", e))?;
+                            let (tcx, ftcx) = path_types(path);
+                            match exp.infer(&tcx, &ftcx) {
+                                Some(Ty::BitVec(width)) if width > 0 => Some((exp, width)),
+                                _ => return Err("Final value of register is not a bitvector".to_string()),
+                            }
+                        }
+                        None => None,
+                    })
+                }
+
+                // Registers not written by any path keep the 64-bit placeholder
+                let mut widths = values.iter().flatten().map(|(_, width)| *width);
+                let width = widths.next().unwrap_or(64);
+                if widths.any(|w| w != width) {
+                    return Err("Final value of register has different widths in different paths".to_string());
+                }
+
+                let v = extra(num_paths + n);
+                constraints.push(Def::DeclareConst(v, Ty::BitVec(width)));
+                for (value, guard) in values.into_iter().zip(thread_guards) {
+                    let value = match value {
+                        Some((exp, _)) => exp,
+                        None => unwritten(width),
+                    };
+                    constraints.push(Def::Assert(guarded(*guard, Exp::Eq(Box::new(Exp::Var(v)), Box::new(value)))))
+                }
+                final_values.insert((*reg, tid), Val::Symbolic(v));
+            }
+
+            paths.last_mut().unwrap().extend(constraints.into_iter().map(Event::Smt))
+        }
+
+        Ok(SymbolicCandidates {
+            paths,
+            path_threads,
+            guards,
+            final_values,
+            num_threads: threads.len(),
+            num_candidates: if threads.is_empty() { 0 } else { threads.iter().map(Vec::len).product() },
+        })
+    }
+
+    /// The number of candidate executions that are combined
+    pub fn total(&self) -> usize {
+        self.num_candidates
+    }
+
+    /// The combined candidate, with each path as a separate thread
+    pub fn candidate(&self) -> Vec<&[Event<B>]> {
+        self.paths.iter().map(|path| path.as_ref()).collect()
+    }
+
+    /// Returns the thread a path (i.e. a thread of the combined
+    /// candidate) belongs to, and its index in that thread's paths.
+    pub fn path_thread(&self, path: usize) -> (ThreadId, usize) {
+        self.path_threads[path]
+    }
+
+    /// Create the execution info for the combined candidate returned
+    /// by `SymbolicCandidates::candidate`.
+    pub fn execution_info<'ev>(
+        &'ev self,
+        candidate: &'ev [&'ev [Event<B>]],
+        shared_state: &SharedState<B>,
+        isa_config: &ISAConfig<B>,
+    ) -> Result<ExecutionInfo<'ev, B>, CandidateError<B>> {
+        let mut exec = ExecutionInfo::from(candidate, shared_state, isa_config)?;
+        for ev in exec.events.iter_mut() {
+            ev.guard = Some(self.guards[ev.thread_id])
+        }
+        exec.final_writes = self.final_values.iter().map(|(reg, value)| (*reg, value)).collect();
+        Ok(exec)
+    }
+
+    /// Read the path chosen for each thread from a model of the
+    /// combined candidate.
+    pub fn chosen_paths(&self, model: &mut model::Model<B>) -> Result<Vec<usize>, InterpretError> {
+        let mut chosen = vec![0; self.num_threads];
+        for (guard, (tid, i)) in self.guards.iter().zip(self.path_threads.iter()) {
+            if let SexpVal::Bool(true) = model.interpret(&format!("v{}", guard), &[])? {
+                chosen[*tid] = *i
+            }
+        }
+        Ok(chosen)
+    }
+}
+
 /// This module defines utilites for parsing and interpreting the
 /// models returned by Z3 when invoked on the command line.
 pub mod model {
@@ -556,3 +844,82 @@ pub mod model {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use isla_lib::concrete::bitvector64::B64;
+    use isla_lib::ir::RETURN;
+
+    fn path(value: u64) -> EvPath<B64> {
+        vec![
+            Event::Smt(Def::DeclareConst(Sym::from_u32(0), Ty::Bool)),
+            Event::Smt(Def::Assert(Exp::Var(Sym::from_u32(0)))),
+            Event::Cycle,
+            Event::WriteReg(RETURN, vec![], Val::Bits(B64::from_u64(value))),
+        ]
+    }
+
+    #[test]
+    fn test_symbolic_candidates() {
+        let threads = vec![vec![path(1), path(2)], vec![path(3)]];
+        let symbolic = SymbolicCandidates::new(&threads, &[(RETURN, 0)]).unwrap();
+        assert_eq!(symbolic.total(), 2);
+        assert_eq!(symbolic.path_thread(1), (0, 1));
+        assert_eq!(symbolic.path_thread(2), (1, 0));
+
+        // Each path declares its own guard, and its variables and
+        // assertions are renumbered and guarded
+        let guard = |p: u32| Sym::from_u32(p * 4 + 3);
+        let candidate = symbolic.candidate();
+        for (p, path) in candidate.iter().enumerate() {
+            let p = p as u32;
+            assert!(matches!(path[0], Event::Smt(Def::DeclareConst(v, Ty::Bool)) if v == guard(p)));
+            assert!(matches!(path[1], Event::Smt(Def::DeclareConst(v, Ty::Bool)) if v == Sym::from_u32(p)));
+            assert!(
+                matches!(&path[2], Event::Smt(Def::Assert(exp)) if *exp == guarded(guard(p), Exp::Var(Sym::from_u32(p))))
+            );
+        }
+
+        // The final value of the register is defined by the chosen
+        // path of thread 0, and the constraints are added to its last
+        // path
+        let v = Sym::from_u32(15);
+        assert!(matches!(symbolic.final_values.get(&(RETURN, 0)), Some(Val::Symbolic(x)) if *x == v));
+        let defines_value = |path: &[Event<B64>], p: u32, value: u64| {
+            let exp = guarded(guard(p), Exp::Eq(Box::new(Exp::Var(v)), Box::new(Exp::Bits64(value, 64))));
+            path.iter().any(|ev| matches!(ev, Event::Smt(Def::Assert(e)) if *e == exp))
+        };
+        assert!(defines_value(candidate[1], 0, 1));
+        assert!(defines_value(candidate[1], 1, 2));
+        assert!(!candidate[2].iter().any(|ev| matches!(ev, Event::Smt(Def::DeclareConst(x, _)) if *x == v)))
+    }
+
+    #[test]
+    fn test_symbolic_candidates_width() {
+        // A 32-bit register written with a symbolic value by one path,
+        // and not written by the other
+        let x = Sym::from_u32(0);
+        let write: EvPath<B64> = vec![
+            Event::Smt(Def::DeclareConst(x, Ty::BitVec(32))),
+            Event::Cycle,
+            Event::WriteReg(RETURN, vec![], Val::Symbolic(x)),
+        ];
+        let threads = vec![vec![write, vec![Event::Cycle]]];
+        let symbolic = SymbolicCandidates::new(&threads, &[(RETURN, 0)]).unwrap();
+
+        let v = Sym::from_u32(8);
+        let candidate = symbolic.candidate();
+        assert!(candidate[1]
+            .iter()
+            .any(|ev| matches!(ev, Event::Smt(Def::DeclareConst(x, Ty::BitVec(32))) if *x == v)));
+        let unwritten = guarded(Sym::from_u32(5), Exp::Eq(Box::new(Exp::Var(v)), Box::new(Exp::Bits64(0xDEAD, 32))));
+        assert!(candidate[1].iter().any(|ev| matches!(ev, Event::Smt(Def::Assert(e)) if *e == unwritten)));
+
+        // Each path of a thread must agree on the register's width
+        let write = |width| vec![Event::Cycle, Event::WriteReg(RETURN, vec![], Val::Bits(B64::new(1, width)))];
+        assert!(SymbolicCandidates::new(&[vec![write(32)], vec![write(16)]], &[(RETURN, 0), (RETURN, 1)]).is_ok());
+        assert!(SymbolicCandidates::new(&[vec![write(32), write(16)]], &[(RETURN, 0)]).is_err())
+    }
+}
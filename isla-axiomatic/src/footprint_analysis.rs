@@ -38,23 +38,24 @@
 use crossbeam::queue::SegQueue;
 use serde::{Deserialize, Serialize};
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::error::Error;
 use std::fmt;
 use std::io::Write;
-use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Instant;
 
-use isla_lib::cache::{Cacheable, Cachekey};
+use isla_lib::cache::{Cache, Cacheable, Cachekey};
 use isla_lib::concrete::BV;
 use isla_lib::config::ISAConfig;
+use isla_lib::error::ExecError;
 use isla_lib::executor;
-use isla_lib::executor::LocalFrame;
+use isla_lib::executor::{Backtrace, LocalFrame};
 use isla_lib::ir::*;
 use isla_lib::log;
 use isla_lib::simplify::{EventReferences, Taints};
-use isla_lib::smt::{Accessor, EvPath, Event, Sym};
+use isla_lib::smt::{Accessor, EvPath, Event, Solver, Sym};
 use isla_lib::zencode;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -87,6 +88,10 @@ pub struct Footprint {
     is_exclusive: bool,
     /// A cache-op is any event with a CacheOp event
     is_cache_op: bool,
+    /// A tag load is any instruction with a ReadTag event
+    is_tag_load: bool,
+    /// A tag store is any instruction with a WriteTag event
+    is_tag_store: bool,
 }
 
 pub struct Footprintkey {
@@ -104,7 +109,7 @@ impl Cacheable for Footprint {
 }
 
 impl Footprint {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Footprint {
             write_data_taints: (HashSet::new(), false),
             mem_addr_taints: (HashSet::new(), false),
@@ -118,6 +123,8 @@ impl Footprint {
             is_branch: false,
             is_exclusive: false,
             is_cache_op: false,
+            is_tag_load: false,
+            is_tag_store: false,
         }
     }
 
@@ -170,9 +177,79 @@ impl Footprint {
         write!(buf, "\n  Is load: {}", self.is_load)?;
         write!(buf, "\n  Is exclusive: {}", self.is_exclusive)?;
         write!(buf, "\n  Is branch: {}", self.is_branch)?;
+        write!(buf, "\n  Is tag load: {}", self.is_tag_load)?;
+        write!(buf, "\n  Is tag store: {}", self.is_tag_store)?;
         writeln!(buf)?;
         Ok(())
     }
+
+    /// Returns a summary of the footprint with registers displayed by
+    /// name. All the sets are ordered so the output is stable.
+    pub fn summary(&self, symtab: &Symtab) -> FootprintSummary {
+        let registers = |regs: &mut dyn Iterator<Item = &(Name, Vec<Accessor>)>| -> BTreeSet<String> {
+            regs.map(|(reg, accessor)| {
+                let mut name = zencode::decode(symtab.to_str(*reg));
+                for Accessor::Field(field) in accessor {
+                    name.push('.');
+                    name.push_str(&zencode::decode(symtab.to_str(*field)))
+                }
+                name
+            })
+            .collect()
+        };
+        let taints =
+            |taints: &(Taints, bool)| TaintSummary { registers: registers(&mut taints.0.iter()), memory: taints.1 };
+
+        FootprintSummary {
+            write_data_taints: taints(&self.write_data_taints),
+            mem_addr_taints: taints(&self.mem_addr_taints),
+            branch_addr_taints: taints(&self.branch_addr_taints),
+            register_reads: registers(&mut self.register_reads.iter()),
+            register_writes: registers(&mut self.register_writes.iter()),
+            register_writes_tainted: registers(&mut self.register_writes_tainted.iter()),
+            register_writes_ignored: self
+                .register_writes_ignored
+                .iter()
+                .map(|reg| zencode::decode(symtab.to_str(*reg)))
+                .collect(),
+            is_store: self.is_store,
+            is_load: self.is_load,
+            is_branch: self.is_branch,
+            is_exclusive: self.is_exclusive,
+            is_cache_op: self.is_cache_op,
+            is_tag_load: self.is_tag_load,
+            is_tag_store: self.is_tag_store,
+        }
+    }
+}
+
+/// The registers that can feed into some part of an instruction, and
+/// whether a memory read can.
+#[derive(Debug, Serialize)]
+pub struct TaintSummary {
+    pub registers: BTreeSet<String>,
+    pub memory: bool,
+}
+
+/// A `Footprint` with register names resolved, for serializing to
+/// external tools (e.g. as JSON). See `Footprint` for the meaning of
+/// each field.
+#[derive(Debug, Serialize)]
+pub struct FootprintSummary {
+    pub write_data_taints: TaintSummary,
+    pub mem_addr_taints: TaintSummary,
+    pub branch_addr_taints: TaintSummary,
+    pub register_reads: BTreeSet<String>,
+    pub register_writes: BTreeSet<String>,
+    pub register_writes_tainted: BTreeSet<String>,
+    pub register_writes_ignored: BTreeSet<String>,
+    pub is_store: bool,
+    pub is_load: bool,
+    pub is_branch: bool,
+    pub is_exclusive: bool,
+    pub is_cache_op: bool,
+    pub is_tag_load: bool,
+    pub is_tag_store: bool,
 }
 
 // There is an rmw dependency from `from` to `to` if `from` is a
@@ -324,6 +401,44 @@ pub fn ctrl_dep<B: BV>(from: usize, to: usize, instrs: &[B], footprints: &HashMa
     false
 }
 
+/// The syntactic dependency relations between the instructions of a
+/// single thread, as pairs of indices `(from, to)` into the
+/// instruction sequence.
+#[derive(Debug, Default, Serialize)]
+pub struct Dependencies {
+    pub addr: Vec<(usize, usize)>,
+    pub data: Vec<(usize, usize)>,
+    pub ctrl: Vec<(usize, usize)>,
+    pub rmw: Vec<(usize, usize)>,
+}
+
+/// Computes all the address, data, control and rmw dependencies
+/// between pairs of instructions in `instrs`.
+///
+/// # Panics
+///
+/// See `addr_dep`
+pub fn dependencies<B: BV>(instrs: &[B], footprints: &HashMap<B, Footprint>) -> Dependencies {
+    let mut deps = Dependencies::default();
+    for from in 0..instrs.len() {
+        for to in (from + 1)..instrs.len() {
+            if addr_dep(from, to, instrs, footprints) {
+                deps.addr.push((from, to))
+            }
+            if data_dep(from, to, instrs, footprints) {
+                deps.data.push((from, to))
+            }
+            if ctrl_dep(from, to, instrs, footprints) {
+                deps.ctrl.push((from, to))
+            }
+            if rmw_dep(from, to, instrs, footprints) {
+                deps.rmw.push((from, to))
+            }
+        }
+    }
+    deps
+}
+
 #[derive(Debug)]
 pub enum FootprintError {
     NoIslaFootprintFn,
@@ -354,6 +469,79 @@ impl Error for FootprintError {
     }
 }
 
+/// An encoding pattern describes a set of opcodes. It is written as a
+/// string of `0`, `1`, and `x` characters from the most significant bit
+/// to the least, where `x` matches either bit value, e.g.
+/// `1x111000010xxxxxxxxx01xxxxxxxxxx`. Underscores and whitespace can
+/// be used as separators and are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodingPattern {
+    /// Mask of the bits that are fixed by the pattern
+    mask: u64,
+    /// Values of the fixed bits
+    bits: u64,
+    len: u32,
+}
+
+impl FromStr for EncodingPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pattern = EncodingPattern { mask: 0, bits: 0, len: 0 };
+        for c in s.chars().filter(|c| *c != '_' && !c.is_whitespace()) {
+            if pattern.len == 64 {
+                return Err(format!("Encoding pattern {} is longer than 64 bits", s));
+            }
+            pattern.mask <<= 1;
+            pattern.bits <<= 1;
+            match c {
+                '0' => pattern.mask |= 1,
+                '1' => {
+                    pattern.mask |= 1;
+                    pattern.bits |= 1
+                }
+                'x' | 'X' => (),
+                _ => return Err(format!("Invalid character '{}' in encoding pattern {}", c, s)),
+            }
+            pattern.len += 1
+        }
+        if pattern.len == 0 {
+            return Err("Encoding pattern is empty".to_string());
+        }
+        Ok(pattern)
+    }
+}
+
+impl EncodingPattern {
+    /// The length of the pattern in bits
+    pub fn width(&self) -> u32 {
+        self.len
+    }
+
+    /// The number of opcodes matched by the pattern
+    pub fn count(&self) -> u128 {
+        1 << (self.len - self.mask.count_ones())
+    }
+
+    pub fn matches(&self, opcode: u64) -> bool {
+        opcode & self.mask == self.bits && (self.len == 64 || opcode >> self.len == 0)
+    }
+
+    /// Returns an iterator over all the opcodes matched by the
+    /// pattern in ascending order.
+    pub fn opcodes(&self) -> impl Iterator<Item = u64> {
+        let free = !self.mask & (u64::MAX >> (64 - self.len));
+        let bits = self.bits;
+        // Step through every subset of the free bits in increasing order
+        let mut next = Some(0);
+        std::iter::from_fn(move || {
+            let subset = next?;
+            next = if subset == free { None } else { Some(subset.wrapping_sub(free) & free) };
+            Some(bits | subset)
+        })
+    }
+}
+
 /// # Arguments
 ///
 /// * `num_threads` - How many threads to use for analysing footprints
@@ -362,44 +550,107 @@ impl Error for FootprintError {
 /// * `regs` - The initial register state
 /// * `shared_state` - The state shared between all symbolic execution runs
 /// * `isa_config` - The architecture specific configuration information
-/// * `cache_dir` - A directory to cache footprint results
-pub fn footprint_analysis<'ir, B, P>(
+/// * `cache` - A cache for footprint results
+pub fn footprint_analysis<'ir, B: BV>(
     num_threads: usize,
     thread_buckets: &[Vec<EvPath<B>>],
     lets: &Bindings<'ir, B>,
     regs: &Bindings<'ir, B>,
     shared_state: &SharedState<B>,
     isa_config: &ISAConfig<B>,
-    cache_dir: P,
-) -> Result<HashMap<B, Footprint>, FootprintError>
-where
-    B: BV,
-    P: AsRef<Path>,
-{
-    use FootprintError::*;
-    let mut concrete_opcodes: HashSet<B> = HashSet::new();
-    let mut footprints = HashMap::new();
+    cache: &Cache,
+) -> Result<HashMap<B, Footprint>, FootprintError> {
+    let mut opcodes: HashSet<B> = HashSet::new();
 
     for thread in thread_buckets {
         for path in thread {
             for event in path {
                 match event {
                     Event::Instr(Val::Bits(bv)) => {
-                        if let Some(footprint) =
-                            Footprint::from_cache(Footprintkey { opcode: bv.to_string() }, cache_dir.as_ref())
-                        {
-                            footprints.insert(*bv, footprint);
-                        } else {
-                            concrete_opcodes.insert(*bv);
-                        }
+                        opcodes.insert(*bv);
                     }
-                    Event::Instr(_) => return Err(SymbolicInstruction),
+                    Event::Instr(_) => return Err(FootprintError::SymbolicInstruction),
                     _ => (),
                 }
             }
         }
     }
 
+    let opcodes: Vec<B> = opcodes.into_iter().collect();
+    opcode_footprints(num_threads, &opcodes, lets, regs, shared_state, isa_config, Some(cache))
+}
+
+/// Compute the footprint of each of a set of concrete opcodes. The
+/// arguments are as for `footprint_analysis`, except the cache is
+/// optional.
+pub fn opcode_footprints<'ir, B: BV>(
+    num_threads: usize,
+    opcodes: &[B],
+    lets: &Bindings<'ir, B>,
+    regs: &Bindings<'ir, B>,
+    shared_state: &SharedState<B>,
+    isa_config: &ISAConfig<B>,
+    cache: Option<&Cache>,
+) -> Result<HashMap<B, Footprint>, FootprintError> {
+    let (footprints, mut failures) =
+        opcode_footprints_partial(num_threads, opcodes, lets, regs, shared_state, isa_config, cache)?;
+    match failures.pop() {
+        Some((_, msg)) => Err(FootprintError::ExecutionError(msg)),
+        None => Ok(footprints),
+    }
+}
+
+type OpcodeQueue<B> = SegQueue<Result<(usize, Vec<Event<B>>), (usize, String)>>;
+
+/// As `executor::footprint_collector`, but errors are tagged with the
+/// task (and therefore the opcode) that caused them.
+fn opcode_collector<'ir, B: BV>(
+    _: usize,
+    task_id: usize,
+    result: Result<(Val<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+    _: &SharedState<'ir, B>,
+    solver: Solver<B>,
+    collected: &OpcodeQueue<B>,
+) {
+    match result {
+        Ok((Val::Bool(true), _)) => {
+            let mut events = solver.trace().to_vec();
+            collected.push(Ok((task_id, events.drain(..).cloned().collect())))
+        }
+        Ok((Val::Bool(false), _)) => (),
+        Ok((val, _)) => collected.push(Err((task_id, format!("Unexpected footprint return value: {:?}", val)))),
+        Err((ExecError::Dead, _)) => (),
+        Err((err, _)) => collected.push(Err((task_id, format!("Error {:?}", err)))),
+    }
+}
+
+/// As `opcode_footprints`, but an error while executing an opcode
+/// only causes that opcode to be skipped. The skipped opcodes are
+/// returned along with their error messages, and are not cached.
+#[allow(clippy::type_complexity)]
+pub fn opcode_footprints_partial<'ir, B: BV>(
+    num_threads: usize,
+    opcodes: &[B],
+    lets: &Bindings<'ir, B>,
+    regs: &Bindings<'ir, B>,
+    shared_state: &SharedState<B>,
+    isa_config: &ISAConfig<B>,
+    cache: Option<&Cache>,
+) -> Result<(HashMap<B, Footprint>, Vec<(B, String)>), FootprintError> {
+    use FootprintError::*;
+    let mut concrete_opcodes: HashSet<B> = HashSet::new();
+    let mut footprints = HashMap::new();
+
+    for opcode in opcodes {
+        if let Some(footprint) =
+            cache.and_then(|cache| Footprint::from_cache(Footprintkey { opcode: opcode.to_string() }, cache))
+        {
+            footprints.insert(*opcode, footprint);
+        } else {
+            concrete_opcodes.insert(*opcode);
+        }
+    }
+
     log!(log::VERBOSE, &format!("Got {} uncached concrete opcodes for footprint analysis", concrete_opcodes.len()));
 
     let function_id = match shared_state.symtab.get("zisla_footprint") {
@@ -424,10 +675,11 @@ where
         .unzip();
 
     let mut footprint_buckets: Vec<Vec<EvPath<B>>> = vec![Vec::new(); tasks.len()];
+    let mut failed: Vec<Option<String>> = vec![None; tasks.len()];
     let queue = Arc::new(SegQueue::new());
 
     let now = Instant::now();
-    executor::start_multi(num_threads, None, tasks, &shared_state, queue.clone(), &executor::footprint_collector);
+    executor::start_multi(num_threads, None, tasks, shared_state, queue.clone(), &opcode_collector);
     log!(log::VERBOSE, &format!("Footprint analysis symbolic execution took: {}ms", now.elapsed().as_millis()));
 
     loop {
@@ -438,14 +690,20 @@ where
                     .rev()
                     // The first cycle is reserved for initialization
                     .skip_while(|ev| !ev.is_cycle())
-                    .filter(|ev| ev.is_reg() || ev.is_memory() || ev.is_branch() || ev.is_smt() || ev.is_fork())
+                    .filter(|ev| {
+                        ev.is_reg() || ev.is_memory() || ev.is_tag() || ev.is_branch() || ev.is_smt() || ev.is_fork()
+                    })
                     .collect();
                 isla_lib::simplify::remove_unused(&mut events);
 
                 footprint_buckets[task_id].push(events)
             }
-            // Error during execution
-            Ok(Err(msg)) => return Err(ExecutionError(msg)),
+            // Error during execution, only the first for each opcode is kept
+            Ok(Err((task_id, msg))) => {
+                if failed[task_id].is_none() {
+                    failed[task_id] = Some(msg)
+                }
+            }
             // Empty queue
             Err(_) => break,
         }
@@ -459,8 +717,14 @@ where
     let write_exclusives: Vec<usize> =
         isa_config.write_exclusives.iter().map(|k| shared_state.enum_member(*k).unwrap()).collect();
 
+    let mut failures = Vec::new();
+
     for (i, paths) in footprint_buckets.iter().enumerate() {
         let opcode = task_opcodes[i];
+        if let Some(msg) = failed[i].take() {
+            failures.push((opcode, msg));
+            continue;
+        }
         log!(log::VERBOSE, &format!("{:?}", opcode));
 
         let mut footprint = Footprint::new();
@@ -517,6 +781,24 @@ where
                             &mut footprint.write_data_taints.1,
                         );
                     }
+                    Event::ReadTag { address, .. } => {
+                        footprint.is_tag_load = true;
+                        evrefs.collect_value_taints(
+                            address,
+                            events,
+                            &mut footprint.mem_addr_taints.0,
+                            &mut footprint.mem_addr_taints.1,
+                        )
+                    }
+                    Event::WriteTag { address, .. } => {
+                        footprint.is_tag_store = true;
+                        evrefs.collect_value_taints(
+                            address,
+                            events,
+                            &mut footprint.mem_addr_taints.0,
+                            &mut footprint.mem_addr_taints.1,
+                        )
+                    }
                     Event::CacheOp { address, .. } => {
                         footprint.is_cache_op = true;
                         evrefs.collect_value_taints(
@@ -548,9 +830,193 @@ where
             }
         }
 
-        footprint.cache(Footprintkey { opcode: opcode.to_string() }, cache_dir.as_ref());
+        if let Some(cache) = cache {
+            footprint.cache(Footprintkey { opcode: opcode.to_string() }, cache)
+        }
         footprints.insert(opcode, footprint);
     }
 
-    Ok(footprints)
+    Ok((footprints, failures))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use isla_lib::concrete::bitvector64::B64;
+    use isla_lib::ir_parser::IrParser;
+    use isla_lib::lexer::Lexer;
+
+    #[test]
+    fn test_encoding_pattern() {
+        let pattern = EncodingPattern::from_str("1x0_x").unwrap();
+        assert_eq!(pattern.width(), 4);
+        assert_eq!(pattern.count(), 4);
+        assert_eq!(pattern.opcodes().collect::<Vec<_>>(), vec![0b1000, 0b1001, 0b1100, 0b1101]);
+        assert!(pattern.opcodes().all(|opcode| pattern.matches(opcode)));
+        assert!(!pattern.matches(0b1010));
+        assert!(!pattern.matches(0b11000));
+
+        let pattern = EncodingPattern::from_str("0101").unwrap();
+        assert_eq!(pattern.opcodes().collect::<Vec<_>>(), vec![0b0101]);
+
+        let pattern = EncodingPattern::from_str(&"x".repeat(64)).unwrap();
+        assert_eq!(pattern.count(), 1 << 64);
+        assert_eq!(pattern.opcodes().take(3).collect::<Vec<_>>(), vec![0, 1, 2]);
+
+        assert!(EncodingPattern::from_str("").is_err());
+        assert!(EncodingPattern::from_str("10y").is_err());
+        assert!(EncodingPattern::from_str(&"1".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut symtab = Symtab::new();
+        let x0 = symtab.intern("zR0");
+        let pstate = symtab.intern("zPSTATE");
+        let el = symtab.intern("zEL");
+
+        let mut footprint = Footprint::new();
+        footprint.write_data_taints = ([(x0, vec![])].iter().cloned().collect(), true);
+        footprint.register_reads.insert((x0, vec![]));
+        footprint.register_reads.insert((pstate, vec![Accessor::Field(el)]));
+        footprint.register_writes_ignored.insert(pstate);
+        footprint.is_store = true;
+
+        let summary = footprint.summary(&symtab);
+        assert_eq!(summary.write_data_taints.registers.iter().collect::<Vec<_>>(), vec!["R0"]);
+        assert!(summary.write_data_taints.memory);
+        assert!(summary.mem_addr_taints.registers.is_empty());
+        assert!(!summary.mem_addr_taints.memory);
+        assert_eq!(summary.register_reads.iter().collect::<Vec<_>>(), vec!["PSTATE.EL", "R0"]);
+        assert_eq!(summary.register_writes_ignored.iter().collect::<Vec<_>>(), vec!["PSTATE"]);
+        assert!(summary.is_store && !summary.is_load)
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let mut symtab = Symtab::new();
+        let regs: Vec<(Name, Vec<Accessor>)> =
+            ["zR0", "zR1", "zR2", "zR3", "zR4", "zR5"].iter().map(|r| (symtab.intern(r), vec![])).collect();
+        let set = |rs: &[usize]| -> HashSet<(Name, Vec<Accessor>)> { rs.iter().map(|r| regs[*r].clone()).collect() };
+
+        // 0: R1 = [R0]
+        let mut load = Footprint::new();
+        load.mem_addr_taints.0 = set(&[0]);
+        load.register_reads = set(&[0]);
+        load.register_writes = set(&[1]);
+        load.register_writes_tainted = set(&[1]);
+        load.is_load = true;
+        // 1: [R2] = R1
+        let mut store = Footprint::new();
+        store.write_data_taints.0 = set(&[1]);
+        store.mem_addr_taints.0 = set(&[2]);
+        store.register_reads = set(&[1, 2]);
+        store.is_store = true;
+        // 2: R3 = [R1]
+        let mut dependent_load = Footprint::new();
+        dependent_load.mem_addr_taints.0 = set(&[1]);
+        dependent_load.register_reads = set(&[1]);
+        dependent_load.register_writes = set(&[3]);
+        dependent_load.register_writes_tainted = set(&[3]);
+        dependent_load.is_load = true;
+        // 3: branch on R3
+        let mut branch = Footprint::new();
+        branch.branch_addr_taints.0 = set(&[3]);
+        branch.register_reads = set(&[3]);
+        branch.is_branch = true;
+        // 4: [R5] = R4
+        let mut independent_store = Footprint::new();
+        independent_store.write_data_taints.0 = set(&[4]);
+        independent_store.mem_addr_taints.0 = set(&[5]);
+        independent_store.register_reads = set(&[4, 5]);
+        independent_store.is_store = true;
+
+        let instrs: Vec<B64> = (0..5).map(B64::from_u32).collect();
+        let mut footprints = HashMap::new();
+        for (opcode, footprint) in instrs.iter().zip(vec![load, store, dependent_load, branch, independent_store]) {
+            footprints.insert(*opcode, footprint);
+        }
+
+        let deps = dependencies(&instrs, &footprints);
+        assert_eq!(deps.addr, vec![(0, 2)]);
+        assert_eq!(deps.data, vec![(0, 1)]);
+        assert_eq!(deps.ctrl, vec![(0, 4), (2, 4)]);
+        assert!(deps.rmw.is_empty());
+
+        // A load exclusive followed by a store exclusive is an rmw
+        // dependency, unless there is another exclusive in between
+        let mut load_exclusive = Footprint::new();
+        load_exclusive.is_load = true;
+        load_exclusive.is_exclusive = true;
+        let mut store_exclusive = Footprint::new();
+        store_exclusive.is_store = true;
+        store_exclusive.is_exclusive = true;
+        let mut footprints = HashMap::new();
+        footprints.insert(B64::from_u32(0), load_exclusive);
+        footprints.insert(B64::from_u32(1), store_exclusive);
+
+        let instrs: Vec<B64> = [0, 1].iter().copied().map(B64::from_u32).collect();
+        assert_eq!(dependencies(&instrs, &footprints).rmw, vec![(0, 1)]);
+        let instrs: Vec<B64> = [0, 0, 1].iter().copied().map(B64::from_u32).collect();
+        assert_eq!(dependencies(&instrs, &footprints).rmw, vec![(1, 2)]);
+    }
+
+    #[test]
+    fn test_opcode_footprints_partial() {
+        // Opcode 1 fails, every other opcode writes R
+        let ir = "
+            enum zread_kind { zRead_plain, zRead_ifetch }
+            register zPC : %bv64
+            register zR : %bv32
+            val zisla_footprint : (%bv32) -> %bool
+            fn zisla_footprint(zopcode) {
+                jump @eq(zopcode, 0x00000001) goto 4 ` \"footprint\";
+                zR = zopcode;
+                return = true;
+                end;
+                failure
+            }";
+        let config = r#"
+            pc = "PC"
+            ifetch = "Read_ifetch"
+            read_exclusives = []
+            write_exclusives = []
+            assembler = "sh"
+            objdump = "sh"
+            linker = "sh"
+
+            [threads]
+            base = "0x400000"
+            top = "0x500000"
+            stride = "0x10000"
+
+            [symbolic_addrs]
+            base = "0x600000"
+            stride = "0x10"
+
+            [reads]
+
+            [writes]
+
+            [cache_ops]
+        "#;
+        let defs: Vec<Def<String, B64>> = IrParser::new().parse(Lexer::new(ir)).ok().unwrap();
+        let mut symtab = Symtab::new();
+        let defs = symtab.intern_defs(&defs);
+        let isa_config = ISAConfig::<B64>::parse(config, &symtab).unwrap();
+        let shared_state = SharedState::new(symtab, &defs, HashSet::new(), HashMap::new());
+        let (lets, regs) = (HashMap::new(), HashMap::new());
+
+        let opcodes: Vec<B64> = (0..3).map(B64::from_u32).collect();
+        let (footprints, failures) =
+            opcode_footprints_partial(2, &opcodes, &lets, &regs, &shared_state, &isa_config, None).unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, B64::from_u32(1));
+        assert!(footprints.contains_key(&B64::from_u32(0)));
+        assert!(!footprints.contains_key(&B64::from_u32(1)));
+        assert!(footprints.contains_key(&B64::from_u32(2)));
+
+        assert!(opcode_footprints(2, &opcodes, &lets, &regs, &shared_state, &isa_config, None).is_err());
+        assert!(opcode_footprints(2, &opcodes[..1], &lets, &regs, &shared_state, &isa_config, None).is_ok())
+    }
 }
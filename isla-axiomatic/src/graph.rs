@@ -31,21 +31,26 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt;
+use std::ops::Range;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 use isla_lib::concrete::BV;
+use isla_lib::config::{ISAConfig, Kind, MemoryKind};
 use isla_lib::ir::*;
 use isla_lib::smt::Event;
 
 use isla_cat::cat;
+use isla_cat::interpret::{interpret_cat, Execution};
+use isla_cat::smt::approximated_defs;
 
 use crate::axiomatic::model::Model;
 use crate::axiomatic::relations;
 use crate::axiomatic::{AxEvent, ExecutionInfo, Pairs, ThreadId};
 use crate::footprint_analysis::Footprint;
-use crate::litmus::instruction_from_objdump;
 use crate::litmus::Litmus;
+use crate::litmus::{instruction_from_objdump, opcode_from_objdump};
 use crate::sexp::{InterpretError, SexpVal};
+use crate::smt_events::is_mixed_size;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GraphEvent {
@@ -227,7 +232,14 @@ impl Error for GraphError {
     }
 }
 
+type EventRel<'a, B> = Box<dyn Fn(&AxEvent<B>, &AxEvent<B>) -> bool + 'a>;
+
+type EventSet<'a, B> = Box<dyn Fn(&AxEvent<B>) -> bool + 'a>;
+
+type GraphEventRel = fn(&GraphEvent, &GraphEvent) -> bool;
+
 /// Generate a graph from the output of a Z3 invocation that returned sat.
+#[allow(clippy::too_many_arguments)]
 pub fn graph_from_z3_output<B: BV>(
     exec: ExecutionInfo<B>,
     footprints: &HashMap<B, Footprint>,
@@ -235,6 +247,8 @@ pub fn graph_from_z3_output<B: BV>(
     litmus: &Litmus<B>,
     cat: &cat::Cat<cat::Ty>,
     ifetch: bool,
+    shared_state: &SharedState<B>,
+    isa_config: &ISAConfig<B>,
 ) -> Result<Graph, GraphError> {
     use GraphError::*;
 
@@ -263,7 +277,8 @@ pub fn graph_from_z3_output<B: BV>(
 
     let mut builtin_relations = vec!["rf", "co"];
     if ifetch {
-        builtin_relations.push("irf")
+        builtin_relations.push("irf");
+        builtin_relations.push("wco")
     }
 
     for rel in cat.relations().iter().chain(builtin_relations.iter()) {
@@ -274,6 +289,175 @@ pub fn graph_from_z3_output<B: BV>(
         })
     }
 
+    // The sets and relations defined by `smt_of_candidate` are not in
+    // the model, so we compute them from the events, using the model
+    // to find any symbolic addresses and values. Including them allows
+    // the graph to be checked against the cat model with `check_graph`.
+    let mut addresses: HashMap<&str, u64> = HashMap::new();
+    let mut values: HashMap<&str, B> = HashMap::new();
+    for ev in exec.events.iter() {
+        if let Some(address) = ev.address() {
+            if let Ok(address) = model.interpret_bits(address) {
+                addresses.insert(&ev.name, address.lower_u64());
+            }
+        }
+        if let Some((value, _)) = ev.read_value().or_else(|| ev.write_data()) {
+            if let Ok(value) = model.interpret_bits(value) {
+                values.insert(&ev.name, value);
+            }
+        }
+    }
+    let address = |ev: &AxEvent<B>| addresses.get(&*ev.name).copied();
+    let value = |ev: &AxEvent<B>| values.get(&*ev.name).copied();
+
+    let same_location = |ev1: &AxEvent<B>, ev2: &AxEvent<B>| match (address(ev1), address(ev2)) {
+        (Some(addr1), Some(addr2)) => addr1 == addr2,
+        _ => false,
+    };
+    let overlapping = |ev1: &AxEvent<B>, ev2: &AxEvent<B>| match (address(ev1), ev1.bytes(), address(ev2), ev2.bytes())
+    {
+        (Some(addr1), Some(bytes1), Some(addr2), Some(bytes2)) => {
+            addr1 < addr2 + bytes2 as u64 && addr2 < addr1 + bytes1 as u64
+        }
+        _ => false,
+    };
+    let mixed_size = is_mixed_size(&exec.events, litmus);
+    let loc = |ev1: &AxEvent<B>, ev2: &AxEvent<B>| {
+        if mixed_size {
+            overlapping(ev1, ev2)
+        } else {
+            same_location(ev1, ev2)
+        }
+    };
+    // A write and a read whose values agree, where the written data
+    // is truncated or zero-extended to the width of the read
+    let rw_pair =
+        |ev1: &AxEvent<B>, ev2: &AxEvent<B>| match (ev1.write_data(), ev2.read_value(), value(ev1), value(ev2)) {
+            (Some(_), Some(_), Some(data), Some(read)) => {
+                if read.len() > data.len() {
+                    read == data.zero_extend(read.len())
+                } else {
+                    data.extract(read.len() - 1, 0) == Some(read)
+                }
+            }
+            _ => false,
+        };
+
+    let mut structural_relations: Vec<(&str, EventRel<B>)> = vec![
+        ("int", Box::new(relations::internal)),
+        ("ext", Box::new(relations::external)),
+        ("loc", Box::new(|ev1, ev2| relations::disjoint(ev1, ev2) && loc(ev1, ev2))),
+        ("po-loc", Box::new(|ev1, ev2| relations::po(ev1, ev2) && loc(ev1, ev2))),
+        ("overlap", Box::new(|ev1, ev2| relations::disjoint(ev1, ev2) && overlapping(ev1, ev2))),
+        (
+            "sfp",
+            Box::new(|ev1, ev2| relations::disjoint(ev1, ev2) && ev1.bytes() == ev2.bytes() && same_location(ev1, ev2)),
+        ),
+        ("rw-pair", Box::new(rw_pair)),
+        ("amo", Box::new(relations::amo)),
+    ];
+    if ifetch {
+        structural_relations
+            .push(("po", Box::new(|ev1, ev2| relations::po(ev1, ev2) && !(ev1.is_ifetch && ev2.is_ifetch))));
+        structural_relations
+            .push(("fpo", Box::new(|ev1, ev2| relations::po(ev1, ev2) && ev1.is_ifetch && ev2.is_ifetch)));
+        structural_relations.push(("scl", Box::new(|ev1, ev2| relations::disjoint(ev1, ev2) && loc(ev1, ev2))));
+        structural_relations.push((
+            "fe",
+            Box::new(|ev1, ev2| ev1.is_ifetch && !ev2.is_ifetch && ev1.po == ev2.po && ev1.thread_id == ev2.thread_id),
+        ));
+    } else {
+        structural_relations.push(("po", Box::new(relations::po)))
+    }
+
+    for (name, rel) in structural_relations.iter() {
+        let edges: Vec<(&AxEvent<B>, &AxEvent<B>)> =
+            Pairs::from_slice(&exec.events).filter(|(ev1, ev2)| rel(ev1, ev2)).collect();
+        relations.push(GraphRelation {
+            name: (*name).to_string(),
+            edges: edges.iter().map(|(from, to)| (from.name.clone(), to.name.clone())).collect(),
+        })
+    }
+
+    let devices: Vec<&Range<u64>> = isa_config
+        .memory
+        .iter()
+        .filter(|region| matches!(region.kind, MemoryKind::Device))
+        .map(|region| &region.range)
+        .collect();
+
+    let mut base_sets: Vec<(&str, EventSet<B>)> = vec![
+        ("R", Box::new(relations::is_read)),
+        ("W", Box::new(relations::is_write)),
+        ("M", Box::new(|ev| relations::is_read(ev) || relations::is_write(ev))),
+        ("IF", Box::new(relations::is_ifetch)),
+        ("F", Box::new(relations::is_barrier)),
+        ("C", Box::new(relations::is_cache_op)),
+        ("TE", Box::new(relations::is_exception_entry)),
+        ("ERET", Box::new(relations::is_exception_return)),
+        ("RTAG", Box::new(relations::is_tag_read)),
+        ("WTAG", Box::new(relations::is_tag_write)),
+        ("T", Box::new(relations::is_translate)),
+        (
+            "DEV",
+            Box::new(|ev| {
+                (relations::is_read(ev) || relations::is_write(ev))
+                    && matches!(address(ev), Some(addr) if devices.iter().any(|range| range.contains(&addr)))
+            }),
+        ),
+        (
+            "r-initial",
+            Box::new(|ev| match (ev.read_value(), address(ev), value(ev)) {
+                (Some((_, bytes)), Some(addr), Some(value)) => {
+                    value.lower_u64() == litmus.initial_memory.read(addr, bytes)
+                }
+                _ => false,
+            }),
+        ),
+    ];
+    if ifetch {
+        base_sets.push(("ifetch-match", Box::new(|ev| ev.read_value().is_some() && value(ev) == Some(ev.opcode))));
+        base_sets.push((
+            "ifetch-initial",
+            Box::new(|ev| match (ev.address(), value(ev)) {
+                (Some(Val::Bits(addr)), Some(value)) if ev.read_value().is_some() => {
+                    opcode_from_objdump(*addr, &litmus.objdump) == Some(value) && value == ev.opcode
+                }
+                _ => false,
+            }),
+        ))
+    }
+
+    for (set, kinds) in isa_config.event_sets.iter() {
+        base_sets.push((
+            set,
+            Box::new(move |ev| {
+                kinds.iter().any(|k| match k {
+                    Kind::Read(rk) => matches!(shared_state.enum_member(*rk), Some(rk) if ev.base.has_read_kind(rk)),
+                    Kind::Write(wk) => {
+                        matches!(shared_state.enum_member(*wk), Some(wk) if ev.base.has_write_kind(wk))
+                    }
+                    Kind::CacheOp(ck) => {
+                        matches!(shared_state.enum_member(*ck), Some(ck) if ev.base.has_cache_op_kind(ck))
+                    }
+                })
+            }),
+        ))
+    }
+    for (barrier_kind, name) in isa_config.barriers.iter() {
+        let bk = shared_state.enum_member(*barrier_kind);
+        base_sets.push((name, Box::new(move |ev| matches!(bk, Some(bk) if ev.base.has_barrier_kind(bk)))))
+    }
+
+    let mut sets: Vec<GraphSet> = base_sets
+        .iter()
+        .map(|(name, set)| GraphSet {
+            name: (*name).to_string(),
+            elems: exec.events.iter().filter(|ev| set(ev)).map(|ev| ev.name.clone()).collect(),
+        })
+        .collect();
+    sets.push(GraphSet { name: "IW".to_string(), elems: vec!["IW".to_string()] });
+
     // Now we want to get the memory read and write values for each event
     let mut rw_values: HashMap<String, String> = HashMap::new();
 
@@ -330,8 +514,410 @@ pub fn graph_from_z3_output<B: BV>(
 
     Ok(Graph {
         events: exec.events.iter().map(|ev| GraphEvent::from_axiomatic(ev, &litmus.objdump, &mut rw_values)).collect(),
-        sets: vec![],
+        sets,
         relations,
         show: cat.shows(),
     })
 }
+
+/// Find a cycle in a relation, returning the edges that form it.
+fn find_cycle<'a>(edges: &[(&'a str, &'a str)]) -> Option<Vec<(&'a str, &'a str)>> {
+    fn visit<'a>(
+        ev: &'a str,
+        edges: &[(&'a str, &'a str)],
+        path: &mut Vec<&'a str>,
+        finished: &mut HashSet<&'a str>,
+    ) -> Option<Vec<(&'a str, &'a str)>> {
+        if let Some(i) = path.iter().position(|p| *p == ev) {
+            let mut cycle: Vec<(&str, &str)> = path[i..].windows(2).map(|w| (w[0], w[1])).collect();
+            cycle.push((path[path.len() - 1], ev));
+            return Some(cycle);
+        }
+        if finished.contains(ev) {
+            return None;
+        }
+        path.push(ev);
+        for (_, to) in edges.iter().filter(|(from, _)| *from == ev) {
+            if let Some(cycle) = visit(to, edges, path, finished) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        finished.insert(ev);
+        None
+    }
+
+    let mut finished = HashSet::new();
+    for (from, _) in edges {
+        if let Some(cycle) = visit(from, edges, &mut Vec::new(), &mut finished) {
+            return Some(cycle);
+        }
+    }
+    None
+}
+
+/// Generate a graph from the output of a Z3 invocation for a
+/// forbidden execution with some of its checks removed, as found by
+/// `run_litmus::explain_per_candidate`. For each removed check (the
+/// `relaxed` checks of an explanation) the graph shows the edges that
+/// violate it, e.g. the cycle for an acyclic check.
+#[allow(clippy::too_many_arguments)]
+pub fn graph_from_violation<B: BV>(
+    exec: ExecutionInfo<B>,
+    footprints: &HashMap<B, Footprint>,
+    z3_output: &str,
+    litmus: &Litmus<B>,
+    cat: &cat::Cat<cat::Ty>,
+    ifetch: bool,
+    shared_state: &SharedState<B>,
+    isa_config: &ISAConfig<B>,
+    violated: &[String],
+) -> Result<Graph, GraphError> {
+    use GraphError::*;
+
+    let mut event_names: Vec<&str> = exec.events.iter().map(|ev| ev.name.as_ref()).collect();
+    event_names.push("IW");
+    let mut model = Model::<B>::parse(&event_names, &z3_output[3..]).ok_or(SmtParseError)?;
+
+    let mut violations = Vec::new();
+    for (name, check, exp) in cat.checks().into_iter().filter(|(name, _, _)| violated.contains(name)) {
+        let edges = model.interpret_rel(&format!("check:{}", name), &event_names).map_err(InterpretError)?;
+        let is_set = cat::ty_of(exp) == cat::Ty::Set;
+        let edges = match check {
+            cat::Check::Acyclic => find_cycle(&edges).unwrap_or_default(),
+            cat::Check::Empty if !is_set => edges,
+            // The check function for a set ignores its second argument,
+            // so for both these cases we want the reflexive edges
+            cat::Check::Irreflexive | cat::Check::Empty => edges.into_iter().filter(|(from, to)| from == to).collect(),
+            // Checks that something exists are violated by its absence
+            cat::Check::NonAcyclic | cat::Check::NonIrreflexive | cat::Check::NonEmpty => Vec::new(),
+        };
+        violations.push(GraphRelation {
+            name,
+            edges: edges.iter().map(|(from, to)| ((*from).to_string(), (*to).to_string())).collect(),
+        })
+    }
+
+    let mut graph = graph_from_z3_output(exec, footprints, z3_output, litmus, cat, ifetch, shared_state, isa_config)?;
+    graph.show.extend(violations.iter().map(|rel| rel.name.clone()));
+    graph.relations.append(&mut violations);
+    Ok(graph)
+}
+
+impl Graph {
+    /// Create a concrete execution from a graph, which can be used to
+    /// interpret a cat model without an SMT solver. The initial write
+    /// `IW` is always an event. Hand-written graphs need not contain
+    /// `po`, `int`, or `ext`, as they are derived from the events if
+    /// missing.
+    pub fn execution(&self) -> Result<Execution, String> {
+        let mut exec = Execution::new();
+        exec.add_event("IW");
+        for ev in &self.events {
+            exec.add_event(&ev.name);
+        }
+
+        for set in &self.sets {
+            exec.add_set(&set.name, &set.elems)?
+        }
+        for rel in &self.relations {
+            exec.add_relation(&rel.name, rel.edges.iter().map(|(from, to)| (from, to)))?
+        }
+
+        if !exec.defines("IW") {
+            exec.add_set("IW", ["IW"])?
+        }
+
+        let derived: [(&str, GraphEventRel); 3] = [
+            ("po", |ev1, ev2| ev1.po < ev2.po && ev1.thread_id == ev2.thread_id),
+            ("int", |ev1, ev2| ev1.po != ev2.po && ev1.thread_id == ev2.thread_id),
+            ("ext", |ev1, ev2| ev1.po != ev2.po && ev1.thread_id != ev2.thread_id),
+        ];
+        for (name, rel) in derived.iter() {
+            if !exec.defines(name) {
+                let edges = Pairs::from_slice(&self.events)
+                    .filter(|(ev1, ev2)| rel(ev1, ev2))
+                    .map(|(ev1, ev2)| (&ev1.name, &ev2.name));
+                exec.add_relation(name, edges)?
+            }
+        }
+
+        Ok(exec)
+    }
+}
+
+/// Check the relations in a graph generated from the SMT solver's
+/// output against the cat model, by interpreting the model over the
+/// execution in the graph. Returns the names of the relations defined
+/// by the model whose edges in the graph differ from the interpreter.
+/// Relations which the SMT encoding only approximates (see
+/// `isla_cat::smt::approximated_defs`) are not checked.
+pub fn check_graph(graph: &Graph, cat: &cat::Cat<cat::Ty>) -> Result<Vec<String>, String> {
+    let exec = graph.execution()?;
+    let interpretation = interpret_cat(cat, &exec)?;
+    let approximated = approximated_defs(cat);
+
+    let mut mismatched = Vec::new();
+    for rel in &graph.relations {
+        if !interpretation.defines(&rel.name) || approximated.contains(&rel.name) {
+            continue;
+        }
+        if let Some(edges) = interpretation.relation(&rel.name) {
+            let expected: HashSet<(&str, &str)> = edges.into_iter().collect();
+            let found: HashSet<(&str, &str)> =
+                rel.edges.iter().map(|(from, to)| (from.as_ref(), to.as_ref())).collect();
+            if expected != found {
+                mismatched.push(rel.name.clone())
+            }
+        }
+    }
+    Ok(mismatched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_cycle() {
+        let edges = vec![("R1", "W1"), ("W1", "R2"), ("R2", "W2"), ("W2", "W1")];
+        assert_eq!(find_cycle(&edges), Some(vec![("W1", "R2"), ("R2", "W2"), ("W2", "W1")]));
+        assert_eq!(find_cycle(&[("W1", "W1")]), Some(vec![("W1", "W1")]));
+        assert_eq!(find_cycle(&edges[0..3]), None)
+    }
+
+    fn event(name: &str, po: usize, thread_id: ThreadId) -> GraphEvent {
+        GraphEvent { instr: None, opcode: "0".to_string(), po, thread_id, name: name.to_string(), value: None }
+    }
+
+    fn relation(name: &str, edges: &[(&str, &str)]) -> GraphRelation {
+        GraphRelation {
+            name: name.to_string(),
+            edges: edges.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_check_graph() {
+        let cat = cat::ParseCat::from_string("\"SC\"\ninclude \"cos.cat\"\nacyclic po | rf | co | fr as sc\n")
+            .and_then(|parse_cat| cat::resolve_includes(&[], parse_cat))
+            .unwrap();
+        let cat = cat::infer_cat(&mut cat::initial_tcx(Vec::new().into_iter()), cat).unwrap();
+
+        // Store buffering, with both reads from the initial state
+        let mut graph = Graph {
+            events: vec![event("W0", 0, 0), event("R1", 1, 0), event("W2", 0, 1), event("R3", 1, 1)],
+            sets: vec![
+                GraphSet { name: "W".to_string(), elems: vec!["W0".to_string(), "W2".to_string()] },
+                GraphSet { name: "R".to_string(), elems: vec!["R1".to_string(), "R3".to_string()] },
+            ],
+            relations: vec![
+                relation("rf", &[("IW", "R1"), ("IW", "R3")]),
+                relation("co", &[("IW", "W0"), ("IW", "W2")]),
+                relation("loc", &[("W0", "R3"), ("R3", "W0"), ("W2", "R1"), ("R1", "W2")]),
+            ],
+            show: vec![],
+        };
+
+        let exec = graph.execution().unwrap();
+        let interpretation = interpret_cat(&cat, &exec).unwrap();
+        assert_eq!(interpretation.relation("po").unwrap(), vec![("W0", "R1"), ("W2", "R3")]);
+        assert_eq!(interpretation.violated(), vec!["sc"]);
+
+        graph.relations.push(relation("fr", &[("R1", "W2"), ("R3", "W0")]));
+        assert!(check_graph(&graph, &cat).unwrap().is_empty());
+        graph.relations.last_mut().unwrap().edges.pop();
+        assert_eq!(check_graph(&graph, &cat).unwrap(), vec!["fr".to_string()])
+    }
+
+    #[test]
+    fn test_check_graph_from_z3_output() {
+        use isla_lib::concrete::bitvector64::B64;
+        use isla_lib::smt::Sym;
+
+        use crate::litmus::layout::InitialMemory;
+        use crate::litmus::{Prop, Quantifier};
+
+        let mut symtab = Symtab::new();
+        let read_kind = symtab.intern("zread_kind");
+        let read_kinds =
+            vec![symtab.intern("zRead_plain"), symtab.intern("zRead_ifetch"), symtab.intern("zRead_acquire")];
+        let barrier_kind = symtab.intern("zbarrier_kind");
+        let dmb = symtab.intern("zBarrier_DMB");
+        symtab.intern("zPC");
+        let config = r#"
+            pc = "PC"
+            ifetch = "Read_ifetch"
+            read_exclusives = []
+            write_exclusives = []
+            assembler = "sh"
+            objdump = "sh"
+            linker = "sh"
+
+            [threads]
+            base = "0x400000"
+            top = "0x500000"
+            stride = "0x10000"
+
+            [symbolic_addrs]
+            base = "0x600000"
+            stride = "0x10"
+
+            [barriers]
+            Barrier_DMB = "DMB"
+
+            [reads]
+            Read_acquire = "A"
+
+            [writes]
+
+            [cache_ops]
+
+            [memory.device]
+            kind = "device"
+            base = "0x2000"
+            top = "0x3000"
+        "#;
+        let isa_config = ISAConfig::<B64>::parse(config, &symtab).unwrap();
+        let defs = vec![Def::Enum(read_kind, read_kinds), Def::Enum(barrier_kind, vec![dmb])];
+        let shared_state = SharedState::new(symtab, &defs, HashSet::new(), HashMap::new());
+
+        // Thread 0 writes x, then a DMB, then reads y from the initial
+        // state. Thread 1 writes y, then reads x from thread 0.
+        let rk = |member| Val::Enum(EnumMember { enum_id: 0, member });
+        let x = Val::Bits(B64::from_u64(0x1000));
+        let one = Val::Bits(B64::from_u64(1));
+        let events = [
+            Event::WriteMem {
+                value: Sym::from_u32(0),
+                write_kind: Val::Unit,
+                address: x.clone(),
+                data: one.clone(),
+                bytes: 8,
+            },
+            Event::Barrier { barrier_kind: Val::Enum(EnumMember { enum_id: 1, member: 0 }) },
+            Event::ReadMem {
+                value: Val::Symbolic(Sym::from_u32(2)),
+                read_kind: rk(0),
+                address: Val::Symbolic(Sym::from_u32(1)),
+                bytes: 8,
+            },
+            Event::WriteMem {
+                value: Sym::from_u32(3),
+                write_kind: Val::Unit,
+                address: Val::Symbolic(Sym::from_u32(1)),
+                data: one,
+                bytes: 8,
+            },
+            Event::ReadMem { value: Val::Symbolic(Sym::from_u32(4)), read_kind: rk(2), address: x, bytes: 8 },
+        ];
+        let ax_event = |po, thread_id, name: &str, base| AxEvent {
+            opcode: B64::zeros(32),
+            po,
+            intra_instruction_order: 0,
+            thread_id,
+            name: name.to_string(),
+            base,
+            is_ifetch: false,
+            is_translate: false,
+            guard: None,
+        };
+        let exec = || ExecutionInfo {
+            events: vec![
+                ax_event(0, 0, "W0", &events[0]),
+                ax_event(1, 0, "F1", &events[1]),
+                ax_event(2, 0, "R2", &events[2]),
+                ax_event(0, 1, "W3", &events[3]),
+                ax_event(1, 1, "R4", &events[4]),
+            ],
+            thread_opcodes: vec![vec![B64::zeros(32); 3], vec![B64::zeros(32); 2]],
+            final_writes: HashMap::new(),
+        };
+
+        let litmus = Litmus {
+            name: "test".to_string(),
+            hash: None,
+            symbolic_addrs: HashMap::new(),
+            initial_memory: InitialMemory::default(),
+            symbolic_sizeof: HashMap::new(),
+            assembled: Vec::new(),
+            self_modify_regions: Vec::new(),
+            page_tables: None,
+            exception_handlers: Vec::new(),
+            objdump: String::new(),
+            quantifier: Quantifier::Exists,
+            final_assertion: Prop::True,
+        };
+
+        let mut footprints = HashMap::new();
+        footprints.insert(B64::zeros(32), Footprint::new());
+
+        let cat = cat::ParseCat::from_string(
+            "\"test\"\n\
+             let fr = ((rf^-1; co) \\ id) & loc\n\
+             let fence = fencerel(DMB)\n\
+             let obs = rf | co | fr\n\
+             acyclic po-loc | obs as coherence\n\
+             acyclic fence | obs as ordering\n",
+        )
+        .and_then(|parse_cat| cat::resolve_includes(&[], parse_cat))
+        .unwrap();
+        let cat =
+            cat::infer_cat(&mut cat::initial_tcx(vec!["DMB".to_string(), "A".to_string()].into_iter()), cat).unwrap();
+
+        // The model as the SMT solver would produce it, where fence is
+        // empty as function applications are not compiled
+        let z3_output = |obs: &str| {
+            format!(
+                "sat\n(model\n\
+                 (define-fun v1 () (_ BitVec 64) #x0000000000002000)\n\
+                 (define-fun v2 () (_ BitVec 64) #x0000000000000000)\n\
+                 (define-fun v4 () (_ BitVec 64) #x0000000000000001)\n\
+                 (define-fun rf ((x!0 Event) (x!1 Event)) Bool \
+                   (or (and (= x!0 IW) (= x!1 R2)) (and (= x!0 W0) (= x!1 R4))))\n\
+                 (define-fun co ((x!0 Event) (x!1 Event)) Bool \
+                   (or (and (= x!0 IW) (= x!1 W0)) (and (= x!0 IW) (= x!1 W3))))\n\
+                 (define-fun fr ((x!0 Event) (x!1 Event)) Bool (and (= x!0 R2) (= x!1 W3)))\n\
+                 (define-fun fence ((x!0 Event) (x!1 Event)) Bool false)\n\
+                 (define-fun obs ((x!0 Event) (x!1 Event)) Bool {}))",
+                obs
+            )
+        };
+        let obs = "(or (rf x!0 x!1) (co x!0 x!1) (fr x!0 x!1))";
+
+        let graph = graph_from_z3_output(
+            exec(),
+            &footprints,
+            &z3_output(obs),
+            &litmus,
+            &cat,
+            false,
+            &shared_state,
+            &isa_config,
+        )
+        .unwrap();
+        let set = |name: &str| &graph.sets.iter().find(|set| set.name == name).unwrap().elems;
+        assert_eq!(set("DMB"), &vec!["F1".to_string()]);
+        assert_eq!(set("A"), &vec!["R4".to_string()]);
+        assert_eq!(set("DEV"), &vec!["R2".to_string(), "W3".to_string()]);
+        assert_eq!(set("IW"), &vec!["IW".to_string()]);
+        assert!(graph.relations.iter().any(|rel| rel.name == "po-loc"));
+        // The interpreter applies fencerel, so fence is not checked
+        let execution = graph.execution().unwrap();
+        assert_eq!(interpret_cat(&cat, &execution).unwrap().relation("fence").unwrap(), vec![("W0", "R2")]);
+        assert!(check_graph(&graph, &cat).unwrap().is_empty());
+
+        let graph = graph_from_z3_output(
+            exec(),
+            &footprints,
+            &z3_output("(or (rf x!0 x!1) (co x!0 x!1))"),
+            &litmus,
+            &cat,
+            false,
+            &shared_state,
+            &isa_config,
+        )
+        .unwrap();
+        assert_eq!(check_graph(&graph, &cat).unwrap(), vec!["obs".to_string()])
+    }
+}
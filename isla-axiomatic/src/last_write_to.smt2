@@ -1,7 +1,8 @@
 
-(define-fun last_write_to_$LEN ((addr (_ BitVec 64)) (v (_ BitVec 64))) Bool
+(define-fun last_write_to_$LEN ((addr (_ BitVec 64)) (v (_ BitVec $LEN))) Bool
   (exists ((ev Event))
-    (or (and (= (val_of_$LEN ev) ((_ extract $LEN_MINUS_1 0) v))
+    (or (and (W ev)
+             (= (val_of_$LEN ev) v)
              (= (addr_of ev) addr)
              (not (exists ((ev2 Event))
                     (co ev ev2))))
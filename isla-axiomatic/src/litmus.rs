@@ -28,8 +28,11 @@
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
+use std::iter;
+use std::ops::Range;
 use std::path::Path;
 use std::process::Stdio;
 use std::sync::Arc;
@@ -46,6 +49,14 @@ use isla_lib::zencode;
 use crate::sandbox::SandboxedCommand;
 use crate::sexp::Sexp;
 
+pub mod cycle;
+pub mod herd;
+pub mod layout;
+pub mod page_table;
+
+use layout::{parse_u64, InitialMemory, Layout, LocType, Placement, PAGE_SIZE};
+use page_table::PageTables;
+
 /// We have a special purpose temporary file module which is used to
 /// create the output file for each assembler/linker invocation. Each
 /// call to new just creates a new file name using our PID and a
@@ -103,8 +114,13 @@ type ThreadName = String;
 /// instructions have addresses that will match the location at which
 /// we load each thread in memory. To do this we invoke the linker and
 /// give it a linker script with the address for each thread in the
-/// litmus thread.
-fn generate_linker_script<B>(threads: &[(ThreadName, &str)], isa: &ISAConfig<B>) -> String {
+/// litmus thread. Exception handlers are placed at their own fixed
+/// addresses.
+fn generate_linker_script<B>(
+    threads: &[(ThreadName, &str)],
+    handlers: &[(ThreadName, u64, &str)],
+    isa: &ISAConfig<B>,
+) -> String {
     use std::fmt::Write;
 
     let mut thread_address = isa.thread_base;
@@ -117,11 +133,15 @@ fn generate_linker_script<B>(threads: &[(ThreadName, &str)], isa: &ISAConfig<B>)
         thread_address += isa.thread_stride;
     }
 
+    for (handler, address, _) in handlers {
+        writeln!(&mut script, "  litmus_{} 0x{:x} : {{ *(litmus_{}) }}", handler, address, handler).unwrap();
+    }
+
     writeln!(&mut script, "}}").unwrap();
     script
 }
 
-type AssembledThreads = (Vec<(ThreadName, Vec<u8>)>, String);
+type AssembledThreads = (Vec<(ThreadName, Vec<u8>)>, Vec<(u64, Vec<u8>)>, String);
 
 #[cfg(feature = "sandbox")]
 fn validate_code(code: &str) -> Result<(), String> {
@@ -156,8 +176,15 @@ fn validate_code(_: &str) -> Result<(), String> {
 /// the assembled code is returned as a vector of bytes corresponding
 /// to it's section in the ELF file as given by the thread name. If
 /// `reloc` is true, then we will also invoke the linker to place each
-/// thread's section at the correct address.
-fn assemble<B>(threads: &[(ThreadName, &str)], reloc: bool, isa: &ISAConfig<B>) -> Result<AssembledThreads, String> {
+/// thread's section at the correct address. Any exception `handlers`
+/// are assembled in their own sections, and returned along with the
+/// address they are linked at.
+fn assemble<B>(
+    threads: &[(ThreadName, &str)],
+    handlers: &[(ThreadName, u64, &str)],
+    reloc: bool,
+    isa: &ISAConfig<B>,
+) -> Result<AssembledThreads, String> {
     use goblin::Object;
 
     let objfile = tmpfile::TmpFile::new();
@@ -176,7 +203,9 @@ fn assemble<B>(threads: &[(ThreadName, &str)], reloc: bool, isa: &ISAConfig<B>)
     // Write each thread to the assembler's standard input, in a section called `litmus_N` for each thread `N`
     {
         let stdin = assembler.stdin.as_mut().ok_or_else(|| "Failed to open stdin for assembler".to_string())?;
-        for (thread_name, code) in threads.iter() {
+        let sections = threads.iter().map(|(name, code)| (name, *code));
+        let handler_sections = handlers.iter().map(|(name, _, code)| (name, *code));
+        for (thread_name, code) in sections.chain(handler_sections) {
             validate_code(code)?;
             stdin
                 .write_all(format!("\t.section litmus_{}\n", thread_name).as_bytes())
@@ -197,8 +226,8 @@ fn assemble<B>(threads: &[(ThreadName, &str)], reloc: bool, isa: &ISAConfig<B>)
         {
             let mut fd = File::create(linker_script.path())
                 .or_else(|_| Err("Failed to create temp file for linker script".to_string()))?;
-            fd.write_all(generate_linker_script(threads, isa).as_bytes())
-                .or_else(|_| Err("Failed to write linker script".to_string()))?;
+            fd.write_all(generate_linker_script(threads, handlers, isa).as_bytes())
+                .map_err(|_| "Failed to write linker script".to_string())?;
         }
 
         let linker_status = SandboxedCommand::from_tool(&isa.linker)
@@ -239,6 +268,7 @@ fn assemble<B>(threads: &[(ThreadName, &str)], reloc: bool, isa: &ISAConfig<B>)
 
     // Get the code from the generated ELF's `litmus_N` section for each thread
     let mut assembled: Vec<(ThreadName, Vec<u8>)> = Vec::new();
+    let mut assembled_handlers: Vec<(u64, Vec<u8>)> = Vec::new();
     match Object::parse(&buffer) {
         Ok(Object::Elf(elf)) => {
             let shdr_strtab = elf.shdr_strtab;
@@ -251,6 +281,13 @@ fn assemble<B>(threads: &[(ThreadName, &str)], reloc: bool, isa: &ISAConfig<B>)
                             assembled.push((thread_name.to_string(), buffer[offset..(offset + size)].to_vec()))
                         }
                     }
+                    for (handler_name, address, _) in handlers.iter() {
+                        if section_name == format!("litmus_{}", handler_name) {
+                            let offset = section.sh_offset as usize;
+                            let size = section.sh_size as usize;
+                            assembled_handlers.push((*address, buffer[offset..(offset + size)].to_vec()))
+                        }
+                    }
                 }
             }
         }
@@ -262,7 +299,11 @@ fn assemble<B>(threads: &[(ThreadName, &str)], reloc: bool, isa: &ISAConfig<B>)
         return Err("Could not find all threads in generated ELF file".to_string());
     };
 
-    Ok((assembled, objdump))
+    if assembled_handlers.len() != handlers.len() {
+        return Err("Could not find all exception handlers in generated ELF file".to_string());
+    };
+
+    Ok((assembled, assembled_handlers, objdump))
 }
 
 /// For error reporting it's very helpful to be able to turn the raw
@@ -317,41 +358,41 @@ fn label_from_objdump(label: &str, objdump: &str) -> Option<u64> {
 
 pub fn assemble_instruction<B>(instr: &str, isa: &ISAConfig<B>) -> Result<Vec<u8>, String> {
     let instr = instr.to_owned() + "\n";
-    if let [(_, bytes)] = assemble(&[("single".to_string(), &instr)], false, isa)?.0.as_slice() {
+    if let [(_, bytes)] = assemble(&[("single".to_string(), &instr)], &[], false, isa)?.0.as_slice() {
         Ok(bytes.to_vec())
     } else {
         Err(format!("Failed to assemble instruction {}", instr))
     }
 }
 
-fn parse_symbolic_locations(
-    litmus_toml: &Value,
-    symbolic_addrs: &HashMap<String, u64>,
-) -> Result<HashMap<String, u64>, String> {
+fn parse_symbolic_locations(litmus_toml: &Value, layout: &Layout) -> Result<InitialMemory, String> {
+    let mut memory = InitialMemory::default();
+
     let sym_locs_table = match litmus_toml.get("locations") {
         Some(value) => value
             .as_table()
             .ok_or_else(|| "[locations] must be a table of <symbolic address> = <value> pairs".to_string())?,
         // Most litmus tests won't define any symbolic locations.
-        None => return Ok(HashMap::new()),
+        None => return Ok(memory),
     };
 
-    let mut sym_locs = HashMap::new();
     for (sym_loc, value) in sym_locs_table {
-        let value = value.as_str().ok_or_else(|| "Invalid symbolic address value")?;
-        let value = match i64::from_str_radix(value, 10) {
-            Ok(n) => n as u64,
-            Err(_) => *symbolic_addrs.get(value).ok_or_else(|| {
-                format!("Could not parse symbolic location value {} as an integer or address value", value)
-            })?,
+        // Arrays and structs can be initialised with a list of values
+        let values = match value {
+            Value::String(value) => vec![value.as_str()],
+            Value::Array(values) => values
+                .iter()
+                .map(|value| value.as_str().ok_or_else(|| format!("Invalid initial value for {}", sym_loc)))
+                .collect::<Result<_, _>>()?,
+            _ => return Err(format!("Invalid initial value for {}", sym_loc)),
         };
-        sym_locs.insert(sym_loc.clone(), value);
+        layout.initialise(&mut memory, sym_loc, &values)?
     }
 
-    Ok(sym_locs)
+    Ok(memory)
 }
 
-fn parse_symbolic_types(litmus_toml: &Value) -> Result<HashMap<String, u32>, String> {
+fn parse_symbolic_types(litmus_toml: &Value) -> Result<HashMap<String, LocType>, String> {
     let sym_types_table = match litmus_toml.get("types") {
         Some(value) => value
             .as_table()
@@ -360,25 +401,50 @@ fn parse_symbolic_types(litmus_toml: &Value) -> Result<HashMap<String, u32>, Str
         None => return Ok(HashMap::new()),
     };
 
-    let mut sym_sizeof = HashMap::new();
+    let mut sym_types = HashMap::new();
     for (sym_type, ty) in sym_types_table {
-        let sizeof = match ty.as_str() {
-            Some("uint64_t") => 8,
-            Some("uint32_t") => 4,
-            Some("uint16_t") => 2,
-            Some("uint8_t") => 1,
-            _ => return Err("Invalid type in litmus [types] table".to_string()),
+        let ty = ty.as_str().ok_or_else(|| "Invalid type in litmus [types] table".to_string())?;
+        sym_types.insert(sym_type.clone(), LocType::parse(ty)?);
+    }
+
+    Ok(sym_types)
+}
+
+/// The `[layout]` table can place locations at a fixed address, e.g.
+/// `x = { address = "0x1000" }`, or align them, e.g.
+/// `y = { align = "page" }` or `z = { align = 64 }`.
+fn parse_placements(litmus_toml: &Value) -> Result<HashMap<String, Placement>, String> {
+    let layout_table = match litmus_toml.get("layout") {
+        Some(value) => value
+            .as_table()
+            .ok_or_else(|| "[layout] must be a table of <symbolic address> = <placement> pairs".to_string())?,
+        None => return Ok(HashMap::new()),
+    };
+
+    let mut placements = HashMap::new();
+    for (sym_loc, placement) in layout_table {
+        let err = || format!("Invalid placement for {} in litmus [layout] table", sym_loc);
+        let placement = if let Some(address) = placement.get("address") {
+            Placement::Fixed(address.as_str().and_then(parse_u64).ok_or_else(err)?)
+        } else {
+            match placement.get("align") {
+                Some(Value::String(align)) if align == "page" => Placement::Aligned(PAGE_SIZE),
+                Some(Value::String(align)) => Placement::Aligned(parse_u64(align).ok_or_else(err)?),
+                Some(Value::Integer(align)) if *align > 0 => Placement::Aligned(*align as u64),
+                _ => return Err(err()),
+            }
         };
-        sym_sizeof.insert(sym_type.clone(), sizeof);
+        placements.insert(sym_loc.clone(), placement);
     }
 
-    Ok(sym_sizeof)
+    Ok(placements)
 }
 
-fn parse_init<B>(
+fn parse_init_value<B>(
     reg: &str,
-    value: &Value,
-    symbolic_addrs: &HashMap<String, u64>,
+    value: &str,
+    layout: &Layout,
+    page_tables: Option<&PageTables>,
     objdump: &str,
     symtab: &Symtab,
     isa: &ISAConfig<B>,
@@ -388,9 +454,30 @@ fn parse_init<B>(
         None => symtab.get(&zencode::encode(reg)).ok_or_else(|| format!("No register {} in thread init", reg))?,
     };
 
-    let value = value.as_str().ok_or_else(|| "Init value must be a string".to_string())?;
+    // With page tables, `pte(x)` is the address of the descriptor
+    // translating x, and `desc(x)` is a descriptor mapping a page to
+    // x, so tests can change the mappings
+    if let Some(page_tables) = page_tables {
+        if let Some(loc) = value.strip_prefix("pte(").and_then(|v| v.strip_suffix(')')) {
+            return layout
+                .value(loc)
+                .ok()
+                .and_then(|va| page_tables.descriptor_address(va))
+                .map(|desc| (reg, desc))
+                .ok_or_else(|| format!("No page table entry for {} in thread init", loc));
+        } else if let Some(loc) = value.strip_prefix("desc(").and_then(|v| v.strip_suffix(')')) {
+            return if loc == "invalid" {
+                Ok((reg, page_tables.invalid_descriptor()))
+            } else {
+                layout
+                    .value(loc)
+                    .map(|pa| (reg, page_tables.page_descriptor(pa)))
+                    .map_err(|_| format!("Invalid location for descriptor {} in thread init", loc))
+            };
+        }
+    }
 
-    match symbolic_addrs.get(value) {
+    match layout.addrs.get(value) {
         Some(addr) => Ok((reg, *addr)),
         None => {
             if value.starts_with("0x") {
@@ -406,16 +493,35 @@ fn parse_init<B>(
             } else {
                 match i64::from_str_radix(value, 10) {
                     Ok(n) => Ok((reg, n as u64)),
-                    Err(_) => Err(format!("Cannot handle initial value in litmus: {}", value)),
+                    // Otherwise the value could be a pointer into a
+                    // symbolic location, such as `x[1]` or `x+8`
+                    Err(_) => match layout.resolve(value) {
+                        Ok((addr, _)) => Ok((reg, addr)),
+                        Err(_) => Err(format!("Cannot handle initial value in litmus: {}", value)),
+                    },
                 }
             }
         }
     }
 }
 
+fn parse_init<B>(
+    reg: &str,
+    value: &Value,
+    layout: &Layout,
+    page_tables: Option<&PageTables>,
+    objdump: &str,
+    symtab: &Symtab,
+    isa: &ISAConfig<B>,
+) -> Result<(Name, u64), String> {
+    let value = value.as_str().ok_or_else(|| "Init value must be a string".to_string())?;
+    parse_init_value(reg, value, layout, page_tables, objdump, symtab, isa)
+}
+
 fn parse_thread_inits<'a, B>(
     thread: &'a Value,
-    symbolic_addrs: &HashMap<String, u64>,
+    layout: &Layout,
+    page_tables: Option<&PageTables>,
     objdump: &str,
     symtab: &Symtab,
     isa: &ISAConfig<B>,
@@ -427,10 +533,100 @@ fn parse_thread_inits<'a, B>(
 
     inits
         .iter()
-        .map(|(reg, value)| parse_init(reg, value, symbolic_addrs, objdump, symtab, isa))
+        .map(|(reg, value)| parse_init(reg, value, layout, page_tables, objdump, symtab, isa))
         .collect::<Result<_, _>>()
 }
 
+/// Litmus tests with a `[page_table]` section run with address
+/// translation, and require an `[mmu]` section in the ISA config.
+fn parse_page_tables<B>(
+    litmus_toml: &Value,
+    layout: &Layout,
+    initial_memory: &mut InitialMemory,
+    isa: &ISAConfig<B>,
+) -> Result<Option<PageTables>, String> {
+    let table = match litmus_toml.get("page_table") {
+        Some(value) => value.as_table().ok_or_else(|| {
+            "[page_table] must be a table of <virtual location> = <physical location> pairs".to_string()
+        })?,
+        None => return Ok(None),
+    };
+    let mmu = isa.mmu.as_ref().ok_or("Litmus test has page tables, but the ISA config has no [mmu] section")?;
+    let identity: Vec<Range<u64>> = iter::once(isa.thread_base..isa.thread_top)
+        .chain(isa.memory.iter().map(|region| region.range.clone()))
+        .collect();
+    let page_tables = PageTables::from_toml(table, layout, &identity, mmu)?;
+    page_tables.initialise(initial_memory);
+    Ok(Some(page_tables))
+}
+
+/// Parse the `[thread.N.handlers]` table for each thread, which maps
+/// offsets from the thread's exception vector base to handler code.
+/// Each thread with handlers gets its own vector table, and an
+/// initial value for the vector base register pointing at it.
+#[allow(clippy::type_complexity)]
+fn parse_handlers<'a, B: BV>(
+    threads: &'a toml::value::Table,
+    isa: &ISAConfig<B>,
+) -> Result<(Vec<(ThreadName, u64, &'a str)>, Vec<Option<(Name, u64)>>), String> {
+    let mut handlers = Vec::new();
+    let mut vector_inits = Vec::new();
+
+    for (i, (thread_name, thread)) in threads.iter().enumerate() {
+        let table = match thread.get("handlers") {
+            Some(table) => table.as_table().ok_or_else(|| {
+                format!("Handlers for thread {} must be a table of <offset> = <code> pairs", thread_name)
+            })?,
+            None => {
+                vector_inits.push(None);
+                continue;
+            }
+        };
+
+        let exceptions = isa
+            .exceptions
+            .as_ref()
+            .ok_or("Litmus test has exception handlers, but the ISA config has no [exceptions] section")?;
+        let vector_table = exceptions
+            .vector_region
+            .as_ref()
+            .and_then(|region| isa.memory_region(region))
+            .map(|region| region.range.clone())
+            .ok_or("Litmus test has exception handlers, but the ISA config has no exceptions.vector_region")?;
+        let vector_base = vector_table.start + i as u64 * exceptions.vector_size;
+        if vector_base + exceptions.vector_size > vector_table.end {
+            return Err(format!("No space for the exception vector table of thread {}", thread_name));
+        }
+
+        for (offset, code) in table {
+            let offset = match parse_u64(offset) {
+                Some(offset) if offset < exceptions.vector_size => offset,
+                _ => return Err(format!("Invalid exception vector offset {} for thread {}", offset, thread_name)),
+            };
+            let code = code
+                .as_str()
+                .ok_or_else(|| format!("Exception handler code for thread {} must be a string", thread_name))?;
+            handlers.push((format!("{}_handler_{:x}", thread_name, offset), vector_base + offset, code))
+        }
+
+        vector_inits.push(Some((exceptions.vector_register, vector_base)))
+    }
+
+    Ok((handlers, vector_inits))
+}
+
+/// Point the vector base register of each thread with handlers at its
+/// vector table, unless the thread sets the register explicitly.
+fn init_vector_bases(inits: &mut [Vec<(Name, u64)>], vector_inits: Vec<Option<(Name, u64)>>) {
+    for (init, vector_init) in inits.iter_mut().zip(vector_inits) {
+        if let Some((reg, vector_base)) = vector_init {
+            if init.iter().all(|(r, _)| *r != reg) {
+                init.push((reg, vector_base))
+            }
+        }
+    }
+}
+
 fn parse_assertion(assertion: &str) -> Result<Sexp<'_>, String> {
     let lexer = crate::sexp_lexer::SexpLexer::new(assertion);
     match crate::sexp_parser::SexpParser::new().parse(lexer) {
@@ -488,20 +684,44 @@ fn parse_self_modify<B: BV>(toml: &Value, objdump: &str) -> Result<Vec<Region<B>
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Loc {
     Register { reg: Name, thread_id: usize },
     LastWriteTo { address: u64, bytes: u32 },
 }
 
 impl Loc {
-    fn from_sexp<'a, B: BV>(
-        sexp: &Sexp<'a>,
-        symbolic_addrs: &HashMap<String, u64>,
-        symbolic_sizeof: &HashMap<String, u32>,
-        symtab: &Symtab,
-        isa: &ISAConfig<B>,
-    ) -> Option<Self> {
+    /// The width of the location in bits, if known without looking
+    /// at the architecture.
+    fn width(&self) -> Option<u32> {
+        match self {
+            Loc::Register { .. } => None,
+            Loc::LastWriteTo { bytes, .. } => Some(bytes * 8),
+        }
+    }
+
+    /// Returns a herd-style name for the location, i.e. `1:X0` for a
+    /// register of thread 1, and the symbolic name for a memory
+    /// location if it has one.
+    pub fn name<B>(&self, symbolic_addrs: &HashMap<String, u64>, symtab: &Symtab, isa: &ISAConfig<B>) -> String {
+        match self {
+            Loc::Register { reg, thread_id } => {
+                let name = match isa.register_renames.iter().find(|(_, r)| *r == reg) {
+                    Some((rename, _)) => rename.clone(),
+                    None => zencode::decode(symtab.to_str(*reg)),
+                };
+                format!("{}:{}", thread_id, name)
+            }
+            Loc::LastWriteTo { address, .. } => {
+                match symbolic_addrs.iter().filter(|(_, addr)| *addr == address).map(|(name, _)| name).min() {
+                    Some(name) => name.clone(),
+                    None => format!("[#x{:x}]", address),
+                }
+            }
+        }
+    }
+
+    fn from_sexp<'a, B: BV>(sexp: &Sexp<'a>, layout: &Layout, symtab: &Symtab, isa: &ISAConfig<B>) -> Option<Self> {
         use Loc::*;
         match sexp {
             Sexp::List(sexps) => {
@@ -513,11 +733,21 @@ impl Loc {
                     };
                     let thread_id = sexps[2].as_usize()?;
                     Some(Register { reg, thread_id })
-                } else if sexp.is_fn("last_write_to", 1) && sexps.len() == 2 {
-                    let symbolic_addr = sexps[1].as_str()?;
-                    let address = *symbolic_addrs.get(symbolic_addr)?;
-                    // Default is 32 bits (4 bytes) if unspecified
-                    let bytes = *symbolic_sizeof.get(symbolic_addr).unwrap_or(&4);
+                } else if sexp.is_fn("last_write_to", 1) && sexps.len() <= 3 {
+                    // The location is either a path to a symbolic
+                    // location, or a path plus an offset in bytes,
+                    // e.g. `(+ x 8)`.
+                    let (address, bytes) = match &sexps[1] {
+                        Sexp::List(addr) if sexps[1].is_fn("+", 2) && addr.len() == 3 => {
+                            let (address, bytes) = layout.access(addr[1].as_str()?).ok()?;
+                            (address + addr[2].as_u64()?, bytes)
+                        }
+                        addr => layout.access(addr.as_str()?).ok()?,
+                    };
+                    let bytes = match sexps.get(2) {
+                        Some(bytes) => bytes.as_u64()? as u32,
+                        None => bytes,
+                    };
                     Some(LastWriteTo { address, bytes })
                 } else {
                     None
@@ -528,9 +758,59 @@ impl Loc {
     }
 }
 
+/// Comparisons (other than equality) between a location and a value
+/// in a final assertion. The ordered comparisons are either unsigned
+/// or signed, and are performed at the width of the location.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cmp {
+    Ne,
+    Ult,
+    Ule,
+    Ugt,
+    Uge,
+    Slt,
+    Sle,
+    Sgt,
+    Sge,
+}
+
+impl Cmp {
+    /// Returns the comparison for the name used in the final assertion
+    /// of a TOML litmus file, which are the same as the SMTLIB names
+    /// except for `!=`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        use Cmp::*;
+        match name {
+            "!=" => Some(Ne),
+            "bvult" => Some(Ult),
+            "bvule" => Some(Ule),
+            "bvugt" => Some(Ugt),
+            "bvuge" => Some(Uge),
+            "bvslt" => Some(Slt),
+            "bvsle" => Some(Sle),
+            "bvsgt" => Some(Sgt),
+            "bvsge" => Some(Sge),
+            _ => None,
+        }
+    }
+
+    /// Returns the signed variant of an unsigned comparison
+    pub fn signed(self) -> Self {
+        use Cmp::*;
+        match self {
+            Ult => Slt,
+            Ule => Sle,
+            Ugt => Sgt,
+            Uge => Sge,
+            cmp => cmp,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Prop<B> {
     EqLoc(Loc, B),
+    CmpLoc(Cmp, Loc, B),
     True,
     False,
     And(Vec<Prop<B>>),
@@ -540,13 +820,69 @@ pub enum Prop<B> {
 }
 
 impl<B: BV> Prop<B> {
-    fn from_sexp<'a>(
-        sexp: &Sexp<'a>,
-        symbolic_addrs: &HashMap<String, u64>,
-        symbolic_sizeof: &HashMap<String, u32>,
-        symtab: &Symtab,
-        isa: &ISAConfig<B>,
-    ) -> Option<Self> {
+    /// Returns all the distinct locations mentioned by a proposition,
+    /// in the order they first appear.
+    pub fn locations(&self) -> Vec<&Loc> {
+        fn collect<'a, B>(prop: &'a Prop<B>, locs: &mut Vec<&'a Loc>) {
+            use Prop::*;
+            match prop {
+                EqLoc(loc, _) | CmpLoc(_, loc, _) => {
+                    if !locs.contains(&loc) {
+                        locs.push(loc)
+                    }
+                }
+                And(props) | Or(props) => props.iter().for_each(|prop| collect(prop, locs)),
+                Implies(prop1, prop2) => {
+                    collect(prop1, locs);
+                    collect(prop2, locs)
+                }
+                Not(prop) => collect(prop, locs),
+                True | False => (),
+            }
+        }
+        let mut locs = Vec::new();
+        collect(self, &mut locs);
+        locs
+    }
+
+    /// Evaluate a proposition given the final values of its
+    /// locations. As in the SMT encoding comparisons are performed at
+    /// the width of the location. Returns `None` if any location has
+    /// no value.
+    pub fn eval(&self, value: &dyn Fn(&Loc) -> Option<B>) -> Option<bool> {
+        use Prop::*;
+        let operands = |loc: &Loc, bv: B| -> Option<(B, B)> {
+            let v = value(loc)?;
+            let width = loc.width().unwrap_or_else(|| v.len());
+            Some((v.slice(0, width)?, bv.slice(0, width)?))
+        };
+        match self {
+            EqLoc(loc, bv) => operands(loc, *bv).map(|(lhs, rhs)| lhs == rhs),
+            CmpLoc(cmp, loc, bv) => {
+                let (lhs, rhs) = operands(loc, *bv)?;
+                let (ul, ur, sl, sr) = (lhs.lower_u64(), rhs.lower_u64(), lhs.signed(), rhs.signed());
+                Some(match cmp {
+                    Cmp::Ne => lhs != rhs,
+                    Cmp::Ult => ul < ur,
+                    Cmp::Ule => ul <= ur,
+                    Cmp::Ugt => ul > ur,
+                    Cmp::Uge => ul >= ur,
+                    Cmp::Slt => sl < sr,
+                    Cmp::Sle => sl <= sr,
+                    Cmp::Sgt => sl > sr,
+                    Cmp::Sge => sl >= sr,
+                })
+            }
+            True => Some(true),
+            False => Some(false),
+            And(props) => props.iter().try_fold(true, |b, prop| Some(prop.eval(value)? && b)),
+            Or(props) => props.iter().try_fold(false, |b, prop| Some(prop.eval(value)? || b)),
+            Not(prop) => prop.eval(value).map(|b| !b),
+            Implies(prop1, prop2) => Some(!prop1.eval(value)? || prop2.eval(value)?),
+        }
+    }
+
+    fn from_sexp<'a>(sexp: &Sexp<'a>, layout: &Layout, symtab: &Symtab, isa: &ISAConfig<B>) -> Option<Self> {
         use Prop::*;
         match sexp {
             Sexp::Atom("true") => Some(True),
@@ -554,32 +890,40 @@ impl<B: BV> Prop<B> {
             Sexp::List(sexps) => {
                 if sexp.is_fn("=", 2) && sexps.len() == 3 {
                     Some(EqLoc(
-                        Loc::from_sexp(&sexps[1], symbolic_addrs, symbolic_sizeof, symtab, isa)?,
+                        Loc::from_sexp(&sexps[1], layout, symtab, isa)?,
                         match sexps[2].as_u64() {
                             Some(n) => B::from_u64(n),
-                            None => B::from_u64(*symbolic_addrs.get(sexps[2].as_str()?)?),
+                            None => B::from_u64(layout.value(sexps[2].as_str()?).ok()?),
+                        },
+                    ))
+                } else if sexps.len() == 3 && sexps[0].as_str().and_then(Cmp::from_name).is_some() {
+                    Some(CmpLoc(
+                        Cmp::from_name(sexps[0].as_str()?)?,
+                        Loc::from_sexp(&sexps[1], layout, symtab, isa)?,
+                        match sexps[2].as_u64() {
+                            Some(n) => B::from_u64(n),
+                            None => B::from_u64(layout.value(sexps[2].as_str()?).ok()?),
                         },
                     ))
                 } else if sexp.is_fn("and", 1) {
                     sexps[1..]
                         .iter()
-                        .map(|s| Prop::from_sexp(s, symbolic_addrs, symbolic_sizeof, symtab, isa))
+                        .map(|s| Prop::from_sexp(s, layout, symtab, isa))
                         .collect::<Option<_>>()
                         .map(Prop::And)
                 } else if sexp.is_fn("or", 1) {
                     sexps[1..]
                         .iter()
-                        .map(|s| Prop::from_sexp(s, symbolic_addrs, symbolic_sizeof, symtab, isa))
+                        .map(|s| Prop::from_sexp(s, layout, symtab, isa))
                         .collect::<Option<_>>()
                         .map(Prop::Or)
                 } else if sexp.is_fn("=>", 2) && sexps.len() == 3 {
                     Some(Prop::Implies(
-                        Box::new(Prop::from_sexp(&sexps[1], symbolic_addrs, symbolic_sizeof, symtab, isa)?),
-                        Box::new(Prop::from_sexp(&sexps[2], symbolic_addrs, symbolic_sizeof, symtab, isa)?),
+                        Box::new(Prop::from_sexp(&sexps[1], layout, symtab, isa)?),
+                        Box::new(Prop::from_sexp(&sexps[2], layout, symtab, isa)?),
                     ))
                 } else if sexp.is_fn("not", 1) && sexps.len() == 2 {
-                    Prop::from_sexp(&sexps[1], symbolic_addrs, symbolic_sizeof, symtab, isa)
-                        .map(|s| Prop::Not(Box::new(s)))
+                    Prop::from_sexp(&sexps[1], layout, symtab, isa).map(|s| Prop::Not(Box::new(s)))
                 } else {
                     None
                 }
@@ -589,17 +933,74 @@ impl<B: BV> Prop<B> {
     }
 }
 
+/// The quantifier for the final condition of a litmus test, as in
+/// herd7. The final assertion in a `Litmus` is always checked
+/// existentially, so for `Forall` it is the negation of the
+/// condition, i.e. an execution that violates it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quantifier {
+    Exists,
+    NotExists,
+    Forall,
+}
+
+impl Quantifier {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "exists" => Some(Quantifier::Exists),
+            "~exists" => Some(Quantifier::NotExists),
+            "forall" => Some(Quantifier::Forall),
+            _ => None,
+        }
+    }
+
+    /// Whether the condition holds, given whether any allowed
+    /// execution satisfies the final assertion.
+    pub fn holds(self, observed: bool) -> bool {
+        match self {
+            Quantifier::Exists => observed,
+            Quantifier::NotExists | Quantifier::Forall => !observed,
+        }
+    }
+
+    /// The kind of the test in herd's output, i.e. whether the
+    /// condition is expected to be allowed, forbidden, or required.
+    pub fn herd_kind(self) -> &'static str {
+        match self {
+            Quantifier::Exists => "Allowed",
+            Quantifier::NotExists => "Forbidden",
+            Quantifier::Forall => "Required",
+        }
+    }
+}
+
+impl fmt::Display for Quantifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Quantifier::Exists => write!(f, "exists"),
+            Quantifier::NotExists => write!(f, "~exists"),
+            Quantifier::Forall => write!(f, "forall"),
+        }
+    }
+}
+
 pub type AssembledThread = (ThreadName, Vec<(Name, u64)>, Vec<u8>);
 
 pub struct Litmus<B> {
     pub name: String,
     pub hash: Option<String>,
     pub symbolic_addrs: HashMap<String, u64>,
-    pub symbolic_locations: HashMap<String, u64>,
+    pub initial_memory: InitialMemory,
     pub symbolic_sizeof: HashMap<String, u32>,
     pub assembled: Vec<AssembledThread>,
     pub self_modify_regions: Vec<Region<B>>,
+    /// Page tables for tests which run with address translation
+    pub page_tables: Option<PageTables>,
+    /// Assembled exception handlers and the addresses they are
+    /// placed at
+    pub exception_handlers: Vec<(u64, Vec<u8>)>,
     pub objdump: String,
+    pub quantifier: Quantifier,
     pub final_assertion: Prop<B>,
 }
 
@@ -609,6 +1010,7 @@ impl<B: BV> Litmus<B> {
         log!(log::LITMUS, &format!("Litmus test hash: {:?}", self.hash));
         log!(log::LITMUS, &format!("Litmus test symbolic addresses: {:?}", self.symbolic_addrs));
         log!(log::LITMUS, &format!("Litmus test data: {:#?}", self.assembled));
+        log!(log::LITMUS, &format!("Litmus test quantifier: {}", self.quantifier));
         log!(log::LITMUS, &format!("Litmus test final assertion: {:?}", self.final_assertion));
     }
 
@@ -628,20 +1030,16 @@ impl<B: BV> Litmus<B> {
         let symbolic = litmus_toml
             .get("symbolic")
             .and_then(Value::as_array)
-            .ok_or("No symbolic addresses found in litmus file")?;
-        let symbolic_addrs = symbolic
+            .ok_or("No symbolic addresses found in litmus file")?
             .iter()
-            .enumerate()
-            .map(|(i, sym_addr)| match sym_addr.as_str() {
-                Some(sym_addr) => {
-                    Ok((sym_addr.to_string(), isa.symbolic_addr_base + (i as u64 * isa.symbolic_addr_stride)))
-                }
-                None => Err("Symbolic addresses must be strings"),
-            })
-            .collect::<Result<_, _>>()?;
+            .map(|sym_addr| sym_addr.as_str().map(str::to_string).ok_or("Symbolic addresses must be strings"))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let symbolic_locations = parse_symbolic_locations(&litmus_toml, &symbolic_addrs)?;
-        let symbolic_sizeof = parse_symbolic_types(&litmus_toml)?;
+        let layout =
+            Layout::allocate(&symbolic, parse_symbolic_types(&litmus_toml)?, &parse_placements(&litmus_toml)?, isa)?;
+        let mut initial_memory = parse_symbolic_locations(&litmus_toml, &layout)?;
+        let page_tables = parse_page_tables(&litmus_toml, &layout, &mut initial_memory, isa)?;
+        let symbolic_sizeof = layout.sizeof();
 
         let threads = litmus_toml.get("thread").and_then(|t| t.as_table()).ok_or("No threads found in litmus file")?;
 
@@ -654,13 +1052,16 @@ impl<B: BV> Litmus<B> {
                     .ok_or_else(|| format!("No code found for thread {}", thread_name))
             })
             .collect::<Result<_, _>>()?;
-        let (mut assembled, objdump) = assemble(&code, true, isa)?;
+        let (handlers, vector_inits) = parse_handlers(threads, isa)?;
+        let (mut assembled, exception_handlers, objdump) = assemble(&code, &handlers, true, isa)?;
 
         let mut inits: Vec<Vec<(Name, u64)>> = threads
             .iter()
-            .map(|(_, thread)| parse_thread_inits(thread, &symbolic_addrs, &objdump, symtab, isa))
+            .map(|(_, thread)| parse_thread_inits(thread, &layout, page_tables.as_ref(), &objdump, symtab, isa))
             .collect::<Result<_, _>>()?;
 
+        init_vector_bases(&mut inits, vector_inits);
+
         let assembled = assembled
             .drain(..)
             .zip(inits.drain(..))
@@ -670,23 +1071,41 @@ impl<B: BV> Litmus<B> {
         let self_modify_regions = parse_self_modify::<B>(&litmus_toml, &objdump)?;
 
         let fin = litmus_toml.get("final").ok_or("No final section found in litmus file")?;
+
+        // Older files produced by isla-litmus have no quantifier, and
+        // instead set `expect = "unsat"` for both `~exists` and
+        // (negated) `forall` conditions.
+        let quantifier = match fin.get("quantifier").and_then(Value::as_str) {
+            Some(quantifier) => Quantifier::from_name(quantifier)
+                .ok_or_else(|| format!("Invalid quantifier {} in litmus file", quantifier))?,
+            None if fin.get("expect").and_then(Value::as_str) == Some("unsat") => Quantifier::NotExists,
+            None => Quantifier::Exists,
+        };
+
         let final_assertion = (match fin.get("assertion").and_then(Value::as_str) {
             Some(assertion) => parse_assertion(assertion).and_then(|s| {
-                Prop::from_sexp(&s, &symbolic_addrs, &symbolic_sizeof, symtab, isa)
-                    .ok_or_else(|| "Cannot parse final assertion".to_string())
+                Prop::from_sexp(&s, &layout, symtab, isa).ok_or_else(|| "Cannot parse final assertion".to_string())
             }),
             None => Err("No final.assertion found in litmus file".to_string()),
         })?;
 
+        let final_assertion = match (quantifier, fin.get("quantifier")) {
+            (Quantifier::Forall, Some(_)) => Prop::Not(Box::new(final_assertion)),
+            _ => final_assertion,
+        };
+
         Ok(Litmus {
             name,
             hash,
-            symbolic_addrs,
-            symbolic_locations,
+            symbolic_addrs: layout.addrs,
+            initial_memory,
             symbolic_sizeof,
             assembled,
             self_modify_regions,
+            page_tables,
+            exception_handlers,
             objdump,
+            quantifier,
             final_assertion,
         })
     }
@@ -704,6 +1123,136 @@ impl<B: BV> Litmus<B> {
             Err(e) => return Err(format!("Error when loading litmus '{}': {}", path.as_ref().display(), e)),
         };
 
-        Self::parse(&contents, symtab, isa)
+        if matches!(path.as_ref().extension(), Some(ext) if ext == "litmus") {
+            Self::parse_herd(&contents, symtab, isa)
+        } else {
+            Self::parse(&contents, symtab, isa)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use isla_lib::concrete::bitvector64::B64;
+
+    #[test]
+    fn prop_eval() {
+        use Prop::*;
+        let x = Loc::LastWriteTo { address: 0x1000, bytes: 4 };
+        let y = Loc::LastWriteTo { address: 0x2000, bytes: 1 };
+        let prop: Prop<B64> = And(vec![
+            EqLoc(x.clone(), B64::from_u64(1)),
+            Not(Box::new(CmpLoc(Cmp::Slt, y.clone(), B64::from_u64(0)))),
+            EqLoc(x.clone(), B64::from_u64(0x1_0000_0001)),
+        ]);
+        assert_eq!(prop.locations(), vec![&x, &y]);
+
+        let value = |loc: &Loc| if *loc == x { Some(B64::new(1, 32)) } else { Some(B64::new(0x7f, 8)) };
+        assert_eq!(prop.eval(&value), Some(true));
+        let value = |loc: &Loc| if *loc == x { Some(B64::new(1, 32)) } else { Some(B64::new(0x80, 8)) };
+        assert_eq!(prop.eval(&value), Some(false));
+        assert_eq!(prop.eval(&|_| None), None)
+    }
+
+    #[test]
+    fn quantifier_herd_kind() {
+        // A ~exists test is forbidden, and holds if nothing is observed
+        let not_exists = Quantifier::from_name("~exists").unwrap();
+        assert_eq!(not_exists.herd_kind(), "Forbidden");
+        assert!(not_exists.holds(false));
+        assert!(!not_exists.holds(true));
+
+        assert_eq!(Quantifier::Exists.herd_kind(), "Allowed");
+        assert_eq!(Quantifier::Forall.herd_kind(), "Required")
+    }
+
+    #[test]
+    fn thread_handlers() {
+        let mut symtab = Symtab::new();
+        symtab.intern("zPC");
+        symtab.intern("zRead_ifetch");
+        let vbar = symtab.intern("zVBAR_EL1");
+        let r0 = symtab.intern("zR0");
+        let config = r#"
+            pc = "PC"
+            ifetch = "Read_ifetch"
+            read_exclusives = []
+            write_exclusives = []
+            assembler = "sh"
+            objdump = "sh"
+            linker = "sh"
+
+            [threads]
+            base = "0x400000"
+            top = "0x500000"
+            stride = "0x10000"
+
+            [symbolic_addrs]
+            base = "0x600000"
+            stride = "0x10"
+
+            [reads]
+
+            [writes]
+
+            [cache_ops]
+
+            [exceptions]
+            vector_register = "VBAR_EL1"
+            vector_size = "0x800"
+            vector_region = "vectors"
+
+            [memory.vectors]
+            kind = "concrete"
+            base = "0x10000"
+            top = "0x11000"
+        "#;
+        let isa = ISAConfig::<B64>::parse(config, &symtab).unwrap();
+
+        let litmus = r#"
+            [thread.0]
+            code = "nop"
+
+            [thread.1]
+            code = "nop"
+
+            [thread.1.handlers]
+            "0x400" = "eret"
+        "#
+        .parse::<Value>()
+        .unwrap();
+        let threads = litmus["thread"].as_table().unwrap();
+        let (handlers, vector_inits) = parse_handlers(threads, &isa).unwrap();
+        assert_eq!(handlers, vec![("1_handler_400".to_string(), 0x10c00, "eret")]);
+        assert_eq!(vector_inits, vec![None, Some((vbar, 0x10800))]);
+
+        // An explicit initial value for the vector base register is kept
+        let mut inits = vec![vec![(r0, 1)], vec![(vbar, 0x10000)]];
+        init_vector_bases(&mut inits, vector_inits.clone());
+        assert_eq!(inits, vec![vec![(r0, 1)], vec![(vbar, 0x10000)]]);
+        let mut inits = vec![vec![], vec![(r0, 1)]];
+        init_vector_bases(&mut inits, vector_inits);
+        assert_eq!(inits, vec![vec![], vec![(r0, 1), (vbar, 0x10800)]]);
+
+        // Handlers must fit within the vector table, and each
+        // thread's table must fit in the region
+        let litmus = r#"
+            [thread.0.handlers]
+            "0x800" = "eret"
+        "#
+        .parse::<Value>()
+        .unwrap();
+        assert!(parse_handlers(litmus["thread"].as_table().unwrap(), &isa).is_err());
+        let litmus = r#"
+            [thread.0]
+            [thread.1]
+            [thread.2]
+            [thread.2.handlers]
+            "0x0" = "eret"
+        "#
+        .parse::<Value>()
+        .unwrap();
+        assert!(parse_handlers(litmus["thread"].as_table().unwrap(), &isa).is_err())
     }
 }
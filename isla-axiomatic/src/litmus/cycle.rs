@@ -0,0 +1,653 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module generates litmus tests from relaxation cycles, in the
+//! style of the diy tool from herdtools. A cycle such as `PodWW Rfe
+//! PodRR Fre` is a sequence of edges between memory events, and the
+//! generated test asks whether the cycle can be observed. The
+//! instructions for each event come from the templates in the
+//! `[generator]` section of the ISA configuration.
+//!
+//! The supported edges are:
+//!
+//! * `Rfe`, `Rfi`, `Fre`, `Fri`, `Coe`, and `Coi` (or `Wse` and
+//!   `Wsi`) for communication between events to the same location,
+//!   either external (between threads) or internal.
+//! * `PodXY` and `PosXY` for program order between events to
+//!   different or the same locations, where `X` and `Y` are `R` or `W`.
+//! * `<fence>dXY` and `<fence>sXY`, e.g. `DMB.SYdWW`, for program
+//!   order with a fence in between.
+//! * `DpAddrdY`, `DpDatadW`, and `DpCtrldY` (or with `s` for the same
+//!   location) for dependencies from a read.
+//!
+//! Each edge can be followed by two annotation letters for its source
+//! and target events, as in `PodWRLA`, which select the read and write
+//! templates. Events without an annotation use the `P` templates.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use isla_lib::concrete::BV;
+use isla_lib::config::{CycleTemplates, ISAConfig};
+use isla_lib::ir::Symtab;
+
+use super::Litmus;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dir {
+    R,
+    W,
+}
+
+impl Dir {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            'R' => Some(Dir::R),
+            'W' => Some(Dir::W),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EdgeKind {
+    Rf,
+    Fr,
+    Co,
+    Po,
+    Fence(String),
+    Dep(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    /// True if the edge is between events on different threads
+    pub external: bool,
+    /// True if the edge is between events to the same location
+    pub same_loc: bool,
+    pub source: Dir,
+    pub target: Dir,
+    /// Optional annotations for the source and target events
+    pub annotations: Option<(char, char)>,
+}
+
+fn parse_annotations(edge: &str, suffix: &str) -> Result<Option<(char, char)>, String> {
+    let chars: Vec<char> = suffix.chars().collect();
+    match chars.as_slice() {
+        [] => Ok(None),
+        [a1, a2] if a1.is_ascii_uppercase() && a2.is_ascii_uppercase() => Ok(Some((*a1, *a2))),
+        _ => Err(format!("Invalid annotations {} for edge {}", suffix, edge)),
+    }
+}
+
+fn parse_dirs(edge: &str, suffix: &str) -> Result<(Dir, Dir, usize), String> {
+    let mut chars = suffix.chars();
+    match (chars.next().and_then(Dir::from_char), chars.next().and_then(Dir::from_char)) {
+        (Some(source), Some(target)) => Ok((source, target, 2)),
+        _ => Err(format!("Edge {} must specify the directions of its events, e.g. {}WR", edge, edge)),
+    }
+}
+
+fn parse_loc(edge: &str, c: Option<char>) -> Result<bool, String> {
+    match c {
+        Some('s') => Ok(true),
+        Some('d') => Ok(false),
+        _ => Err(format!("Edge {} must specify either d or s for different or same locations", edge)),
+    }
+}
+
+impl Edge {
+    pub fn parse(edge: &str, templates: &CycleTemplates) -> Result<Self, String> {
+        use Dir::*;
+        use EdgeKind::*;
+
+        let communication = [
+            ("Rfe", Rf, true, W, R),
+            ("Rfi", Rf, false, W, R),
+            ("Fre", Fr, true, R, W),
+            ("Fri", Fr, false, R, W),
+            ("Coe", Co, true, W, W),
+            ("Coi", Co, false, W, W),
+            ("Wse", Co, true, W, W),
+            ("Wsi", Co, false, W, W),
+        ];
+        for (name, kind, external, source, target) in communication.iter() {
+            if let Some(suffix) = edge.strip_prefix(name) {
+                return Ok(Edge {
+                    kind: kind.clone(),
+                    external: *external,
+                    same_loc: true,
+                    source: *source,
+                    target: *target,
+                    annotations: parse_annotations(edge, suffix)?,
+                });
+            }
+        }
+
+        if let Some(suffix) = edge.strip_prefix("Po") {
+            let same_loc = parse_loc(edge, suffix.chars().next())?;
+            let (source, target, n) = parse_dirs(edge, &suffix[1..])?;
+            return Ok(Edge {
+                kind: Po,
+                external: false,
+                same_loc,
+                source,
+                target,
+                annotations: parse_annotations(edge, &suffix[1 + n..])?,
+            });
+        }
+
+        if let Some(suffix) = edge.strip_prefix("Dp") {
+            let dep = ["Addr", "Data", "Ctrl"]
+                .iter()
+                .find(|dep| suffix.starts_with(*dep))
+                .ok_or_else(|| format!("Unknown dependency in edge {}", edge))?;
+            let suffix = &suffix[dep.len()..];
+            let same_loc = parse_loc(edge, suffix.chars().next())?;
+            let target = suffix[1..]
+                .chars()
+                .next()
+                .and_then(Dir::from_char)
+                .ok_or_else(|| format!("Edge {} must specify the direction of its target, e.g. {}R", edge, edge))?;
+            if *dep == "Data" && target == R {
+                return Err(format!("Data dependency {} must target a write", edge));
+            }
+            return Ok(Edge {
+                kind: Dep(dep.to_lowercase()),
+                external: false,
+                same_loc,
+                source: R,
+                target,
+                annotations: parse_annotations(edge, &suffix[2..])?,
+            });
+        }
+
+        // Fence names can be prefixes of each other, like DMB.ISH and
+        // DMB.ISHLD, so we pick the longest one followed by d or s.
+        let fence = templates
+            .fences
+            .keys()
+            .filter(|fence| match edge.strip_prefix(fence.as_str()) {
+                Some(suffix) => suffix.starts_with('d') || suffix.starts_with('s'),
+                None => false,
+            })
+            .max_by_key(|fence| fence.len());
+        if let Some(fence) = fence {
+            let suffix = &edge[fence.len()..];
+            let same_loc = parse_loc(edge, suffix.chars().next())?;
+            let (source, target, n) = parse_dirs(edge, &suffix[1..])?;
+            return Ok(Edge {
+                kind: Fence(fence.to_string()),
+                external: false,
+                same_loc,
+                source,
+                target,
+                annotations: parse_annotations(edge, &suffix[1 + n..])?,
+            });
+        }
+
+        Err(format!("Unknown edge {}", edge))
+    }
+}
+
+/// Parse a whitespace separated sequence of edges
+pub fn parse_cycle(cycle: &str, templates: &CycleTemplates) -> Result<Vec<Edge>, String> {
+    cycle.split_whitespace().map(|edge| Edge::parse(edge, templates)).collect()
+}
+
+/// A memory event in the cycle
+#[derive(Debug)]
+struct Event {
+    dir: Dir,
+    annotation: char,
+    thread: usize,
+    loc: usize,
+}
+
+const LOCATION_NAMES: [&str; 26] = [
+    "x", "y", "z", "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t",
+    "u", "v", "w",
+];
+
+fn find(classes: &mut [usize], i: usize) -> usize {
+    if classes[i] != i {
+        let root = find(classes, classes[i]);
+        classes[i] = root
+    }
+    classes[i]
+}
+
+/// Compute the events of a cycle, which must already be rotated so
+/// the first event is the first event on thread 0. Edge `i` goes
+/// from event `i` to event `i + 1`, with the last edge returning to
+/// the first event.
+fn events(edges: &[Edge]) -> Result<Vec<Event>, String> {
+    let n = edges.len();
+    let mut classes: Vec<usize> = (0..n).collect();
+    for (i, edge) in edges.iter().enumerate() {
+        if edge.same_loc {
+            let (c1, c2) = (find(&mut classes, i), find(&mut classes, (i + 1) % n));
+            classes[c1] = c2
+        }
+    }
+    for (i, edge) in edges.iter().enumerate() {
+        if !edge.same_loc && find(&mut classes, i) == find(&mut classes, (i + 1) % n) {
+            return Err(format!("Events on either side of edge {} must be to different locations", i));
+        }
+    }
+
+    let mut loc_names: HashMap<usize, usize> = HashMap::new();
+    let mut events = Vec::new();
+    let mut thread = 0;
+    for i in 0..n {
+        let incoming = &edges[(i + n - 1) % n];
+        let outgoing = &edges[i];
+        if incoming.target != outgoing.source {
+            return Err(format!(
+                "Edges {} and {} disagree on the direction of the event between them",
+                (i + n - 1) % n,
+                i
+            ));
+        }
+        let annotation = match (incoming.annotations.map(|a| a.1), outgoing.annotations.map(|a| a.0)) {
+            (Some(a1), Some(a2)) if a1 != a2 => {
+                return Err(format!(
+                    "Edges {} and {} have conflicting annotations {} and {}",
+                    (i + n - 1) % n,
+                    i,
+                    a1,
+                    a2
+                ))
+            }
+            (Some(a), _) | (_, Some(a)) => a,
+            (None, None) => 'P',
+        };
+        let class = find(&mut classes, i);
+        let next_loc = loc_names.len();
+        let loc = *loc_names.entry(class).or_insert(next_loc);
+        if loc >= LOCATION_NAMES.len() {
+            return Err("Too many locations in cycle".to_string());
+        }
+        events.push(Event { dir: outgoing.source, annotation, thread, loc });
+        if outgoing.external {
+            thread += 1
+        }
+    }
+    Ok(events)
+}
+
+/// Returns the coherence order for the writes to each location, as
+/// indices into the events.
+fn coherence(edges: &[Edge], events: &[Event]) -> Result<BTreeMap<usize, Vec<usize>>, String> {
+    let n = edges.len();
+    let mut before: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, edge) in edges.iter().enumerate() {
+        if edge.kind == EdgeKind::Co {
+            before.entry((i + 1) % n).or_default().push(i)
+        } else if edge.kind == EdgeKind::Rf && edges[(i + 1) % n].kind == EdgeKind::Fr {
+            // A write read by a read must be coherence-before any write
+            // the read is from-read-before
+            before.entry((i + 2) % n).or_default().push(i)
+        }
+    }
+
+    let mut co: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for event in events.iter() {
+        if event.dir == Dir::W {
+            co.entry(event.loc).or_default();
+        }
+    }
+    for (loc, order) in co.iter_mut() {
+        let mut remaining: Vec<usize> = (0..n).filter(|i| events[*i].dir == Dir::W && events[*i].loc == *loc).collect();
+        while !remaining.is_empty() {
+            let next = remaining
+                .iter()
+                .position(|w| before.get(w).map(|ws| ws.iter().all(|w| order.contains(w))).unwrap_or(true))
+                .ok_or_else(|| {
+                    format!("No coherence order for location {} is consistent with the cycle", LOCATION_NAMES[*loc])
+                })?;
+            order.push(remaining.remove(next))
+        }
+    }
+    Ok(co)
+}
+
+struct ThreadBuilder<'a> {
+    tid: usize,
+    registers: std::slice::Iter<'a, String>,
+    addrs: HashMap<usize, String>,
+    init: Vec<(String, String)>,
+    code: String,
+    labels: usize,
+}
+
+impl<'a> ThreadBuilder<'a> {
+    fn fresh(&mut self) -> Result<String, String> {
+        self.registers.next().cloned().ok_or_else(|| format!("Ran out of registers for thread {}", self.tid))
+    }
+
+    fn fresh_init(&mut self, value: String) -> Result<String, String> {
+        let reg = self.fresh()?;
+        self.init.push((reg.clone(), value));
+        Ok(reg)
+    }
+
+    fn addr(&mut self, loc: usize) -> Result<String, String> {
+        if let Some(reg) = self.addrs.get(&loc) {
+            return Ok(reg.clone());
+        }
+        let reg = self.fresh_init(LOCATION_NAMES[loc].to_string())?;
+        self.addrs.insert(loc, reg.clone());
+        Ok(reg)
+    }
+
+    /// Add the instructions in a template to the thread. Variables
+    /// not bound in `env` are allocated as they are used, with
+    /// `lazy` giving the initial values for some of them.
+    fn instantiate(
+        &mut self,
+        template: &[String],
+        env: &mut HashMap<String, String>,
+        lazy: &HashMap<&str, String>,
+    ) -> Result<(), String> {
+        for instr in template {
+            let mut chars = instr.chars().peekable();
+            let mut line = String::new();
+            while let Some(c) = chars.next() {
+                if c != '$' {
+                    line.push(c);
+                    continue;
+                }
+                let mut var = String::new();
+                while let Some(c) = chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_') {
+                    var.push(*c);
+                    chars.next();
+                }
+                if !env.contains_key(&var) {
+                    let value = match (var.as_str(), lazy.get(var.as_str())) {
+                        (_, Some(init)) => self.fresh_init(init.clone())?,
+                        ("tmp", None) => self.fresh()?,
+                        ("label", None) => {
+                            self.labels += 1;
+                            format!("L{}_{}", self.tid, self.labels)
+                        }
+                        _ => return Err(format!("Unknown variable ${} in template {}", var, instr)),
+                    };
+                    env.insert(var.clone(), value);
+                }
+                line.push_str(&env[&var])
+            }
+            writeln!(self.code, "\t{}", line).unwrap()
+        }
+        Ok(())
+    }
+}
+
+/// A litmus test generated from a cycle
+#[derive(Debug)]
+pub struct Generated {
+    pub name: String,
+    /// The final assertion which holds if the cycle is observed
+    pub assertion: String,
+    /// The test in the TOML litmus format
+    pub toml: String,
+}
+
+/// Generate a litmus test from a cycle. If no name is given, the
+/// test is named after its edges.
+pub fn generate(name: Option<&str>, cycle: &str, templates: &CycleTemplates) -> Result<Generated, String> {
+    let mut edges = parse_cycle(cycle, templates)?;
+    let name = name.map(str::to_string).unwrap_or_else(|| cycle.split_whitespace().collect::<Vec<_>>().join("+"));
+
+    if edges.iter().filter(|edge| edge.external).count() < 2 {
+        return Err("A cycle must contain at least two external edges".to_string());
+    }
+    // Rotate the cycle so it starts just after an external edge, and
+    // therefore at the beginning of a thread. Using the last external
+    // edge keeps cycles that already start a thread as they are.
+    let n = edges.len();
+    let last = edges.iter().rposition(|edge| edge.external).unwrap();
+    edges.rotate_left((last + 1) % n);
+
+    let events = events(&edges)?;
+    let co = coherence(&edges, &events)?;
+
+    let mut values: HashMap<usize, u64> = HashMap::new();
+    let mut expected: HashMap<usize, u64> = HashMap::new();
+    for order in co.values() {
+        for (i, w) in order.iter().enumerate() {
+            values.insert(*w, i as u64 + 1);
+            expected.insert(*w, i as u64);
+        }
+    }
+
+    // The value each read must see for the cycle to be observed, if
+    // it is constrained by the cycle
+    let mut read_values: HashMap<usize, u64> = HashMap::new();
+    for (i, event) in events.iter().enumerate() {
+        if event.dir != Dir::R {
+            continue;
+        }
+        let incoming = (i + n - 1) % n;
+        if edges[incoming].kind == EdgeKind::Rf {
+            read_values.insert(i, values[&incoming]);
+        } else if edges[i].kind == EdgeKind::Fr {
+            read_values.insert(i, expected[&((i + 1) % n)]);
+        }
+    }
+
+    let threads = events.last().unwrap().thread + 1;
+    let mut builders: Vec<ThreadBuilder> = (0..threads)
+        .map(|tid| ThreadBuilder {
+            tid,
+            registers: templates.registers.iter(),
+            addrs: HashMap::new(),
+            init: Vec::new(),
+            code: String::new(),
+            labels: 0,
+        })
+        .collect();
+
+    let mut read_registers: HashMap<usize, String> = HashMap::new();
+    for (i, event) in events.iter().enumerate() {
+        let thread = &mut builders[event.thread];
+        let mut env: HashMap<String, String> = HashMap::new();
+        env.insert("addr".to_string(), thread.addr(event.loc)?);
+        env.insert(
+            "value".to_string(),
+            match event.dir {
+                Dir::R => thread.fresh()?,
+                Dir::W => thread.fresh_init(values[&i].to_string())?,
+            },
+        );
+
+        let incoming = &edges[(i + n - 1) % n];
+        if !incoming.external {
+            match &incoming.kind {
+                EdgeKind::Fence(fence) => {
+                    thread.instantiate(&templates.fences[fence], &mut HashMap::new(), &HashMap::new())?
+                }
+                EdgeKind::Dep(dep) => {
+                    let template = templates
+                        .dependencies
+                        .get(dep)
+                        .ok_or_else(|| format!("No template for {} dependencies", dep))?;
+                    let mut dep_env = env.clone();
+                    dep_env.insert("src".to_string(), read_registers[&((i + n - 1) % n)].clone());
+                    thread.instantiate(template, &mut dep_env, &HashMap::new())?;
+                    let rebind = match dep.as_str() {
+                        "addr" => Some("addr"),
+                        "data" => Some("value"),
+                        _ => None,
+                    };
+                    if let (Some(var), Some(tmp)) = (rebind, dep_env.get("tmp")) {
+                        env.insert(var.to_string(), tmp.clone());
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let annotation = event.annotation.to_string();
+        let (template, lazy) = match event.dir {
+            Dir::R => (templates.reads.get(&annotation), HashMap::new()),
+            Dir::W => {
+                let mut lazy = HashMap::new();
+                lazy.insert("expected", expected[&i].to_string());
+                (templates.writes.get(&annotation), lazy)
+            }
+        };
+        let template = template.ok_or_else(|| {
+            format!(
+                "No template for {} with annotation {}",
+                if event.dir == Dir::R { "reads" } else { "writes" },
+                annotation
+            )
+        })?;
+        thread.instantiate(template, &mut env, &lazy)?;
+        if event.dir == Dir::R {
+            read_registers.insert(i, env["value"].clone());
+        }
+    }
+
+    let mut props = Vec::new();
+    for (i, event) in events.iter().enumerate() {
+        if let Some(value) = read_values.get(&i) {
+            props.push(format!("(= (register {} {}) {})", read_registers[&i], event.thread, value))
+        }
+    }
+    for (loc, order) in co.iter() {
+        if order.len() > 1 {
+            props.push(format!("(= (last_write_to {}) {})", LOCATION_NAMES[*loc], values[order.last().unwrap()]))
+        }
+    }
+    let assertion = match props.len() {
+        0 => "true".to_string(),
+        1 => props.pop().unwrap(),
+        _ => format!("(and {})", props.join(" ")),
+    };
+
+    let locs = events.iter().map(|event| event.loc).max().unwrap() + 1;
+    let mut toml = String::new();
+    writeln!(toml, "name = \"{}\"", name).unwrap();
+    writeln!(toml, "cycle = \"{}\"", cycle.split_whitespace().collect::<Vec<_>>().join(" ")).unwrap();
+    let symbolic: Vec<String> = LOCATION_NAMES[..locs].iter().map(|loc| format!("\"{}\"", loc)).collect();
+    writeln!(toml, "symbolic = [{}]", symbolic.join(", ")).unwrap();
+    writeln!(toml, "\n[types]").unwrap();
+    for loc in &LOCATION_NAMES[..locs] {
+        writeln!(toml, "{} = \"uint{}_t\"", loc, templates.bytes * 8).unwrap()
+    }
+    for thread in builders {
+        writeln!(toml, "\n[thread.{}]", thread.tid).unwrap();
+        let init: Vec<String> = thread.init.iter().map(|(reg, value)| format!("{} = \"{}\"", reg, value)).collect();
+        writeln!(toml, "init = {{ {} }}", init.join(", ")).unwrap();
+        writeln!(toml, "code = \"\"\"\n{}\"\"\"", thread.code).unwrap()
+    }
+    writeln!(toml, "\n[final]").unwrap();
+    writeln!(toml, "assertion = \"{}\"", assertion).unwrap();
+    writeln!(toml, "quantifier = \"exists\"").unwrap();
+
+    Ok(Generated { name, assertion, toml })
+}
+
+impl<B: BV> Litmus<B> {
+    /// Generate a litmus test from a relaxation cycle using the
+    /// templates in the ISA configuration.
+    pub fn from_cycle(name: Option<&str>, cycle: &str, symtab: &Symtab, isa: &ISAConfig<B>) -> Result<Self, String> {
+        let generated = generate(name, cycle, &isa.templates)?;
+        Litmus::parse(&generated.toml, symtab, isa)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn templates() -> CycleTemplates {
+        let table = |entries: &[(&str, &[&str])]| -> HashMap<String, Vec<String>> {
+            entries.iter().map(|(k, v)| (k.to_string(), v.iter().map(|s| s.to_string()).collect())).collect()
+        };
+        CycleTemplates {
+            registers: (0..16).map(|n| format!("X{}", n)).collect(),
+            bytes: 8,
+            reads: table(&[("P", &["LDR $value,[$addr]"]), ("A", &["LDAR $value,[$addr]"])]),
+            writes: table(&[("P", &["STR $value,[$addr]"]), ("C", &["CAS $expected,$value,[$addr]"])]),
+            fences: table(&[("DMB.SY", &["DMB SY"]), ("DMB.SYX", &["DMB SYX"])]),
+            dependencies: table(&[
+                ("addr", &["EOR $tmp,$src,$src", "ADD $tmp,$addr,$tmp"]),
+                ("ctrl", &["CBNZ $src,$label", "$label:"]),
+            ]),
+        }
+    }
+
+    #[test]
+    fn cycle_edges() {
+        let t = templates();
+        let edge = Edge::parse("PodWRPA", &t).unwrap();
+        assert_eq!((edge.kind, edge.same_loc, edge.source, edge.target), (EdgeKind::Po, false, Dir::W, Dir::R));
+        assert_eq!(edge.annotations, Some(('P', 'A')));
+        assert!(Edge::parse("Rfe", &t).unwrap().external);
+        assert_eq!(Edge::parse("DMB.SYdWW", &t).unwrap().kind, EdgeKind::Fence("DMB.SY".to_string()));
+        assert_eq!(Edge::parse("DMB.SYXsWW", &t).unwrap().kind, EdgeKind::Fence("DMB.SYX".to_string()));
+        assert_eq!(Edge::parse("DpAddrdW", &t).unwrap().kind, EdgeKind::Dep("addr".to_string()));
+        assert!(Edge::parse("DpDatadR", &t).is_err());
+        assert!(Edge::parse("PodW", &t).is_err());
+        assert!(Edge::parse("Foo", &t).is_err());
+    }
+
+    #[test]
+    fn cycle_mp() {
+        let test = generate(None, "PodWW Rfe PodRR Fre", &templates()).unwrap();
+        assert_eq!(test.name, "PodWW+Rfe+PodRR+Fre");
+        assert_eq!(test.assertion, "(and (= (register X1 1) 1) (= (register X3 1) 0))");
+        assert!(test.toml.contains("[thread.0]\ninit = { X0 = \"x\", X1 = \"1\", X2 = \"y\", X3 = \"1\" }"));
+        assert!(test.toml.contains("code = \"\"\"\n\tSTR X1,[X0]\n\tSTR X3,[X2]\n\"\"\""));
+        assert!(test.toml.contains("code = \"\"\"\n\tLDR X1,[X0]\n\tLDR X3,[X2]\n\"\"\""));
+        assert!(test.toml.parse::<toml::Value>().is_ok())
+    }
+
+    #[test]
+    fn cycle_coherence() {
+        // 2+2W, with a CAS for the second write on each thread
+        let test = generate(Some("2+2W"), "PodWWPC Coe PodWWPC Coe", &templates()).unwrap();
+        assert_eq!(test.assertion, "(and (= (last_write_to x) 2) (= (last_write_to y) 2))");
+        assert!(test.toml.contains("init = { X0 = \"x\", X1 = \"2\", X2 = \"y\", X3 = \"1\", X4 = \"0\" }"));
+        assert!(test.toml.contains("\tCAS X4,X3,[X2]\n"));
+
+        let test = generate(Some("LB+addr+ctrl"), "DpAddrdW Rfe DpCtrldW Rfe", &templates()).unwrap();
+        assert_eq!(test.assertion, "(and (= (register X1 0) 1) (= (register X1 1) 1))");
+        assert!(test.toml.contains("\tLDR X1,[X0]\n\tEOR X4,X1,X1\n\tADD X4,X2,X4\n\tSTR X3,[X4]\n"));
+        assert!(test.toml.contains("\tCBNZ X1,L1_1\n\tL1_1:\n\tSTR X3,[X2]\n"));
+
+        assert!(generate(None, "PodWR Fre", &templates()).is_err());
+        assert!(generate(None, "PosWW Rfe PodRR Fre", &templates()).is_err());
+        assert!(generate(None, "PodWR Rfe PodRR Fre", &templates()).is_err());
+    }
+}
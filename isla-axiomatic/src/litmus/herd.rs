@@ -0,0 +1,884 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module implements a parser for the `.litmus` format used by
+//! herd7 and the other herdtools, for AArch64 and RISC-V tests. It
+//! produces the same `Litmus` structure as the TOML format, so that
+//! `.litmus` files can be used directly without converting them
+//! first.
+//!
+//! A test consists of a header line with the architecture and test
+//! name, some optional information lines (a quoted description and
+//! `key=value` pairs), the initial state in braces, the code for each
+//! thread in columns separated by `|`, and finally the condition on
+//! the final state.
+
+use std::collections::{BTreeSet, HashMap};
+
+use isla_lib::concrete::BV;
+use isla_lib::config::ISAConfig;
+use isla_lib::ir::{Name, Symtab};
+use isla_lib::log;
+use isla_lib::zencode;
+
+use super::layout::{int_bytes, parse_u64, path_base, InitialMemory, Layout, LocType};
+use super::{assemble, parse_init_value, Cmp, Litmus, Loc, Prop, Quantifier, ThreadName};
+
+/// A location in the initial state or final condition, either a
+/// register in some thread or a symbolic memory location. Memory
+/// locations in the final condition can have an offset in bytes and
+/// an explicit width, written `*(x+8)` or `*(uint16_t *)(x+2)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum HerdLoc {
+    Register(usize, String),
+    Memory { name: String, offset: u64, bytes: Option<u32> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Cond {
+    Eq(HerdLoc, String),
+    Cmp(Cmp, HerdLoc, String),
+    True,
+    False,
+    And(Vec<Cond>),
+    Or(Vec<Cond>),
+    Not(Box<Cond>),
+    Implies(Box<Cond>, Box<Cond>),
+}
+
+/// The contents of a `.litmus` file before the code is assembled
+/// and symbolic addresses are resolved
+#[derive(Debug)]
+struct HerdLitmus {
+    arch: String,
+    name: String,
+    info: Vec<(String, String)>,
+    symbolic: BTreeSet<String>,
+    types: Vec<(String, LocType)>,
+    locations: Vec<(String, Vec<String>)>,
+    registers: Vec<(usize, String, String)>,
+    threads: Vec<(usize, String)>,
+    quantifier: Quantifier,
+    condition: Cond,
+}
+
+/// Remove `(* ... *)` comments, which may be nested. A comment must
+/// start with whitespace after the `(*`, as otherwise it could be a
+/// dereference in the final condition, such as `(*(x+8)=1)`.
+fn strip_comments(contents: &str) -> String {
+    let mut stripped = String::new();
+    let mut depth = 0;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        let comment_start = c == '(' && chars.peek() == Some(&'*') && {
+            let mut ahead = chars.clone();
+            ahead.next();
+            !matches!(ahead.peek(), Some(c) if !c.is_whitespace())
+        };
+        if comment_start {
+            chars.next();
+            depth += 1
+        } else if depth > 0 && c == '*' && chars.peek() == Some(&')') {
+            chars.next();
+            depth -= 1
+        } else if depth == 0 {
+            stripped.push(c)
+        }
+    }
+    stripped
+}
+
+fn is_number(value: &str) -> bool {
+    let value = value.strip_prefix('-').unwrap_or(value);
+    if let Some(hex) = value.strip_prefix("0x") {
+        !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit())
+    } else {
+        !value.is_empty() && value.chars().all(|c| c.is_ascii_digit())
+    }
+}
+
+/// Paths to memory locations are a symbolic location, optionally
+/// followed by array indices and struct fields, e.g. `x[1]` or `s.f`.
+fn is_path(path: &str) -> bool {
+    path.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && path.chars().all(|c| c.is_alphanumeric() || "_[].".contains(c))
+}
+
+fn parse_loc(loc: &str) -> Result<HerdLoc, String> {
+    let loc = loc.trim();
+    if let Some((tid, reg)) = loc.split_once(':') {
+        let tid = tid.trim().parse::<usize>().map_err(|_| format!("Invalid thread id in location {}", loc))?;
+        Ok(HerdLoc::Register(tid, reg.trim().to_string()))
+    } else {
+        // herd allows memory locations to be written as `[x]`
+        let name = match loc.strip_prefix('[') {
+            Some(name) => name.strip_suffix(']').ok_or_else(|| format!("Invalid location {}", loc))?.trim(),
+            None => loc,
+        };
+        if !is_path(name) {
+            return Err(format!("Invalid location {}", loc));
+        }
+        Ok(HerdLoc::Memory { name: name.to_string(), offset: 0, bytes: None })
+    }
+}
+
+/// Values can be numbers, pointers to symbolic locations, or code
+/// labels written as `P<n>:<label>`. Labels are converted into the
+/// `<label>:` form used by the TOML format.
+fn parse_value(value: &str, symbolic: &mut BTreeSet<String>) -> Result<String, String> {
+    let value = value.trim().trim_start_matches('&');
+    if is_number(value) {
+        Ok(value.to_string())
+    } else if let Some((thread, label)) = value.split_once(':') {
+        if thread.starts_with('P') {
+            Ok(format!("{}:", label.trim()))
+        } else {
+            Err(format!("Invalid value {}", value))
+        }
+    } else if is_path(value) {
+        symbolic.insert(path_base(value).to_string());
+        Ok(value.to_string())
+    } else {
+        Err(format!("Invalid value {}", value))
+    }
+}
+
+fn parse_init_entry(entry: &str, litmus: &mut HerdLitmus) -> Result<(), String> {
+    let (lhs, rhs) = match entry.split_once('=') {
+        Some((lhs, rhs)) => (lhs, Some(rhs.trim())),
+        None => (entry, None),
+    };
+
+    // The location may be preceded by a type, e.g. `uint64_t x`,
+    // `uint64_t *p`, or `uint64_t x[4]`
+    let words: Vec<&str> = lhs.split_whitespace().collect();
+    let (mut ty, mut loc) = match words.split_last() {
+        Some((loc, ty)) => (ty.join(" "), *loc),
+        None => return Err(format!("Invalid initial state entry {}", entry)),
+    };
+    if let Some(name) = loc.strip_prefix('*') {
+        ty.push('*');
+        loc = name
+    }
+    if !ty.is_empty() {
+        if let Some(dims) = loc.find('[') {
+            ty.push_str(&loc[dims..]);
+            loc = &loc[..dims]
+        }
+    }
+
+    match parse_loc(loc)? {
+        HerdLoc::Register(tid, reg) => {
+            if let Some(value) = rhs {
+                let value = parse_value(value, &mut litmus.symbolic)?;
+                litmus.registers.push((tid, reg, value))
+            }
+        }
+        HerdLoc::Memory { name, .. } => {
+            litmus.symbolic.insert(path_base(&name).to_string());
+            if !ty.is_empty() {
+                litmus.types.push((name.clone(), LocType::parse(&ty)?))
+            }
+            if let Some(value) = rhs {
+                // Arrays can be initialised with a list of values, e.g. `{1, 2}`
+                let values = match value.strip_prefix('{').and_then(|values| values.strip_suffix('}')) {
+                    Some(values) => values.split(',').map(str::trim).filter(|value| !value.is_empty()).collect(),
+                    None => vec![value],
+                };
+                let values = values
+                    .iter()
+                    .map(|value| parse_value(value, &mut litmus.symbolic))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if values.iter().any(|value| value.ends_with(':')) {
+                    return Err(format!("Memory location {} cannot be initialised with a label", name));
+                }
+                litmus.locations.push((name, values))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_code(code: &str, litmus: &mut HerdLitmus) -> Result<(), String> {
+    let mut rows = code.split(';').map(str::trim).filter(|row| !row.is_empty());
+
+    let header = rows.next().ok_or_else(|| "No threads found in litmus file".to_string())?;
+    for thread in header.split('|') {
+        let thread = thread.trim();
+        let tid = thread
+            .strip_prefix('P')
+            .and_then(|tid| tid.parse::<usize>().ok())
+            .ok_or_else(|| format!("Invalid thread name {} in litmus file", thread))?;
+        litmus.threads.push((tid, String::new()))
+    }
+
+    for row in rows {
+        let columns: Vec<&str> = row.split('|').collect();
+        if columns.len() > litmus.threads.len() {
+            return Err(format!("Too many columns in litmus code: {}", row));
+        }
+        for (i, instr) in columns.iter().enumerate() {
+            let instr = instr.trim();
+            if !instr.is_empty() {
+                let code = &mut litmus.threads[i].1;
+                code.push('\t');
+                code.push_str(instr);
+                code.push('\n')
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Tok {
+    Lparen,
+    Rparen,
+    And,
+    Or,
+    Not,
+    Implies,
+    Eq,
+    Cmp(Cmp),
+    Star,
+    Plus,
+    Id(String),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Tok>, String> {
+    let mut toks = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => (),
+            '(' => toks.push(Tok::Lparen),
+            ')' => toks.push(Tok::Rparen),
+            '~' => toks.push(Tok::Not),
+            '/' if chars.peek() == Some(&'\\') => {
+                chars.next();
+                toks.push(Tok::And)
+            }
+            '\\' if chars.peek() == Some(&'/') => {
+                chars.next();
+                toks.push(Tok::Or)
+            }
+            '=' if chars.peek() == Some(&'>') => {
+                chars.next();
+                toks.push(Tok::Implies)
+            }
+            '=' => toks.push(Tok::Eq),
+            '!' if chars.peek() == Some(&'=') => {
+                chars.next();
+                toks.push(Tok::Cmp(Cmp::Ne))
+            }
+            '<' | '>' => {
+                let strict = chars.peek() != Some(&'=');
+                if !strict {
+                    chars.next();
+                }
+                let cmp = match (c, strict) {
+                    ('<', true) => Cmp::Ult,
+                    ('<', false) => Cmp::Ule,
+                    (_, true) => Cmp::Ugt,
+                    (_, false) => Cmp::Uge,
+                };
+                // Comparisons are unsigned unless followed by `s`
+                if chars.peek() == Some(&'s') {
+                    chars.next();
+                    toks.push(Tok::Cmp(cmp.signed()))
+                } else {
+                    toks.push(Tok::Cmp(cmp))
+                }
+            }
+            '*' => toks.push(Tok::Star),
+            '+' => toks.push(Tok::Plus),
+            c if c.is_alphanumeric() || "_:[]-&".contains(c) => {
+                let mut id = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || "_:[]&.".contains(c) {
+                        id.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                toks.push(Tok::Id(id))
+            }
+            _ => return Err(format!("Unexpected character '{}' in litmus condition", c)),
+        }
+    }
+    Ok(toks)
+}
+
+/// A recursive descent parser for herd conditions. From loosest to
+/// tightest binding the operators are `=>`, `\/`, `/\`, and `~`.
+struct CondParser<'a> {
+    toks: &'a [Tok],
+    pos: usize,
+    symbolic: &'a mut BTreeSet<String>,
+}
+
+impl<'a> CondParser<'a> {
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Tok> {
+        let tok = self.toks.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn implies(&mut self) -> Result<Cond, String> {
+        let lhs = self.or()?;
+        if self.peek() == Some(&Tok::Implies) {
+            self.next();
+            Ok(Cond::Implies(Box::new(lhs), Box::new(self.implies()?)))
+        } else {
+            Ok(lhs)
+        }
+    }
+
+    fn or(&mut self) -> Result<Cond, String> {
+        let mut disjuncts = vec![self.and()?];
+        while self.peek() == Some(&Tok::Or) {
+            self.next();
+            disjuncts.push(self.and()?)
+        }
+        Ok(if disjuncts.len() == 1 { disjuncts.pop().unwrap() } else { Cond::Or(disjuncts) })
+    }
+
+    fn and(&mut self) -> Result<Cond, String> {
+        let mut conjuncts = vec![self.unary()?];
+        while self.peek() == Some(&Tok::And) {
+            self.next();
+            conjuncts.push(self.unary()?)
+        }
+        Ok(if conjuncts.len() == 1 { conjuncts.pop().unwrap() } else { Cond::And(conjuncts) })
+    }
+
+    fn unary(&mut self) -> Result<Cond, String> {
+        match self.next() {
+            Some(Tok::Not) => Ok(Cond::Not(Box::new(self.unary()?))),
+            Some(Tok::Id(id)) if id == "not" => Ok(Cond::Not(Box::new(self.unary()?))),
+            Some(Tok::Lparen) => {
+                let cond = self.implies()?;
+                match self.next() {
+                    Some(Tok::Rparen) => Ok(cond),
+                    _ => Err("Expected ')' in litmus condition".to_string()),
+                }
+            }
+            Some(Tok::Id(id)) if id == "true" => Ok(Cond::True),
+            Some(Tok::Id(id)) if id == "false" => Ok(Cond::False),
+            Some(Tok::Id(loc)) => {
+                let loc = parse_loc(&loc)?;
+                self.comparison(loc)
+            }
+            Some(Tok::Star) => {
+                let loc = self.deref()?;
+                self.comparison(loc)
+            }
+            _ => Err("Unexpected end of litmus condition".to_string()),
+        }
+    }
+
+    fn comparison(&mut self, loc: HerdLoc) -> Result<Cond, String> {
+        if let HerdLoc::Memory { name, .. } = &loc {
+            self.symbolic.insert(path_base(name).to_string());
+        }
+        match (self.next(), self.next()) {
+            (Some(Tok::Eq), Some(Tok::Id(value))) => Ok(Cond::Eq(loc, parse_value(&value, self.symbolic)?)),
+            (Some(Tok::Cmp(cmp)), Some(Tok::Id(value))) => Ok(Cond::Cmp(cmp, loc, parse_value(&value, self.symbolic)?)),
+            _ => Err("Expected <location> <comparison> <value> in litmus condition".to_string()),
+        }
+    }
+
+    /// Parses a memory location following a `*`, which is either
+    /// `x` or `(x+<offset>)`, optionally preceded by a pointer cast
+    /// giving the width, e.g. `(uint16_t *)`. Unlike C, the offset is
+    /// always in bytes.
+    fn deref(&mut self) -> Result<HerdLoc, String> {
+        let mut bytes = None;
+        if let [Tok::Lparen, Tok::Id(ty), Tok::Star, Tok::Rparen, ..] = &self.toks[self.pos..] {
+            bytes = Some(int_bytes(ty).ok_or_else(|| format!("Unsupported type {} in litmus condition", ty))?);
+            self.pos += 4
+        }
+
+        let (name, offset) = match self.next() {
+            Some(Tok::Id(name)) => (name, 0),
+            Some(Tok::Lparen) => {
+                let name = match self.next() {
+                    Some(Tok::Id(name)) => name,
+                    _ => return Err("Expected symbolic address in litmus condition".to_string()),
+                };
+                let offset = if self.peek() == Some(&Tok::Plus) {
+                    self.next();
+                    match self.next() {
+                        Some(Tok::Id(offset)) => parse_u64(&offset)
+                            .ok_or_else(|| format!("Invalid offset {} in litmus condition", offset))?,
+                        _ => return Err("Expected offset in litmus condition".to_string()),
+                    }
+                } else {
+                    0
+                };
+                if self.next() != Some(Tok::Rparen) {
+                    return Err("Expected ')' in litmus condition".to_string());
+                }
+                (name, offset)
+            }
+            _ => return Err("Expected memory location after '*' in litmus condition".to_string()),
+        };
+
+        match parse_loc(&name)? {
+            HerdLoc::Memory { name, .. } => Ok(HerdLoc::Memory { name, offset, bytes }),
+            HerdLoc::Register(..) => Err(format!("Cannot dereference register {} in litmus condition", name)),
+        }
+    }
+}
+
+fn parse_condition(condition: &str, litmus: &mut HerdLitmus) -> Result<(), String> {
+    let mut condition = condition.trim();
+
+    // The `locations` clause lists additional locations to print,
+    // which we do not need
+    if let Some(rest) = condition.strip_prefix("locations") {
+        let end = rest.find(']').ok_or_else(|| "Unterminated locations clause in litmus file".to_string())?;
+        condition = rest[end + 1..].trim()
+    }
+
+    if condition.starts_with("filter") {
+        return Err("Litmus filter clauses are not supported".to_string());
+    }
+
+    let (quantifier, rest) = if let Some(rest) = condition.strip_prefix("exists") {
+        (Quantifier::Exists, rest)
+    } else if let Some(rest) = condition.strip_prefix("forall") {
+        (Quantifier::Forall, rest)
+    } else if let Some(rest) = condition.strip_prefix('~').map(str::trim_start).and_then(|c| c.strip_prefix("exists")) {
+        (Quantifier::NotExists, rest)
+    } else {
+        return Err("Litmus condition must start with exists, ~exists, or forall".to_string());
+    };
+
+    // Anything after the condition, e.g. a `with` clause, is ignored
+    let rest = find_word(rest, "with").map_or(rest, |i| &rest[..i]);
+
+    let toks = tokenize(rest)?;
+    let mut parser = CondParser { toks: &toks, pos: 0, symbolic: &mut litmus.symbolic };
+    let cond = parser.implies()?;
+    if parser.pos != toks.len() {
+        return Err("Unexpected tokens at end of litmus condition".to_string());
+    }
+
+    litmus.quantifier = quantifier;
+    litmus.condition = cond;
+    Ok(())
+}
+
+/// Find the first occurrence of `word` in `s` which is not part of a
+/// longer identifier.
+fn find_word(s: &str, word: &str) -> Option<usize> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    s.match_indices(word)
+        .map(|(i, _)| i)
+        .find(|i| !s[..*i].ends_with(is_ident) && !s[*i + word.len()..].starts_with(is_ident))
+}
+
+fn find_condition(s: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in s.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if ["exists", "~exists", "~ exists", "forall", "locations", "filter"]
+            .iter()
+            .any(|keyword| trimmed.starts_with(keyword))
+        {
+            return Some(offset + (line.len() - trimmed.len()));
+        }
+        offset += line.len()
+    }
+    None
+}
+
+fn parse_herd(contents: &str) -> Result<HerdLitmus, String> {
+    let contents = strip_comments(contents);
+
+    let open = contents.find('{').ok_or_else(|| "No initial state found in litmus file".to_string())?;
+    // Array initialisers can contain nested braces
+    let mut depth = 0;
+    let close = contents[open..]
+        .find(|c| {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => (),
+            }
+            depth == 0
+        })
+        .ok_or_else(|| "Unterminated initial state in litmus file".to_string())?
+        + open;
+
+    let mut header = contents[..open].lines().map(str::trim).filter(|line| !line.is_empty());
+    let mut first = header.next().ok_or_else(|| "No header found in litmus file".to_string())?.split_whitespace();
+    let arch = first.next().unwrap().to_string();
+    let name = first.next().ok_or_else(|| "No name found in litmus file".to_string())?.to_string();
+
+    let mut litmus = HerdLitmus {
+        arch,
+        name,
+        info: Vec::new(),
+        symbolic: BTreeSet::new(),
+        types: Vec::new(),
+        locations: Vec::new(),
+        registers: Vec::new(),
+        threads: Vec::new(),
+        quantifier: Quantifier::Exists,
+        condition: Cond::True,
+    };
+
+    for line in header {
+        if let Some((key, value)) = line.split_once('=') {
+            litmus.info.push((key.trim().to_lowercase(), value.trim().to_string()))
+        }
+    }
+
+    for entry in contents[open + 1..close].split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+        parse_init_entry(entry, &mut litmus)?
+    }
+
+    let rest = &contents[close + 1..];
+    let condition_start = find_condition(rest).ok_or_else(|| "No final condition found in litmus file".to_string())?;
+    parse_code(&rest[..condition_start], &mut litmus)?;
+    parse_condition(&rest[condition_start..], &mut litmus)?;
+
+    Ok(litmus)
+}
+
+fn loc_from_herd<B>(loc: &HerdLoc, layout: &Layout, symtab: &Symtab, isa: &ISAConfig<B>) -> Result<Loc, String> {
+    match loc {
+        HerdLoc::Register(thread_id, reg) => {
+            let reg: Name = match isa.register_renames.get(reg) {
+                Some(reg) => *reg,
+                None => symtab
+                    .get(&zencode::encode(reg))
+                    .ok_or_else(|| format!("No register {} in litmus condition", reg))?,
+            };
+            Ok(Loc::Register { reg, thread_id: *thread_id })
+        }
+        HerdLoc::Memory { name, offset, bytes } => {
+            let (address, default_bytes) = layout.access(name)?;
+            Ok(Loc::LastWriteTo { address: address + offset, bytes: bytes.unwrap_or(default_bytes) })
+        }
+    }
+}
+
+fn prop_from_cond<B: BV>(cond: &Cond, layout: &Layout, symtab: &Symtab, isa: &ISAConfig<B>) -> Result<Prop<B>, String> {
+    let props = |conds: &[Cond]| {
+        conds.iter().map(|cond| prop_from_cond(cond, layout, symtab, isa)).collect::<Result<Vec<_>, _>>()
+    };
+    Ok(match cond {
+        Cond::Eq(loc, value) => {
+            Prop::EqLoc(loc_from_herd(loc, layout, symtab, isa)?, B::from_u64(layout.value(value)?))
+        }
+        Cond::Cmp(cmp, loc, value) => {
+            Prop::CmpLoc(*cmp, loc_from_herd(loc, layout, symtab, isa)?, B::from_u64(layout.value(value)?))
+        }
+        Cond::True => Prop::True,
+        Cond::False => Prop::False,
+        Cond::And(conds) => Prop::And(props(conds)?),
+        Cond::Or(conds) => Prop::Or(props(conds)?),
+        Cond::Not(cond) => Prop::Not(Box::new(prop_from_cond(cond, layout, symtab, isa)?)),
+        Cond::Implies(lhs, rhs) => Prop::Implies(
+            Box::new(prop_from_cond(lhs, layout, symtab, isa)?),
+            Box::new(prop_from_cond(rhs, layout, symtab, isa)?),
+        ),
+    })
+}
+
+impl<B: BV> Litmus<B> {
+    /// Parse a litmus test in the herd7 `.litmus` format.
+    pub fn parse_herd(contents: &str, symtab: &Symtab, isa: &ISAConfig<B>) -> Result<Self, String> {
+        let herd = parse_herd(contents)?;
+        log!(log::LITMUS, &format!("Parsed {} litmus test {}", herd.arch, herd.name));
+
+        let symbolic: Vec<String> = herd.symbolic.iter().cloned().collect();
+        let layout = Layout::allocate(&symbolic, herd.types.iter().cloned().collect(), &HashMap::new(), isa)?;
+
+        let mut initial_memory = InitialMemory::default();
+        for (loc, values) in herd.locations.iter() {
+            let values: Vec<&str> = values.iter().map(String::as_str).collect();
+            layout.initialise(&mut initial_memory, loc, &values)?
+        }
+
+        let code: Vec<(ThreadName, &str)> =
+            herd.threads.iter().map(|(tid, code)| (tid.to_string(), code.as_str())).collect();
+        let (mut assembled, _, objdump) = assemble(&code, &[], true, isa)?;
+
+        let mut inits: Vec<Vec<(Name, u64)>> = herd
+            .threads
+            .iter()
+            .map(|(tid, _)| {
+                herd.registers
+                    .iter()
+                    .filter(|(init_tid, _, _)| init_tid == tid)
+                    .map(|(_, reg, value)| parse_init_value(reg, value, &layout, None, &objdump, symtab, isa))
+                    .collect::<Result<_, _>>()
+            })
+            .collect::<Result<_, _>>()?;
+
+        let assembled = assembled
+            .drain(..)
+            .zip(inits.drain(..))
+            .map(|((thread_name, code), init)| (thread_name, init, code))
+            .collect();
+
+        let condition = prop_from_cond(&herd.condition, &layout, symtab, isa)?;
+        let final_assertion = match herd.quantifier {
+            Quantifier::Exists | Quantifier::NotExists => condition,
+            Quantifier::Forall => Prop::Not(Box::new(condition)),
+        };
+
+        Ok(Litmus {
+            name: herd.name,
+            hash: herd.info.iter().find(|(key, _)| key == "hash").map(|(_, hash)| hash.clone()),
+            symbolic_sizeof: layout.sizeof(),
+            symbolic_addrs: layout.addrs,
+            initial_memory,
+            assembled,
+            self_modify_regions: Vec::new(),
+            page_tables: None,
+            exception_handlers: Vec::new(),
+            objdump,
+            quantifier: herd.quantifier,
+            final_assertion,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MP: &str = r#"AArch64 MP
+"PodWW Rfe PodRR Fre"
+Hash=211d5b298572efe2c3bbf2a0d9c6e32c
+{
+0:X1=x; 0:X3=y;
+1:X1=y; 1:X3=x;
+uint64_t x = 0;
+}
+ P0          | P1          ;
+ MOV W0,#1   | LDR W0,[X1] ;
+ STR W0,[X1] | LDR W2,[X3] ;
+ MOV W2,#1   |             ;
+ STR W2,[X3] |             ;
+exists
+(1:X0=1 /\ 1:X2=0)
+"#;
+
+    #[test]
+    fn herd_mp() {
+        let litmus = parse_herd(MP).unwrap();
+        assert_eq!(litmus.arch, "AArch64");
+        assert_eq!(litmus.name, "MP");
+        assert_eq!(litmus.info, vec![("hash".to_string(), "211d5b298572efe2c3bbf2a0d9c6e32c".to_string())]);
+        assert_eq!(litmus.symbolic.iter().collect::<Vec<_>>(), vec!["x", "y"]);
+        assert_eq!(litmus.types, vec![("x".to_string(), LocType::Int(8))]);
+        assert_eq!(litmus.locations, vec![("x".to_string(), vec!["0".to_string()])]);
+        assert_eq!(litmus.registers.len(), 4);
+        assert_eq!(litmus.threads[0], (0, "\tMOV W0,#1\n\tSTR W0,[X1]\n\tMOV W2,#1\n\tSTR W2,[X3]\n".to_string()));
+        assert_eq!(litmus.threads[1], (1, "\tLDR W0,[X1]\n\tLDR W2,[X3]\n".to_string()));
+        assert_eq!(litmus.quantifier, Quantifier::Exists);
+        assert_eq!(
+            litmus.condition,
+            Cond::And(vec![
+                Cond::Eq(HerdLoc::Register(1, "X0".to_string()), "1".to_string()),
+                Cond::Eq(HerdLoc::Register(1, "X2".to_string()), "0".to_string())
+            ])
+        )
+    }
+
+    #[test]
+    fn herd_comments() {
+        let test = r#"AArch64 MP+deref (* a comment,
+with exists (* and a nested comment *) inside *)
+{
+0:X1=x; 0:X3=y;
+1:X1=y; 1:X3=x;
+}
+ P0          | P1          ;
+ STR W0,[X1] | LDR W0,[X1] ;
+ STR W2,[X3] | LDR W2,[X3] ;
+exists (*(y+0)=1 /\ 1:X2=0) (* final comment *)
+"#;
+        let litmus = parse_herd(test).unwrap();
+        assert_eq!(litmus.name, "MP+deref");
+        assert!(litmus.info.is_empty());
+        assert_eq!(litmus.threads[1].1, "\tLDR W0,[X1]\n\tLDR W2,[X3]\n");
+        assert_eq!(
+            litmus.condition,
+            Cond::And(vec![
+                Cond::Eq(HerdLoc::Memory { name: "y".to_string(), offset: 0, bytes: None }, "1".to_string()),
+                Cond::Eq(HerdLoc::Register(1, "X2".to_string()), "0".to_string())
+            ])
+        )
+    }
+
+    #[test]
+    fn herd_riscv() {
+        let test = r#"RISCV SB (* store buffering *)
+{
+0:x6=x; 0:x8=y;
+1:x6=y; 1:x8=x;
+}
+ P0          | P1          ;
+ ori x5,x0,1 | ori x5,x0,1 ;
+ sw x5,0(x6) | sw x5,0(x6) ;
+ lw x7,0(x8) | lw x7,0(x8) ;
+locations [x;y;]
+~exists (0:x7=0 /\ 1:x7=0)
+"#;
+        let litmus = parse_herd(test).unwrap();
+        assert_eq!(litmus.name, "SB");
+        assert_eq!(litmus.quantifier, Quantifier::NotExists);
+        assert_eq!(litmus.threads.len(), 2);
+        assert_eq!(litmus.threads[1].1, "\tori x5,x0,1\n\tsw x5,0(x6)\n\tlw x7,0(x8)\n");
+    }
+
+    #[test]
+    fn herd_conditions() {
+        let mut litmus = parse_herd(MP).unwrap();
+        parse_condition("forall (0:X0=1 \\/ ~[z]=0x2 /\\ x=-1 => true)", &mut litmus).unwrap();
+        assert_eq!(litmus.quantifier, Quantifier::Forall);
+        assert!(litmus.symbolic.contains("z"));
+        assert_eq!(
+            litmus.condition,
+            Cond::Implies(
+                Box::new(Cond::Or(vec![
+                    Cond::Eq(HerdLoc::Register(0, "X0".to_string()), "1".to_string()),
+                    Cond::And(vec![
+                        Cond::Not(Box::new(Cond::Eq(
+                            HerdLoc::Memory { name: "z".to_string(), offset: 0, bytes: None },
+                            "0x2".to_string()
+                        ))),
+                        Cond::Eq(HerdLoc::Memory { name: "x".to_string(), offset: 0, bytes: None }, "-1".to_string())
+                    ])
+                ])),
+                Box::new(Cond::True)
+            )
+        );
+
+        parse_condition("~exists (0:X0 != 1 /\\ 1:X2 <s -1 \\/ *(y+8) >= 2 /\\ *(uint16_t *)(x+0x2)<3)", &mut litmus)
+            .unwrap();
+        assert_eq!(litmus.quantifier, Quantifier::NotExists);
+        assert_eq!(
+            litmus.condition,
+            Cond::Or(vec![
+                Cond::And(vec![
+                    Cond::Cmp(Cmp::Ne, HerdLoc::Register(0, "X0".to_string()), "1".to_string()),
+                    Cond::Cmp(Cmp::Slt, HerdLoc::Register(1, "X2".to_string()), "-1".to_string())
+                ]),
+                Cond::And(vec![
+                    Cond::Cmp(
+                        Cmp::Uge,
+                        HerdLoc::Memory { name: "y".to_string(), offset: 8, bytes: None },
+                        "2".to_string()
+                    ),
+                    Cond::Cmp(
+                        Cmp::Ult,
+                        HerdLoc::Memory { name: "x".to_string(), offset: 2, bytes: Some(2) },
+                        "3".to_string()
+                    )
+                ])
+            ])
+        );
+
+        // Only a whole `with` token starts a with clause
+        parse_condition("exists (width=1 /\\ 0:X_with=2) with tso: true", &mut litmus).unwrap();
+        assert_eq!(
+            litmus.condition,
+            Cond::And(vec![
+                Cond::Eq(HerdLoc::Memory { name: "width".to_string(), offset: 0, bytes: None }, "1".to_string()),
+                Cond::Eq(HerdLoc::Register(0, "X_with".to_string()), "2".to_string())
+            ])
+        );
+
+        assert!(parse_condition("exists (0:X0=1 /\\ )", &mut litmus).is_err());
+        assert!(parse_condition("exists (*0:X0=1)", &mut litmus).is_err());
+        assert!(parse_condition("filter (0:X0=1) exists (0:X0=1)", &mut litmus).is_err());
+        assert!(parse_condition("sometimes (0:X0=1)", &mut litmus).is_err());
+    }
+
+    #[test]
+    fn herd_arrays() {
+        let test = r#"AArch64 ARR
+{
+uint64_t x[2] = {1, 2}; uint64_t *p = &x[1];
+uint32_t y[4]; y[2] = 3;
+0:X1=p; 0:X2=&y[2];
+}
+ P0          ;
+ LDR X0,[X1] ;
+exists (0:X0=2 /\ y[1]=0 /\ *(y+4)=0)
+"#;
+        let litmus = parse_herd(test).unwrap();
+        assert_eq!(litmus.symbolic.iter().collect::<Vec<_>>(), vec!["p", "x", "y"]);
+        assert_eq!(
+            litmus.types,
+            vec![
+                ("x".to_string(), LocType::parse("uint64_t[2]").unwrap()),
+                ("p".to_string(), LocType::Pointer),
+                ("y".to_string(), LocType::parse("uint32_t[4]").unwrap())
+            ]
+        );
+        assert_eq!(
+            litmus.locations,
+            vec![
+                ("x".to_string(), vec!["1".to_string(), "2".to_string()]),
+                ("p".to_string(), vec!["x[1]".to_string()]),
+                ("y[2]".to_string(), vec!["3".to_string()])
+            ]
+        );
+        assert_eq!(litmus.registers[1], (0, "X2".to_string(), "y[2]".to_string()));
+    }
+
+    #[test]
+    fn herd_labels() {
+        let test = r#"AArch64 LB+label
+{
+0:X1=P1:L0; 1:X2=x;
+}
+ P0 | P1              ;
+ NOP | L0: LDR W0,[X2] ;
+exists (1:X0=0)
+"#;
+        let litmus = parse_herd(test).unwrap();
+        assert_eq!(litmus.registers[0], (0, "X1".to_string(), "L0:".to_string()));
+        assert_eq!(litmus.threads[1].1, "\tL0: LDR W0,[X2]\n");
+    }
+}
@@ -0,0 +1,512 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module determines the memory layout of the symbolic locations
+//! in a litmus test, and their initial contents. Each location can be
+//! given a C-like type, which may be an integer, a pointer, or an
+//! array or struct of these, e.g. `uint64_t[4]` or
+//! `struct { uint32_t a; uint64_t *b; }`. Locations are allocated in
+//! order starting from the symbolic address base in the ISA
+//! configuration, each taking at least the symbolic address stride,
+//! unless they are explicitly aligned or placed at a fixed address.
+//!
+//! Parts of a location are referred to by paths such as `x[2]`,
+//! `s.b`, or `x+8`, where the offset after `+` is in bytes.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use isla_lib::config::ISAConfig;
+
+pub const PAGE_SIZE: u64 = 4096;
+
+/// The size in bytes of an integer type
+pub fn int_bytes(ty: &str) -> Option<u32> {
+    match ty {
+        "uint64_t" | "int64_t" | "long" => Some(8),
+        "uint32_t" | "int32_t" | "int" => Some(4),
+        "uint16_t" | "int16_t" | "short" => Some(2),
+        "uint8_t" | "int8_t" | "char" => Some(1),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LocType {
+    Int(u32),
+    Pointer,
+    Array(Box<LocType>, u64),
+    Struct(Vec<(String, LocType)>),
+}
+
+struct TypeParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> TypeParser<'a> {
+    fn skip_whitespace(&mut self) {
+        while matches!(self.chars.peek(), Some(c) if c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&c) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn word(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        let mut word = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_alphanumeric() || c == '_' {
+                word.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        if word.is_empty() {
+            None
+        } else {
+            Some(word)
+        }
+    }
+
+    fn base(&mut self) -> Result<LocType, String> {
+        let word = self.word().ok_or_else(|| "Expected type".to_string())?;
+        let mut ty = if word == "struct" {
+            if !self.eat('{') {
+                return Err("Expected '{' after struct".to_string());
+            }
+            let mut fields = Vec::new();
+            while !self.eat('}') {
+                let field_ty = self.base()?;
+                let name = self.word().ok_or_else(|| "Expected struct field name".to_string())?;
+                let field_ty = self.arrays(field_ty)?;
+                if !self.eat(';') {
+                    return Err(format!("Expected ';' after struct field {}", name));
+                }
+                fields.push((name, field_ty))
+            }
+            if fields.is_empty() {
+                return Err("Structs must have at least one field".to_string());
+            }
+            LocType::Struct(fields)
+        } else {
+            LocType::Int(int_bytes(&word).ok_or_else(|| format!("Unsupported type {}", word))?)
+        };
+        while self.eat('*') {
+            ty = LocType::Pointer
+        }
+        Ok(ty)
+    }
+
+    fn arrays(&mut self, ty: LocType) -> Result<LocType, String> {
+        let mut dims = Vec::new();
+        while self.eat('[') {
+            let len = self.word().and_then(|len| parse_u64(&len)).ok_or_else(|| "Invalid array length".to_string())?;
+            if !self.eat(']') {
+                return Err("Expected ']' in array type".to_string());
+            }
+            dims.push(len)
+        }
+        // For `t x[n][m]` the outer array has length n
+        Ok(dims.iter().rev().fold(ty, |ty, len| LocType::Array(Box::new(ty), *len)))
+    }
+}
+
+impl LocType {
+    /// Parses a type, where any array dimensions directly follow the
+    /// element type, e.g. `uint64_t[4]`.
+    pub fn parse(ty: &str) -> Result<Self, String> {
+        let mut parser = TypeParser { chars: ty.chars().peekable() };
+        let base = parser.base()?;
+        let loc_type = parser.arrays(base)?;
+        parser.skip_whitespace();
+        if parser.chars.peek().is_some() {
+            return Err(format!("Unexpected characters at end of type {}", ty));
+        }
+        Ok(loc_type)
+    }
+
+    pub fn size(&self) -> u64 {
+        match self {
+            LocType::Int(bytes) => *bytes as u64,
+            LocType::Pointer => 8,
+            LocType::Array(ty, len) => ty.size() * len,
+            LocType::Struct(fields) => {
+                let end = fields.iter().fold(0, |offset, (_, ty)| align_up(offset, ty.align()) + ty.size());
+                align_up(end, self.align())
+            }
+        }
+    }
+
+    pub fn align(&self) -> u64 {
+        match self {
+            LocType::Int(bytes) => *bytes as u64,
+            LocType::Pointer => 8,
+            LocType::Array(ty, _) => ty.align(),
+            LocType::Struct(fields) => fields.iter().map(|(_, ty)| ty.align()).max().unwrap_or(1),
+        }
+    }
+
+    /// The width of the first scalar in the type, which is the width
+    /// used when accessing the location without an explicit width.
+    pub fn access_bytes(&self) -> u32 {
+        match self {
+            LocType::Int(bytes) => *bytes,
+            LocType::Pointer => 8,
+            LocType::Array(ty, _) => ty.access_bytes(),
+            LocType::Struct(fields) => fields[0].1.access_bytes(),
+        }
+    }
+
+    /// The offset and width of each scalar in the type, in order
+    pub fn scalars(&self) -> Vec<(u64, u32)> {
+        let mut scalars = Vec::new();
+        self.collect_scalars(0, &mut scalars);
+        scalars
+    }
+
+    fn collect_scalars(&self, base: u64, scalars: &mut Vec<(u64, u32)>) {
+        match self {
+            LocType::Int(_) | LocType::Pointer => scalars.push((base, self.access_bytes())),
+            LocType::Array(ty, len) => (0..*len).for_each(|i| ty.collect_scalars(base + i * ty.size(), scalars)),
+            LocType::Struct(fields) => {
+                let mut offset = 0;
+                for (_, ty) in fields {
+                    offset = align_up(offset, ty.align());
+                    ty.collect_scalars(base + offset, scalars);
+                    offset += ty.size()
+                }
+            }
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<(u64, &LocType)> {
+        if let LocType::Struct(fields) = self {
+            let mut offset = 0;
+            for (field, ty) in fields {
+                offset = align_up(offset, ty.align());
+                if field == name {
+                    return Some((offset, ty));
+                }
+                offset += ty.size()
+            }
+        }
+        None
+    }
+}
+
+fn align_up(n: u64, align: u64) -> u64 {
+    if align <= 1 {
+        n
+    } else {
+        n + (align - n % align) % align
+    }
+}
+
+pub fn parse_u64(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let n = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<u64>().ok()?,
+    };
+    Some(if value.starts_with('-') { n.wrapping_neg() } else { n })
+}
+
+/// Returns the symbolic location a path refers to
+pub fn path_base(path: &str) -> &str {
+    let path = path.trim().trim_start_matches('&');
+    let end = path.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(path.len());
+    &path[..end]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Placement {
+    Aligned(u64),
+    Fixed(u64),
+}
+
+#[derive(Debug, Default)]
+pub struct Layout {
+    pub addrs: HashMap<String, u64>,
+    pub types: HashMap<String, LocType>,
+}
+
+impl Layout {
+    /// Allocate addresses for each symbolic location, in order. Untyped
+    /// locations take a single stride.
+    pub fn allocate<B>(
+        symbolic: &[String],
+        types: HashMap<String, LocType>,
+        placements: &HashMap<String, Placement>,
+        isa: &ISAConfig<B>,
+    ) -> Result<Self, String> {
+        let stride = isa.symbolic_addr_stride;
+        let mut next = isa.symbolic_addr_base;
+        let mut addrs = HashMap::new();
+        let mut regions: Vec<(u64, u64, &str)> = Vec::new();
+
+        for name in symbolic {
+            let size = types.get(name).map_or(stride, LocType::size).max(1);
+            let addr = match placements.get(name) {
+                Some(Placement::Fixed(addr)) => *addr,
+                Some(Placement::Aligned(align)) => {
+                    let addr = align_up(next, *align);
+                    next = addr + align_up(size, stride);
+                    addr
+                }
+                None => {
+                    let addr = align_up(next, types.get(name).map_or(1, LocType::align));
+                    next = addr + align_up(size, stride);
+                    addr
+                }
+            };
+            if let Some((_, _, other)) = regions.iter().find(|(lo, hi, _)| addr < *hi && *lo < addr + size) {
+                return Err(format!("Symbolic locations {} and {} overlap", other, name));
+            }
+            regions.push((addr, addr + size, name));
+            addrs.insert(name.clone(), addr);
+        }
+
+        for name in types.keys() {
+            if !addrs.contains_key(name) {
+                return Err(format!("Type given for unknown symbolic location {}", name));
+            }
+        }
+        for name in placements.keys() {
+            if !addrs.contains_key(name) {
+                return Err(format!("Placement given for unknown symbolic location {}", name));
+            }
+        }
+
+        Ok(Layout { addrs, types })
+    }
+
+    /// Resolve a path to an address, and the type of the value at that
+    /// address if it is known.
+    pub fn resolve(&self, path: &str) -> Result<(u64, Option<&LocType>), String> {
+        let path = path.trim().trim_start_matches('&');
+        let (path, offset) = match path.split_once('+') {
+            Some((path, offset)) => {
+                (path.trim(), Some(parse_u64(offset).ok_or_else(|| format!("Invalid offset in {}", path))?))
+            }
+            None => (path, None),
+        };
+
+        let base = path_base(path);
+        let mut address = *self.addrs.get(base).ok_or_else(|| format!("Unknown symbolic location {}", base))?;
+        let mut ty = self.types.get(base);
+
+        let mut rest = &path[base.len()..];
+        while !rest.is_empty() {
+            let err = || format!("Invalid location {}", path);
+            if let Some(index) = rest.strip_prefix('[') {
+                let end = index.find(']').ok_or_else(err)?;
+                let i = parse_u64(&index[..end]).ok_or_else(err)?;
+                match ty {
+                    Some(LocType::Array(elem, len)) if i < *len => {
+                        address += i * elem.size();
+                        ty = Some(elem)
+                    }
+                    _ => return Err(format!("Invalid array access in {}", path)),
+                }
+                rest = &index[end + 1..]
+            } else if let Some(field) = rest.strip_prefix('.') {
+                let end = field.find(&['.', '['][..]).unwrap_or(field.len());
+                let (offset, field_ty) =
+                    ty.and_then(|ty| ty.field(&field[..end])).ok_or_else(|| format!("Invalid field in {}", path))?;
+                address += offset;
+                ty = Some(field_ty);
+                rest = &field[end..]
+            } else {
+                return Err(err());
+            }
+        }
+
+        match offset {
+            Some(offset) => Ok((address + offset, None)),
+            None => Ok((address, ty)),
+        }
+    }
+
+    /// The address and width of a location when it is accessed. The
+    /// default width is 4 bytes if the location has no type.
+    pub fn access(&self, path: &str) -> Result<(u64, u32), String> {
+        let (address, ty) = self.resolve(path)?;
+        Ok((address, ty.map_or(4, LocType::access_bytes)))
+    }
+
+    /// A value is either an integer or the address of a location.
+    pub fn value(&self, value: &str) -> Result<u64, String> {
+        match parse_u64(value) {
+            Some(n) => Ok(n),
+            None => self.resolve(value).map(|(address, _)| address),
+        }
+    }
+
+    /// The access width of each typed symbolic location
+    pub fn sizeof(&self) -> HashMap<String, u32> {
+        self.types.iter().map(|(name, ty)| (name.clone(), ty.access_bytes())).collect()
+    }
+
+    /// Set the initial value of a location. Aggregate types are
+    /// initialised with a list of values for their scalars in order,
+    /// with any remaining scalars being zero. Locations without a type
+    /// hold a 64-bit value.
+    pub fn initialise(&self, memory: &mut InitialMemory, path: &str, values: &[&str]) -> Result<(), String> {
+        let (address, ty) = self.resolve(path)?;
+        let scalars = match ty {
+            Some(ty) => ty.scalars(),
+            None => vec![(0, 8)],
+        };
+        if values.len() > scalars.len() || (values.len() > 1 && scalars.len() == 1) {
+            return Err(format!("Too many initial values for {}", path));
+        }
+        for ((offset, bytes), value) in scalars.iter().zip(values.iter()) {
+            memory.write(address + offset, *bytes, self.value(value)?)
+        }
+        Ok(())
+    }
+}
+
+/// The initial contents of memory for the symbolic locations, as
+/// individual bytes. Any byte not in the map is zero.
+#[derive(Clone, Debug, Default)]
+pub struct InitialMemory {
+    bytes: BTreeMap<u64, u8>,
+}
+
+impl InitialMemory {
+    /// Write a little-endian value of the given width. Bytes beyond
+    /// the first eight are zero.
+    pub fn write(&mut self, address: u64, bytes: u32, value: u64) {
+        for i in 0..bytes as u64 {
+            let byte = if i < 8 { (value >> (8 * i)) as u8 } else { 0 };
+            self.bytes.insert(address + i, byte);
+        }
+    }
+
+    /// Read a little-endian value of the given width, of which only
+    /// the first eight bytes can be non-zero.
+    pub fn read(&self, address: u64, bytes: u32) -> u64 {
+        (0..(bytes as u64).min(8))
+            .fold(0, |value, i| value | ((*self.bytes.get(&(address + i)).unwrap_or(&0) as u64) << (8 * i)))
+    }
+
+    /// All the addresses at which a read of the given width has a
+    /// non-zero initial value.
+    pub fn addresses(&self, bytes: u32) -> Vec<u64> {
+        let mut addresses = BTreeSet::new();
+        for (address, _) in self.bytes.iter().filter(|(_, byte)| **byte != 0) {
+            for i in 0..(bytes as u64).min(8) {
+                if let Some(start) = address.checked_sub(i) {
+                    addresses.insert(start);
+                }
+            }
+        }
+        addresses.into_iter().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.values().all(|byte| *byte == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loc_types() {
+        assert_eq!(LocType::parse("uint64_t"), Ok(LocType::Int(8)));
+        assert_eq!(LocType::parse("uint32_t *"), Ok(LocType::Pointer));
+        assert_eq!(LocType::parse("uint16_t[4]"), Ok(LocType::Array(Box::new(LocType::Int(2)), 4)));
+        assert!(LocType::parse("float").is_err());
+        assert!(LocType::parse("uint64_t[4").is_err());
+
+        let ty = LocType::parse("struct { uint8_t a; uint64_t *b; uint32_t c[3]; }").unwrap();
+        assert_eq!(ty.size(), 32);
+        assert_eq!(ty.align(), 8);
+        assert_eq!(ty.access_bytes(), 1);
+        assert_eq!(ty.scalars(), vec![(0, 1), (8, 8), (16, 4), (20, 4), (24, 4)]);
+
+        let ty = LocType::parse("uint8_t[2][3]").unwrap();
+        assert_eq!(ty, LocType::Array(Box::new(LocType::Array(Box::new(LocType::Int(1)), 3)), 2));
+        assert_eq!(ty.size(), 6);
+    }
+
+    #[test]
+    fn layout_paths() {
+        let mut layout = Layout::default();
+        layout.addrs.insert("x".to_string(), 0x1000);
+        layout.addrs.insert("s".to_string(), 0x2000);
+        layout.addrs.insert("p".to_string(), 0x3000);
+        layout.types.insert("x".to_string(), LocType::parse("uint16_t[4]").unwrap());
+        layout.types.insert("s".to_string(), LocType::parse("struct { uint8_t a; uint32_t b[2]; }").unwrap());
+
+        assert_eq!(layout.access("x"), Ok((0x1000, 2)));
+        assert_eq!(layout.access("x[3]"), Ok((0x1006, 2)));
+        assert_eq!(layout.access("s.b[1]"), Ok((0x2008, 4)));
+        assert_eq!(layout.access("&s.b+1"), Ok((0x2005, 4)));
+        assert_eq!(layout.access("p"), Ok((0x3000, 4)));
+        assert!(layout.access("x[4]").is_err());
+        assert!(layout.access("s.c").is_err());
+        assert!(layout.access("y").is_err());
+
+        let mut memory = InitialMemory::default();
+        layout.initialise(&mut memory, "x", &["1", "2"]).unwrap();
+        layout.initialise(&mut memory, "s.b[1]", &["-1"]).unwrap();
+        layout.initialise(&mut memory, "p", &["x[2]"]).unwrap();
+        assert_eq!(memory.read(0x1000, 8), 0x0002_0001);
+        assert_eq!(memory.read(0x2008, 8), 0xFFFF_FFFF);
+        assert_eq!(memory.read(0x3000, 8), 0x1004);
+        assert!(layout.initialise(&mut memory, "s.a", &["1", "2"]).is_err());
+    }
+
+    #[test]
+    fn initial_memory() {
+        let mut memory = InitialMemory::default();
+        assert!(memory.is_empty());
+        memory.write(0x100, 4, 0x11223344);
+        memory.write(0x104, 2, 0xFFFF_0055);
+        assert_eq!(memory.read(0x100, 8), 0x0055_11223344);
+        assert_eq!(memory.read(0x102, 2), 0x1122);
+        assert_eq!(memory.read(0x105, 4), 0);
+        assert_eq!(memory.addresses(2), vec![0xFF, 0x100, 0x101, 0x102, 0x103, 0x104]);
+        assert!(!memory.is_empty())
+    }
+}
@@ -0,0 +1,382 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module builds the page tables for litmus tests which run
+//! with address translation enabled. A test describes its mappings
+//! in a `[page_table]` section, where each virtual location maps to
+//! a physical location, e.g. `x = "y"`, or to `"invalid"`. A list of
+//! mappings such as `x = ["x", "invalid"]` gives the initial mapping
+//! followed by the other descriptors the test may write, so that
+//! translation table walks only need to consider those values.
+//!
+//! The thread code, the exception vector table, and any symbolic
+//! location without an explicit mapping are identity mapped.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::Arc;
+
+use isla_lib::concrete::BV;
+use isla_lib::config::MmuConfig;
+use isla_lib::memory::Region;
+use isla_lib::smt::Solver;
+
+use super::layout::{parse_u64, InitialMemory, Layout};
+
+/// Every descriptor is 8 bytes
+const DESCRIPTOR_BYTES: u64 = 8;
+
+#[derive(Clone, Debug)]
+pub struct PageTables {
+    base: u64,
+    next: u64,
+    page_size: u64,
+    levels: u32,
+    bits_per_level: u32,
+    descriptor_shift: u32,
+    table_descriptor: u64,
+    page_descriptor: u64,
+    invalid_descriptor: u64,
+    /// The table for each level, keyed by the level and the bits of
+    /// the virtual address translated by the levels above it
+    tables: HashMap<(u32, u64), u64>,
+    /// The possible values of each valid descriptor, with the
+    /// initial value first
+    descriptors: BTreeMap<u64, Vec<u64>>,
+}
+
+impl PageTables {
+    pub fn new<B>(mmu: &MmuConfig<B>) -> Self {
+        let mut tables = HashMap::new();
+        tables.insert((0, 0), mmu.page_table_base);
+        PageTables {
+            base: mmu.page_table_base,
+            next: mmu.page_table_base + mmu.page_size,
+            page_size: mmu.page_size,
+            levels: mmu.levels,
+            bits_per_level: mmu.bits_per_level,
+            descriptor_shift: mmu.descriptor_shift,
+            table_descriptor: mmu.table_descriptor,
+            page_descriptor: mmu.page_descriptor,
+            invalid_descriptor: mmu.invalid_descriptor,
+            tables,
+            descriptors: BTreeMap::new(),
+        }
+    }
+
+    /// The number of virtual address bits below the index for a level
+    fn shift(&self, level: u32) -> u32 {
+        self.page_size.trailing_zeros() + self.bits_per_level * (self.levels - 1 - level)
+    }
+
+    fn index(&self, va: u64, level: u32) -> u64 {
+        (va >> self.shift(level)) & ((1 << self.bits_per_level) - 1)
+    }
+
+    fn prefix(&self, va: u64, level: u32) -> u64 {
+        if level == 0 {
+            0
+        } else {
+            va >> self.shift(level - 1)
+        }
+    }
+
+    fn encode(&self, address: u64, attributes: u64) -> u64 {
+        (address >> self.descriptor_shift) | attributes
+    }
+
+    /// The descriptor mapping a page to the given physical address
+    pub fn page_descriptor(&self, pa: u64) -> u64 {
+        self.encode(pa & !(self.page_size - 1), self.page_descriptor)
+    }
+
+    pub fn invalid_descriptor(&self) -> u64 {
+        self.invalid_descriptor
+    }
+
+    /// The address of the last level descriptor for a virtual address,
+    /// allocating tables as required
+    fn leaf(&mut self, va: u64) -> u64 {
+        let mut table = self.base;
+        for level in 1..self.levels {
+            let parent = table + self.index(va, level - 1) * DESCRIPTOR_BYTES;
+            let key = (level, self.prefix(va, level));
+            table = match self.tables.get(&key) {
+                Some(table) => *table,
+                None => {
+                    let table = self.next;
+                    self.next += self.page_size;
+                    self.tables.insert(key, table);
+                    self.descriptors.insert(parent, vec![self.encode(table, self.table_descriptor)]);
+                    table
+                }
+            }
+        }
+        table + self.index(va, self.levels - 1) * DESCRIPTOR_BYTES
+    }
+
+    /// The address of the descriptor that translates a virtual address,
+    /// if it is mapped
+    pub fn descriptor_address(&self, va: u64) -> Option<u64> {
+        let mut table = self.base;
+        for level in 1..self.levels {
+            table = *self.tables.get(&(level, self.prefix(va, level)))?
+        }
+        let desc = table + self.index(va, self.levels - 1) * DESCRIPTOR_BYTES;
+        self.descriptors.get(&desc).map(|_| desc)
+    }
+
+    /// Map the page containing a virtual address with the given
+    /// descriptors, the first of which is the initial one. A page can
+    /// be mapped more than once with the same descriptors.
+    pub fn map(&mut self, va: u64, descriptors: Vec<u64>) -> Result<(), String> {
+        let leaf = self.leaf(va);
+        match self.descriptors.get(&leaf) {
+            Some(existing) if *existing != descriptors => {
+                Err(format!("Conflicting mappings for the page containing virtual address 0x{:x}", va))
+            }
+            _ => {
+                self.descriptors.insert(leaf, descriptors);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn identity_map(&mut self, range: Range<u64>) -> Result<(), String> {
+        let mut page = range.start & !(self.page_size - 1);
+        while page < range.end {
+            let desc = self.page_descriptor(page);
+            self.map(page, vec![desc])?;
+            page += self.page_size
+        }
+        Ok(())
+    }
+
+    /// The physical memory used by the tables
+    pub fn range(&self) -> Range<u64> {
+        self.base..self.next
+    }
+
+    /// Write the initial descriptors into memory
+    pub fn initialise(&self, memory: &mut InitialMemory) {
+        for (address, values) in &self.descriptors {
+            memory.write(*address, DESCRIPTOR_BYTES as u32, values[0])
+        }
+    }
+
+    /// Reads of each descriptor are constrained to its possible
+    /// values, and any other entry in the tables is invalid.
+    pub fn regions<B: BV>(&self) -> Vec<Region<B>> {
+        let mut regions = Vec::new();
+        let mut address = self.base;
+        while address < self.next {
+            let values = match self.descriptors.get(&address) {
+                Some(values) => values.clone(),
+                None => vec![self.invalid_descriptor],
+            };
+            regions.push(Region::Constrained(
+                address..(address + DESCRIPTOR_BYTES),
+                Arc::new(move |solver: &mut Solver<B>| {
+                    use isla_lib::smt::smtlib::{Def, Exp, Ty};
+                    let v = solver.fresh();
+                    let exp: Exp = values.iter().fold(Exp::Bool(false), |exp, value| {
+                        Exp::Or(
+                            Box::new(Exp::Eq(Box::new(Exp::Var(v)), Box::new(Exp::Bits64(*value, 64)))),
+                            Box::new(exp),
+                        )
+                    });
+                    solver.add(Def::DeclareConst(v, Ty::BitVec(64)));
+                    solver.add(Def::Assert(exp));
+                    v
+                }),
+            ));
+            address += DESCRIPTOR_BYTES
+        }
+        regions
+    }
+
+    /// Parse an address or symbolic location in the `[page_table]`
+    /// section, returning the descriptor that maps to it
+    fn parse_descriptor(&self, target: &str, layout: &Layout) -> Result<u64, String> {
+        if target == "invalid" {
+            Ok(self.invalid_descriptor)
+        } else {
+            parse_u64(target)
+                .or_else(|| layout.resolve(target).ok().map(|(address, _)| address))
+                .map(|pa| self.page_descriptor(pa))
+                .ok_or_else(|| format!("Invalid physical location {} in [page_table]", target))
+        }
+    }
+
+    /// Build the page tables for a litmus test from its `[page_table]`
+    /// section. Regions of memory in `identity` are identity mapped,
+    /// along with any symbolic location not mentioned in the table.
+    pub fn from_toml<B>(
+        table: &toml::value::Table,
+        layout: &Layout,
+        identity: &[Range<u64>],
+        mmu: &MmuConfig<B>,
+    ) -> Result<Self, String> {
+        let mut page_tables = PageTables::new(mmu);
+
+        for (virt, targets) in table {
+            let va = parse_u64(virt)
+                .or_else(|| layout.resolve(virt).ok().map(|(address, _)| address))
+                .ok_or_else(|| format!("Invalid virtual location {} in [page_table]", virt))?;
+            let descriptors = match targets {
+                toml::Value::String(target) => vec![page_tables.parse_descriptor(target, layout)?],
+                toml::Value::Array(targets) if !targets.is_empty() => targets
+                    .iter()
+                    .map(|target| {
+                        target
+                            .as_str()
+                            .ok_or_else(|| format!("Mappings for {} in [page_table] must be strings", virt))
+                            .and_then(|target| page_tables.parse_descriptor(target, layout))
+                    })
+                    .collect::<Result<_, _>>()?,
+                _ => return Err(format!("Invalid mapping for {} in [page_table]", virt)),
+            };
+            page_tables.map(va, descriptors)?
+        }
+
+        for range in identity {
+            page_tables.identity_map(range.clone())?
+        }
+
+        let mut locations: Vec<_> = layout.addrs.iter().filter(|(name, _)| !table.contains_key(*name)).collect();
+        locations.sort();
+        for (name, address) in locations {
+            let size = layout.types.get(name).map_or(1, |ty| ty.size()).max(1);
+            page_tables.identity_map(*address..(address + size)).map_err(|_| {
+                format!(
+                    "Symbolic location {} shares a page with a location that is not identity mapped, \
+                     consider placing it on its own page with [layout] {} = {{ align = \"page\" }}",
+                    name, name
+                )
+            })?
+        }
+
+        Ok(page_tables)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mmu() -> MmuConfig<isla_lib::concrete::bitvector64::B64> {
+        MmuConfig {
+            page_table_base: 0x300000,
+            page_size: 0x1000,
+            levels: 4,
+            bits_per_level: 9,
+            descriptor_shift: 0,
+            table_descriptor: 0x3,
+            page_descriptor: 0x743,
+            invalid_descriptor: 0,
+            read_kind: None,
+            registers: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn page_table_walk() {
+        let mut pt = PageTables::new(&mmu());
+        pt.map(0x600010, vec![pt.page_descriptor(0x601000), 0]).unwrap();
+        pt.identity_map(0x400000..0x402000).unwrap();
+        // Root, level 1, and level 2 tables, and a level 3 table for
+        // each 2MB block
+        assert_eq!(pt.range(), 0x300000..0x305000);
+        assert_eq!(pt.descriptors[&0x300000], vec![0x301003]);
+        assert_eq!(pt.descriptors[&0x301000], vec![0x302003]);
+        assert_eq!(pt.descriptors[&0x302018], vec![0x303003]);
+        assert_eq!(pt.descriptors[&0x302010], vec![0x304003]);
+        assert_eq!(pt.descriptor_address(0x600fff), Some(0x303000));
+        assert_eq!(pt.descriptors[&0x303000], vec![0x601743, 0]);
+        assert_eq!(pt.descriptors[&0x304008], vec![0x401743]);
+        assert_eq!(pt.descriptor_address(0x700000), None);
+        assert!(pt.map(0x600000, vec![0x600743]).is_err());
+        assert!(pt.identity_map(0x401000..0x401001).is_ok())
+    }
+
+    #[test]
+    fn page_table_from_toml() {
+        use isla_lib::concrete::bitvector64::B64;
+        use isla_lib::ir::Val;
+        use isla_lib::memory::Memory;
+        use isla_lib::smt::smtlib::Exp;
+        use isla_lib::smt::{Config, Context, SmtResult};
+
+        let mut layout = Layout::default();
+        layout.addrs.insert("x".to_string(), 0x600000);
+        layout.addrs.insert("y".to_string(), 0x601000);
+        layout.addrs.insert("z".to_string(), 0x602000);
+        let table = r#"x = ["y", "invalid"]"#.parse::<toml::Value>().unwrap();
+        let code = vec![Range { start: 0x400000, end: 0x401000 }];
+        let pt = PageTables::from_toml(table.as_table().unwrap(), &layout, &code, &mmu()).unwrap();
+
+        let mut initial_memory = InitialMemory::default();
+        pt.initialise(&mut initial_memory);
+        let leaf = pt.descriptor_address(0x600000).unwrap();
+        assert_eq!(initial_memory.read(leaf, 8), 0x601743);
+        // Locations not in the table are identity mapped
+        assert_eq!(initial_memory.read(pt.descriptor_address(0x602000).unwrap(), 8), 0x602743);
+        assert_eq!(initial_memory.read(pt.descriptor_address(0x400000).unwrap(), 8), 0x400743);
+
+        // Walk the tables for x through memory, as a translation
+        // table walk in the model would
+        let cfg = Config::new();
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let mut memory = Memory::new();
+        for region in pt.regions() {
+            memory.add_region(region)
+        }
+        let mut table = 0x300000;
+        for level in 0..4 {
+            let desc = table + pt.index(0x600000, level) * DESCRIPTOR_BYTES;
+            let value = memory.read(Val::Unit, Val::Bits(B64::from_u64(desc)), Val::I128(8), &mut solver).unwrap();
+            let v = match value {
+                Val::Symbolic(v) => v,
+                _ => panic!("Expected a symbolic descriptor"),
+            };
+            let is = |value| Exp::Eq(Box::new(Exp::Var(v)), Box::new(Exp::Bits64(value, 64)));
+            if level < 3 {
+                let next = pt.descriptors[&desc][0];
+                assert!(solver.check_sat_with(&Exp::Not(Box::new(is(next)))) == SmtResult::Unsat);
+                table = next & !0xFFF
+            } else {
+                assert_eq!(desc, leaf);
+                assert!(solver.check_sat_with(&is(0x601743)) == SmtResult::Sat);
+                assert!(solver.check_sat_with(&is(0)) == SmtResult::Sat);
+                assert!(solver.check_sat_with(&is(0x600743)) == SmtResult::Unsat)
+            }
+        }
+    }
+}
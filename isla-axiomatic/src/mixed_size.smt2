@@ -0,0 +1,59 @@
+(define-fun covers ((ev Event) (a (_ BitVec 64))) Bool
+  (and (bvule (addr_of ev) a)
+       (bvult a (bvadd (addr_of ev) (size_of ev)))))
+
+; Coherence is derived from a rank for each write, so the coherence
+; order for each byte is the order of the writes which write it.
+(declare-fun co-rank (Event) Int)
+
+(assert (= (co-rank IW) 0))
+
+(assert (forall ((ev Event))
+  (=> (W ev) (> (co-rank ev) 0))))
+
+; Two distinct overlapping writes have distinct ranks
+(assert (forall ((ev1 Event) (ev2 Event))
+  (=>
+    (and (not (= ev1 ev2))
+         (W ev1)
+         (W ev2)
+         (overlap ev1 ev2))
+    (not (= (co-rank ev1) (co-rank ev2))))))
+
+(declare-fun co (Event Event) Bool)
+
+; co relates the initial write to all writes, and overlapping writes
+; by rank. It is closed transitively, so writes are also ordered if
+; they are linked by a chain of overlapping writes in increasing
+; rank. As co can only increase the rank, this has a unique solution.
+(assert (forall ((ev1 Event) (ev2 Event))
+  (= (co ev1 ev2)
+     (and (W ev2)
+          (or (= ev1 IW)
+              (and (W ev1)
+                   (< (co-rank ev1) (co-rank ev2))
+                   (or (overlap ev1 ev2)
+                       (exists ((ev3 Event))
+                         (and (co ev1 ev3) (co ev3 ev2))))))))))
+
+; A byte read from a write must be written by it with the same value
+(define-fun rf-byte-ok ((ev Event) (a (_ BitVec 64)) (b (_ BitVec 8))) Bool
+  (or (and (= ev IW) (= b (initial_byte a)))
+      (and (W ev) (covers ev a) (= b (byte_of ev a)))))
+
+(declare-fun rf (Event Event) Bool)
+
+; A read reads-from every write it reads at least one byte from
+(assert (forall ((ev1 Event) (ev2 Event))
+  (= (rf ev1 ev2) (rf-bytes ev1 ev2))))
+
+(define-fun last_byte ((a (_ BitVec 64)) (b (_ BitVec 8))) Bool
+  (or (exists ((ev Event))
+        (and (W ev)
+             (covers ev a)
+             (= (byte_of ev a) b)
+             (not (exists ((ev2 Event))
+                    (and (co ev ev2) (covers ev2 a))))))
+      (and (= b (initial_byte a))
+           (not (exists ((ev Event))
+                  (and (W ev) (covers ev a)))))))
@@ -0,0 +1,276 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module implements a cheap filter on candidate executions,
+//! which is applied before they are combined with a memory model and
+//! passed to the SMT solver.
+//!
+//! A path through a thread will often only be possible if some read
+//! returns a specific value, for example when it branches on the
+//! value it read. We find these values once for each path, and can
+//! then discard any candidate containing such a read if no write in
+//! the candidate (or the initial state of memory) could provide the
+//! value it requires. This is independent of the memory model, as
+//! every read must read from some write.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use isla_lib::concrete::BV;
+use isla_lib::ir::Val;
+use isla_lib::log;
+use isla_lib::smt::smtlib::Exp;
+use isla_lib::smt::{Config, Context, EvPath, Event, Model, Solver, Sym};
+
+use crate::axiomatic::relations::{is_read, is_write};
+use crate::axiomatic::{AxEvent, ExecutionInfo};
+use crate::litmus::layout::InitialMemory;
+use crate::litmus::Litmus;
+use crate::smt_events::is_mixed_size;
+
+/// Find the values read or written by memory events in a path that
+/// are forced to be a single concrete value by the path's
+/// constraints. Values wider than 64 bits are ignored.
+pub fn forced_values<B: BV>(path: &[Event<B>]) -> HashMap<Sym, B> {
+    let mut forced = HashMap::new();
+
+    let cfg = Config::new();
+    let ctx = Context::new(cfg);
+    let mut solver = Solver::<B>::new(&ctx);
+
+    for event in path {
+        if let Event::Smt(def) = event {
+            solver.add(def.clone())
+        }
+    }
+
+    if !matches!(solver.check_sat().is_sat(), Ok(true)) {
+        return forced;
+    }
+
+    let vars: Vec<Sym> = path
+        .iter()
+        .filter_map(|event| match event {
+            Event::ReadMem { value: Val::Symbolic(v), .. } | Event::WriteMem { data: Val::Symbolic(v), .. } => Some(*v),
+            _ => None,
+        })
+        .collect();
+
+    // Any value in a model is a candidate for being forced, so we
+    // check if the constraints are unsatisfiable without it.
+    let values: Vec<(Sym, u64, u32)> = {
+        let mut model = Model::new(&solver);
+        vars.iter()
+            .filter_map(|v| match model.get_var(*v) {
+                Ok(Some(Exp::Bits64(bits, len))) => Some((*v, bits, len)),
+                _ => None,
+            })
+            .collect()
+    };
+
+    for (v, bits, len) in values {
+        let other_value = Exp::Neq(Box::new(Exp::Var(v)), Box::new(Exp::Bits64(bits, len)));
+        if matches!(solver.check_sat_with(&other_value).is_unsat(), Ok(true)) {
+            forced.insert(v, B::new(bits, len));
+        }
+    }
+
+    forced
+}
+
+/// The forced values for every path through each thread of a litmus
+/// test, used to decide if a candidate execution could be consistent.
+pub struct CandidateFilter<B> {
+    forced: Vec<Vec<HashMap<Sym, B>>>,
+}
+
+fn byte_of<B: BV>(value: B, i: u32) -> u8 {
+    (value.lower_u64() >> (8 * i)) as u8
+}
+
+impl<B: BV> CandidateFilter<B> {
+    pub fn new(threads: &[Vec<EvPath<B>>]) -> Self {
+        let now = Instant::now();
+        let forced = threads.iter().map(|paths| paths.iter().map(|path| forced_values(path)).collect()).collect();
+        log!(log::VERBOSE, &format!("Finding forced values took: {}ms", now.elapsed().as_millis()));
+        CandidateFilter { forced }
+    }
+
+    /// The concrete value of `val` for an event, if it has one.
+    fn value(&self, paths: &[usize], ev: &AxEvent<B>, val: &Val<B>) -> Option<B> {
+        match val {
+            Val::Bits(bv) => Some(*bv),
+            Val::Symbolic(v) => self.forced[ev.thread_id][paths[ev.thread_id]].get(v).copied(),
+            _ => None,
+        }
+    }
+
+    /// Returns false if a read in the candidate execution is forced
+    /// to read a value that no write can provide. `paths` is the
+    /// index of the path taken by each thread.
+    fn read_satisfiable(
+        &self,
+        paths: &[usize],
+        read: &AxEvent<B>,
+        events: &[AxEvent<B>],
+        initial_memory: &InitialMemory,
+        mixed_size: bool,
+    ) -> bool {
+        let (addr, bytes, value) = match (read.address(), read.read_value()) {
+            (Some(Val::Bits(addr)), Some((Val::Symbolic(v), bytes))) if bytes <= 8 => {
+                match self.forced[read.thread_id][paths[read.thread_id]].get(v) {
+                    Some(value) => (addr.lower_u64(), bytes, *value),
+                    None => return true,
+                }
+            }
+            _ => return true,
+        };
+
+        if mixed_size {
+            // Each byte can be read from the initial state, or any
+            // write which covers it
+            let mut possible: Vec<Vec<u8>> =
+                (0..bytes).map(|i| vec![initial_memory.read(addr + i as u64, 1) as u8]).collect();
+            for write in events.iter().filter(|ev| is_write(ev)) {
+                let waddr = match write.address() {
+                    Some(Val::Bits(waddr)) => waddr.lower_u64(),
+                    _ => return true,
+                };
+                let (data, wbytes) = write.write_data().unwrap();
+                if waddr + wbytes as u64 <= addr || addr + bytes as u64 <= waddr {
+                    continue;
+                }
+                match self.value(paths, write, data) {
+                    Some(data) if wbytes <= 8 => {
+                        for (i, bytes) in possible.iter_mut().enumerate() {
+                            let a = addr + i as u64;
+                            if waddr <= a && a < waddr + wbytes as u64 {
+                                bytes.push(byte_of(data, (a - waddr) as u32))
+                            }
+                        }
+                    }
+                    _ => return true,
+                }
+            }
+            possible.iter().enumerate().all(|(i, bytes)| bytes.contains(&byte_of(value, i as u32)))
+        } else {
+            for write in events.iter().filter(|ev| is_write(ev)) {
+                match write.address() {
+                    Some(Val::Bits(waddr)) if waddr.lower_u64() != addr => continue,
+                    Some(Val::Bits(_)) => (),
+                    _ => return true,
+                }
+                let (data, wbytes) = write.write_data().unwrap();
+                match self.value(paths, write, data) {
+                    Some(data) if wbytes == bytes && data != value => (),
+                    _ => return true,
+                }
+            }
+            value.lower_u64() == initial_memory.read(addr, bytes)
+        }
+    }
+
+    /// Decide if a candidate execution could be consistent with any
+    /// memory model. If this returns false the candidate can be
+    /// discarded without invoking the SMT solver.
+    pub fn may_be_consistent(&self, paths: &[usize], exec: &ExecutionInfo<B>, litmus: &Litmus<B>) -> bool {
+        let mixed_size = is_mixed_size(&exec.events, litmus);
+        exec.events
+            .iter()
+            .filter(|ev| is_read(ev))
+            .all(|read| self.read_satisfiable(paths, read, &exec.events, &litmus.initial_memory, mixed_size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use isla_lib::concrete::bitvector64::B64;
+    use isla_lib::smt::smtlib::{Def, Ty};
+
+    fn ax_event<'ev, B: BV>(thread_id: usize, name: &str, base: &'ev Event<B>) -> AxEvent<'ev, B> {
+        AxEvent {
+            opcode: B::zeros(32),
+            po: 0,
+            intra_instruction_order: 0,
+            thread_id,
+            name: name.to_string(),
+            base,
+            is_ifetch: false,
+            is_translate: false,
+            guard: None,
+        }
+    }
+
+    #[test]
+    fn test_forced_read() {
+        let v0 = Sym::from_u32(0);
+        let v1 = Sym::from_u32(1);
+        let address = Val::Bits(B64::new(0x1000, 64));
+        let read =
+            Event::ReadMem { value: Val::Symbolic(v0), read_kind: Val::Unit, address: address.clone(), bytes: 8 };
+        let path: EvPath<B64> = vec![
+            Event::Smt(Def::DeclareConst(v0, Ty::BitVec(64))),
+            Event::Smt(Def::DeclareConst(v1, Ty::BitVec(64))),
+            Event::Smt(Def::Assert(Exp::Eq(Box::new(Exp::Var(v0)), Box::new(Exp::Bits64(1, 64))))),
+            read.clone(),
+            Event::ReadMem { value: Val::Symbolic(v1), read_kind: Val::Unit, address: address.clone(), bytes: 8 },
+        ];
+
+        let forced = forced_values(&path);
+        assert_eq!(forced.get(&v0), Some(&B64::new(1, 64)));
+        assert_eq!(forced.get(&v1), None);
+
+        let write = |value| Event::WriteMem {
+            value: Sym::from_u32(2),
+            write_kind: Val::Unit,
+            address: address.clone(),
+            data: Val::Bits(B64::new(value, 64)),
+            bytes: 8,
+        };
+        let write1 = write(1);
+        let write2 = write(2);
+        let filter = CandidateFilter::new(&[vec![path], vec![vec![write1.clone()], vec![write2.clone()]]]);
+        let initial_memory = InitialMemory::default();
+
+        let events = vec![ax_event(0, "R", &read), ax_event(1, "W", &write1)];
+        assert!(filter.read_satisfiable(&[0, 0], &events[0], &events, &initial_memory, false));
+        assert!(filter.read_satisfiable(&[0, 0], &events[0], &events, &initial_memory, true));
+
+        let events = vec![ax_event(0, "R", &read), ax_event(1, "W", &write2)];
+        assert!(!filter.read_satisfiable(&[0, 1], &events[0], &events, &initial_memory, false));
+        assert!(!filter.read_satisfiable(&[0, 1], &events[0], &events, &initial_memory, true));
+
+        // The value can also be read from the initial state
+        let mut initial_memory = InitialMemory::default();
+        initial_memory.write(0x1000, 8, 1);
+        assert!(filter.read_satisfiable(&[0, 1], &events[0], &events, &initial_memory, false));
+        assert!(filter.read_satisfiable(&[0, 1], &events[0], &events, &initial_memory, true));
+    }
+}
@@ -33,17 +33,18 @@ use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
-use std::process::Command;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 use std::sync::Arc;
 use std::time::Instant;
 
 use isla_cat::cat;
 use isla_cat::cat::Cat;
 
+use isla_lib::cache::Cache;
 use isla_lib::concrete::BV;
-use isla_lib::config::ISAConfig;
+use isla_lib::config::{ISAConfig, MemoryKind};
 use isla_lib::executor;
 use isla_lib::executor::LocalFrame;
 use isla_lib::ir::*;
@@ -54,10 +55,14 @@ use isla_lib::simplify::{write_events_with_opts, WriteOpts};
 use isla_lib::smt::smtlib;
 use isla_lib::smt::{EvPath, Event};
 
-use crate::axiomatic::{Candidates, ExecutionInfo, ThreadId};
+use crate::axiomatic::{Candidates, ExecutionInfo, SymbolicCandidates, ThreadId};
 use crate::footprint_analysis::{footprint_analysis, Footprint, FootprintError};
-use crate::litmus::Litmus;
-use crate::smt_events::smt_of_candidate;
+use crate::litmus::{Litmus, Loc};
+use crate::pruning::CandidateFilter;
+use crate::sexp::Sexp;
+use crate::sexp_lexer::SexpLexer;
+use crate::sexp_parser::SexpParser;
+use crate::smt_events::{smt_of_candidate, smt_of_outcome_locations};
 
 #[derive(Debug)]
 pub enum LitmusRunError<E> {
@@ -96,10 +101,19 @@ impl<E: Error> Error for LitmusRunError<E> {
 
 pub struct LitmusRunInfo {
     pub candidates: usize,
+    /// The number of candidates discarded before being passed to
+    /// the callback, see `pruning::CandidateFilter`
+    pub pruned: usize,
 }
 
+/// The paths through each thread of a litmus test, and the footprints
+/// of the instructions they execute.
+type LitmusThreads<B> = (Vec<Vec<EvPath<B>>>, HashMap<B, Footprint>);
+
+/// Symbolically execute each thread of a litmus test, and run the
+/// footprint analysis on the results.
 #[allow(clippy::too_many_arguments)]
-pub fn litmus_per_candidate<B, P, F, E>(
+fn litmus_threads<B: BV, E>(
     num_threads: usize,
     timeout: Option<u64>,
     litmus: &Litmus<B>,
@@ -108,15 +122,8 @@ pub fn litmus_per_candidate<B, P, F, E>(
     mut lets: Bindings<B>,
     shared_state: &SharedState<B>,
     isa_config: &ISAConfig<B>,
-    cache: P,
-    callback: &F,
-) -> Result<LitmusRunInfo, LitmusRunError<E>>
-where
-    B: BV,
-    P: AsRef<Path>,
-    F: Sync + Send + Fn(ThreadId, &[&[Event<B>]], &HashMap<B, Footprint>) -> Result<(), E>,
-    E: Send,
-{
+    cache: &Cache,
+) -> Result<LitmusThreads<B>, LitmusRunError<E>> {
     use LitmusRunError::*;
 
     let mut memory = Memory::new();
@@ -125,9 +132,23 @@ where
         memory.add_region(region.clone())
     }
 
+    if let Some(page_tables) = &litmus.page_tables {
+        for region in page_tables.regions() {
+            memory.add_region(region)
+        }
+    }
+
     memory.add_concrete_region(isa_config.thread_base..isa_config.thread_top, HashMap::new());
-    // FIXME: Insert a blank exception vector table for AArch64
-    memory.add_concrete_region(0x0_u64..0x8000_u64, HashMap::new());
+
+    for region in &isa_config.memory {
+        match &region.kind {
+            MemoryKind::Concrete(contents) => memory.add_concrete_region(region.range.clone(), contents.clone()),
+            MemoryKind::Symbolic | MemoryKind::Device => memory.add_symbolic_region(region.range.clone()),
+        }
+        if let (true, Some(tags)) = (region.tagged, &isa_config.tags) {
+            memory.add_tagged_region(region.range.clone(), tags.granule, tags.width)
+        }
+    }
 
     let mut current_base = isa_config.thread_base;
     for (thread, _, code) in litmus.assembled.iter() {
@@ -137,6 +158,12 @@ where
         }
         current_base += isa_config.thread_stride
     }
+    for (address, code) in litmus.exception_handlers.iter() {
+        log!(log::VERBOSE, &format!("Exception handler @ 0x{:x}", address));
+        for (i, byte) in code.iter().enumerate() {
+            memory.write_byte(address + i as u64, *byte)
+        }
+    }
     memory.log();
 
     let function_id = match shared_state.symtab.get("zmain") {
@@ -144,6 +171,15 @@ where
         None => return Err(NoMain),
     };
 
+    // Tests with page tables run with the registers that enable
+    // address translation
+    let mut regs = regs;
+    if let (Some(_), Some(mmu)) = (&litmus.page_tables, &isa_config.mmu) {
+        for (reg, value) in &mmu.registers {
+            regs.insert(*reg, UVal::Init(value.clone()));
+        }
+    }
+
     let (args, _, instrs) = shared_state.functions.get(&function_id).unwrap();
     let tasks: Vec<_> = litmus
         .assembled
@@ -185,6 +221,8 @@ where
                             || ev.is_instr()
                             || ev.is_cycle()
                             || ev.is_write_reg()
+                            || ev.is_exception()
+                            || ev.is_tag()
                     })
                     .collect();
                 simplify::remove_unused(&mut events);
@@ -204,14 +242,49 @@ where
     let footprints = footprint_analysis(num_threads, &thread_buckets, &lets, &regs, &shared_state, &isa_config, cache)
         .map_err(Footprint)?;
 
-    let candidates = Candidates::new(&thread_buckets);
+    Ok((thread_buckets, footprints))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn litmus_per_candidate<B, F, E>(
+    num_threads: usize,
+    timeout: Option<u64>,
+    litmus: &Litmus<B>,
+    ignore_ifetch: bool,
+    regs: Bindings<B>,
+    lets: Bindings<B>,
+    shared_state: &SharedState<B>,
+    isa_config: &ISAConfig<B>,
+    cache: &Cache,
+    callback: &F,
+) -> Result<LitmusRunInfo, LitmusRunError<E>>
+where
+    B: BV,
+    F: Sync + Send + Fn(ThreadId, &[&[Event<B>]], &HashMap<B, Footprint>) -> Result<(), E>,
+    E: Send,
+{
+    let (thread_buckets, footprints) =
+        litmus_threads(num_threads, timeout, litmus, ignore_ifetch, regs, lets, shared_state, isa_config, cache)?;
+
+    let mut candidates = Candidates::new(&thread_buckets);
     let num_candidates = candidates.total();
     log!(log::VERBOSE, &format!("There are {} candidate executions", num_candidates));
 
+    // Discard any candidates which cannot be consistent under any
+    // memory model before we send them to the solver
+    let filter = CandidateFilter::new(&thread_buckets);
+    let mut pruned = 0;
+
     let cqueue = ArrayQueue::new(num_candidates);
-    for (i, candidate) in candidates.enumerate() {
-        cqueue.push((i, candidate)).unwrap();
+    let mut i = 0;
+    while let Some((paths, candidate)) = candidates.next_with_paths() {
+        match ExecutionInfo::from(&candidate, shared_state, isa_config) {
+            Ok(exec) if !filter.may_be_consistent(&paths, &exec, litmus) => pruned += 1,
+            _ => cqueue.push((i, candidate)).unwrap(),
+        }
+        i += 1
     }
+    log!(log::VERBOSE, &format!("Pruned {} of {} candidate executions", pruned, num_candidates));
 
     let err_queue = ArrayQueue::new(num_candidates);
 
@@ -234,9 +307,9 @@ where
     }
 
     if callback_errors.is_empty() {
-        Ok(LitmusRunInfo { candidates: num_candidates })
+        Ok(LitmusRunInfo { candidates: num_candidates, pruned })
     } else {
-        Err(CallbackErrors(callback_errors))
+        Err(LitmusRunError::CallbackErrors(callback_errors))
     }
 }
 
@@ -270,10 +343,121 @@ impl<E: Error> Error for CallbackError<E> {
     }
 }
 
+/// Write the SMT definitions for a candidate execution: the thread
+/// traces and the events and relations derived from them, which the
+/// cat model should be compiled after. The final assertion is
+/// included if `assert_final` is true.
+#[allow(clippy::too_many_arguments)]
+fn write_candidate_smt<B: BV>(
+    output: &mut dyn Write,
+    candidate: &[&[Event<B>]],
+    exec: &ExecutionInfo<B>,
+    litmus: &Litmus<B>,
+    ignore_ifetch: bool,
+    footprints: &HashMap<B, Footprint>,
+    shared_state: &SharedState<B>,
+    isa_config: &ISAConfig<B>,
+    assert_final: bool,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(output, "(set-option :produce-models true)")?;
+
+    let mut enums = HashSet::new();
+    for thread in candidate {
+        for event in *thread {
+            if let Event::Smt(smtlib::Def::DefineEnum(_, size)) = event {
+                enums.insert(*size);
+            }
+        }
+    }
+
+    for size in enums {
+        write!(output, "(declare-datatypes ((Enum{} 0)) ((", size)?;
+        for i in 0..size {
+            write!(output, "(e{}_{})", size, i)?
+        }
+        writeln!(output, ")))")?
+    }
+
+    for thread in candidate {
+        write_events_with_opts(output, thread, &shared_state.symtab, &WriteOpts::smtlib())?;
+    }
+
+    // We want to make sure we can extract the values read and written by the model if they are
+    // symbolic. Therefore we declare new variables that are guaranteed to appear in the generated model.
+    for (name, event) in exec.events.iter().map(|ev| (&ev.name, ev.base)) {
+        match event {
+            Event::ReadMem { value, address, bytes, .. } | Event::WriteMem { data: value, address, bytes, .. } => {
+                if let Val::Symbolic(v) = value {
+                    writeln!(output, "(declare-const |{}:value| (_ BitVec {}))", name, bytes * 8)?;
+                    writeln!(output, "(assert (= |{}:value| v{}))", name, v)?;
+                }
+                if let Val::Symbolic(v) = address {
+                    // TODO handle non 64-bit physical addresses
+                    writeln!(output, "(declare-const |{}:address| (_ BitVec 64))", name)?;
+                    writeln!(output, "(assert (= |{}:address| v{}))", name, v)?;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    smt_of_candidate(output, exec, litmus, ignore_ifetch, footprints, shared_state, isa_config, assert_final)
+}
+
+/// Write the SMT for a candidate execution combined with a cat model
+/// to the file at `path`, and return the output of running z3 on it
+/// with an optional timeout in seconds. The file is removed
+/// afterwards.
+#[allow(clippy::too_many_arguments)]
+fn run_z3_on_candidate<B: BV, E>(
+    path: &Path,
+    timeout: Option<u64>,
+    candidate: &[&[Event<B>]],
+    exec: &ExecutionInfo<B>,
+    litmus: &Litmus<B>,
+    ignore_ifetch: bool,
+    cat: &Cat<cat::Ty>,
+    footprints: &HashMap<B, Footprint>,
+    shared_state: &SharedState<B>,
+    isa_config: &ISAConfig<B>,
+) -> Result<String, CallbackError<E>> {
+    // Create the SMT file with all the thread traces and the cat model.
+    let written = File::create(path).map_err(internal_err).and_then(|mut fd| {
+        write_candidate_smt(
+            &mut fd,
+            candidate,
+            exec,
+            litmus,
+            ignore_ifetch,
+            footprints,
+            shared_state,
+            isa_config,
+            true,
+        )
+        .map_err(internal_err_boxed)?;
+        isla_cat::smt::compile_cat(&mut fd, cat).map_err(internal_err_boxed)?;
+        writeln!(&mut fd, "(check-sat)").map_err(internal_err)?;
+        writeln!(&mut fd, "(get-model)").map_err(internal_err)
+    });
+
+    let z3 = written.and_then(|()| {
+        let mut z3_command = Command::new("z3");
+        if let Some(secs) = timeout {
+            z3_command.arg(format!("-T:{}", secs));
+        }
+        z3_command.arg(path);
+        z3_command.output().map_err(internal_err)
+    });
+
+    let _ = std::fs::remove_file(path);
+
+    String::from_utf8(z3?.stdout).map_err(internal_err)
+}
+
 /// This function runs a callback on the output of the SMT solver for
 /// each candidate execution combined with a cat model.
 #[allow(clippy::too_many_arguments)]
-pub fn smt_output_per_candidate<B, P, F, E>(
+pub fn smt_output_per_candidate<B, F, E>(
     uid: &str,
     num_threads: usize,
     timeout: Option<u64>,
@@ -284,12 +468,11 @@ pub fn smt_output_per_candidate<B, P, F, E>(
     lets: Bindings<B>,
     shared_state: &SharedState<B>,
     isa_config: &ISAConfig<B>,
-    cache: P,
+    cache: &Cache,
     callback: &F,
 ) -> Result<LitmusRunInfo, LitmusRunError<CallbackError<E>>>
 where
     B: BV,
-    P: AsRef<Path>,
     F: Sync + Send + Fn(ExecutionInfo<B>, &HashMap<B, Footprint>, &str) -> Result<(), E>,
     E: Send,
 {
@@ -302,7 +485,7 @@ where
         lets,
         &shared_state,
         &isa_config,
-        &cache,
+        cache,
         &|tid, candidate, footprints| {
             let now = Instant::now();
 
@@ -311,78 +494,461 @@ where
             let mut path = std::env::temp_dir();
             path.push(format!("isla_candidate_{}_{}_{}.smt2", uid, std::process::id(), tid));
 
-            // Create the SMT file with all the thread traces and the cat model.
-            {
-                let mut fd = File::create(&path).unwrap();
-                writeln!(&mut fd, "(set-option :produce-models true)").map_err(internal_err)?;
-
-                let mut enums = HashSet::new();
-                for thread in candidate {
-                    for event in *thread {
-                        if let Event::Smt(smtlib::Def::DefineEnum(_, size)) = event {
-                            enums.insert(*size);
-                        }
-                    }
-                }
+            let z3_output = run_z3_on_candidate(
+                &path,
+                timeout,
+                candidate,
+                &exec,
+                litmus,
+                ignore_ifetch,
+                cat,
+                footprints,
+                shared_state,
+                isa_config,
+            )?;
 
-                for size in enums {
-                    write!(&mut fd, "(declare-datatypes ((Enum{} 0)) ((", size).map_err(internal_err)?;
-                    for i in 0..size {
-                        write!(&mut fd, "(e{}_{})", size, i).map_err(internal_err)?
-                    }
-                    writeln!(&mut fd, ")))").map_err(internal_err)?
-                }
+            log!(log::VERBOSE, &format!("solver took: {}ms", now.elapsed().as_millis()));
 
-                for thread in candidate {
-                    write_events_with_opts(&mut fd, thread, &shared_state.symtab, &WriteOpts::smtlib())
-                        .map_err(internal_err)?;
-                }
+            callback(exec, footprints, &z3_output).map_err(CallbackError::User)
+        },
+    )
+}
 
-                // We want to make sure we can extract the values read and written by the model if they are
-                // symbolic. Therefore we declare new variables that are guaranteed to appear in the generated model.
-                for (name, event) in exec.events.iter().map(|ev| (&ev.name, ev.base)) {
-                    match event {
-                        Event::ReadMem { value, address, bytes, .. }
-                        | Event::WriteMem { data: value, address, bytes, .. } => {
-                            if let Val::Symbolic(v) = value {
-                                writeln!(&mut fd, "(declare-const |{}:value| (_ BitVec {}))", name, bytes * 8)
-                                    .map_err(internal_err)?;
-                                writeln!(&mut fd, "(assert (= |{}:value| v{}))", name, v).map_err(internal_err)?;
-                            }
-                            if let Val::Symbolic(v) = address {
-                                // TODO handle non 64-bit physical addresses
-                                writeln!(&mut fd, "(declare-const |{}:address| (_ BitVec 64))", name)
-                                    .map_err(internal_err)?;
-                                writeln!(&mut fd, "(assert (= |{}:address| v{}))", name, v).map_err(internal_err)?;
-                            }
-                        }
-                        _ => (),
-                    }
-                }
+#[allow(clippy::too_many_arguments)]
+fn symbolic_smt_output<B, F, E>(
+    uid: &str,
+    timeout: Option<u64>,
+    litmus: &Litmus<B>,
+    ignore_ifetch: bool,
+    cat: &Cat<cat::Ty>,
+    symbolic: &SymbolicCandidates<B>,
+    footprints: &HashMap<B, Footprint>,
+    shared_state: &SharedState<B>,
+    isa_config: &ISAConfig<B>,
+    callback: &F,
+) -> Result<(), CallbackError<E>>
+where
+    B: BV,
+    F: Fn(&SymbolicCandidates<B>, ExecutionInfo<B>, &HashMap<B, Footprint>, &str) -> Result<(), E>,
+{
+    let now = Instant::now();
 
-                smt_of_candidate(&mut fd, &exec, &litmus, ignore_ifetch, footprints, &shared_state, &isa_config)
-                    .map_err(internal_err_boxed)?;
-                isla_cat::smt::compile_cat(&mut fd, &cat).map_err(internal_err_boxed)?;
+    let candidate = symbolic.candidate();
+    let exec = symbolic.execution_info(&candidate, shared_state, isa_config).map_err(internal_err)?;
 
-                writeln!(&mut fd, "(check-sat)").map_err(internal_err)?;
-                writeln!(&mut fd, "(get-model)").map_err(internal_err)?;
-            }
+    let mut path = std::env::temp_dir();
+    path.push(format!("isla_symbolic_{}_{}.smt2", uid, std::process::id()));
+
+    let z3_output = run_z3_on_candidate(
+        &path,
+        timeout,
+        &candidate,
+        &exec,
+        litmus,
+        ignore_ifetch,
+        cat,
+        footprints,
+        shared_state,
+        isa_config,
+    )?;
+
+    log!(log::VERBOSE, &format!("solver took: {}ms", now.elapsed().as_millis()));
 
-            let mut z3_command = Command::new("z3");
-            if let Some(secs) = timeout {
-                z3_command.arg(format!("-T:{}", secs));
+    callback(symbolic, exec, footprints, &z3_output).map_err(CallbackError::User)
+}
+
+/// This function decides a litmus test with a single SMT query,
+/// rather than one for each candidate execution. The path taken by
+/// each thread is chosen by the solver, as described for
+/// `SymbolicCandidates`. The callback is run once on the solver
+/// output, and if it is sat `SymbolicCandidates::chosen_paths` can
+/// be used to find the candidate that was chosen.
+#[allow(clippy::too_many_arguments)]
+pub fn smt_output_symbolic<B, F, E>(
+    uid: &str,
+    num_threads: usize,
+    timeout: Option<u64>,
+    litmus: &Litmus<B>,
+    ignore_ifetch: bool,
+    cat: &Cat<cat::Ty>,
+    regs: Bindings<B>,
+    lets: Bindings<B>,
+    shared_state: &SharedState<B>,
+    isa_config: &ISAConfig<B>,
+    cache: &Cache,
+    callback: &F,
+) -> Result<LitmusRunInfo, LitmusRunError<CallbackError<E>>>
+where
+    B: BV,
+    F: Fn(&SymbolicCandidates<B>, ExecutionInfo<B>, &HashMap<B, Footprint>, &str) -> Result<(), E>,
+{
+    let (thread_buckets, footprints) =
+        litmus_threads(num_threads, timeout, litmus, ignore_ifetch, regs, lets, shared_state, isa_config, cache)?;
+
+    let final_regs: Vec<(Name, ThreadId)> = litmus
+        .final_assertion
+        .locations()
+        .into_iter()
+        .filter_map(|loc| match loc {
+            Loc::Register { reg, thread_id } => Some((*reg, *thread_id)),
+            Loc::LastWriteTo { .. } => None,
+        })
+        .collect();
+
+    let symbolic = SymbolicCandidates::new(&thread_buckets, &final_regs)
+        .map_err(|msg| LitmusRunError::CallbackErrors(vec![CallbackError::Internal(msg)]))?;
+    let num_candidates = symbolic.total();
+    log!(log::VERBOSE, &format!("Combining {} candidate executions", num_candidates));
+    if num_candidates == 0 {
+        return Ok(LitmusRunInfo { candidates: 0, pruned: 0 });
+    }
+
+    symbolic_smt_output(
+        uid,
+        timeout,
+        litmus,
+        ignore_ifetch,
+        cat,
+        &symbolic,
+        &footprints,
+        shared_state,
+        isa_config,
+        callback,
+    )
+    .map_err(|err| LitmusRunError::CallbackErrors(vec![err]))?;
+
+    Ok(LitmusRunInfo { candidates: num_candidates, pruned: 0 })
+}
+
+/// An interactive z3 process, used when we want to make several
+/// queries about the same candidate execution.
+struct Z3Session {
+    z3: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Z3Session {
+    fn new(timeout: Option<u64>) -> Result<Self, String> {
+        let mut z3_command = Command::new("z3");
+        if let Some(secs) = timeout {
+            z3_command.arg(format!("-T:{}", secs));
+        }
+        let mut z3 = z3_command
+            .arg("-in")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start z3: {}", e))?;
+        let stdin = z3.stdin.take().unwrap();
+        let stdout = BufReader::new(z3.stdout.take().unwrap());
+        Ok(Z3Session { z3, stdin, stdout })
+    }
+
+    /// Send input to the solver for which we expect no response.
+    fn send(&mut self, smt: &[u8]) -> Result<(), String> {
+        self.stdin.write_all(smt).map_err(|e| e.to_string())
+    }
+
+    /// Send a command to the solver and read the response, which may
+    /// span multiple lines if it is an S-expression.
+    fn query(&mut self, command: &str) -> Result<String, String> {
+        writeln!(self.stdin, "{}", command).and_then(|_| self.stdin.flush()).map_err(|e| e.to_string())?;
+        let mut response = String::new();
+        let mut depth: i64 = 0;
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+                return Err(format!("SMT solver exited unexpectedly: {}", response));
             }
-            z3_command.arg(&path);
+            depth += line.matches('(').count() as i64 - line.matches(')').count() as i64;
+            response.push_str(&line);
+            if depth <= 0 && !response.trim().is_empty() {
+                return Ok(response);
+            }
+        }
+    }
 
-            let z3 = z3_command.output().map_err(internal_err)?;
+    /// Run a `check-sat` or `check-sat-assuming` command, returning
+    /// an error if the result is unknown.
+    fn check_sat(&mut self, command: &str) -> Result<bool, String> {
+        let response = self.query(command)?;
+        match response.trim() {
+            "sat" => Ok(true),
+            "unsat" => Ok(false),
+            response => Err(format!("Unexpected solver response: {}", response)),
+        }
+    }
+}
 
-            let z3_output = std::str::from_utf8(&z3.stdout).map_err(internal_err)?;
+impl Drop for Z3Session {
+    fn drop(&mut self) {
+        let _ = self.z3.kill();
+        let _ = self.z3.wait();
+    }
+}
 
-            log!(log::VERBOSE, &format!("solver took: {}ms", now.elapsed().as_millis()));
+fn parse_outcome_values<B: BV>(response: &str) -> Result<Vec<B>, String> {
+    let lexer = SexpLexer::new(response);
+    let sexp = SexpParser::new().parse(lexer).map_err(|e| format!("Failed to parse solver values: {}", e))?;
+    sexp.dest_list()
+        .and_then(|pairs| {
+            pairs
+                .into_iter()
+                .map(|pair| match pair.dest_pair()? {
+                    (_, Sexp::Bits(bv)) => B::from_str(bv),
+                    _ => None,
+                })
+                .collect::<Option<Vec<B>>>()
+        })
+        .ok_or_else(|| format!("Unexpected solver values: {}", response))
+}
 
-            if std::fs::remove_file(&path).is_err() {}
+/// Find all the distinct final states of a candidate allowed by the
+/// cat model, using blocking clauses with an interactive solver.
+fn enumerate_outcomes<B: BV>(smt: &[u8], terms: &[String], timeout: Option<u64>) -> Result<Vec<Vec<B>>, String> {
+    let mut z3 = Z3Session::new(timeout)?;
+    z3.send(smt)?;
+
+    let mut outcomes = Vec::new();
+    while z3.check_sat("(check-sat)")? {
+        // A final assertion with no locations has a single outcome
+        if terms.is_empty() {
+            outcomes.push(Vec::new());
+            break;
+        }
 
-            callback(exec, footprints, &z3_output).map_err(CallbackError::User)
+        let values = parse_outcome_values::<B>(&z3.query(&format!("(get-value ({}))", terms.join(" ")))?)?;
+
+        let mut block = String::new();
+        for (term, value) in terms.iter().zip(values.iter()) {
+            block = format!("{} (= {} {})", block, term, value)
+        }
+        z3.send(format!("(assert (not (and{})))\n", block).as_bytes())?;
+        outcomes.push(values)
+    }
+
+    Ok(outcomes)
+}
+
+/// This function runs a callback with every distinct final state
+/// allowed by the cat model for each candidate execution. A final
+/// state is the value of each location in the final assertion, in
+/// the order given by `Prop::locations`.
+#[allow(clippy::too_many_arguments)]
+pub fn outcomes_per_candidate<B, F, E>(
+    num_threads: usize,
+    timeout: Option<u64>,
+    litmus: &Litmus<B>,
+    ignore_ifetch: bool,
+    cat: &Cat<cat::Ty>,
+    regs: Bindings<B>,
+    lets: Bindings<B>,
+    shared_state: &SharedState<B>,
+    isa_config: &ISAConfig<B>,
+    cache: &Cache,
+    callback: &F,
+) -> Result<LitmusRunInfo, LitmusRunError<CallbackError<E>>>
+where
+    B: BV,
+    F: Sync + Send + Fn(ExecutionInfo<B>, &HashMap<B, Footprint>, Vec<Vec<B>>) -> Result<(), E>,
+    E: Send,
+{
+    let locs = litmus.final_assertion.locations();
+
+    litmus_per_candidate(
+        num_threads,
+        timeout,
+        litmus,
+        ignore_ifetch,
+        regs,
+        lets,
+        shared_state,
+        isa_config,
+        cache,
+        &|_, candidate, footprints| {
+            let now = Instant::now();
+
+            let exec = ExecutionInfo::from(candidate, shared_state, isa_config).map_err(internal_err)?;
+
+            let mut smt = Vec::new();
+            write_candidate_smt(
+                &mut smt,
+                candidate,
+                &exec,
+                litmus,
+                ignore_ifetch,
+                footprints,
+                shared_state,
+                isa_config,
+                false,
+            )
+            .map_err(internal_err_boxed)?;
+            isla_cat::smt::compile_cat(&mut smt, cat).map_err(internal_err_boxed)?;
+            let terms = smt_of_outcome_locations(&mut smt, &exec, &locs).map_err(internal_err_boxed)?;
+
+            let outcomes = enumerate_outcomes(&smt, &terms, timeout).map_err(CallbackError::Internal)?;
+
+            log!(
+                log::VERBOSE,
+                &format!("solver took: {}ms for {} outcomes", now.elapsed().as_millis(), outcomes.len())
+            );
+
+            callback(exec, footprints, outcomes).map_err(CallbackError::User)
+        },
+    )
+}
+
+/// Why a candidate execution is allowed or forbidden by a cat model.
+#[derive(Debug)]
+pub enum Explanation {
+    /// The candidate is allowed and satisfies the final assertion
+    Allowed,
+    /// The final assertion cannot hold for the candidate, regardless
+    /// of the model
+    Unreachable,
+    /// The candidate is forbidden by the `violated` checks, which are
+    /// an unsat core. If the checks in `relaxed` (which include
+    /// `violated`) are removed the candidate is allowed, and `witness`
+    /// is the solver output with a model of that execution.
+    Forbidden { violated: Vec<String>, relaxed: Vec<String>, witness: Option<String> },
+}
+
+fn parse_unsat_core(response: &str) -> Result<Vec<String>, String> {
+    let lexer = SexpLexer::new(response);
+    let sexp = SexpParser::new().parse(lexer).map_err(|e| format!("Failed to parse unsat core: {}", e))?;
+    sexp.dest_list()
+        .and_then(|assumptions| {
+            assumptions
+                .iter()
+                .map(|assumption| assumption.as_str()?.strip_prefix("axiom:").map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or_else(|| format!("Unexpected unsat core: {}", response))
+}
+
+fn check_sat_assuming(z3: &mut Z3Session, checks: &[&String]) -> Result<bool, String> {
+    if checks.is_empty() {
+        return z3.check_sat("(check-sat)");
+    }
+    let assumptions: Vec<String> = checks.iter().map(|check| format!("|axiom:{}|", check)).collect();
+    z3.check_sat(&format!("(check-sat-assuming ({}))", assumptions.join(" ")))
+}
+
+fn explain_candidate(
+    smt: &[u8],
+    checks: &[String],
+    witness: bool,
+    timeout: Option<u64>,
+) -> Result<Explanation, String> {
+    let mut z3 = Z3Session::new(timeout)?;
+    z3.send(smt)?;
+
+    if check_sat_assuming(&mut z3, &checks.iter().collect::<Vec<_>>())? {
+        return Ok(Explanation::Allowed);
+    }
+    let violated = parse_unsat_core(&z3.query("(get-unsat-core)")?)?;
+    if violated.is_empty() {
+        return Ok(Explanation::Unreachable);
+    }
+    if !witness {
+        return Ok(Explanation::Forbidden { relaxed: violated.clone(), violated, witness: None });
+    }
+
+    // There may be more than one reason the candidate is forbidden,
+    // so keep removing checks until we find an execution.
+    let mut relaxed = violated.clone();
+    loop {
+        let remaining: Vec<&String> = checks.iter().filter(|check| !relaxed.contains(check)).collect();
+        if check_sat_assuming(&mut z3, &remaining)? {
+            let model = z3.query("(get-model)")?;
+            return Ok(Explanation::Forbidden { violated, relaxed, witness: Some(format!("sat\n{}", model)) });
+        }
+        let core = parse_unsat_core(&z3.query("(get-unsat-core)")?)?;
+        if core.is_empty() {
+            return Ok(Explanation::Forbidden { violated, relaxed, witness: None });
+        }
+        relaxed.extend(core)
+    }
+}
+
+/// This function runs a callback with an explanation of why each
+/// candidate execution is allowed or forbidden by the cat model,
+/// i.e. which checks in the model rule it out. If `witness` is true,
+/// a model of each forbidden candidate with its violated checks
+/// removed is also found. The callback is given the index of each
+/// candidate, counting candidates which were pruned.
+#[allow(clippy::too_many_arguments)]
+pub fn explain_per_candidate<B, F, E>(
+    num_threads: usize,
+    timeout: Option<u64>,
+    litmus: &Litmus<B>,
+    ignore_ifetch: bool,
+    cat: &Cat<cat::Ty>,
+    regs: Bindings<B>,
+    lets: Bindings<B>,
+    shared_state: &SharedState<B>,
+    isa_config: &ISAConfig<B>,
+    cache: &Cache,
+    witness: bool,
+    callback: &F,
+) -> Result<LitmusRunInfo, LitmusRunError<CallbackError<E>>>
+where
+    B: BV,
+    F: Sync + Send + Fn(usize, ExecutionInfo<B>, &HashMap<B, Footprint>, Explanation) -> Result<(), E>,
+    E: Send,
+{
+    litmus_per_candidate(
+        num_threads,
+        timeout,
+        litmus,
+        ignore_ifetch,
+        regs,
+        lets,
+        shared_state,
+        isa_config,
+        cache,
+        &|i, candidate, footprints| {
+            let now = Instant::now();
+
+            let exec = ExecutionInfo::from(candidate, shared_state, isa_config).map_err(internal_err)?;
+
+            let mut smt = Vec::new();
+            writeln!(&mut smt, "(set-option :produce-unsat-cores true)").map_err(internal_err)?;
+            writeln!(&mut smt, "(set-option :smt.core.minimize true)").map_err(internal_err)?;
+            write_candidate_smt(
+                &mut smt,
+                candidate,
+                &exec,
+                litmus,
+                ignore_ifetch,
+                footprints,
+                shared_state,
+                isa_config,
+                true,
+            )
+            .map_err(internal_err_boxed)?;
+            let checks = isla_cat::smt::compile_cat_with_assumptions(&mut smt, cat).map_err(internal_err_boxed)?;
+
+            let explanation = explain_candidate(&smt, &checks, witness, timeout).map_err(CallbackError::Internal)?;
+
+            log!(log::VERBOSE, &format!("solver took: {}ms", now.elapsed().as_millis()));
+
+            callback(i, exec, footprints, explanation).map_err(CallbackError::User)
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_unsat_core() {
+        assert_eq!(parse_unsat_core("(|axiom:sc| |axiom:atomic|)").unwrap(), vec!["sc", "atomic"]);
+        assert!(parse_unsat_core("()").unwrap().is_empty());
+        // Only the check assumptions should appear in a core
+        assert!(parse_unsat_core("(|axiom:sc| x)").is_err());
+        assert!(parse_unsat_core("unknown").is_err())
+    }
+}
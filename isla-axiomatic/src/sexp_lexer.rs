@@ -78,7 +78,7 @@ lazy_static! {
         table.push(Keyword::new(")", Rparen));
         table
     };
-    pub static ref ATOM_REGEX: Regex = Regex::new(r"^[a-zA-Z_=><.!/-][0-9a-zA-Z_=><.!/-]*").unwrap();
+    pub static ref ATOM_REGEX: Regex = Regex::new(r"^[a-zA-Z_=><.!/+-][0-9a-zA-Z_=><.!/+-]*").unwrap();
     pub static ref BAR_ATOM_REGEX: Regex = Regex::new(r"^\|[^|]+\|").unwrap();
 }
 
@@ -30,9 +30,10 @@
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::io::Write;
+use std::ops::Range;
 
 use isla_lib::concrete::BV;
-use isla_lib::config::{ISAConfig, Kind};
+use isla_lib::config::{ISAConfig, Kind, MemoryKind};
 use isla_lib::ir::{Name, SharedState, Val};
 use isla_lib::smt::{Event, Sym};
 
@@ -41,7 +42,7 @@ use isla_cat::smt::Sexp;
 use crate::axiomatic::relations::*;
 use crate::axiomatic::{AxEvent, ExecutionInfo, Pairs};
 use crate::footprint_analysis::Footprint;
-use crate::litmus::{opcode_from_objdump, Litmus, Loc, Prop};
+use crate::litmus::{opcode_from_objdump, Cmp, Litmus, Loc, Prop};
 
 fn smt_bitvec<B: BV>(val: &Val<B>) -> String {
     match val {
@@ -75,6 +76,41 @@ fn same_location<B: BV>(ev1: &AxEvent<B>, ev2: &AxEvent<B>) -> Sexp {
     }
 }
 
+/// Two memory events overlap if they access at least one common byte.
+fn overlapping<B: BV>(ev1: &AxEvent<B>, ev2: &AxEvent<B>) -> Sexp {
+    use Sexp::*;
+    match (ev1.address(), ev1.bytes(), ev2.address(), ev2.bytes()) {
+        (Some(Val::Bits(bv1)), Some(bytes1), Some(Val::Bits(bv2)), Some(bytes2)) => {
+            let (addr1, addr2) = (bv1.lower_u64(), bv2.lower_u64());
+            if addr1 < addr2 + bytes2 as u64 && addr2 < addr1 + bytes1 as u64 {
+                True
+            } else {
+                False
+            }
+        }
+        (Some(addr1), Some(bytes1), Some(addr2), Some(bytes2)) => Literal(format!(
+            "(and (bvult {} (bvadd {} {})) (bvult {} (bvadd {} {})))",
+            smt_bitvec(addr1),
+            smt_bitvec(addr2),
+            B::new(bytes2 as u64, 64),
+            smt_bitvec(addr2),
+            smt_bitvec(addr1),
+            B::new(bytes1 as u64, 64)
+        )),
+        (_, _, _, _) => False,
+    }
+}
+
+/// Two memory events have the same footprint if they access the
+/// same address with the same size.
+fn same_footprint<B: BV>(ev1: &AxEvent<B>, ev2: &AxEvent<B>) -> Sexp {
+    if ev1.bytes() == ev2.bytes() {
+        same_location(ev1, ev2)
+    } else {
+        Sexp::False
+    }
+}
+
 fn read_write_pair<B: BV>(ev1: &AxEvent<B>, ev2: &AxEvent<B>) -> Sexp {
     use Sexp::*;
     match (ev2.read_value(), ev1.write_data()) {
@@ -118,15 +154,14 @@ fn read_initial_symbolic<B: BV>(sym: Sym, addr1: &Val<B>, bytes: u32, litmus: &L
     let mut expr = "".to_string();
     let mut ites = 0;
 
-    for (sym_loc, value) in litmus.symbolic_locations.iter() {
-        let addr2 = litmus.symbolic_addrs.get(sym_loc).expect("Could not find symbolic location");
+    for addr2 in litmus.initial_memory.addresses(bytes) {
         expr = format!(
             "{}(ite (= {} {}) (= v{} {}) ",
             expr,
             smt_bitvec(addr1),
-            B::new(*addr2, 64),
+            B::new(addr2, 64),
             sym,
-            B::new(*value, 8 * bytes)
+            B::new(litmus.initial_memory.read(addr2, bytes), 8 * bytes)
         );
         ites += 1
     }
@@ -143,15 +178,15 @@ fn read_initial_symbolic<B: BV>(sym: Sym, addr1: &Val<B>, bytes: u32, litmus: &L
 fn read_initial_concrete<B: BV>(bv: B, addr1: &Val<B>, litmus: &Litmus<B>) -> Sexp {
     let mut expr = "".to_string();
     let mut ites = 0;
+    let bytes = bv.len() / 8;
 
-    for (sym_loc, value) in litmus.symbolic_locations.iter() {
-        let addr2 = litmus.symbolic_addrs.get(sym_loc).expect("Could not find symbolic location");
+    for addr2 in litmus.initial_memory.addresses(bytes) {
         expr = format!(
             "{}(ite (= {} {}) {} ",
             expr,
             smt_bitvec(addr1),
-            B::new(*addr2, 64),
-            if bv.lower_u64() == *value { "true" } else { "false " }
+            B::new(addr2, 64),
+            if bv.lower_u64() == litmus.initial_memory.read(addr2, bytes) { "true" } else { "false " }
         );
         ites += 1
     }
@@ -169,13 +204,14 @@ fn initial_write_values<B: BV>(addr_name: &str, width: u32, litmus: &Litmus<B>)
     let mut expr = "".to_string();
     let mut ites = 0;
 
-    for (sym_loc, value) in litmus.symbolic_locations.iter() {
-        let addr = litmus.symbolic_addrs.get(sym_loc).expect("Could not find symbolic location");
-        expr = format!("{}(ite (= {} {}) {} ", expr, addr_name, B::new(*addr, 64), B::new(*value, width));
+    for addr in litmus.initial_memory.addresses(width / 8) {
+        let value = litmus.initial_memory.read(addr, width / 8);
+        let value = resize(&format!("{}", B::new(value, width.min(64))), width.min(64), width);
+        expr = format!("{}(ite (= {} {}) {} ", expr, addr_name, B::new(addr, 64), value);
         ites += 1
     }
 
-    expr = format!("{}{}", expr, B::zeros(width));
+    expr = format!("{}(_ bv0 {})", expr, width);
 
     for _ in 0..ites {
         expr = format!("{})", expr)
@@ -195,6 +231,51 @@ fn read_initial<B: BV>(ev: &AxEvent<B>, litmus: &Litmus<B>) -> Sexp {
     }
 }
 
+/// Check whether an address lies within a range, which is decided
+/// statically when the address is concrete.
+fn address_in_range<B: BV>(addr: &Val<B>, range: &Range<u64>) -> Sexp {
+    use Sexp::*;
+    match addr {
+        Val::Bits(addr) => {
+            if range.contains(&addr.lower_u64()) {
+                True
+            } else {
+                False
+            }
+        }
+        addr => Literal(format!(
+            "(and (bvule {} {}) (bvult {} {}))",
+            B::new(range.start, 64),
+            smt_bitvec(addr),
+            smt_bitvec(addr),
+            B::new(range.end, 64)
+        )),
+    }
+}
+
+/// A device access is a read or write to a device region in the
+/// platform memory map.
+fn device_access<B: BV>(ev: &AxEvent<B>, isa_config: &ISAConfig<B>) -> Sexp {
+    use Sexp::*;
+    match ev.address() {
+        Some(addr) if is_read(ev) || is_write(ev) => {
+            let accesses: Vec<Sexp> = isa_config
+                .memory
+                .iter()
+                .filter(|region| matches!(region.kind, MemoryKind::Device))
+                .map(|region| address_in_range(addr, &region.range))
+                .filter(|in_range| !matches!(in_range, False))
+                .collect();
+            if accesses.is_empty() {
+                False
+            } else {
+                Or(accesses)
+            }
+        }
+        _ => False,
+    }
+}
+
 /// [ifetch_initial] checks if a ifetch is a valid fetch from the
 /// initial state, using the opcodes in the objdump. It also performs
 /// the same check as [ifetch_match], so they do not need to be used
@@ -228,6 +309,16 @@ fn ifetch_match<B: BV>(ev: &AxEvent<B>) -> Sexp {
     }
 }
 
+/// Events in a combined candidate (see `SymbolicCandidates`) are only
+/// part of the execution if their path is chosen, so each set and
+/// relation we generate is restricted to active events.
+fn active<B: BV>(ev: &AxEvent<B>) -> Sexp {
+    match ev.guard {
+        Some(guard) => Sexp::Literal(format!("v{}", guard)),
+        None => Sexp::True,
+    }
+}
+
 fn smt_basic_rel<B, F>(rel: F, events: &[AxEvent<B>]) -> Sexp
 where
     B: BV,
@@ -239,6 +330,8 @@ where
         deps.push(And(vec![
             Eq(Box::new(Var(1)), Box::new(Literal(ev1.name.to_string()))),
             Eq(Box::new(Var(2)), Box::new(Literal(ev2.name.to_string()))),
+            active(ev1),
+            active(ev2),
         ]))
     }
     let mut sexp = Or(deps);
@@ -257,6 +350,8 @@ where
         deps.push(And(vec![
             Eq(Box::new(Var(1)), Box::new(Literal(ev1.name.to_string()))),
             Eq(Box::new(Var(2)), Box::new(Literal(ev2.name.to_string()))),
+            active(ev1),
+            active(ev2),
             f(ev1, ev2),
         ]))
     }
@@ -277,6 +372,8 @@ fn smt_dep_rel<B: BV>(
         deps.push(And(vec![
             Eq(Box::new(Var(1)), Box::new(Literal(ev1.name.to_string()))),
             Eq(Box::new(Var(2)), Box::new(Literal(ev2.name.to_string()))),
+            active(ev1),
+            active(ev2),
         ]))
     }
     let mut sexp = Or(deps);
@@ -291,7 +388,7 @@ where
     use Sexp::*;
     let mut deps = Vec::new();
     for ev in events.iter().filter(|ev| set(ev)) {
-        deps.push(Eq(Box::new(Var(1)), Box::new(Literal(ev.name.to_string()))))
+        deps.push(And(vec![Eq(Box::new(Var(1)), Box::new(Literal(ev.name.to_string()))), active(ev)]))
     }
     let mut sexp = Or(deps);
     sexp.simplify(&HashSet::new());
@@ -305,23 +402,111 @@ where
     use Sexp::*;
     let mut deps = Vec::new();
     for ev in events.iter() {
-        deps.push(And(vec![Eq(Box::new(Var(1)), Box::new(Literal(ev.name.to_string()))), set(ev)]))
+        deps.push(And(vec![Eq(Box::new(Var(1)), Box::new(Literal(ev.name.to_string()))), active(ev), set(ev)]))
     }
     let mut sexp = Or(deps);
     sexp.simplify(&HashSet::new());
     sexp
 }
 
+fn register_value<B: BV>(reg: Name, thread_id: usize, final_writes: &HashMap<(Name, usize), &Val<B>>) -> String {
+    match final_writes.get(&(reg, thread_id)) {
+        Some(Val::Symbolic(sym)) => format!("v{}", sym),
+        Some(Val::Bits(reg_bv)) => format!("{}", reg_bv),
+        Some(_) => unreachable!(),
+        None => "#x000000000000DEAD".to_string(),
+    }
+}
+
 fn eq_loc_to_smt<B: BV>(loc: &Loc, bv: B, final_writes: &HashMap<(Name, usize), &Val<B>>) -> String {
     use Loc::*;
     match loc {
-        Register { reg, thread_id } => match final_writes.get(&(*reg, *thread_id)) {
-            Some(Val::Symbolic(sym)) => format!("(= v{} {})", sym, bv),
-            Some(Val::Bits(reg_bv)) => format!("(= {} {})", reg_bv, bv),
-            Some(_) => unreachable!(),
-            None => format!("(= #x000000000000DEAD {})", bv),
-        },
-        LastWriteTo { address, bytes } => format!("(last_write_to_{} {} {})", bytes * 8, B::new(*address, 64), bv),
+        Register { reg, thread_id } => format!("(= {} {})", register_value(*reg, *thread_id, final_writes), bv),
+        LastWriteTo { address, bytes } => format!(
+            "(last_write_to_{} {} {})",
+            bytes * 8,
+            B::new(*address, 64),
+            resize(&format!("{}", bv), bv.len(), bytes * 8)
+        ),
+    }
+}
+
+fn cmp_to_smt(cmp: Cmp, lhs: &str, rhs: &str) -> String {
+    use Cmp::*;
+    let op = match cmp {
+        Ne => return format!("(not (= {} {}))", lhs, rhs),
+        Ult => "bvult",
+        Ule => "bvule",
+        Ugt => "bvugt",
+        Uge => "bvuge",
+        Slt => "bvslt",
+        Sle => "bvsle",
+        Sgt => "bvsgt",
+        Sge => "bvsge",
+    };
+    format!("({} {} {})", op, lhs, rhs)
+}
+
+/// For memory locations `last_write_to` only relates an address to
+/// its final value, so to compare it we quantify over that value and
+/// compare at the width of the location, which may be wider than 64
+/// bits.
+fn cmp_loc_to_smt<B: BV>(cmp: Cmp, loc: &Loc, bv: B, final_writes: &HashMap<(Name, usize), &Val<B>>) -> String {
+    use Loc::*;
+    match loc {
+        Register { reg, thread_id } => {
+            cmp_to_smt(cmp, &register_value(*reg, *thread_id, final_writes), &format!("{}", bv))
+        }
+        LastWriteTo { address, bytes } => format!(
+            "(exists ((v (_ BitVec {}))) (and (last_write_to_{} {} v) {}))",
+            bytes * 8,
+            bytes * 8,
+            B::new(*address, 64),
+            cmp_to_smt(cmp, "v", &resize(&format!("{}", bv), bv.len(), bytes * 8))
+        ),
+    }
+}
+
+/// Declare a variable for the final value of each location, and
+/// return SMT terms that can be used to query them with
+/// `get-value`. This must be called after `smt_of_candidate`, as
+/// memory locations are defined using `last_write_to`.
+pub fn smt_of_outcome_locations<B: BV>(
+    output: &mut dyn Write,
+    exec: &ExecutionInfo<B>,
+    locs: &[&Loc],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    use Loc::*;
+    writeln!(output, "; === OUTCOME LOCATIONS ===\n")?;
+    let mut terms = Vec::new();
+    for (i, loc) in locs.iter().enumerate() {
+        match loc {
+            Register { reg, thread_id } => terms.push(register_value(*reg, *thread_id, &exec.final_writes)),
+            LastWriteTo { address, bytes } => {
+                writeln!(output, "(declare-const |outcome:{}| (_ BitVec {}))", i, bytes * 8)?;
+                writeln!(output, "(assert (last_write_to_{} {} |outcome:{}|))", bytes * 8, B::new(*address, 64), i)?;
+                terms.push(format!("|outcome:{}|", i))
+            }
+        }
+    }
+    Ok(terms)
+}
+
+/// Collect the widths of all the memory locations used in a final
+/// assertion, as we need to generate `last_write_to` for each of them.
+fn prop_widths<B>(prop: &Prop<B>, widths: &mut HashSet<u32>) {
+    use Prop::*;
+    match prop {
+        EqLoc(Loc::LastWriteTo { bytes, .. }, _) | CmpLoc(_, Loc::LastWriteTo { bytes, .. }, _) => {
+            widths.insert(*bytes);
+        }
+        And(props) | Or(props) => props.iter().for_each(|prop| prop_widths(prop, widths)),
+        Implies(prop1, prop2) => {
+            prop_widths(prop1, widths);
+            prop_widths(prop2, widths)
+        }
+        Not(prop) => prop_widths(prop, widths),
+        EqLoc(..) | CmpLoc(..) | True | False => (),
     }
 }
 
@@ -329,6 +514,7 @@ fn prop_to_smt<B: BV>(prop: &Prop<B>, final_writes: &HashMap<(Name, usize), &Val
     use Prop::*;
     match prop {
         EqLoc(loc, bv) => eq_loc_to_smt(loc, *bv, final_writes),
+        CmpLoc(cmp, loc, bv) => cmp_loc_to_smt(*cmp, loc, *bv, final_writes),
         And(props) => {
             let mut conjs = String::new();
             for prop in props {
@@ -352,6 +538,148 @@ fn prop_to_smt<B: BV>(prop: &Prop<B>, final_writes: &HashMap<(Name, usize), &Val
     }
 }
 
+/// We use byte-granular mixed-size semantics when a candidate
+/// contains data memory accesses of different sizes, or accesses that
+/// are not aligned to their size. The final assertion can also read
+/// memory at a different size to the accesses in the test.
+pub(crate) fn is_mixed_size<B: BV>(events: &[AxEvent<B>], litmus: &Litmus<B>) -> bool {
+    let mut widths = HashSet::new();
+    for ev in events.iter().filter(|ev| is_read(ev) || is_write(ev)) {
+        let bytes = ev.bytes().unwrap();
+        widths.insert(bytes);
+        if let Some(Val::Bits(addr)) = ev.address() {
+            if addr.lower_u64() % bytes as u64 != 0 {
+                return true;
+            }
+        }
+    }
+    prop_widths(&litmus.final_assertion, &mut widths);
+    widths.len() > 1
+}
+
+/// Convert a bitvector expression from one width to another by
+/// either truncating or zero-extending it.
+fn resize(expr: &str, from: u32, to: u32) -> String {
+    if from == to {
+        expr.to_string()
+    } else if from > to {
+        format!("((_ extract {} 0) {})", to - 1, expr)
+    } else {
+        format!("((_ zero_extend {}) {})", to - from, expr)
+    }
+}
+
+/// Generates the event-specific definitions used by the mixed-size
+/// model in `mixed_size.smt2`. Each byte of each read chooses a
+/// write (or the initial state) to read from, and `rf-bytes` relates
+/// a read to all the writes chosen by its bytes.
+fn write_mixed_size_defs<B: BV>(
+    output: &mut dyn Write,
+    events: &[AxEvent<B>],
+    litmus: &Litmus<B>,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(output, "(define-fun initial_byte ((a (_ BitVec 64))) (_ BitVec 8)")?;
+    writeln!(output, "  {})\n", initial_write_values("a", 8, litmus))?;
+
+    // Memory is little-endian, so byte a of a write at addr is found
+    // by shifting its data right by 8 * (a - addr) bits.
+    writeln!(output, "(define-fun byte_of ((ev Event) (a (_ BitVec 64))) (_ BitVec 8)")?;
+    let mut ites: usize = 0;
+    for ev in events.iter().filter(|ev| is_write(ev)) {
+        let (data, bytes) = ev.write_data().unwrap();
+        let shift = format!("(bvmul (bvsub a {}) #x0000000000000008)", smt_bitvec(ev.address().unwrap()));
+        writeln!(
+            output,
+            "  (ite (= ev {}) ((_ extract 7 0) (bvlshr {} {}))",
+            ev.name,
+            smt_bitvec(data),
+            resize(&shift, 64, bytes * 8)
+        )?;
+        ites += 1
+    }
+    write!(output, "  #x00")?;
+    for _ in 0..ites {
+        write!(output, ")")?
+    }
+    writeln!(output, ")\n")?;
+
+    let mut sources = Vec::new();
+    for ev in events.iter().filter(|ev| is_read(ev) || is_translate(ev)) {
+        let bytes = ev.bytes().unwrap();
+        let byte_sources: Vec<String> = (0..bytes).map(|i| format!("|{}:rf:{}|", ev.name, i)).collect();
+        for source in &byte_sources {
+            writeln!(output, "(declare-const {} Event)", source)?
+        }
+        sources.push((ev, byte_sources))
+    }
+
+    writeln!(output, "\n(define-fun rf-bytes ((ev1 Event) (ev2 Event)) Bool\n  (or")?;
+    for (ev, byte_sources) in &sources {
+        match ev.guard {
+            Some(guard) => write!(output, "    (and (= ev2 {}) v{} (or", ev.name, guard)?,
+            None => write!(output, "    (and (= ev2 {}) (or", ev.name)?,
+        }
+        for source in byte_sources {
+            write!(output, " (= ev1 {})", source)?
+        }
+        writeln!(output, "))")?
+    }
+    writeln!(output, "    false))\n")?;
+
+    writeln!(output, "{}", MIXED_SIZE_SMTLIB)?;
+
+    for (ev, byte_sources) in &sources {
+        let (value, _) = ev.read_value().unwrap();
+        let addr = smt_bitvec(ev.address().unwrap());
+        for (i, source) in byte_sources.iter().enumerate() {
+            let ok = format!(
+                "(rf-byte-ok {} (bvadd {} {}) ((_ extract {} {}) {}))",
+                source,
+                addr,
+                B::new(i as u64, 64),
+                i * 8 + 7,
+                i * 8,
+                smt_bitvec(value)
+            );
+            match ev.guard {
+                Some(guard) => writeln!(output, "(assert (=> v{} {}))", guard, ok)?,
+                None => writeln!(output, "(assert {})", ok)?,
+            }
+        }
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
+/// A memory location contains its final value if it is the value of
+/// the coherence-final write to it, or the initial value if there is
+/// no such write.
+fn last_write_to<B: BV>(width: u32, litmus: &Litmus<B>) -> String {
+    let lwt = subst_template(LAST_WRITE_TO, "INITIAL", initial_write_values("addr", width * 8, litmus));
+    subst_template(lwt, "LEN", format!("{}", width * 8))
+}
+
+/// In the mixed-size model a memory location contains its final
+/// value if each of its bytes does.
+fn mixed_size_last_write_to<B: BV>(width: u32) -> String {
+    let bytes: Vec<String> = (0..width)
+        .map(|i| {
+            format!(
+                "\n       (last_byte (bvadd addr {}) ((_ extract {} {}) v))",
+                B::new(i as u64, 64),
+                i * 8 + 7,
+                i * 8
+            )
+        })
+        .collect();
+    format!(
+        "(define-fun last_write_to_{} ((addr (_ BitVec 64)) (v (_ BitVec {}))) Bool\n  (and{}))\n",
+        width * 8,
+        width * 8,
+        bytes.join("")
+    )
+}
+
 fn subst_template<T: AsRef<str>, R: AsRef<str>>(template: T, subst: &str, replace: R) -> String {
     use regex::Regex;
     let subst_re = Regex::new(&format!(r"\${}", subst)).unwrap();
@@ -372,6 +700,9 @@ static IFETCH_SMTLIB: &str = include_str!("ifetch.smt2");
 
 static LAST_WRITE_TO: &str = include_str!("last_write_to.smt2");
 
+static MIXED_SIZE_SMTLIB: &str = include_str!("mixed_size.smt2");
+
+#[allow(clippy::too_many_arguments)]
 pub fn smt_of_candidate<B: BV>(
     output: &mut dyn Write,
     exec: &ExecutionInfo<B>,
@@ -380,8 +711,10 @@ pub fn smt_of_candidate<B: BV>(
     footprints: &HashMap<B, Footprint>,
     shared_state: &SharedState<B>,
     isa_config: &ISAConfig<B>,
+    assert_final: bool,
 ) -> Result<(), Box<dyn Error>> {
     let events = &exec.events;
+    let mixed_size = is_mixed_size(events, litmus);
 
     writeln!(output, "\n\n; === EVENTS ===\n")?;
     write!(output, "(declare-datatypes ((Event 0))\n  ((")?;
@@ -392,26 +725,28 @@ pub fn smt_of_candidate<B: BV>(
 
     let mut all_write_widths = HashSet::new();
     // Always make sure we have at least one width to avoid generating invalid SMT for writes
-    all_write_widths.insert(&4);
+    all_write_widths.insert(4);
     for ev in &exec.events {
         if let Event::WriteMem { bytes, .. } = ev.base {
-            all_write_widths.insert(bytes);
+            all_write_widths.insert(*bytes);
         }
     }
+    // The final assertion may also read memory at widths that are never written
+    prop_widths(&litmus.final_assertion, &mut all_write_widths);
     for &width in all_write_widths.iter() {
-        assert!(*width > 0);
+        assert!(width > 0);
         writeln!(output, "(define-fun val_of_{} ((ev Event)) (_ BitVec {})", width * 8, width * 8)?;
         let mut ites: usize = 0;
         for ev in events {
             match ev.base {
-                Event::WriteMem { bytes, data, .. } if bytes == width => {
+                Event::WriteMem { bytes, data, .. } if *bytes == width => {
                     writeln!(output, "  (ite (= ev {}) {}", ev.name, smt_bitvec(data))?;
                     ites += 1
                 }
                 _ => (),
             }
         }
-        write!(output, "  {}", B::zeros(width * 8))?;
+        write!(output, "  (_ bv0 {})", width * 8)?;
         for _ in 0..ites {
             write!(output, ")")?
         }
@@ -437,12 +772,36 @@ pub fn smt_of_candidate<B: BV>(
         writeln!(output, ")\n")?
     }
 
+    {
+        writeln!(output, "(define-fun size_of ((ev Event)) (_ BitVec 64)")?;
+        let mut ites: usize = 0;
+        for ev in events {
+            if let Some(bytes) = ev.bytes() {
+                writeln!(output, "  (ite (= ev {}) {}", ev.name, B::new(bytes as u64, 64))?;
+                ites += 1
+            }
+        }
+        write!(output, "  #x0000000000000000")?;
+        for _ in 0..ites {
+            write!(output, ")")?
+        }
+        writeln!(output, ")\n")?
+    }
+
     smt_set(is_read, events).write_set(output, "R")?;
     smt_set(is_write, events).write_set(output, "W")?;
     smt_set(|ev| is_read(ev) || is_write(ev), events).write_set(output, "M")?;
     smt_set(is_ifetch, events).write_set(output, "IF")?;
     smt_set(is_barrier, events).write_set(output, "F")?;
     smt_set(is_cache_op, events).write_set(output, "C")?;
+    smt_set(is_exception_entry, events).write_set(output, "TE")?;
+    smt_set(is_exception_return, events).write_set(output, "ERET")?;
+    // Tag accesses are not in R or W, so they have no rf or co, and
+    // tag reads can return any value
+    smt_set(is_tag_read, events).write_set(output, "RTAG")?;
+    smt_set(is_tag_write, events).write_set(output, "WTAG")?;
+    smt_set(is_translate, events).write_set(output, "T")?;
+    smt_condition_set(|ev| device_access(ev, isa_config), events).write_set(output, "DEV")?;
 
     for (set, kinds) in isa_config.event_sets.iter() {
         smt_set(
@@ -482,8 +841,13 @@ pub fn smt_of_candidate<B: BV>(
 
     smt_basic_rel(internal, events).write_rel(output, "int")?;
     smt_basic_rel(external, events).write_rel(output, "ext")?;
-    smt_condition_rel(disjoint, events, same_location).write_rel(output, "loc")?;
-    smt_condition_rel(po, events, same_location).write_rel(output, "po-loc")?;
+    // With mixed-size accesses, events are to the same location if
+    // they overlap
+    let loc_condition = if mixed_size { overlapping } else { same_location };
+    smt_condition_rel(disjoint, events, loc_condition).write_rel(output, "loc")?;
+    smt_condition_rel(po, events, loc_condition).write_rel(output, "po-loc")?;
+    smt_condition_rel(disjoint, events, overlapping).write_rel(output, "overlap")?;
+    smt_condition_rel(disjoint, events, same_footprint).write_rel(output, "sfp")?;
     smt_condition_rel(univ, events, read_write_pair).write_rel(output, "rw-pair")?;
     smt_dep_rel(addr, events, &exec.thread_opcodes, footprints).write_rel(output, "addr")?;
     smt_dep_rel(data, events, &exec.thread_opcodes, footprints).write_rel(output, "data")?;
@@ -491,21 +855,28 @@ pub fn smt_of_candidate<B: BV>(
     smt_dep_rel(rmw, events, &exec.thread_opcodes, footprints).write_rel(output, "rmw")?;
 
     writeln!(output, "; === COMMON SMTLIB ===\n")?;
-    writeln!(output, "{}", COMMON_SMTLIB)?;
+    if mixed_size {
+        write_mixed_size_defs(output, events, litmus)?
+    } else {
+        writeln!(output, "{}", COMMON_SMTLIB)?
+    }
 
     if !ignore_ifetch {
         writeln!(output, "{}", IFETCH_SMTLIB)?;
     }
 
     for &width in all_write_widths.iter() {
-        let lwt = subst_template(LAST_WRITE_TO, "INITIAL", initial_write_values("addr", 64, &litmus));
-        let lwt = subst_template(lwt, "LEN_MINUS_1", format!("{}", width * 8 - 1));
-        let lwt = subst_template(lwt, "LEN", format!("{}", width * 8));
-        writeln!(output, "{}", lwt)?;
+        if mixed_size {
+            writeln!(output, "{}", mixed_size_last_write_to::<B>(width))?;
+            continue;
+        }
+        writeln!(output, "{}", last_write_to(width, litmus))?;
     }
 
-    writeln!(output, "; === FINAL ASSERTION ===\n")?;
-    writeln!(output, "(assert {})\n", prop_to_smt(&litmus.final_assertion, &exec.final_writes))?;
+    if assert_final {
+        writeln!(output, "; === FINAL ASSERTION ===\n")?;
+        writeln!(output, "(assert {})\n", prop_to_smt(&litmus.final_assertion, &exec.final_writes))?;
+    }
 
     writeln!(output, "; === BARRIERS ===\n")?;
 
@@ -518,3 +889,181 @@ pub fn smt_of_candidate<B: BV>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use isla_lib::concrete::bitvector64::B64;
+    use isla_lib::smt::ExceptionKind;
+
+    use crate::litmus::layout::InitialMemory;
+    use crate::litmus::Quantifier;
+
+    #[test]
+    fn test_cmp_loc_width() {
+        let final_writes = HashMap::new();
+        let loc = Loc::LastWriteTo { address: 0x1000, bytes: 16 };
+        assert_eq!(
+            cmp_loc_to_smt(Cmp::Ult, &loc, B64::from_u64(2), &final_writes),
+            "(exists ((v (_ BitVec 128))) (and (last_write_to_128 #x0000000000001000 v) \
+             (bvult v ((_ zero_extend 64) #x0000000000000002))))"
+        );
+        let loc = Loc::LastWriteTo { address: 0x1000, bytes: 2 };
+        assert_eq!(
+            eq_loc_to_smt(&loc, B64::from_u64(2), &final_writes),
+            "(last_write_to_16 #x0000000000001000 ((_ extract 15 0) #x0000000000000002))"
+        )
+    }
+
+    fn litmus(final_assertion: Prop<B64>) -> Litmus<B64> {
+        Litmus {
+            name: "test".to_string(),
+            hash: None,
+            symbolic_addrs: HashMap::new(),
+            initial_memory: InitialMemory::default(),
+            symbolic_sizeof: HashMap::new(),
+            assembled: Vec::new(),
+            self_modify_regions: Vec::new(),
+            page_tables: None,
+            exception_handlers: Vec::new(),
+            objdump: String::new(),
+            quantifier: Quantifier::Exists,
+            final_assertion,
+        }
+    }
+
+    fn ax_event<'ev>(name: &str, base: &'ev Event<B64>) -> AxEvent<'ev, B64> {
+        AxEvent {
+            opcode: B64::zeros(32),
+            po: 0,
+            intra_instruction_order: 0,
+            thread_id: 0,
+            name: name.to_string(),
+            base,
+            is_ifetch: false,
+            is_translate: false,
+            guard: None,
+        }
+    }
+
+    fn read(address: u64, bytes: u32) -> Event<B64> {
+        let value = Val::Bits(B64::new(0x2211, bytes * 8));
+        Event::ReadMem { value, read_kind: Val::Unit, address: Val::Bits(B64::new(address, 64)), bytes }
+    }
+
+    fn write(address: u64, bytes: u32) -> Event<B64> {
+        Event::WriteMem {
+            value: Sym::from_u32(0),
+            write_kind: Val::Unit,
+            address: Val::Bits(B64::new(address, 64)),
+            data: Val::Bits(B64::new(0x33, bytes * 8)),
+            bytes,
+        }
+    }
+
+    #[test]
+    fn test_is_mixed_size() {
+        let same = litmus(Prop::True);
+        let (r4, w4, r2, w4u) = (read(0x1000, 4), write(0x1000, 4), read(0x1000, 2), write(0x1002, 4));
+
+        assert!(!is_mixed_size(&[ax_event("R0", &r4), ax_event("W0", &w4)], &same));
+        assert!(is_mixed_size(&[ax_event("R0", &r2), ax_event("W0", &w4)], &same));
+        // Unaligned accesses are mixed-size even if all widths agree
+        assert!(is_mixed_size(&[ax_event("R0", &r4), ax_event("W0", &w4u)], &same));
+
+        let final_byte = litmus(Prop::EqLoc(Loc::LastWriteTo { address: 0x1000, bytes: 1 }, B64::new(0x33, 8)));
+        assert!(is_mixed_size(&[ax_event("R0", &r4), ax_event("W0", &w4)], &final_byte))
+    }
+
+    #[test]
+    fn test_mixed_size_defs() {
+        let (r2, w1) = (read(0x1000, 2), write(0x1001, 1));
+        let events = [ax_event("R0", &r2), ax_event("W0", &w1)];
+        let mut output = Vec::new();
+        write_mixed_size_defs(&mut output, &events, &litmus(Prop::True)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        // Each byte of the read chooses its own source
+        assert!(output.contains("(declare-const |R0:rf:0| Event)"));
+        assert!(output.contains("(declare-const |R0:rf:1| Event)"));
+        assert!(!output.contains("|R0:rf:2|"));
+        assert!(output.contains("(and (= ev2 R0) (or (= ev1 |R0:rf:0|) (= ev1 |R0:rf:1|)))"));
+        assert!(output.contains(
+            "(assert (rf-byte-ok |R0:rf:1| (bvadd #x0000000000001000 #x0000000000000001) ((_ extract 15 8) #x2211)))"
+        ));
+        assert!(output.contains("(ite (= ev W0) ((_ extract 7 0) (bvlshr #x33 ((_ extract 7 0) (bvmul (bvsub a #x0000000000001001) #x0000000000000008))))"));
+        assert!(output.contains(MIXED_SIZE_SMTLIB))
+    }
+
+    #[test]
+    fn test_last_write_to() {
+        // Reads also have values and addresses, so the final value
+        // must come from a write
+        let lwt = last_write_to(4, &litmus(Prop::True));
+        assert!(lwt.starts_with("\n(define-fun last_write_to_32 ((addr (_ BitVec 64)) (v (_ BitVec 32))) Bool"));
+        assert!(lwt.contains("(or (and (W ev)\n             (= (val_of_32 ev) v)"))
+    }
+
+    #[test]
+    fn test_mixed_size_last_write_to() {
+        // Locations wider than 64 bits are compared in every byte
+        let lwt = mixed_size_last_write_to::<B64>(16);
+        assert!(lwt.starts_with("(define-fun last_write_to_128 ((addr (_ BitVec 64)) (v (_ BitVec 128))) Bool"));
+        assert_eq!(lwt.matches("(last_byte ").count(), 16);
+        assert!(lwt.contains("(last_byte (bvadd addr #x000000000000000f) ((_ extract 127 120) v))"))
+    }
+
+    #[test]
+    fn test_translation_reads() {
+        let (r8, w8) = (read(0x300000, 8), write(0x300000, 8));
+        let mut walk = ax_event("R0", &r8);
+        walk.is_translate = true;
+        let events = [walk, ax_event("W0", &w8)];
+        assert!(!is_read(&events[0]) && is_translate(&events[0]));
+
+        // Translation reads are not in R, but still read from writes
+        let mut output = Vec::new();
+        smt_set(is_read, &events).write_set(&mut output, "R").unwrap();
+        smt_set(is_translate, &events).write_set(&mut output, "T").unwrap();
+        write_mixed_size_defs(&mut output, &events, &litmus(Prop::True)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("(define-fun R ((ev1 Event)) Bool\n  false)"));
+        assert!(output.contains("(define-fun T ((ev1 Event)) Bool\n  (= ev1 R0))"));
+        assert!(output.contains("(declare-const |R0:rf:7| Event)"))
+    }
+
+    #[test]
+    fn test_exception_sets() {
+        let (take, eret, r8) =
+            (Event::Exception(ExceptionKind::Taken), Event::Exception(ExceptionKind::Return), read(0x1000, 8));
+        let events = [ax_event("TE0", &take), ax_event("R0", &r8), ax_event("ERET0", &eret)];
+
+        let mut output = Vec::new();
+        smt_set(is_exception_entry, &events).write_set(&mut output, "TE").unwrap();
+        smt_set(is_exception_return, &events).write_set(&mut output, "ERET").unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("(define-fun TE ((ev1 Event)) Bool\n  (= ev1 TE0))"));
+        assert!(output.contains("(define-fun ERET ((ev1 Event)) Bool\n  (= ev1 ERET0))"))
+    }
+
+    #[test]
+    fn test_tag_sets() {
+        let address = Val::Bits(B64::new(0x1000, 64));
+        let read_tag = Event::ReadTag { value: Val::Symbolic(Sym::from_u32(0)), address: address.clone() };
+        let write_tag = Event::WriteTag { address, tag: Val::Bool(true) };
+        let events = [ax_event("RT0", &read_tag), ax_event("WT0", &write_tag)];
+
+        // Tag accesses are only in RTAG and WTAG, so they are not
+        // related by rf or co
+        let mut output = Vec::new();
+        smt_set(is_read, &events).write_set(&mut output, "R").unwrap();
+        smt_set(is_write, &events).write_set(&mut output, "W").unwrap();
+        smt_set(is_tag_read, &events).write_set(&mut output, "RTAG").unwrap();
+        smt_set(is_tag_write, &events).write_set(&mut output, "WTAG").unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("(define-fun R ((ev1 Event)) Bool\n  false)"));
+        assert!(output.contains("(define-fun W ((ev1 Event)) Bool\n  false)"));
+        assert!(output.contains("(define-fun RTAG ((ev1 Event)) Bool\n  (= ev1 RT0))"));
+        assert!(output.contains("(define-fun WTAG ((ev1 Event)) Bool\n  (= ev1 WT0))"))
+    }
+}
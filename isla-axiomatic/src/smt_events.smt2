@@ -55,8 +55,8 @@
       (or (and (W ev1) (loc ev1 ev2) (rw-pair ev1 ev2))
           (and (= ev1 IW) (r-initial ev2))))))
 
-; All reads read from somewhere
+; All reads, including translation reads, read from somewhere
 (assert (forall ((ev1 Event))
   (=>
-    (R ev1)
+    (or (R ev1) (T ev1))
     (exists ((ev2 Event)) (rf ev2 ev1)))))
@@ -244,6 +244,27 @@ impl<T> Cat<T> {
         }
         shows
     }
+
+    /// Returns the name of each check in the cat file, along with
+    /// the kind of check and the expression it checks.
+    pub fn checks(&self) -> Vec<(String, &Check, &Exp<T>)> {
+        let mut checks = Vec::new();
+        for (i, def) in self.defs.iter().enumerate() {
+            if let Def::Check(check, exp, id) = def {
+                checks.push((check_name(i, id), check, exp))
+            }
+        }
+        checks
+    }
+}
+
+/// The name of a check, which is either given explicitly with `as`
+/// or `anon:n` where `n` is the index of the check's definition.
+pub fn check_name(i: usize, id: &Option<String>) -> String {
+    match id {
+        Some(id) => id.clone(),
+        None => format!("anon:{}", i),
+    }
 }
 
 impl Cat<Ty> {
@@ -406,6 +427,20 @@ where
     bindings.insert("IF".to_string(), vec![Ty::Set]); // Instruction fetch reads
     bindings.insert("C".to_string(), vec![Ty::Set]); // All cache events
 
+    // Exception sets
+    bindings.insert("TE".to_string(), vec![Ty::Set]); // Exception entry
+    bindings.insert("ERET".to_string(), vec![Ty::Set]); // Exception return
+
+    // Memory tagging sets. Tag accesses are not in R or W, so have no rf or co
+    bindings.insert("RTAG".to_string(), vec![Ty::Set]); // Memory tag reads
+    bindings.insert("WTAG".to_string(), vec![Ty::Set]); // Memory tag writes
+
+    // Translation sets
+    bindings.insert("T".to_string(), vec![Ty::Set]); // Translation table walk reads
+
+    // Platform memory map sets
+    bindings.insert("DEV".to_string(), vec![Ty::Set]); // Accesses to device memory
+
     // Architecture specific sets
     for set in sets {
         bindings.insert(set, vec![Ty::Set]);
@@ -421,6 +456,8 @@ where
 
     bindings.insert("id".to_string(), vec![Ty::Rel]); // The identity relation
     bindings.insert("loc".to_string(), vec![Ty::Rel]); // Events touching the same address
+    bindings.insert("overlap".to_string(), vec![Ty::Rel]); // Events touching at least one common byte
+    bindings.insert("sfp".to_string(), vec![Ty::Rel]); // Events with the same address and size
     bindings.insert("ext".to_string(), vec![Ty::Rel]); // Events from different threads
     bindings.insert("int".to_string(), vec![Ty::Rel]); // Events from the same thread
     bindings.insert("rf".to_string(), vec![Ty::Rel]); // Reads-from
@@ -0,0 +1,420 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module implements an interpreter for cat models over
+//! concrete executions, where the events and the sets and relations
+//! the model is defined over (`po`, `rf`, `co`, `W`, and so on) are
+//! given explicitly. This allows testing a cat model on small
+//! hand-written executions without an SMT solver.
+//!
+//! Unlike the SMT translation in `smt`, functions such as
+//! `fencerel` are applied rather than treated as empty relations, so
+//! the two only agree on the definitions not in
+//! `smt::approximated_defs`.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::cat::*;
+
+/// The value of a cat expression over the events of an execution,
+/// which are numbered by their position in the `Execution`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Set(BTreeSet<usize>),
+    Rel(BTreeSet<(usize, usize)>),
+}
+
+/// A concrete execution, consisting of named events and the sets
+/// and relations over them that a cat model refers to.
+#[derive(Debug, Default)]
+pub struct Execution {
+    events: Vec<String>,
+    event_ids: HashMap<String, usize>,
+    base: HashMap<String, Value>,
+}
+
+impl Execution {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add an event to the execution, returning its index. Adding an
+    /// event that already exists just returns the existing index.
+    pub fn add_event(&mut self, name: &str) -> usize {
+        if let Some(id) = self.event_ids.get(name) {
+            return *id;
+        }
+        let id = self.events.len();
+        self.events.push(name.to_string());
+        self.event_ids.insert(name.to_string(), id);
+        id
+    }
+
+    fn event_id(&self, name: &str) -> Result<usize, String> {
+        self.event_ids.get(name).copied().ok_or_else(|| format!("Event {} is not part of the execution", name))
+    }
+
+    pub fn events(&self) -> &[String] {
+        &self.events
+    }
+
+    /// Returns true if the execution defines a set or relation with
+    /// the given name.
+    pub fn defines(&self, name: &str) -> bool {
+        self.base.contains_key(name)
+    }
+
+    pub fn add_set<I, S>(&mut self, name: &str, elems: I) -> Result<(), String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let elems = elems.into_iter().map(|ev| self.event_id(ev.as_ref())).collect::<Result<_, _>>()?;
+        self.base.insert(name.to_string(), Value::Set(elems));
+        Ok(())
+    }
+
+    pub fn add_relation<I, S>(&mut self, name: &str, edges: I) -> Result<(), String>
+    where
+        I: IntoIterator<Item = (S, S)>,
+        S: AsRef<str>,
+    {
+        let edges = edges
+            .into_iter()
+            .map(|(ev1, ev2)| Ok((self.event_id(ev1.as_ref())?, self.event_id(ev2.as_ref())?)))
+            .collect::<Result<_, String>>()?;
+        self.base.insert(name.to_string(), Value::Rel(edges));
+        Ok(())
+    }
+
+    fn all(&self) -> BTreeSet<usize> {
+        (0..self.events.len()).collect()
+    }
+
+    fn identity(&self) -> BTreeSet<(usize, usize)> {
+        (0..self.events.len()).map(|ev| (ev, ev)).collect()
+    }
+}
+
+fn type_error<A>(exp: &Exp<Ty>) -> Result<A, String> {
+    Err(format!("Type error when interpreting {:?}", exp))
+}
+
+fn compose(x: &BTreeSet<(usize, usize)>, y: &BTreeSet<(usize, usize)>) -> BTreeSet<(usize, usize)> {
+    let mut successors: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (from, to) in y {
+        successors.entry(*from).or_default().push(*to)
+    }
+    let mut result = BTreeSet::new();
+    for (from, mid) in x {
+        for to in successors.get(mid).into_iter().flatten() {
+            result.insert((*from, *to));
+        }
+    }
+    result
+}
+
+fn transitive_closure(rel: &BTreeSet<(usize, usize)>) -> BTreeSet<(usize, usize)> {
+    let mut closure = rel.clone();
+    loop {
+        let next: BTreeSet<(usize, usize)> = closure.union(&compose(&closure, rel)).copied().collect();
+        if next.len() == closure.len() {
+            return closure;
+        }
+        closure = next
+    }
+}
+
+struct Interpreter<'cat, 'exec> {
+    exec: &'exec Execution,
+    values: HashMap<String, Value>,
+    functions: HashMap<&'cat str, (&'cat str, &'cat Exp<Ty>)>,
+}
+
+impl<'cat, 'exec> Interpreter<'cat, 'exec> {
+    fn lookup(&self, id: &str, locals: &[(String, Value)]) -> Result<Value, String> {
+        if let Some((_, value)) = locals.iter().rev().find(|(local, _)| local == id) {
+            return Ok(value.clone());
+        }
+        if let Some(value) = self.values.get(id).or_else(|| self.exec.base.get(id)) {
+            return Ok(value.clone());
+        }
+        match id {
+            "_" => Ok(Value::Set(self.exec.all())),
+            "emptyset" => Ok(Value::Set(BTreeSet::new())),
+            "id" => Ok(Value::Rel(self.exec.identity())),
+            _ => Err(format!("Set or relation {} is not defined", id)),
+        }
+    }
+
+    fn apply(&self, f: &str, arg: Value, locals: &mut Vec<(String, Value)>) -> Result<Value, String> {
+        use Value::*;
+
+        if let Some((param, body)) = self.functions.get(f) {
+            locals.push((param.to_string(), arg));
+            let result = self.eval(body, locals);
+            locals.pop();
+            return result;
+        }
+
+        match (f, arg) {
+            ("domain", Rel(rel)) => Ok(Set(rel.iter().map(|(from, _)| *from).collect())),
+            ("range", Rel(rel)) => Ok(Set(rel.iter().map(|(_, to)| *to).collect())),
+            // (po & (_ * S)); po and (ctrl & (_ * S)); po
+            ("fencerel", Set(set)) | ("ctrlcfence", Set(set)) => {
+                let before = self.lookup(if f == "fencerel" { "po" } else { "ctrl" }, locals)?;
+                let po = self.lookup("po", locals)?;
+                match (before, po) {
+                    (Rel(before), Rel(po)) => {
+                        let before = before.into_iter().filter(|(_, to)| set.contains(to)).collect();
+                        Ok(Rel(compose(&before, &po)))
+                    }
+                    _ => Err(format!("Type error when applying {}", f)),
+                }
+            }
+            _ => Err(format!("Cannot apply function {}", f)),
+        }
+    }
+
+    fn eval(&self, exp: &Exp<Ty>, locals: &mut Vec<(String, Value)>) -> Result<Value, String> {
+        use Value::*;
+
+        Ok(match exp {
+            Exp::Empty(Ty::Set) => Set(BTreeSet::new()),
+            Exp::Empty(Ty::Rel) => Rel(BTreeSet::new()),
+
+            Exp::Id(id, _) => self.lookup(id, locals)?,
+
+            Exp::Let(id, x, y, _) => {
+                let x = self.eval(x, locals)?;
+                locals.push((id.clone(), x));
+                let y = self.eval(y, locals);
+                locals.pop();
+                y?
+            }
+
+            Exp::TryWith(x, y, _) => match self.eval(x, locals) {
+                Ok(x) => x,
+                Err(_) => self.eval(y, locals)?,
+            },
+
+            Exp::Union(x, y, _) => match (self.eval(x, locals)?, self.eval(y, locals)?) {
+                (Set(x), Set(y)) => Set(x.union(&y).copied().collect()),
+                (Rel(x), Rel(y)) => Rel(x.union(&y).copied().collect()),
+                _ => return type_error(exp),
+            },
+
+            Exp::Inter(x, y, _) => match (self.eval(x, locals)?, self.eval(y, locals)?) {
+                (Set(x), Set(y)) => Set(x.intersection(&y).copied().collect()),
+                (Rel(x), Rel(y)) => Rel(x.intersection(&y).copied().collect()),
+                _ => return type_error(exp),
+            },
+
+            Exp::Diff(x, y, _) => match (self.eval(x, locals)?, self.eval(y, locals)?) {
+                (Set(x), Set(y)) => Set(x.difference(&y).copied().collect()),
+                (Rel(x), Rel(y)) => Rel(x.difference(&y).copied().collect()),
+                _ => return type_error(exp),
+            },
+
+            Exp::Seq(x, y) => match (self.eval(x, locals)?, self.eval(y, locals)?) {
+                (Rel(x), Rel(y)) => Rel(compose(&x, &y)),
+                _ => return type_error(exp),
+            },
+
+            Exp::Cartesian(x, y) => match (self.eval(x, locals)?, self.eval(y, locals)?) {
+                (Set(x), Set(y)) => Rel(x.iter().flat_map(|from| y.iter().map(move |to| (*from, *to))).collect()),
+                _ => return type_error(exp),
+            },
+
+            Exp::Compl(x, _) => match self.eval(x, locals)? {
+                Set(x) => Set(self.exec.all().difference(&x).copied().collect()),
+                Rel(x) => {
+                    let all = self.exec.all();
+                    Rel(all
+                        .iter()
+                        .flat_map(|from| all.iter().map(move |to| (*from, *to)))
+                        .filter(|pair| !x.contains(pair))
+                        .collect())
+                }
+            },
+
+            Exp::Identity(x) => match self.eval(x, locals)? {
+                Set(x) => Rel(x.iter().map(|ev| (*ev, *ev)).collect()),
+                _ => return type_error(exp),
+            },
+
+            Exp::IdentityUnion(x) => match self.eval(x, locals)? {
+                Rel(x) => Rel(x.union(&self.exec.identity()).copied().collect()),
+                _ => return type_error(exp),
+            },
+
+            Exp::Inverse(x) => match self.eval(x, locals)? {
+                Rel(x) => Rel(x.iter().map(|(from, to)| (*to, *from)).collect()),
+                _ => return type_error(exp),
+            },
+
+            Exp::App(f, x, _) => {
+                let x = self.eval(x, locals)?;
+                self.apply(f, x, locals)?
+            }
+        })
+    }
+
+    /// Checks treat a set S as the relation S × _, like `compile_cat`
+    fn check_rel(&self, value: Value) -> BTreeSet<(usize, usize)> {
+        match value {
+            Value::Set(set) => {
+                let all = self.exec.all();
+                set.iter().flat_map(|from| all.iter().map(move |to| (*from, *to))).collect()
+            }
+            Value::Rel(rel) => rel,
+        }
+    }
+}
+
+fn check_holds(check: &Check, rel: &BTreeSet<(usize, usize)>) -> bool {
+    let irreflexive = |rel: &BTreeSet<(usize, usize)>| rel.iter().all(|(from, to)| from != to);
+    match check {
+        Check::Acyclic => irreflexive(&transitive_closure(rel)),
+        Check::Irreflexive => irreflexive(rel),
+        Check::Empty => rel.is_empty(),
+        Check::NonAcyclic => !irreflexive(&transitive_closure(rel)),
+        Check::NonIrreflexive => !irreflexive(rel),
+        Check::NonEmpty => !rel.is_empty(),
+    }
+}
+
+/// The result of interpreting a cat model over an execution.
+pub struct Interpretation<'exec> {
+    exec: &'exec Execution,
+    values: HashMap<String, Value>,
+    checks: Vec<(String, bool)>,
+}
+
+impl<'exec> Interpretation<'exec> {
+    fn value(&self, name: &str) -> Option<&Value> {
+        self.values.get(name).or_else(|| self.exec.base.get(name))
+    }
+
+    /// The elements of a set defined by the cat model or the execution.
+    pub fn set(&self, name: &str) -> Option<Vec<&str>> {
+        match self.value(name)? {
+            Value::Set(set) => Some(set.iter().map(|ev| self.exec.events[*ev].as_ref()).collect()),
+            Value::Rel(_) => None,
+        }
+    }
+
+    /// The edges of a relation defined by the cat model or the execution.
+    pub fn relation(&self, name: &str) -> Option<Vec<(&str, &str)>> {
+        match self.value(name)? {
+            Value::Rel(rel) => Some(
+                rel.iter()
+                    .map(|(from, to)| (self.exec.events[*from].as_ref(), self.exec.events[*to].as_ref()))
+                    .collect(),
+            ),
+            Value::Set(_) => None,
+        }
+    }
+
+    /// Returns true if the cat model defines a set or relation with
+    /// the given name.
+    pub fn defines(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// The names of all the sets and relations defined by the cat model.
+    pub fn defined(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.values.keys().map(String::as_ref).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Each check in the cat model, named as in `Cat::checks`, and
+    /// whether it holds for the execution.
+    pub fn checks(&self) -> &[(String, bool)] {
+        &self.checks
+    }
+
+    /// Returns the names of all the checks which do not hold.
+    pub fn violated(&self) -> Vec<&str> {
+        self.checks.iter().filter(|(_, holds)| !holds).map(|(name, _)| name.as_ref()).collect()
+    }
+
+    /// An execution is allowed by the model if all the checks hold.
+    pub fn allowed(&self) -> bool {
+        self.checks.iter().all(|(_, holds)| *holds)
+    }
+}
+
+/// Interpret a cat model over a concrete execution, computing every
+/// set and relation it defines and whether each of its checks
+/// hold. Any set or relation the model uses that is not defined by
+/// the model itself must be provided by the execution.
+pub fn interpret_cat<'exec>(cat: &Cat<Ty>, exec: &'exec Execution) -> Result<Interpretation<'exec>, String> {
+    let mut interpreter = Interpreter { exec, values: HashMap::new(), functions: HashMap::new() };
+    let mut checks = Vec::new();
+
+    for (i, def) in cat.defs.iter().enumerate() {
+        match def {
+            Def::Let(bindings) => {
+                for (id, exp) in bindings {
+                    let value = interpreter.eval(exp, &mut Vec::new()).map_err(|e| format!("In {}: {}", id, e))?;
+                    interpreter.values.insert(id.clone(), value);
+                }
+            }
+
+            Def::Fn(name, params, body) => match params.as_slice() {
+                [(param, _)] => {
+                    interpreter.functions.insert(name, (param, body));
+                }
+                _ => return Err(format!("Function {} must have exactly one parameter", name)),
+            },
+
+            Def::TClosure(id, exp) | Def::RTClosure(id, exp) => {
+                let rel = match interpreter.eval(exp, &mut Vec::new()).map_err(|e| format!("In {}: {}", id, e))? {
+                    Value::Rel(rel) => transitive_closure(&rel),
+                    Value::Set(_) => return Err(format!("In {}: closure of a set", id)),
+                };
+                let rel =
+                    if let Def::RTClosure(_, _) = def { rel.union(&exec.identity()).copied().collect() } else { rel };
+                interpreter.values.insert(id.clone(), Value::Rel(rel));
+            }
+
+            Def::Check(check, exp, id) => {
+                let name = check_name(i, id);
+                let value = interpreter.eval(exp, &mut Vec::new()).map_err(|e| format!("In check {}: {}", name, e))?;
+                checks.push((name, check_holds(check, &interpreter.check_rel(value))))
+            }
+
+            _ => (),
+        }
+    }
+
+    Ok(Interpretation { exec, values: interpreter.values, checks })
+}
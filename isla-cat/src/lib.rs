@@ -35,6 +35,9 @@
 //! roughly speaking, we support the subset of cat that defines
 //! relations and sets over events which is easily translated into
 //! first-order SMT definitions.
+//!
+//! Cat files can also be interpreted directly over concrete
+//! executions using the `interpret` module.
 
 #[macro_use]
 extern crate lalrpop_util;
@@ -48,4 +51,5 @@ lalrpop_mod!(
 
 pub mod cat;
 mod cat_lexer;
+pub mod interpret;
 pub mod smt;
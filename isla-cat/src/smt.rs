@@ -32,7 +32,6 @@
 //! SMT definitions, where relations are represented as functions from
 //! Event × Event → Bool and sets are Event → Bool functions.
 
-use std::borrow::Cow;
 use std::collections::HashSet;
 use std::error::Error;
 use std::io::Write;
@@ -371,6 +370,47 @@ pub fn compile_rel(exp: &Exp<Ty>, ev1: EventId, ev2: EventId) -> Option<Sexp> {
     })
 }
 
+fn depends_on_app(exp: &Exp<Ty>, approximate: &HashSet<String>) -> bool {
+    use Exp::*;
+    match exp {
+        Empty(_) => false,
+        Id(id, _) => approximate.contains(id),
+        App(_, _, Ty::Rel) => true,
+        App(_, x, _) | Compl(x, _) | Identity(x) | IdentityUnion(x) | Inverse(x) => depends_on_app(x, approximate),
+        Let(_, x, y, _)
+        | TryWith(x, y, _)
+        | Union(x, y, _)
+        | Inter(x, y, _)
+        | Diff(x, y, _)
+        | Seq(x, y)
+        | Cartesian(x, y) => depends_on_app(x, approximate) || depends_on_app(y, approximate),
+    }
+}
+
+/// `compile_rel` treats a function application such as
+/// `fencerel(B)` as the empty relation, so the SMT definitions of
+/// any relation that depends on one only approximate the cat
+/// model. Returns the names of all such definitions.
+pub fn approximated_defs(cat: &Cat<Ty>) -> HashSet<String> {
+    let mut approximate = HashSet::new();
+    for def in cat.defs.iter() {
+        match def {
+            Def::Let(bindings) => {
+                for (id, exp) in bindings {
+                    if depends_on_app(exp, &approximate) {
+                        approximate.insert(id.clone());
+                    }
+                }
+            }
+            Def::TClosure(id, exp) | Def::RTClosure(id, exp) if depends_on_app(exp, &approximate) => {
+                approximate.insert(id.clone());
+            }
+            _ => (),
+        }
+    }
+    approximate
+}
+
 /// `compile_toplevel` compiles a toplevel expression into either a
 /// set or relation depending on its type. The event names are
 /// implicitly `ev1` and `ev2` for relations and `ev1` for sets, which
@@ -445,9 +485,33 @@ fn reflexive_transitive_closure_for(output: &mut dyn Write, id: &str, rtc_id: &s
     Ok(())
 }
 
+fn write_check(output: &mut dyn Write, id: &str, assumptions: bool, prop: &str) -> Result<(), Box<dyn Error>> {
+    if assumptions {
+        writeln!(output, "(assert (=> |axiom:{}| {}))", id, prop)?
+    } else {
+        writeln!(output, "(assert {})", prop)?
+    }
+    Ok(())
+}
+
 /// Compile all the definitions in a cat model.
 pub fn compile_cat(output: &mut dyn Write, cat: &Cat<Ty>) -> Result<(), Box<dyn Error>> {
+    compile_cat_defs(output, cat, false).map(|_| ())
+}
+
+/// Compile all the definitions in a cat model, but rather than
+/// asserting each check directly, guard it by a boolean `|axiom:id|`
+/// which can be used as an assumption with `check-sat-assuming`, so
+/// the unsat core tells us which checks forbid an execution. The
+/// relation for each check is declared as a function so it appears
+/// in the model. Returns the names of all the checks.
+pub fn compile_cat_with_assumptions(output: &mut dyn Write, cat: &Cat<Ty>) -> Result<Vec<String>, Box<dyn Error>> {
+    compile_cat_defs(output, cat, true)
+}
+
+fn compile_cat_defs(output: &mut dyn Write, cat: &Cat<Ty>, assumptions: bool) -> Result<Vec<String>, Box<dyn Error>> {
     let mut known_empty = HashSet::new();
+    let mut checks = Vec::new();
 
     for (i, def) in cat.defs.iter().enumerate() {
         match def {
@@ -466,45 +530,57 @@ pub fn compile_cat(output: &mut dyn Write, cat: &Cat<Ty>) -> Result<(), Box<dyn
             }
 
             Def::Check(check, exp, id) => {
-                let id = match id {
-                    Some(id) => Cow::Borrowed(id),
-                    None => Cow::Owned(format!("anon:{}", i)),
-                };
+                let id = check_name(i, id);
 
-                writeln!(output, "(define-fun |check:{}| ((ev1 Event) (ev2 Event)) Bool", id)?;
                 let mut sexp = compile_toplevel(exp).unwrap();
                 sexp.simplify(&known_empty);
-                sexp.write_to(output, true, 2, false)?;
-                writeln!(output, ")")?;
+                if assumptions {
+                    writeln!(output, "(declare-const |axiom:{}| Bool)", id)?;
+                    writeln!(output, "(declare-fun |check:{}| (Event Event) Bool)", id)?;
+                    writeln!(output, "(assert (forall ((ev1 Event) (ev2 Event))\n  (= (|check:{}| ev1 ev2)", id)?;
+                    sexp.write_to(output, true, 4, false)?;
+                    writeln!(output, ")))")?;
+                } else {
+                    writeln!(output, "(define-fun |check:{}| ((ev1 Event) (ev2 Event)) Bool", id)?;
+                    sexp.write_to(output, true, 2, false)?;
+                    writeln!(output, ")")?;
+                }
 
                 match check {
                     Check::Empty => {
-                        writeln!(output, "(assert (forall ((ev1 Event) (ev2 Event)) (not (|check:{}| ev1 ev2))))", id)?
+                        let prop = format!("(forall ((ev1 Event) (ev2 Event)) (not (|check:{}| ev1 ev2)))", id);
+                        write_check(output, &id, assumptions, &prop)?
                     }
                     Check::NonEmpty => {
                         writeln!(output, "(declare-const |ne1:{}| Event)", id)?;
                         writeln!(output, "(declare-const |ne2:{}| Event)", id)?;
-                        writeln!(output, "(assert (|check:{}| |ne1:{}| |ne2:{}|))", id, id, id)?;
+                        let prop = format!("(|check:{}| |ne1:{}| |ne2:{}|)", id, id, id);
+                        write_check(output, &id, assumptions, &prop)?
                     }
                     Check::Irreflexive => {
-                        writeln!(output, "(assert (forall ((ev1 Event)) (not (|check:{}| ev1 ev1))))", id)?
+                        let prop = format!("(forall ((ev1 Event)) (not (|check:{}| ev1 ev1)))", id);
+                        write_check(output, &id, assumptions, &prop)?
                     }
                     Check::NonIrreflexive => {
                         writeln!(output, "(declare-const |some:{}| Event)", id)?;
-                        writeln!(output, "(assert (|check:{}| |some:{}| |some:{}|))", id, id, id)?;
+                        let prop = format!("(|check:{}| |some:{}| |some:{}|)", id, id, id);
+                        write_check(output, &id, assumptions, &prop)?
                     }
                     Check::Acyclic => {
                         transitive_closure_for(output, &format!("check:{}", id), &format!("acyclic:{}", id))?;
-                        writeln!(output, "(assert (forall ((ev1 Event)) (not (|acyclic:{}| ev1 ev1))))", id)?;
+                        let prop = format!("(forall ((ev1 Event)) (not (|acyclic:{}| ev1 ev1)))", id);
+                        write_check(output, &id, assumptions, &prop)?
                     }
                     Check::NonAcyclic => {
                         transitive_closure_for(output, &format!("check:{}", id), &format!("non-acyclic:{}", id))?;
                         writeln!(output, "(declare-const |some:{}| Event)", id)?;
-                        writeln!(output, "(assert (|non-acyclic:{}| |some:{}| |some:{}|))", id, id, id)?;
+                        let prop = format!("(|non-acyclic:{}| |some:{}| |some:{}|)", id, id, id);
+                        write_check(output, &id, assumptions, &prop)?
                     }
                 }
 
                 writeln!(output)?;
+                checks.push(id)
             }
 
             Def::TClosure(id, exp) => {
@@ -532,7 +608,7 @@ pub fn compile_cat(output: &mut dyn Write, cat: &Cat<Ty>) -> Result<(), Box<dyn
             _ => (),
         }
     }
-    Ok(())
+    Ok(checks)
 }
 
 #[cfg(test)]
@@ -576,4 +652,35 @@ mod tests {
         sexp.simplify(&HashSet::new());
         assert_eq!(sexp, True)
     }
+
+    fn cat_from_string(contents: &str) -> Cat<Ty> {
+        use crate::cat::{infer_cat, initial_tcx, resolve_includes, ParseCat, Shadows};
+        let parse_cat = ParseCat::from_string(contents).unwrap();
+        let mut cat = resolve_includes(&[], parse_cat).unwrap();
+        cat.unshadow(&mut Shadows::new());
+        infer_cat(&mut initial_tcx(Vec::new().into_iter()), cat).unwrap()
+    }
+
+    #[test]
+    fn test_compile_cat_with_assumptions() {
+        let cat = cat_from_string("\"test\"\nlet com = rf | co\nacyclic po | com as sc\nempty rmw as atomic\n");
+
+        let mut output = Vec::new();
+        let checks = compile_cat_with_assumptions(&mut output, &cat).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(checks, vec!["sc", "atomic"]);
+        assert!(output.contains("(declare-const |axiom:sc| Bool)"));
+        assert!(output.contains("(declare-fun |check:sc| (Event Event) Bool)"));
+        assert!(output.contains("(assert (=> |axiom:sc| (forall ((ev1 Event)) (not (|acyclic:sc| ev1 ev1)))))"));
+        assert!(output.contains(
+            "(assert (=> |axiom:atomic| (forall ((ev1 Event) (ev2 Event)) (not (|check:atomic| ev1 ev2)))))"
+        ));
+
+        // Without assumptions the checks are asserted directly
+        let mut output = Vec::new();
+        compile_cat(&mut output, &cat).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(!output.contains("axiom:"));
+        assert!(output.contains("(assert (forall ((ev1 Event)) (not (|acyclic:sc| ev1 ev1))))"))
+    }
 }
@@ -0,0 +1,115 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2019, 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+extern crate isla_cat;
+
+use isla_cat::cat::{infer_cat, initial_tcx, resolve_includes, Cat, ParseCat, Shadows, Ty};
+use isla_cat::interpret::{interpret_cat, Execution};
+
+fn cat_from_string(contents: &str) -> Cat<Ty> {
+    let parse_cat = ParseCat::from_string(contents).unwrap();
+    let mut cat = resolve_includes(&[], parse_cat).unwrap();
+    cat.unshadow(&mut Shadows::new());
+    infer_cat(&mut initial_tcx(Vec::new().into_iter()), cat).unwrap()
+}
+
+const SC: &str = r#""SC"
+include "cos.cat"
+let com = rf | co | fr
+acyclic po | com as sc
+"#;
+
+const TSO: &str = r#""TSO"
+include "cos.cat"
+let ppo = po \ (W * R)
+let ghb = ppo | rfe | fr | co
+acyclic po-loc | rf | co | fr as uniproc
+acyclic ghb as tso
+"#;
+
+/// The store buffering test, where both reads read from the initial
+/// state.
+fn store_buffering() -> Execution {
+    let mut exec = Execution::new();
+    for ev in &["IW", "Wx0", "Ry0", "Wy1", "Rx1"] {
+        exec.add_event(ev);
+    }
+    exec.add_set("IW", ["IW"]).unwrap();
+    exec.add_set("W", ["Wx0", "Wy1"]).unwrap();
+    exec.add_set("R", ["Ry0", "Rx1"]).unwrap();
+    exec.add_relation("po", vec![("Wx0", "Ry0"), ("Wy1", "Rx1")]).unwrap();
+    exec.add_relation("int", vec![("Wx0", "Ry0"), ("Ry0", "Wx0"), ("Wy1", "Rx1"), ("Rx1", "Wy1")]).unwrap();
+    exec.add_relation(
+        "ext",
+        vec![
+            ("Wx0", "Wy1"),
+            ("Wx0", "Rx1"),
+            ("Ry0", "Wy1"),
+            ("Ry0", "Rx1"),
+            ("Wy1", "Wx0"),
+            ("Wy1", "Ry0"),
+            ("Rx1", "Wx0"),
+            ("Rx1", "Ry0"),
+        ],
+    )
+    .unwrap();
+    exec.add_relation("loc", vec![("Wx0", "Rx1"), ("Rx1", "Wx0"), ("Wy1", "Ry0"), ("Ry0", "Wy1")]).unwrap();
+    exec.add_relation("po-loc", Vec::<(&str, &str)>::new()).unwrap();
+    exec.add_relation("rf", vec![("IW", "Ry0"), ("IW", "Rx1")]).unwrap();
+    exec.add_relation("co", vec![("IW", "Wx0"), ("IW", "Wy1")]).unwrap();
+    exec
+}
+
+#[test]
+fn test_interpret_sc() {
+    let exec = store_buffering();
+    let result = interpret_cat(&cat_from_string(SC), &exec).unwrap();
+    assert_eq!(result.relation("fr").unwrap(), vec![("Ry0", "Wy1"), ("Rx1", "Wx0")]);
+    assert_eq!(result.relation("fre").unwrap(), vec![("Ry0", "Wy1"), ("Rx1", "Wx0")]);
+    assert_eq!(result.violated(), vec!["sc"]);
+    assert!(!result.allowed())
+}
+
+#[test]
+fn test_interpret_tso() {
+    let exec = store_buffering();
+    let result = interpret_cat(&cat_from_string(TSO), &exec).unwrap();
+    assert_eq!(result.relation("ppo").unwrap(), Vec::<(&str, &str)>::new());
+    assert_eq!(result.checks(), &[("uniproc".to_string(), true), ("tso".to_string(), true)]);
+    assert!(result.allowed())
+}
+
+#[test]
+fn test_interpret_undefined() {
+    let mut exec = store_buffering();
+    exec.add_relation("rf", vec![("Wx0", "Rx1")]).unwrap();
+    assert!(exec.add_relation("rf", vec![("Wx0", "Rz")]).is_err());
+    let cat = cat_from_string("\"missing\"\nacyclic po | addr as missing\n");
+    assert!(interpret_cat(&cat, &exec).is_err())
+}
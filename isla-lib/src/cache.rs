@@ -27,11 +27,43 @@
 // (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+//! This module implements an on-disk cache for intermediate results,
+//! such as instruction footprints.
+//!
+//! Each entry is stored in its own file, with a header recording the
+//! cache format version, the entry's key, a hash of the architecture
+//! and configuration used to compute it, and a checksum of the
+//! contents. Entries for a different architecture or format version
+//! are never returned. Entries are written to a temporary file which
+//! is then renamed into place, so concurrent processes sharing a cache
+//! directory never observe a partially written entry.
+
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// The version of the on-disk format for cache entries. This should
+/// be incremented whenever the format changes, including changes to
+/// the serialized representation of any `Cacheable` type.
+pub const CACHE_FORMAT_VERSION: u32 = 1;
+
+const CACHE_MAGIC: [u8; 8] = *b"ISLACACH";
+
+const CACHE_EXTENSION: &str = "islacache";
 
-use std::fs::File;
-use std::path::Path;
+/// Temporary files older than this are assumed to have been left
+/// behind by a process that failed while writing an entry.
+const STALE_TEMPORARY: Duration = Duration::from_secs(600);
+
+static TEMPORARY_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
 pub trait Cachekey {
     fn key(&self) -> String;
@@ -40,26 +72,336 @@ pub trait Cachekey {
 pub trait Cacheable: Serialize + DeserializeOwned {
     type Key: Cachekey;
 
-    fn from_cache<P: AsRef<Path>>(key: Self::Key, cache: P) -> Option<Self> {
-        if !cache.as_ref().is_dir() {
-            return None;
-        };
+    fn from_cache(key: Self::Key, cache: &Cache) -> Option<Self> {
+        let contents = cache.read(&key.key())?;
+        bincode::deserialize(&contents).ok()
+    }
+
+    fn cache(&self, key: Self::Key, cache: &Cache) {
+        if let Ok(contents) = bincode::serialize(self) {
+            // Failing to write to the cache is not an error, as we
+            // can always recompute the result
+            let _ = cache.write(&key.key(), &contents);
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Add the command line options which modify the architecture, namely
+/// register assignments (`-R/--register`) and linearized functions
+/// (`-L/--linearize`), to an architecture hash. This must come after
+/// the architecture and configuration file contents have been added.
+/// Without any such options the hash is unchanged.
+pub fn hash_arch_options(hasher: &mut Sha256, registers: &[String], linearize: &[String]) {
+    for register in registers {
+        hasher.input(b"register\0");
+        hasher.input(register.as_bytes());
+        hasher.input(b"\0")
+    }
+    for id in linearize {
+        hasher.input(b"linearize\0");
+        hasher.input(id.as_bytes());
+        hasher.input(b"\0")
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    magic: [u8; 8],
+    version: u32,
+    key: String,
+    arch_hash: String,
+    checksum: Vec<u8>,
+}
+
+/// A cache directory, along with the hash of the architecture and
+/// configuration used to compute any entries.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    dir: PathBuf,
+    arch_hash: String,
+}
+
+/// The status of a cache entry, as reported by `Cache::entries`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EntryStatus {
+    Valid,
+    /// The entry was computed for a different architecture than the
+    /// cache handle
+    OtherArchitecture,
+    /// The entry was written with a different format version
+    OtherVersion,
+    Corrupt(String),
+}
+
+impl fmt::Display for EntryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use EntryStatus::*;
+        match self {
+            Valid => write!(f, "valid"),
+            OtherArchitecture => write!(f, "other architecture"),
+            OtherVersion => write!(f, "other format version"),
+            Corrupt(msg) => write!(f, "corrupt: {}", msg),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    /// The key, architecture hash, and format version are only
+    /// available if the entry header could be read
+    pub key: Option<String>,
+    pub arch_hash: Option<String>,
+    pub version: Option<u32>,
+    pub size: u64,
+    pub status: EntryStatus,
+}
+
+impl Cache {
+    /// Create a cache handle for a directory, which must already
+    /// exist. The architecture hash is the SHA-256 digest of the
+    /// architecture and configuration files, followed by any options
+    /// added by `hash_arch_options`, as computed by the option parsing
+    /// in each isla tool.
+    pub fn new<P: AsRef<Path>>(dir: P, arch_hash: &[u8]) -> Self {
+        Cache { dir: dir.as_ref().to_path_buf(), arch_hash: hex(arch_hash) }
+    }
+
+    /// A cache handle for maintenance operations which are not
+    /// specific to any one architecture. Every entry will have status
+    /// `OtherArchitecture` unless `arch_hash` is given as a hex string.
+    pub fn for_maintenance<P: AsRef<Path>>(dir: P, arch_hash: Option<&str>) -> Self {
+        Cache { dir: dir.as_ref().to_path_buf(), arch_hash: arch_hash.unwrap_or("").to_lowercase() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
 
-        let cache_file = cache.as_ref().join(key.key());
+    pub fn arch_hash(&self) -> &str {
+        &self.arch_hash
+    }
 
-        let fd = File::open(&cache_file).ok()?;
-        bincode::deserialize_from(fd).ok()
+    fn entry_path(&self, key: &str) -> PathBuf {
+        // The full hash is checked when reading the entry, a prefix
+        // is sufficient to keep architectures apart in the file name
+        let prefix = &self.arch_hash[..std::cmp::min(16, self.arch_hash.len())];
+        self.dir.join(format!("{}.{}.{}", key, prefix, CACHE_EXTENSION))
+    }
+
+    /// Read the contents of a cache entry, returning `None` if it does
+    /// not exist or is not valid for this cache's architecture.
+    pub fn read(&self, key: &str) -> Option<Vec<u8>> {
+        let (header, contents) = read_entry(&self.entry_path(key)).ok()?;
+        if header.version == CACHE_FORMAT_VERSION
+            && header.key == key
+            && header.arch_hash == self.arch_hash
+            && header.checksum == Sha256::digest(&contents).as_slice()
+        {
+            Some(contents)
+        } else {
+            None
+        }
     }
 
-    fn cache<P: AsRef<Path>>(&self, key: Self::Key, cache: P) {
-        if !cache.as_ref().is_dir() {
-            return;
+    /// Atomically write a cache entry, replacing any existing entry
+    /// with the same key.
+    pub fn write(&self, key: &str, contents: &[u8]) -> io::Result<()> {
+        let header = Header {
+            magic: CACHE_MAGIC,
+            version: CACHE_FORMAT_VERSION,
+            key: key.to_string(),
+            arch_hash: self.arch_hash.clone(),
+            checksum: Sha256::digest(contents).to_vec(),
         };
+        let header = bincode::serialize(&header).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let path = self.entry_path(key);
+        let tmp_path = self.dir.join(format!(
+            ".{}.{}.{}.tmp",
+            path.file_name().unwrap().to_string_lossy(),
+            process::id(),
+            TEMPORARY_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        let result = (|| {
+            let mut fd = File::create(&tmp_path)?;
+            fd.write_all(&header)?;
+            fd.write_all(contents)?;
+            fd.sync_all()?;
+            fs::rename(&tmp_path, &path)
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        result
+    }
 
-        let cache_file = cache.as_ref().join(key.key());
+    /// List all the entries in the cache directory. Files which are
+    /// not cache entries are ignored.
+    pub fn entries(&self) -> io::Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+        for file in fs::read_dir(&self.dir)? {
+            let path = file?.path();
+            if path.extension().map(|ext| ext == CACHE_EXTENSION) != Some(true) || !path.is_file() {
+                continue;
+            }
+            let size = fs::metadata(&path)?.len();
+            let entry = match read_entry(&path) {
+                Ok((header, contents)) => CacheEntry {
+                    path,
+                    status: if header.version != CACHE_FORMAT_VERSION {
+                        EntryStatus::OtherVersion
+                    } else if header.checksum != Sha256::digest(&contents).as_slice() {
+                        EntryStatus::Corrupt("checksum mismatch".to_string())
+                    } else if header.arch_hash != self.arch_hash {
+                        EntryStatus::OtherArchitecture
+                    } else {
+                        EntryStatus::Valid
+                    },
+                    key: Some(header.key),
+                    arch_hash: Some(header.arch_hash),
+                    version: Some(header.version),
+                    size,
+                },
+                Err(e) => CacheEntry {
+                    path,
+                    key: None,
+                    arch_hash: None,
+                    version: None,
+                    size,
+                    status: EntryStatus::Corrupt(e.to_string()),
+                },
+            };
+            entries.push(entry)
+        }
+        entries.sort_by(|e1, e2| e1.path.cmp(&e2.path));
+        Ok(entries)
+    }
 
-        if let Ok(fd) = File::create(&cache_file) {
-            if let Ok(()) = bincode::serialize_into(fd, self) {}
+    /// Remove corrupt entries, entries with another format version,
+    /// and temporary files left behind by failed writes. If
+    /// `other_architectures` is true then entries for architectures
+    /// other than this cache's are also removed. Returns the removed
+    /// entries.
+    pub fn prune(&self, other_architectures: bool) -> io::Result<Vec<CacheEntry>> {
+        let mut removed = Vec::new();
+        for entry in self.entries()? {
+            let remove = match entry.status {
+                EntryStatus::Valid => false,
+                EntryStatus::OtherArchitecture => other_architectures,
+                EntryStatus::OtherVersion | EntryStatus::Corrupt(_) => true,
+            };
+            if remove {
+                fs::remove_file(&entry.path)?;
+                removed.push(entry)
+            }
         }
+
+        for file in fs::read_dir(&self.dir)? {
+            let path = file?.path();
+            let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            if name.starts_with('.') && name.ends_with(".tmp") && name.contains(CACHE_EXTENSION) {
+                let modified = fs::metadata(&path)?.modified()?;
+                if modified.elapsed().map(|age| age > STALE_TEMPORARY).unwrap_or(false) {
+                    fs::remove_file(&path)?
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+fn read_entry(path: &Path) -> io::Result<(Header, Vec<u8>)> {
+    let mut fd = File::open(path)?;
+    let header: Header = bincode::deserialize_from(&mut fd)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid cache entry header"))?;
+    if header.magic != CACHE_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a cache entry"));
+    }
+    let mut contents = Vec::new();
+    fd.read_to_end(&mut contents)?;
+    Ok((header, contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("isla-cache-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn arch_options_hash() {
+        let hash = |registers: &[&str], linearize: &[&str]| {
+            let mut hasher = Sha256::new();
+            hasher.input("arch");
+            let registers: Vec<String> = registers.iter().map(|s| s.to_string()).collect();
+            let linearize: Vec<String> = linearize.iter().map(|s| s.to_string()).collect();
+            hash_arch_options(&mut hasher, &registers, &linearize);
+            hasher.result()
+        };
+        let mut hasher = Sha256::new();
+        hasher.input("arch");
+        assert_eq!(hash(&[], &[]), hasher.result());
+        assert_ne!(hash(&["R0=0x1"], &[]), hash(&[], &[]));
+        assert_ne!(hash(&["R0=0x1"], &[]), hash(&["R0=0x2"], &[]));
+        assert_ne!(hash(&["f"], &[]), hash(&[], &["f"]));
+        assert_ne!(hash(&[], &["f", "g"]), hash(&[], &["fg"]))
+    }
+
+    #[test]
+    fn cache_roundtrip() {
+        let dir = test_dir("roundtrip");
+        let cache = Cache::new(&dir, &[1, 2, 3]);
+        let other = Cache::new(&dir, &[4, 5, 6]);
+
+        cache.write("foo", b"contents").unwrap();
+        assert_eq!(cache.read("foo"), Some(b"contents".to_vec()));
+        assert_eq!(cache.read("bar"), None);
+        assert_eq!(other.read("foo"), None);
+
+        other.write("foo", b"other").unwrap();
+        assert_eq!(cache.read("foo"), Some(b"contents".to_vec()));
+        assert_eq!(other.read("foo"), Some(b"other".to_vec()));
+
+        let statuses: Vec<EntryStatus> = cache.entries().unwrap().drain(..).map(|entry| entry.status).collect();
+        assert_eq!(statuses.iter().filter(|status| **status == EntryStatus::Valid).count(), 1);
+        assert_eq!(statuses.len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap()
+    }
+
+    #[test]
+    fn cache_prune() {
+        let dir = test_dir("prune");
+        let cache = Cache::new(&dir, &[1, 2, 3]);
+        let other = Cache::new(&dir, &[4, 5, 6]);
+        cache.write("foo", b"contents").unwrap();
+        other.write("foo", b"other").unwrap();
+
+        // Corrupt an entry by truncating its contents
+        cache.write("bar", b"contents").unwrap();
+        let path = cache.entry_path("bar");
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.pop();
+        fs::write(&path, bytes).unwrap();
+        assert_eq!(cache.read("bar"), None);
+
+        assert_eq!(cache.prune(false).unwrap().len(), 1);
+        assert_eq!(cache.entries().unwrap().len(), 2);
+        assert_eq!(cache.prune(true).unwrap().len(), 1);
+        assert_eq!(cache.read("foo"), Some(b"contents".to_vec()));
+
+        fs::remove_dir_all(&dir).unwrap()
     }
 }
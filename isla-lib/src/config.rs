@@ -35,6 +35,7 @@ use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::File;
 use std::io::prelude::*;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use toml::Value;
@@ -334,6 +335,370 @@ fn get_barriers(config: &Value, symtab: &Symtab) -> Result<HashMap<Name, String>
     }
 }
 
+/// Instruction templates used to generate litmus tests from
+/// relaxation cycles. Each template is a sequence of assembly
+/// instructions which can refer to variables such as `$addr` and
+/// `$value`, which the generator replaces with registers.
+#[derive(Debug)]
+pub struct CycleTemplates {
+    /// General purpose registers that generated tests may use
+    pub registers: Vec<String>,
+    /// The width in bytes of the memory accesses in the templates
+    pub bytes: u32,
+    /// Templates for reads, keyed by access annotation
+    pub reads: HashMap<String, Vec<String>>,
+    /// Templates for writes, keyed by access annotation
+    pub writes: HashMap<String, Vec<String>>,
+    /// Templates for fences, keyed by the fence name used in cycles
+    pub fences: HashMap<String, Vec<String>>,
+    /// Templates for the `addr`, `data`, and `ctrl` dependencies
+    pub dependencies: HashMap<String, Vec<String>>,
+}
+
+fn get_template_table(generator: &Value, table: &str) -> Result<HashMap<String, Vec<String>>, String> {
+    match generator.get(table) {
+        Some(Value::Table(templates)) => templates
+            .iter()
+            .map(|(name, template)| {
+                let code = match template {
+                    Value::String(instr) => Some(vec![instr.to_string()]),
+                    Value::Array(instrs) => {
+                        instrs.iter().map(|instr| instr.as_str().map(str::to_string)).collect::<Option<Vec<_>>>()
+                    }
+                    _ => None,
+                };
+                code.map(|code| (name.to_string(), code)).ok_or_else(|| {
+                    format!("generator.{}.{} must be an instruction or a list of instructions", table, name)
+                })
+            })
+            .collect(),
+        Some(_) => Err(format!("[generator.{}] must be a table of templates", table)),
+        None => Ok(HashMap::new()),
+    }
+}
+
+fn get_cycle_templates(config: &Value) -> Result<CycleTemplates, String> {
+    if let Some(generator) = config.get("generator") {
+        let registers = match generator.get("registers") {
+            Some(Value::Array(registers)) => registers
+                .iter()
+                .map(|register| register.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| "generator.registers should be a list of register names".to_string())?,
+            Some(_) => return Err("generator.registers should be a list of register names".to_string()),
+            None => Vec::new(),
+        };
+        let bytes = match generator.get("bytes") {
+            Some(Value::Integer(bytes)) if [1, 2, 4, 8].contains(bytes) => *bytes as u32,
+            Some(_) => return Err("generator.bytes must be 1, 2, 4, or 8".to_string()),
+            None => 8,
+        };
+        Ok(CycleTemplates {
+            registers,
+            bytes,
+            reads: get_template_table(generator, "reads")?,
+            writes: get_template_table(generator, "writes")?,
+            fences: get_template_table(generator, "fences")?,
+            dependencies: get_template_table(generator, "dependencies")?,
+        })
+    } else {
+        Ok(CycleTemplates {
+            registers: Vec::new(),
+            bytes: 8,
+            reads: HashMap::new(),
+            writes: HashMap::new(),
+            fences: HashMap::new(),
+            dependencies: HashMap::new(),
+        })
+    }
+}
+
+impl CycleTemplates {
+    /// Read just the templates from a configuration file, which
+    /// unlike the rest of the configuration does not require the
+    /// symbol table for an architecture.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        match contents.parse::<Value>() {
+            Ok(config) => get_cycle_templates(&config),
+            Err(e) => Err(format!("Error when parsing configuration: {}", e)),
+        }
+    }
+}
+
+/// Describes the format of the page tables which litmus tests can
+/// set up when they run with address translation enabled. The tables
+/// form a radix tree with `levels` levels, each indexed by
+/// `bits_per_level` bits of the virtual address, and every descriptor
+/// is 8 bytes.
+#[derive(Debug)]
+pub struct MmuConfig<B> {
+    /// The physical address where the root table is placed, with
+    /// further tables allocated in the following pages
+    pub page_table_base: u64,
+    /// The size of pages and of each table in bytes
+    pub page_size: u64,
+    /// The number of levels in a translation table walk
+    pub levels: u32,
+    /// The number of virtual address bits translated at each level
+    pub bits_per_level: u32,
+    /// The number of bits an output address is shifted right by
+    /// when placed in a descriptor
+    pub descriptor_shift: u32,
+    /// Attribute bits for descriptors pointing to the next level
+    pub table_descriptor: u64,
+    /// Attribute bits for descriptors mapping a page
+    pub page_descriptor: u64,
+    /// The value of an invalid descriptor
+    pub invalid_descriptor: u64,
+    /// The read_kind of reads made by translation table walks, which
+    /// are translation reads (T) rather than ordinary reads (R) in
+    /// cat models
+    pub read_kind: Option<Name>,
+    /// Register values that enable translation using the page tables
+    pub registers: HashMap<Name, Val<B>>,
+}
+
+fn get_mmu_config<B: BV>(config: &Value, symtab: &Symtab) -> Result<Option<MmuConfig<B>>, String> {
+    let mmu = match config.get("mmu") {
+        Some(mmu) => mmu,
+        None => return Ok(None),
+    };
+
+    let get_integer = |key: &str, default: i64| match mmu.get(key) {
+        Some(Value::Integer(n)) if *n >= 0 && *n < 64 => Ok(*n as u32),
+        Some(_) => Err(format!("mmu.{} must be an integer between 0 and 63", key)),
+        None => Ok(default as u32),
+    };
+    let get_optional_value = |key: &str, default: u64| {
+        if mmu.get(key).is_some() {
+            get_table_value(config, "mmu", key)
+        } else {
+            Ok(default)
+        }
+    };
+
+    let registers = match mmu.get("registers") {
+        Some(Value::Table(registers)) => registers
+            .iter()
+            .map(|(register, value)| match symtab.get(&zencode::encode(register)) {
+                Some(register) => from_toml_value(value).map(|value| (register, value)),
+                None => {
+                    Err(format!("Could not find register {} when parsing mmu.registers in configuration", register))
+                }
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err("mmu.registers should be a table of <register> = <value> pairs".to_string()),
+        None => HashMap::new(),
+    };
+
+    let read_kind = match mmu.get("read_kind") {
+        Some(Value::String(rk)) => match symtab.get(&zencode::encode(rk)) {
+            Some(rk) => Some(rk),
+            None => return Err(format!("Read kind {} does not exist in supplied architecture", rk)),
+        },
+        Some(_) => return Err("mmu.read_kind must be a string".to_string()),
+        None => None,
+    };
+
+    let page_size = get_optional_value("page_size", 4096)?;
+    if !page_size.is_power_of_two() || page_size < 8 {
+        return Err("mmu.page_size must be a power of two".to_string());
+    }
+
+    let levels = get_integer("levels", 4)?;
+    if levels < 1 {
+        return Err("mmu.levels must be at least 1".to_string());
+    }
+
+    Ok(Some(MmuConfig {
+        page_table_base: get_table_value(config, "mmu", "page_table_base")?,
+        page_size,
+        levels,
+        bits_per_level: get_integer("bits_per_level", 9)?,
+        descriptor_shift: get_integer("descriptor_shift", 0)?,
+        table_descriptor: get_optional_value("table_descriptor", 0b11)?,
+        page_descriptor: get_optional_value("page_descriptor", 0b11)?,
+        invalid_descriptor: get_optional_value("invalid_descriptor", 0)?,
+        read_kind,
+        registers,
+    }))
+}
+
+/// Describes how exceptions are taken, so they can appear as events
+/// in litmus test executions, and where litmus tests can place
+/// their exception handlers.
+#[derive(Debug)]
+pub struct ExceptionConfig {
+    /// Sail functions which take an exception when called
+    pub entry: HashSet<Name>,
+    /// Sail functions which return from an exception when called
+    pub exit: HashSet<Name>,
+    /// The register holding the base address of the exception vector
+    /// table
+    pub vector_register: Name,
+    /// The number of bytes reserved for the vector table of each
+    /// thread
+    pub vector_size: u64,
+    /// The name of the memory region where the vector tables for
+    /// litmus tests with exception handlers are placed
+    pub vector_region: Option<String>,
+}
+
+fn get_exception_functions(exceptions: &Value, key: &str, symtab: &Symtab) -> Result<HashSet<Name>, String> {
+    match exceptions.get(key) {
+        Some(Value::Array(functions)) => functions
+            .iter()
+            .map(|f| match f.as_str().and_then(|f| symtab.get(&zencode::encode(f))) {
+                Some(f) => Ok(f),
+                None => Err(format!("Could not find function {} when parsing exceptions.{} in configuration", f, key)),
+            })
+            .collect(),
+        Some(_) => Err(format!("exceptions.{} should be a list of function names", key)),
+        None => Ok(HashSet::new()),
+    }
+}
+
+fn get_exception_config(config: &Value, symtab: &Symtab) -> Result<Option<ExceptionConfig>, String> {
+    let exceptions = match config.get("exceptions") {
+        Some(exceptions) => exceptions,
+        None => return Ok(None),
+    };
+
+    let vector_register = match exceptions.get("vector_register").and_then(|r| r.as_str()) {
+        Some(register) => match symtab.get(&zencode::encode(register)) {
+            Some(register) => register,
+            None => {
+                return Err(format!("Could not find register {} when parsing exceptions.vector_register", register))
+            }
+        },
+        None => return Err("exceptions.vector_register must be a register name".to_string()),
+    };
+
+    Ok(Some(ExceptionConfig {
+        entry: get_exception_functions(exceptions, "entry", symtab)?,
+        exit: get_exception_functions(exceptions, "return", symtab)?,
+        vector_register,
+        vector_size: get_table_value(config, "exceptions", "vector_size")?,
+        vector_region: match exceptions.get("vector_region") {
+            Some(Value::String(region)) => Some(region.clone()),
+            Some(_) => return Err("exceptions.vector_region must be the name of a memory region".to_string()),
+            None => None,
+        },
+    }))
+}
+
+/// The kind of memory in a region of the platform memory map
+#[derive(Debug)]
+pub enum MemoryKind {
+    /// Concrete read-only memory, with initial contents optionally
+    /// loaded from a file
+    Concrete(HashMap<u64, u8>),
+    /// Memory with arbitrary symbolic contents
+    Symbolic,
+    /// Device (memory-mapped I/O) memory, which is symbolic, and
+    /// accesses to which are in the `DEV` set in cat models
+    Device,
+}
+
+/// A named region in the platform memory map
+#[derive(Debug)]
+pub struct MemoryRegion {
+    pub name: String,
+    pub range: Range<u64>,
+    pub kind: MemoryKind,
+    /// Whether each granule of the region carries a tag (see `TagConfig`)
+    pub tagged: bool,
+}
+
+fn load_memory_contents(base: u64, path: &Path) -> Result<HashMap<u64, u8>, String> {
+    let mut contents = Vec::new();
+    File::open(path)
+        .and_then(|mut handle| handle.read_to_end(&mut contents))
+        .map_err(|e| format!("Error when loading memory contents from '{}': {}", path.display(), e))?;
+    Ok(contents.iter().enumerate().map(|(i, byte)| (base + i as u64, *byte)).collect())
+}
+
+/// Read the `[memory.<name>]` tables describing the platform memory
+/// map. Files with the initial contents of concrete regions are
+/// relative to `dir`.
+fn get_memory_regions(config: &Value, dir: &Path) -> Result<Vec<MemoryRegion>, String> {
+    let memory = match config.get("memory") {
+        Some(memory) => memory,
+        None => return Ok(Vec::new()),
+    };
+    let table = memory.as_table().ok_or_else(|| "[memory] must be a table of memory regions".to_string())?;
+
+    let mut regions: Vec<MemoryRegion> = Vec::new();
+    for (name, region) in table.iter() {
+        let range = get_table_value(memory, name, "base")?..get_table_value(memory, name, "top")?;
+        if range.start >= range.end {
+            return Err(format!("Memory region {} must have a base below its top", name));
+        }
+
+        let kind = match region.get("kind").and_then(Value::as_str) {
+            Some("concrete") => match region.get("file") {
+                Some(Value::String(file)) => {
+                    let contents = load_memory_contents(range.start, &dir.join(file))?;
+                    if contents.keys().any(|addr| !range.contains(addr)) {
+                        return Err(format!("Contents of memory region {} do not fit in the region", name));
+                    }
+                    MemoryKind::Concrete(contents)
+                }
+                Some(_) => return Err(format!("memory.{}.file must be a path", name)),
+                None => MemoryKind::Concrete(HashMap::new()),
+            },
+            Some("symbolic") => MemoryKind::Symbolic,
+            Some("device") => MemoryKind::Device,
+            _ => return Err(format!("memory.{}.kind must be one of concrete, symbolic, or device", name)),
+        };
+
+        if let Some(other) = regions.iter().find(|other| other.range.start < range.end && range.start < other.range.end)
+        {
+            return Err(format!("Memory regions {} and {} overlap", other.name, name));
+        }
+
+        let tagged = match region.get("tagged") {
+            Some(Value::Boolean(b)) => *b,
+            Some(_) => return Err(format!("memory.{}.tagged must be a boolean", name)),
+            None => false,
+        };
+
+        regions.push(MemoryRegion { name: name.to_string(), range, kind, tagged })
+    }
+
+    Ok(regions)
+}
+
+/// The format of memory tags, e.g. CHERI capability tags (one bit
+/// per 16 bytes) or Arm MTE allocation tags (four bits per 16 bytes)
+#[derive(Debug)]
+pub struct TagConfig {
+    /// The size of each tagged granule in bytes
+    pub granule: u64,
+    /// The width of each tag, where `None` means tags are booleans
+    pub width: Option<u32>,
+}
+
+fn get_tag_config(config: &Value) -> Result<Option<TagConfig>, String> {
+    let tags = match config.get("tags") {
+        Some(tags) => tags,
+        None => return Ok(None),
+    };
+
+    let granule = get_table_value(config, "tags", "granule")?;
+    if !granule.is_power_of_two() {
+        return Err("tags.granule must be a power of two".to_string());
+    }
+
+    let width = match tags.get("width") {
+        Some(Value::Integer(width)) if *width > 0 && *width <= 64 => Some(*width as u32),
+        Some(_) => return Err("tags.width must be an integer between 1 and 64".to_string()),
+        None => None,
+    };
+
+    Ok(Some(TagConfig { granule, width }))
+}
+
 #[derive(Debug)]
 pub struct ISAConfig<B> {
     /// The identifier for the program counter register
@@ -373,16 +738,37 @@ pub struct ISAConfig<B> {
     pub ignored_registers: HashSet<Name>,
     /// Trace any function calls in this set
     pub probes: HashSet<Name>,
+    /// Instruction templates for generating litmus tests
+    pub templates: CycleTemplates,
+    /// Page table format for litmus tests using address translation
+    pub mmu: Option<MmuConfig<B>>,
+    /// Functions which take or return from exceptions, and the
+    /// location of exception vectors
+    pub exceptions: Option<ExceptionConfig>,
+    /// The platform memory map
+    pub memory: Vec<MemoryRegion>,
+    /// The format of tags for tagged memory regions
+    pub tags: Option<TagConfig>,
 }
 
 impl<B: BV> ISAConfig<B> {
+    /// Parse a configuration that was not read from a file, so any
+    /// files it refers to are relative to the current directory. Use
+    /// `parse_in_dir` or `from_file` for a configuration file.
     pub fn parse(contents: &str, symtab: &Symtab) -> Result<Self, String> {
+        Self::parse_in_dir(contents, Path::new("."), symtab)
+    }
+
+    /// Parse a configuration, where any files it refers to are
+    /// relative to `dir`, which should be the directory containing
+    /// the configuration file.
+    pub fn parse_in_dir(contents: &str, dir: &Path, symtab: &Symtab) -> Result<Self, String> {
         let config = match contents.parse::<Value>() {
             Ok(config) => config,
             Err(e) => return Err(format!("Error when parsing configuration: {}", e)),
         };
 
-        Ok(ISAConfig {
+        let isa_config = ISAConfig {
             pc: get_program_counter(&config, symtab)?,
             ifetch_read_kind: get_ifetch_read_kind(&config, symtab)?,
             read_exclusives: get_exclusives(&config, "read_exclusives", symtab)?,
@@ -401,7 +787,31 @@ impl<B: BV> ISAConfig<B> {
             register_renames: get_register_renames(&config, symtab)?,
             ignored_registers: get_ignored_registers(&config, symtab)?,
             probes: HashSet::new(),
-        })
+            templates: get_cycle_templates(&config)?,
+            mmu: get_mmu_config(&config, symtab)?,
+            exceptions: get_exception_config(&config, symtab)?,
+            memory: get_memory_regions(&config, dir)?,
+            tags: get_tag_config(&config)?,
+        };
+
+        if isa_config.tags.is_none() {
+            if let Some(region) = isa_config.memory.iter().find(|region| region.tagged) {
+                return Err(format!("Memory region {} is tagged, but there is no [tags] configuration", region.name));
+            }
+        }
+
+        if let Some(region) = isa_config.exceptions.as_ref().and_then(|exceptions| exceptions.vector_region.as_ref()) {
+            if isa_config.memory_region(region).is_none() {
+                return Err(format!("exceptions.vector_region {} is not a region in [memory]", region));
+            }
+        }
+
+        Ok(isa_config)
+    }
+
+    /// Find a region in the platform memory map by name
+    pub fn memory_region(&self, name: &str) -> Option<&MemoryRegion> {
+        self.memory.iter().find(|region| region.name == name)
     }
 
     /// Use a default configuration when none is specified
@@ -423,6 +833,136 @@ impl<B: BV> ISAConfig<B> {
             Err(e) => return Err(format!("Error when loading config '{}': {}", path.as_ref().display(), e)),
         };
         hasher.input(&contents);
-        Self::parse(&contents, symtab)
+        let dir = path.as_ref().parent().unwrap_or_else(|| Path::new("."));
+        Self::parse_in_dir(&contents, dir, symtab)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::concrete::bitvector64::B64;
+
+    #[test]
+    fn memory_regions() {
+        let config = r#"
+            [memory.rom]
+            kind = "concrete"
+            base = "0x0"
+            top = "0x1000"
+
+            [memory.uart]
+            kind = "device"
+            base = "0x9000000"
+            top = "0x9001000"
+        "#
+        .parse::<Value>()
+        .unwrap();
+        let regions = get_memory_regions(&config, Path::new(".")).unwrap();
+        assert_eq!(regions.len(), 2);
+        let uart = regions.iter().find(|region| region.name == "uart").unwrap();
+        assert_eq!(uart.range, 0x9000000..0x9001000);
+        assert!(matches!(uart.kind, MemoryKind::Device));
+
+        let config = r#"
+            [memory.ram]
+            kind = "symbolic"
+            base = "0x0"
+            top = "0x2000"
+
+            [memory.rom]
+            kind = "concrete"
+            base = "0x1000"
+            top = "0x3000"
+        "#
+        .parse::<Value>()
+        .unwrap();
+        assert!(get_memory_regions(&config, Path::new(".")).is_err())
+    }
+
+    #[test]
+    fn memory_region_file() {
+        let dir = std::env::temp_dir().join(format!("isla-memory-region-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("rom.bin"), [0xAA, 0xBB]).unwrap();
+        let config = r#"
+            [memory.rom]
+            kind = "concrete"
+            base = "0x1000"
+            top = "0x2000"
+            file = "rom.bin"
+        "#
+        .parse::<Value>()
+        .unwrap();
+
+        // The file is found relative to the configuration's directory,
+        // not the current directory
+        let regions = get_memory_regions(&config, &dir);
+        let in_cwd = get_memory_regions(&config, Path::new("."));
+        std::fs::remove_dir_all(&dir).unwrap();
+        match &regions.unwrap()[0].kind {
+            MemoryKind::Concrete(contents) => {
+                assert_eq!(contents.len(), 2);
+                assert_eq!(contents[&0x1001], 0xBB)
+            }
+            _ => panic!("Expected a concrete memory region"),
+        }
+        assert!(in_cwd.is_err())
+    }
+
+    #[test]
+    fn tag_config() {
+        let config = r#"
+            [tags]
+            granule = "16"
+            width = 4
+
+            [memory.heap]
+            kind = "symbolic"
+            base = "0x10000"
+            top = "0x20000"
+            tagged = true
+        "#
+        .parse::<Value>()
+        .unwrap();
+        let tags = get_tag_config(&config).unwrap().unwrap();
+        assert_eq!(tags.granule, 16);
+        assert_eq!(tags.width, Some(4));
+        assert!(get_memory_regions(&config, Path::new(".")).unwrap()[0].tagged);
+
+        let config = r#"
+            [tags]
+            granule = "24"
+        "#
+        .parse::<Value>()
+        .unwrap();
+        assert!(get_tag_config(&config).is_err())
+    }
+
+    #[test]
+    fn mmu_config() {
+        let mut symtab = Symtab::new();
+        symtab.intern("zRead_ttw");
+        let config = r#"
+            [mmu]
+            page_table_base = "0x300000"
+            levels = 3
+            read_kind = "Read_ttw"
+        "#
+        .parse::<Value>()
+        .unwrap();
+        let mmu = get_mmu_config::<B64>(&config, &symtab).unwrap().unwrap();
+        assert_eq!(mmu.levels, 3);
+        assert_eq!(mmu.page_size, 4096);
+        assert_eq!(mmu.read_kind, symtab.get("zRead_ttw"));
+
+        let config = r#"
+            [mmu]
+            page_table_base = "0x300000"
+            levels = 0
+        "#
+        .parse::<Value>()
+        .unwrap();
+        assert!(get_mmu_config::<B64>(&config, &symtab).is_err())
     }
 }
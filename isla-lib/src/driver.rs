@@ -0,0 +1,303 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module runs whole programs rather than single
+//! instructions. Starting from an initial frame, it repeatedly reads
+//! the program counter register (`ISAConfig::pc`), fetches an opcode
+//! from memory at that address, and runs an instruction step function
+//! (such as `isla_footprint`) on it. Every path through the program
+//! is followed until it hits one of its stopping conditions.
+
+use crossbeam::queue::SegQueue;
+use std::collections::HashSet;
+
+use crate::concrete::BV;
+use crate::config::ISAConfig;
+use crate::error::ExecError;
+use crate::executor;
+use crate::executor::{Backtrace, LocalFrame};
+use crate::ir::*;
+use crate::memory::Address;
+use crate::smt;
+use crate::smt::{Checkpoint, Config, Context, Event, Solver};
+
+/// The conditions under which a path through a program stops
+#[derive(Default)]
+pub struct StopConditions {
+    /// Stop before fetching from any of these addresses
+    pub addresses: HashSet<Address>,
+    /// Stop after executing this many instructions
+    pub limit: Option<usize>,
+    /// Stop when any of these Sail functions is called
+    pub functions: HashSet<Name>,
+}
+
+#[derive(Clone, Debug)]
+pub enum StopReason {
+    Address(Address),
+    Limit,
+    /// The program counter became symbolic, so the next instruction
+    /// cannot be fetched
+    SymbolicPC,
+    /// The opcode fetched from this address was symbolic
+    SymbolicOpcode(Address),
+    /// Either a stop function was called, or a stop address in memory
+    /// was read (see `Memory::add_stop_address`).
+    Stopped(String),
+}
+
+/// A complete path through a program, with the number of instructions
+/// it executed and its trace. As with the collectors in `executor`, the
+/// events are in reverse order.
+pub struct ProgramPath<B> {
+    pub reason: StopReason,
+    pub instructions: usize,
+    pub events: Vec<Event<B>>,
+}
+
+type StepQueue<'ir, B> = SegQueue<Result<(LocalFrame<'ir, B>, Checkpoint<B>), Result<StopPath<B>, String>>>;
+
+type StopPath<B> = (String, Vec<Event<B>>);
+
+fn step_collector<'ir, B: BV>(
+    _: usize,
+    _: usize,
+    result: Result<(Val<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+    _: &SharedState<'ir, B>,
+    mut solver: Solver<B>,
+    collected: &StepQueue<'ir, B>,
+) {
+    match result {
+        Ok((_, frame)) => collected.push(Ok((frame, smt::checkpoint(&mut solver)))),
+        Err((ExecError::Dead, _)) => (),
+        Err((ExecError::Stopped(name), _)) => {
+            let mut events = solver.trace().to_vec();
+            collected.push(Err(Ok((name, events.drain(..).cloned().collect()))))
+        }
+        Err((err, _)) => collected.push(Err(Err(format!("Error {:?}", err)))),
+    }
+}
+
+fn finish<B: BV>(reason: StopReason, instructions: usize, checkpoint: Checkpoint<B>) -> ProgramPath<B> {
+    let ctx = Context::new(Config::new());
+    let solver = Solver::from_checkpoint(&ctx, checkpoint);
+    let mut events = solver.trace().to_vec();
+    ProgramPath { reason, instructions, events: events.drain(..).cloned().collect() }
+}
+
+/// Read the program counter from a frame, returning `None` if it is
+/// symbolic.
+fn program_counter<B: BV>(frame: &LocalFrame<B>, isa_config: &ISAConfig<B>) -> Result<Option<Address>, String> {
+    match frame.regs().get(&isa_config.pc) {
+        Some(UVal::Init(Val::Bits(bv))) => Ok(Some(bv.lower_u64())),
+        Some(UVal::Init(Val::Symbolic(_))) => Ok(None),
+        _ => Err("Program counter is not initialized with a bitvector".to_string()),
+    }
+}
+
+/// Run a program starting from `frame`, which should contain the
+/// initial registers, let bindings, and memory. The step function is
+/// called once for each instruction, and must take the opcode as its
+/// only argument. Before each call a `Cycle` and an `Instr` event are
+/// added to the trace, marking where each instruction begins.
+pub fn run_program<'ir, B: BV>(
+    step_function: Name,
+    frame: LocalFrame<'ir, B>,
+    stop: &StopConditions,
+    shared_state: &SharedState<'ir, B>,
+    isa_config: &ISAConfig<B>,
+) -> Result<Vec<ProgramPath<B>>, String> {
+    let (args, _, instrs) = shared_state.functions.get(&step_function).ok_or("Step function not found")?;
+    let opcode_bytes = match args.as_slice() {
+        [(_, Ty::Bits(sz))] if sz % 8 == 0 => *sz / 8,
+        _ => return Err("The step function must take a single bitvector opcode argument".to_string()),
+    };
+    let (ifetch_member, ifetch_size) =
+        *shared_state.enum_members.get(&isa_config.ifetch_read_kind).ok_or("Instruction fetch read kind not found")?;
+
+    let mut paths = Vec::new();
+    let mut pending = vec![(frame, Checkpoint::new(), 0)];
+
+    while let Some((mut frame, checkpoint, instructions)) = pending.pop() {
+        let address = match program_counter(&frame, isa_config)? {
+            Some(address) => address,
+            None => {
+                paths.push(finish(StopReason::SymbolicPC, instructions, checkpoint));
+                continue;
+            }
+        };
+        if stop.addresses.contains(&address) {
+            paths.push(finish(StopReason::Address(address), instructions, checkpoint));
+            continue;
+        }
+        if matches!(stop.limit, Some(limit) if instructions >= limit) {
+            paths.push(finish(StopReason::Limit, instructions, checkpoint));
+            continue;
+        }
+
+        let ctx = Context::new(Config::new());
+        let mut solver = Solver::from_checkpoint(&ctx, checkpoint);
+        solver.add_event(Event::Cycle);
+        let read_kind = Val::Enum(EnumMember { enum_id: solver.get_enum(ifetch_size), member: ifetch_member });
+        let opcode = match frame.memory_mut().read(
+            read_kind,
+            Val::Bits(B::from_u64(address)),
+            Val::I128(opcode_bytes as i128),
+            &mut solver,
+        ) {
+            Ok(Val::Bits(opcode)) => opcode,
+            Ok(_) => {
+                paths.push(finish(StopReason::SymbolicOpcode(address), instructions, smt::checkpoint(&mut solver)));
+                continue;
+            }
+            Err(ExecError::Stopped(name)) => {
+                paths.push(finish(StopReason::Stopped(name), instructions, smt::checkpoint(&mut solver)));
+                continue;
+            }
+            Err(err) => return Err(format!("Failed to fetch instruction from 0x{:x}: {:?}", address, err)),
+        };
+        solver.add_event(Event::Instr(Val::Bits(opcode)));
+
+        let mut task = frame
+            .new_call(step_function, args, Some(&[Val::Bits(opcode)]), instrs)
+            .task_with_checkpoint(0, smt::checkpoint(&mut solver));
+        task.set_stop_functions(&stop.functions);
+
+        let queue = SegQueue::new();
+        executor::start_single(task, shared_state, &queue, &step_collector);
+        while let Ok(result) = queue.pop() {
+            match result {
+                Ok((frame, checkpoint)) => pending.push((frame, checkpoint, instructions + 1)),
+                Err(Ok((name, events))) => paths.push(ProgramPath {
+                    reason: StopReason::Stopped(name),
+                    instructions: instructions + 1,
+                    events,
+                }),
+                Err(Err(msg)) => return Err(msg),
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::concrete::bitvector64::B64;
+    use crate::ir_parser::IrParser;
+    use crate::lexer::Lexer;
+    use std::collections::HashMap;
+
+    // Each instruction adds 4 to the PC, except 0xffffffff which sets
+    // it to an undefined value
+    const STEP: &str = "
+        enum zread_kind { zRead_plain, zRead_ifetch }
+        register zPC : %bv64
+        val zstep : (%bv32) -> %unit
+        fn zstep(zopcode) {
+            jump @eq(zopcode, 0xffffffff) goto 3 ` \"step\";
+            zPC = @bvadd(zPC, 0x0000000000000004);
+            goto 4;
+            zPC = undefined : %bv64;
+            return = ();
+            end
+        }";
+
+    const CONFIG: &str = r#"
+        pc = "PC"
+        ifetch = "Read_ifetch"
+        read_exclusives = []
+        write_exclusives = []
+        assembler = "sh"
+        objdump = "sh"
+        linker = "sh"
+
+        [threads]
+        base = "0x400000"
+        top = "0x500000"
+        stride = "0x10000"
+
+        [symbolic_addrs]
+        base = "0x600000"
+        stride = "0x10"
+
+        [reads]
+
+        [writes]
+
+        [cache_ops]
+    "#;
+
+    #[test]
+    fn test_run_program() {
+        let defs: Vec<Def<String, B64>> = IrParser::new().parse(Lexer::new(STEP)).ok().unwrap();
+        let mut symtab = Symtab::new();
+        let defs = symtab.intern_defs(&defs);
+        let step = symtab.lookup("zstep");
+        let pc = symtab.lookup("zPC");
+        let isa_config = ISAConfig::<B64>::parse(CONFIG, &symtab).unwrap();
+        let shared_state = SharedState::new(symtab, &defs, HashSet::new(), HashMap::new());
+
+        // Two instructions at 0x1000 followed by symbolic code, and
+        // an instruction at 0x2000 which makes the PC symbolic
+        let mut code: HashMap<u64, u8> = (0x1000..0x1008).map(|address| (address, 0)).collect();
+        code.extend((0x2000..0x2004).map(|address| (address, 0xff)));
+        let (args, _, instrs) = shared_state.functions.get(&step).unwrap();
+        let frame = |start: u64| {
+            let mut frame = LocalFrame::new(step, args, None, instrs);
+            frame.regs_mut().insert(pc, UVal::Init(Val::Bits(B64::from_u64(start))));
+            frame.memory_mut().add_concrete_region(0x1000..0x1008, code.clone());
+            frame.memory_mut().add_symbolic_code_region(0x1008..0x2000);
+            frame.memory_mut().add_concrete_region(0x2000..0x2004, code.clone());
+            frame
+        };
+        let run = |start, stop| run_program(step, frame(start), &stop, &shared_state, &isa_config).unwrap();
+
+        let stop = StopConditions { addresses: [0x1004].iter().copied().collect(), ..StopConditions::default() };
+        let paths = run(0x1000, stop);
+        assert_eq!(paths.len(), 1);
+        assert!(matches!(paths[0].reason, StopReason::Address(0x1004)));
+        assert_eq!(paths[0].instructions, 1);
+        assert_eq!(paths[0].events.iter().filter(|ev| matches!(ev, Event::Instr(_))).count(), 1);
+
+        let paths = run(0x1000, StopConditions { limit: Some(1), ..StopConditions::default() });
+        assert!(matches!(paths[0].reason, StopReason::Limit));
+
+        // A symbolic program counter or opcode ends the path
+        let paths = run(0x1000, StopConditions::default());
+        assert_eq!(paths.len(), 1);
+        assert!(matches!(paths[0].reason, StopReason::SymbolicOpcode(0x1008)));
+        assert_eq!(paths[0].instructions, 2);
+        let paths = run(0x2000, StopConditions::default());
+        assert_eq!(paths.len(), 1);
+        assert!(matches!(paths[0].reason, StopReason::SymbolicPC));
+        assert_eq!(paths[0].instructions, 1)
+    }
+}
@@ -0,0 +1,203 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module loads ELF files into memory, so that small bare-metal
+//! binaries can be executed rather than just individual
+//! instructions. Each `PT_LOAD` segment becomes a concrete memory
+//! region at its virtual address, except for any part of the segment
+//! not backed by the file (i.e. `.bss`), which is symbolic. Virtual
+//! addresses are used so segments are in the same address space as
+//! the entry point and symbols.
+
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::sym::{STT_FILE, STT_SECTION};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::concrete::BV;
+use crate::ir::{Bindings, UVal, Val, ELF_ENTRY};
+use crate::memory::{Address, Memory};
+
+/// A loadable segment of an ELF file
+#[derive(Debug)]
+struct Segment {
+    address: Address,
+    contents: Vec<u8>,
+    size: u64,
+}
+
+#[derive(Debug)]
+pub struct Elf {
+    entry: Address,
+    segments: Vec<Segment>,
+    symbols: HashMap<String, Address>,
+}
+
+impl Elf {
+    pub fn parse(buffer: &[u8]) -> Result<Self, String> {
+        let elf = goblin::elf::Elf::parse(buffer).map_err(|e| format!("Failed to parse ELF file: {}", e))?;
+
+        let mut segments = Vec::new();
+        for header in elf.program_headers.iter().filter(|header| header.p_type == PT_LOAD) {
+            let offset = header.p_offset as usize;
+            let file_end = offset
+                .checked_add(header.p_filesz as usize)
+                .ok_or_else(|| format!("ELF segment at 0x{:x} has an invalid file size", header.p_vaddr))?;
+            let contents = buffer
+                .get(offset..file_end)
+                .ok_or_else(|| format!("ELF segment at 0x{:x} extends past the end of the file", header.p_vaddr))?;
+            if header.p_memsz < header.p_filesz {
+                return Err(format!("ELF segment at 0x{:x} is smaller in memory than in the file", header.p_vaddr));
+            }
+            segments.push(Segment { address: header.p_vaddr, contents: contents.to_vec(), size: header.p_memsz })
+        }
+
+        let mut symbols = HashMap::new();
+        for sym in elf.syms.iter().filter(|sym| sym.st_type() != STT_SECTION && sym.st_type() != STT_FILE) {
+            match elf.strtab.get(sym.st_name) {
+                Some(Ok("")) | None => (),
+                Some(Ok(name)) => {
+                    symbols.insert(name.to_string(), sym.st_value);
+                }
+                Some(Err(e)) => return Err(format!("Failed to read ELF symbol name: {}", e)),
+            }
+        }
+
+        Ok(Elf { entry: elf.entry, segments, symbols })
+    }
+
+    pub fn from_file<P>(path: P) -> Result<Self, String>
+    where
+        P: AsRef<Path>,
+    {
+        let mut buffer = Vec::new();
+        File::open(&path)
+            .and_then(|mut handle| handle.read_to_end(&mut buffer))
+            .map_err(|e| format!("Error when loading ELF file '{}': {}", path.as_ref().display(), e))?;
+        Self::parse(&buffer)
+    }
+
+    /// The entry point of the ELF file
+    pub fn entry(&self) -> Address {
+        self.entry
+    }
+
+    /// Look up the address of a symbol by name
+    pub fn symbol(&self, name: &str) -> Option<Address> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Find the name of a symbol at an address, if there is one
+    pub fn symbol_at(&self, address: Address) -> Option<&str> {
+        self.symbols.iter().find(|(_, addr)| **addr == address).map(|(name, _)| name.as_str())
+    }
+
+    /// The address ranges occupied by each segment when loaded
+    pub fn ranges(&self) -> Vec<Range<Address>> {
+        self.segments.iter().map(|segment| segment.address..(segment.address + segment.size)).collect()
+    }
+
+    /// Add a region to `memory` for each segment
+    pub fn load<B: BV>(&self, memory: &mut Memory<B>) {
+        for segment in &self.segments {
+            let file_end = segment.address + segment.contents.len() as u64;
+            if !segment.contents.is_empty() {
+                let contents =
+                    segment.contents.iter().enumerate().map(|(i, byte)| (segment.address + i as u64, *byte)).collect();
+                memory.add_concrete_region(segment.address..file_end, contents)
+            }
+            if segment.size > segment.contents.len() as u64 {
+                memory.add_symbolic_region(file_end..(segment.address + segment.size))
+            }
+        }
+    }
+
+    /// Set the `elf_entry` value returned by the primop of the same
+    /// name, which the Sail model uses to set the initial program
+    /// counter.
+    pub fn set_entry<B: BV>(&self, lets: &mut Bindings<B>) {
+        lets.insert(ELF_ENTRY, UVal::Init(Val::I128(self.entry as i128)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal 64-bit little-endian ELF file with a single segment
+    /// containing one instruction followed by 12 bytes of `.bss`. The
+    /// segment's physical address differs from its virtual address.
+    fn minimal_elf() -> Vec<u8> {
+        let mut elf = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        elf.extend_from_slice(&2u16.to_le_bytes()); // e_type
+        elf.extend_from_slice(&0xb7u16.to_le_bytes()); // e_machine
+        elf.extend_from_slice(&1u32.to_le_bytes()); // e_version
+        elf.extend_from_slice(&0x400000u64.to_le_bytes()); // e_entry
+        elf.extend_from_slice(&64u64.to_le_bytes()); // e_phoff
+        elf.extend_from_slice(&0u64.to_le_bytes()); // e_shoff
+        elf.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+        elf.extend_from_slice(&64u16.to_le_bytes()); // e_ehsize
+        elf.extend_from_slice(&56u16.to_le_bytes()); // e_phentsize
+        elf.extend_from_slice(&1u16.to_le_bytes()); // e_phnum
+        elf.extend_from_slice(&64u16.to_le_bytes()); // e_shentsize
+        elf.extend_from_slice(&0u16.to_le_bytes()); // e_shnum
+        elf.extend_from_slice(&0u16.to_le_bytes()); // e_shstrndx
+        elf.extend_from_slice(&PT_LOAD.to_le_bytes()); // p_type
+        elf.extend_from_slice(&5u32.to_le_bytes()); // p_flags
+        elf.extend_from_slice(&120u64.to_le_bytes()); // p_offset
+        elf.extend_from_slice(&0x400000u64.to_le_bytes()); // p_vaddr
+        elf.extend_from_slice(&0x80000000u64.to_le_bytes()); // p_paddr
+        elf.extend_from_slice(&4u64.to_le_bytes()); // p_filesz
+        elf.extend_from_slice(&16u64.to_le_bytes()); // p_memsz
+        elf.extend_from_slice(&0x1000u64.to_le_bytes()); // p_align
+        elf.extend_from_slice(&[0x1f, 0x20, 0x03, 0xd5]); // NOP
+        elf
+    }
+
+    #[test]
+    fn load_segments() {
+        let elf = Elf::parse(&minimal_elf()).unwrap();
+        assert_eq!(elf.entry(), 0x400000);
+        assert_eq!(elf.ranges(), vec![0x400000..0x400010]);
+        assert_eq!(elf.segments[0].contents, vec![0x1f, 0x20, 0x03, 0xd5]);
+        assert_eq!(elf.symbol("_start"), None);
+
+        let mut truncated = minimal_elf();
+        truncated.truncate(122);
+        assert!(Elf::parse(&truncated).is_err());
+
+        // A file size which overflows when added to the offset
+        let mut overflow = minimal_elf();
+        overflow[96..104].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(Elf::parse(&overflow).unwrap_err().contains("invalid file size"))
+    }
+}
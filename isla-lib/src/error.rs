@@ -60,7 +60,8 @@ pub enum ExecError {
     NoModel,
     Z3Error(String),
     Z3Unknown,
-    /// Execution stopped because this function is in the stop_functions set
+    /// Execution stopped because this function is in the stop_functions set,
+    /// or because a stop address in memory was read
     Stopped(String),
 }
 
@@ -400,7 +400,7 @@ type Stack<'ir, B> = Option<
     >,
 >;
 
-type Backtrace = Vec<(Name, usize)>;
+pub type Backtrace = Vec<(Name, usize)>;
 
 /// A `Frame` is an immutable snapshot of the program state while it
 /// is being symbolically executed.
@@ -841,6 +841,10 @@ fn run_loop<'ir, 'task, B: BV>(
                             probe::args_info(tid, &args, shared_state, solver)
                         }
 
+                        if let Some(kind) = shared_state.exception_functions.get(f) {
+                            solver.add_event(Event::Exception(*kind))
+                        }
+
                         let caller_pc = frame.pc;
                         let caller_instrs = frame.instrs;
                         let caller_stack_call = frame.stack_call.clone();
@@ -1318,3 +1322,38 @@ pub fn footprint_collector<'ir, B: BV>(
         Err((err, _)) => collected.push(Err(format!("Error {:?}", err))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::concrete::bitvector64::B64;
+    use crate::ir_parser::IrParser;
+    use crate::lexer::Lexer;
+
+    #[test]
+    fn test_exception_event() {
+        let ir = "val take : (%unit) -> %unit
+                  fn take(x) { return = x; end }
+                  val main : (%unit) -> %unit
+                  fn main(x) { y : %unit = x; y = take(x); return = y; end }";
+        let defs: Vec<Def<String, B64>> = IrParser::new().parse(Lexer::new(ir)).ok().unwrap();
+        let mut symtab = Symtab::new();
+        let defs = symtab.intern_defs(&defs);
+        let take = symtab.lookup("take");
+        let main = symtab.lookup("main");
+
+        let mut exception_functions = HashMap::new();
+        exception_functions.insert(take, ExceptionKind::Taken);
+        let shared_state = SharedState::new(symtab, &defs, HashSet::new(), exception_functions);
+
+        let (args, _, instrs) = shared_state.functions.get(&main).unwrap();
+        let task = LocalFrame::new(main, args, Some(&[Val::Unit]), instrs).task(0);
+        let queue = SegQueue::new();
+        start_single(task, &shared_state, &queue, &trace_collector);
+
+        let (_, events) = queue.pop().unwrap().unwrap();
+        assert!(queue.pop().is_err());
+        let exceptions: Vec<_> = events.iter().filter(|ev| ev.is_exception()).collect();
+        assert!(matches!(exceptions.as_slice(), [Event::Exception(ExceptionKind::Taken)]))
+    }
+}
@@ -59,6 +59,7 @@ use crate::config::ISAConfig;
 use crate::executor::{start_single, LocalFrame};
 use crate::ir::*;
 use crate::log;
+use crate::smt::ExceptionKind;
 use crate::zencode;
 
 fn initialize_letbindings<'ir, B: BV>(
@@ -137,7 +138,16 @@ pub fn initialize_architecture<'ir, B: BV>(
 
     let regs = initialize_register_state(arch, &isa_config.default_registers, &symtab);
     let lets = Mutex::new(HashMap::new());
-    let shared_state = SharedState::new(symtab, arch, isa_config.probes.clone());
+    let exception_functions = match &isa_config.exceptions {
+        Some(exceptions) => exceptions
+            .entry
+            .iter()
+            .map(|f| (*f, ExceptionKind::Taken))
+            .chain(exceptions.exit.iter().map(|f| (*f, ExceptionKind::Return)))
+            .collect(),
+        None => HashMap::new(),
+    };
+    let shared_state = SharedState::new(symtab, arch, isa_config.probes.clone(), exception_functions);
 
     initialize_letbindings(arch, &shared_state, &regs, &lets);
 
@@ -48,7 +48,7 @@ use std::hash::Hash;
 
 use crate::concrete::{bitvector64::B64, BV};
 use crate::primop::{Binary, Primops, Unary, Variadic};
-use crate::smt::Sym;
+use crate::smt::{ExceptionKind, Sym};
 use crate::zencode;
 
 pub mod linearize;
@@ -138,7 +138,7 @@ pub enum Op {
     Tail,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct EnumMember {
     pub enum_id: usize,
     pub member: usize,
@@ -728,10 +728,18 @@ pub struct SharedState<'ir, B> {
     pub union_ctors: HashSet<Name>,
     /// `probes` is a set of function/location identifers to trace
     pub probes: HashSet<Name>,
+    /// `exception_functions` maps functions which take or return
+    /// from exceptions to the kind of event they generate
+    pub exception_functions: HashMap<Name, ExceptionKind>,
 }
 
 impl<'ir, B: BV> SharedState<'ir, B> {
-    pub fn new(symtab: Symtab<'ir>, defs: &'ir [Def<Name, B>], probes: HashSet<Name>) -> Self {
+    pub fn new(
+        symtab: Symtab<'ir>,
+        defs: &'ir [Def<Name, B>],
+        probes: HashSet<Name>,
+        exception_functions: HashMap<Name, ExceptionKind>,
+    ) -> Self {
         let mut vals = HashMap::new();
         let mut functions: HashMap<Name, Fn<'ir, B>> = HashMap::new();
         let mut structs: HashMap<Name, HashMap<Name, Ty<Name>>> = HashMap::new();
@@ -777,7 +785,7 @@ impl<'ir, B: BV> SharedState<'ir, B> {
             }
         }
 
-        SharedState { functions, symtab, structs, enums, enum_members, union_ctors, probes }
+        SharedState { functions, symtab, structs, enums, enum_members, union_ctors, probes, exception_functions }
     }
 
     pub fn enum_member_from_str(&self, member: &str) -> Option<usize> {
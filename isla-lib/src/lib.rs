@@ -43,6 +43,8 @@ lalrpop_mod!(#[allow(clippy::all)] pub value_parser);
 pub mod cache;
 pub mod concrete;
 pub mod config;
+pub mod driver;
+pub mod elf_loader;
 pub mod error;
 pub mod executor;
 pub mod init;
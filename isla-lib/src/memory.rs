@@ -47,11 +47,33 @@ use crate::error::ExecError;
 use crate::ir::Val;
 use crate::log;
 use crate::smt::smtlib::{Def, Exp};
-use crate::smt::{Event, Solver, Sym};
+use crate::smt::{Event, Model, Solver, Sym};
 
 /// For now, we assume that we only deal with 64-bit architectures.
 pub type Address = u64;
 
+/// A memory-mapped device, such as a UART or an interrupt
+/// controller. Reads and writes to a device region call into the
+/// device rather than being left unconstrained, with addresses given
+/// as offsets from the start of the region. Devices are cloned along
+/// with the memory whenever execution forks, so each path has its
+/// own device state. Devices can use `Event::Device` to record
+/// anything interesting in the trace, as accesses to a device do not
+/// produce `ReadMem` or `WriteMem` events.
+pub trait Device<B>: Send + Sync {
+    fn read(&mut self, offset: Address, bytes: u32, solver: &mut Solver<B>) -> Result<Val<B>, ExecError>;
+
+    fn write(&mut self, offset: Address, data: Val<B>, solver: &mut Solver<B>) -> Result<(), ExecError>;
+
+    fn clone_device(&self) -> Box<dyn Device<B>>;
+}
+
+impl<B> Clone for Box<dyn Device<B>> {
+    fn clone(&self) -> Self {
+        self.clone_device()
+    }
+}
+
 #[derive(Clone)]
 pub enum Region<B> {
     /// A region with a symbolic value constrained by a symbolic
@@ -65,6 +87,8 @@ pub enum Region<B> {
     SymbolicCode(Range<Address>),
     /// A region of concrete read-only memory
     Concrete(Range<Address>, HashMap<Address, u8>),
+    /// A region where reads and writes are handled by a device
+    Device(Range<Address>, Box<dyn Device<B>>),
 }
 
 pub enum SmtKind {
@@ -81,6 +105,7 @@ impl<B> fmt::Debug for Region<B> {
             Symbolic(r) => write!(f, "Symbolic({:?})", r),
             SymbolicCode(r) => write!(f, "SymbolicCode({:?})", r),
             Concrete(r, locs) => write!(f, "Concrete({:?}, {:?})", r, locs),
+            Device(r, _) => write!(f, "Device({:?}, <device>)", r),
         }
     }
 }
@@ -92,67 +117,339 @@ impl<B> Region<B> {
             Region::Symbolic(r) => r,
             Region::SymbolicCode(r) => r,
             Region::Concrete(r, _) => r,
+            Region::Device(r, _) => r,
         }
     }
 }
 
-// Optional client interface.  At the time of writing this is only
-// used by the test generation to enforce sequential memory, so we
-// jump through a few hoops to avoid other clients seeing it.  If it
-// was used more generally then it would be better to parametrise the
-// Memory struct instead.
+/// A byte written to memory in sequential mode
+#[derive(Clone, Debug)]
+enum WrittenByte {
+    Concrete(u8),
+    Symbolic(Exp),
+}
+
+impl WrittenByte {
+    fn to_exp(&self) -> Exp {
+        match self {
+            WrittenByte::Concrete(byte) => Exp::Bits64(*byte as u64, 8),
+            WrittenByte::Symbolic(exp) => exp.clone(),
+        }
+    }
+}
+
+/// A write to a symbolic address in sequential mode
+#[derive(Clone, Debug)]
+struct SymbolicWrite {
+    seq: usize,
+    address: Exp,
+    data: Vec<WrittenByte>,
+}
+
+/// In sequential mode memory remembers the writes made in each path,
+/// so later reads in the same path see them. Writes to concrete
+/// addresses are stored in a copy-on-write byte map, and writes to
+/// symbolic addresses are resolved using if-then-else chains when
+/// memory is read. Each write is numbered so more recent writes take
+/// precedence.
+#[derive(Clone, Debug, Default)]
+struct Sequential {
+    seq: usize,
+    concrete: Arc<HashMap<Address, (usize, WrittenByte)>>,
+    symbolic: Arc<Vec<SymbolicWrite>>,
+}
+
+impl Sequential {
+    fn write<B: BV>(&mut self, address: &Val<B>, data: &Val<B>, solver: &mut Solver<B>) -> Result<(), ExecError> {
+        let data: Vec<WrittenByte> = match data {
+            Val::Bits(bv) => (0..bv.len() / 8)
+                .map(|i| WrittenByte::Concrete(bv.extract(i * 8 + 7, i * 8).unwrap().lower_u64() as u8))
+                .collect(),
+            _ => {
+                let bytes = crate::primop::length_bits(data, solver)? / 8;
+                let v = solver.define_const(crate::primop::smt_value(data)?);
+                (0..bytes)
+                    .map(|i| WrittenByte::Symbolic(Exp::Extract(i * 8 + 7, i * 8, Box::new(Exp::Var(v)))))
+                    .collect()
+            }
+        };
+
+        self.seq += 1;
+        match address {
+            Val::Bits(addr) => {
+                let concrete = Arc::make_mut(&mut self.concrete);
+                for (i, byte) in data.into_iter().enumerate() {
+                    concrete.insert(addr.lower_u64() + i as u64, (self.seq, byte));
+                }
+            }
+            _ => {
+                let address = crate::primop::smt_value(address)?;
+                Arc::make_mut(&mut self.symbolic).push(SymbolicWrite { seq: self.seq, address, data })
+            }
+        }
+        Ok(())
+    }
+
+    /// The written bytes which may be at `address`, each with the
+    /// condition under which they are, in the order they were written
+    fn candidates(&self, address: &Exp, concrete_address: Option<Address>) -> Vec<(usize, Exp, Exp)> {
+        let mut candidates: Vec<(usize, Exp, Exp)> = Vec::new();
+
+        match concrete_address {
+            Some(addr) => {
+                if let Some((seq, byte)) = self.concrete.get(&addr) {
+                    candidates.push((*seq, Exp::Bool(true), byte.to_exp()))
+                }
+            }
+            None => {
+                for (addr, (seq, byte)) in self.concrete.iter() {
+                    let cond = Exp::Eq(Box::new(address.clone()), Box::new(Exp::Bits64(*addr, 64)));
+                    candidates.push((*seq, cond, byte.to_exp()))
+                }
+            }
+        }
+
+        for write in self.symbolic.iter() {
+            for (i, byte) in write.data.iter().enumerate() {
+                let write_address = Exp::Bvadd(Box::new(write.address.clone()), Box::new(Exp::Bits64(i as u64, 64)));
+                let cond = Exp::Eq(Box::new(address.clone()), Box::new(write_address));
+                candidates.push((write.seq, cond, byte.to_exp()))
+            }
+        }
 
-pub trait MemoryCallbacks<B>: fmt::Debug + MemoryCallbacksClone<B> + Send + Sync {
-    fn symbolic_read(
+        candidates.sort_by_key(|(seq, _, _)| *seq);
+        candidates
+    }
+
+    fn read<B: BV>(
         &self,
-        regions: &[Region<B>],
-        solver: &mut Solver<B>,
-        value: &Val<B>,
-        read_kind: &Val<B>,
-        address: &Val<B>,
+        memory: &mut Memory<B>,
+        read_kind: Val<B>,
+        address: Val<B>,
         bytes: u32,
-    );
-    #[allow(clippy::too_many_arguments)]
-    fn symbolic_write(
-        &mut self,
-        regions: &[Region<B>],
         solver: &mut Solver<B>,
-        value: Sym,
-        write_kind: &Val<B>,
-        address: &Val<B>,
-        data: &Val<B>,
-        bytes: u32,
-    );
+    ) -> Result<Val<B>, ExecError> {
+        let concrete_address = if let Val::Bits(addr) = address { Some(addr.lower_u64()) } else { None };
+        let address_exp = crate::primop::smt_value(&address)?;
+
+        let mut candidates: Vec<Vec<(usize, Exp, Exp)>> = Vec::new();
+        for i in 0..bytes {
+            let byte_address = Exp::Bvadd(Box::new(address_exp.clone()), Box::new(Exp::Bits64(i as u64, 64)));
+            candidates.push(self.candidates(&byte_address, concrete_address.map(|addr| addr + i as u64)))
+        }
+
+        if candidates.iter().all(Vec::is_empty) {
+            return memory.read_regions(read_kind, address, bytes, solver);
+        }
+
+        // If every byte was definitely written we don't need to read
+        // the underlying memory at all, otherwise the written bytes
+        // are combined with what we read.
+        let definitely_written =
+            |candidates: &[(usize, Exp, Exp)]| candidates.iter().any(|(_, cond, _)| matches!(cond, Exp::Bool(true)));
+        let underlying = if candidates.iter().all(|c| definitely_written(c)) {
+            None
+        } else {
+            let (underlying, _) = memory.read_regions_value(&address, bytes, solver)?;
+            Some(crate::primop::smt_value(&underlying)?)
+        };
+
+        let mut byte_exps: Vec<Exp> = Vec::new();
+        for (i, candidates) in candidates.drain(..).enumerate() {
+            let i = i as u32;
+            let mut exp = match &underlying {
+                Some(underlying) => Exp::Extract(i * 8 + 7, i * 8, Box::new(underlying.clone())),
+                None => Exp::Bits64(0, 8),
+            };
+            for (_, cond, byte) in candidates {
+                exp = match cond {
+                    Exp::Bool(true) => byte,
+                    cond => Exp::Ite(Box::new(cond), Box::new(byte), Box::new(exp)),
+                }
+            }
+            byte_exps.push(exp)
+        }
+
+        // Reads wider than a concrete bitvector can hold are always
+        // defined symbolically.
+        let fits = bytes * 8 <= B::MAX_WIDTH;
+        let value = if fits && byte_exps.iter().all(|exp| matches!(exp, Exp::Bits64(_, 8))) {
+            let bytes: Vec<u8> = byte_exps
+                .iter()
+                .rev()
+                .map(|exp| if let Exp::Bits64(byte, _) = exp { *byte as u8 } else { 0 })
+                .collect();
+            Val::Bits(B::from_bytes(&bytes))
+        } else {
+            let exp = byte_exps.drain(..).fold(None, |acc, byte| match acc {
+                None => Some(byte),
+                Some(lower) => Some(Exp::Concat(Box::new(byte), Box::new(lower))),
+            });
+            Val::Symbolic(solver.define_const(exp.unwrap()))
+        };
+
+        log!(log::MEMORY, &format!("Read sequential: {:?}", value));
+        solver.add_event(Event::ReadMem { value: value.clone(), read_kind, address, bytes });
+        Ok(value)
+    }
 }
 
-pub trait MemoryCallbacksClone<B> {
-    fn clone_box(&self) -> Box<dyn MemoryCallbacks<B>>;
+/// The address of a tag write in sequential mode, which is either
+/// the base of a concrete granule, or a symbolic granule base along
+/// with the condition that it lies in a tagged region.
+#[derive(Clone, Debug)]
+enum TagAddress {
+    Concrete(Address),
+    Symbolic { granule: Exp, tagged: Exp },
 }
 
-impl<B, T> MemoryCallbacksClone<B> for T
-where
-    T: 'static + MemoryCallbacks<B> + Clone,
-{
-    fn clone_box(&self) -> Box<dyn MemoryCallbacks<B>> {
-        Box::new(self.clone())
-    }
+/// Memory tags, such as CHERI capability tags or Arm MTE allocation
+/// tags, where every aligned granule of a tagged region carries a
+/// tag. Tags are booleans if `width` is `None`, and bitvectors
+/// otherwise. Like memory itself, tags are only tracked in sequential
+/// mode, and otherwise tag reads are unconstrained. Note that
+/// isla-axiomatic only places tag accesses in the `RTAG` and `WTAG`
+/// sets, and has no reads-from or coherence for tags, so the value of
+/// a tag read in a litmus test is not tied to any tag write.
+#[derive(Clone, Debug)]
+struct Tags<B> {
+    granule: u64,
+    width: Option<u32>,
+    regions: Vec<Range<Address>>,
+    /// Tag writes in sequential mode, oldest first. Any granule which
+    /// has not been written has a cleared tag.
+    writes: Arc<Vec<(TagAddress, Val<B>)>>,
 }
 
-impl<B> Clone for Box<dyn MemoryCallbacks<B>> {
-    fn clone(&self) -> Box<dyn MemoryCallbacks<B>> {
-        self.clone_box()
+impl<B: BV> Tags<B> {
+    fn cleared(&self) -> Val<B> {
+        match self.width {
+            None => Val::Bool(false),
+            Some(width) => Val::Bits(B::zeros(width)),
+        }
+    }
+
+    fn granule_base(&self, address: Address) -> Address {
+        address & !(self.granule - 1)
+    }
+
+    fn granule_exp(&self, address: Exp) -> Exp {
+        Exp::Bvand(Box::new(address), Box::new(Exp::Bits64(!(self.granule - 1), 64)))
+    }
+
+    fn is_tagged(&self, address: Address) -> bool {
+        self.regions.iter().any(|r| r.contains(&address))
+    }
+
+    fn is_tagged_exp(&self, address: &Exp) -> Exp {
+        use crate::smt::smtlib::Exp::*;
+        self.regions
+            .iter()
+            .map(|r| {
+                And(
+                    Box::new(Bvule(Box::new(Bits64(r.start, 64)), Box::new(address.clone()))),
+                    Box::new(Bvult(Box::new(address.clone()), Box::new(Bits64(r.end, 64)))),
+                )
+            })
+            .fold(Bool(false), |acc, e| match acc {
+                Bool(false) => e,
+                _ => Or(Box::new(acc), Box::new(e)),
+            })
+    }
+
+    fn read(&self, address: &Val<B>, solver: &mut Solver<B>) -> Result<Val<B>, ExecError> {
+        let concrete_granule = match address {
+            Val::Bits(addr) if !self.is_tagged(addr.lower_u64()) => return Ok(self.cleared()),
+            Val::Bits(addr) => Some(self.granule_base(addr.lower_u64())),
+            _ => None,
+        };
+        let granule_exp = self.granule_exp(crate::primop::smt_value(address)?);
+
+        // Walk back through the writes until we find one which
+        // definitely wrote this granule, collecting any writes that
+        // might have.
+        let mut latest = self.cleared();
+        let mut candidates: Vec<(Exp, &Val<B>)> = Vec::new();
+        for (write_address, tag) in self.writes.iter().rev() {
+            match (concrete_granule, write_address) {
+                (Some(granule), TagAddress::Concrete(write_granule)) => {
+                    if granule == *write_granule {
+                        latest = tag.clone();
+                        break;
+                    }
+                }
+                (None, TagAddress::Concrete(write_granule)) => candidates
+                    .push((Exp::Eq(Box::new(granule_exp.clone()), Box::new(Exp::Bits64(*write_granule, 64))), tag)),
+                (_, TagAddress::Symbolic { granule, tagged }) => candidates.push((
+                    Exp::And(
+                        Box::new(tagged.clone()),
+                        Box::new(Exp::Eq(Box::new(granule_exp.clone()), Box::new(granule.clone()))),
+                    ),
+                    tag,
+                )),
+            }
+        }
+
+        if candidates.is_empty() {
+            return Ok(latest);
+        }
+        let mut exp = crate::primop::smt_value(&latest)?;
+        for (cond, tag) in candidates.drain(..).rev() {
+            exp = Exp::Ite(Box::new(cond), Box::new(crate::primop::smt_value(tag)?), Box::new(exp))
+        }
+        Ok(Val::Symbolic(solver.define_const(exp)))
+    }
+
+    fn write(&mut self, address: &Val<B>, tag: Val<B>) -> Result<(), ExecError> {
+        let address = match address {
+            Val::Bits(addr) if !self.is_tagged(addr.lower_u64()) => return Ok(()),
+            Val::Bits(addr) => TagAddress::Concrete(self.granule_base(addr.lower_u64())),
+            _ => {
+                let address = crate::primop::smt_value(address)?;
+                TagAddress::Symbolic {
+                    granule: self.granule_exp(address.clone()),
+                    tagged: self.is_tagged_exp(&address),
+                }
+            }
+        };
+        let writes = Arc::make_mut(&mut self.writes);
+        // A concrete write hides every earlier write to the same granule
+        if let TagAddress::Concrete(granule) = address {
+            writes.retain(|(write_address, _)| !matches!(write_address, TagAddress::Concrete(g) if *g == granule))
+        }
+        writes.push((address, tag));
+        Ok(())
     }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct Memory<B> {
     regions: Vec<Region<B>>,
-    client_info: Option<Box<dyn MemoryCallbacks<B>>>,
+    /// Writes remembered when memory is in sequential mode
+    sequential: Option<Sequential>,
+    /// Memory tags, if any regions are tagged
+    tags: Option<Tags<B>>,
+    /// If set, reads from symbolic addresses which can only take up
+    /// to this many values, all in concrete regions, are resolved
+    /// against those regions
+    resolve_limit: Option<usize>,
+    /// Reads from these addresses stop execution, e.g. when an
+    /// instruction is fetched from a symbol in a loaded ELF file
+    stop_addresses: HashMap<Address, String>,
+    /// Reads from these addresses are logged as probes
+    probe_addresses: HashMap<Address, String>,
 }
 
 impl<B: BV> Memory<B> {
     pub fn new() -> Self {
-        Memory { regions: Vec::new(), client_info: None }
+        Memory {
+            regions: Vec::new(),
+            sequential: None,
+            tags: None,
+            resolve_limit: None,
+            stop_addresses: HashMap::new(),
+            probe_addresses: HashMap::new(),
+        }
     }
 
     pub fn log(&self) {
@@ -170,6 +467,9 @@ impl<B: BV> Memory<B> {
                 Region::Concrete(range, _) => {
                     log!(log::MEMORY, &format!("Memory range: [0x{:x}, 0x{:x}) concrete", range.start, range.end))
                 }
+                Region::Device(range, _) => {
+                    log!(log::MEMORY, &format!("Memory range: [0x{:x}, 0x{:x}) device", range.start, range.end))
+                }
             }
         }
     }
@@ -190,8 +490,51 @@ impl<B: BV> Memory<B> {
         self.regions.push(Region::Concrete(range, contents))
     }
 
-    pub fn set_client_info(&mut self, info: Box<dyn MemoryCallbacks<B>>) {
-        self.client_info = Some(info);
+    pub fn add_device_region(&mut self, range: Range<Address>, device: Box<dyn Device<B>>) {
+        self.regions.push(Region::Device(range, device))
+    }
+
+    /// Give every aligned granule of `range` a tag. All tagged regions
+    /// share the same granule size and tag width, with `None` meaning
+    /// boolean tags. The granule size must be a power of two.
+    pub fn add_tagged_region(&mut self, range: Range<Address>, granule: u64, width: Option<u32>) {
+        assert!(granule.is_power_of_two(), "Tag granule size must be a power of two");
+        match &mut self.tags {
+            Some(tags) => {
+                assert!(tags.granule == granule && tags.width == width, "Tagged regions must agree on tag format");
+                tags.regions.push(range)
+            }
+            None => self.tags = Some(Tags { granule, width, regions: vec![range], writes: Arc::new(Vec::new()) }),
+        }
+    }
+
+    /// Stop execution with `ExecError::Stopped(name)` when `address` is read
+    pub fn add_stop_address(&mut self, address: Address, name: String) {
+        self.stop_addresses.insert(address, name);
+    }
+
+    /// Log reads from `address` as probes, using `name` for the location
+    pub fn add_probe_address(&mut self, address: Address, name: String) {
+        self.probe_addresses.insert(address, name);
+    }
+
+    /// Put memory into sequential mode, where the reads in each path
+    /// see the writes made earlier in the same path, as when
+    /// executing a single thread of real code. Without this, memory
+    /// is left for a concurrency model to define, and reads are
+    /// unconstrained by earlier writes.
+    pub fn set_sequential(&mut self) {
+        self.sequential = Some(Sequential::default())
+    }
+
+    /// Resolve reads from symbolic addresses against concrete memory,
+    /// provided the address can take at most `limit` values. The
+    /// value read is an if-then-else over the contents of memory at
+    /// each of those addresses. This is useful for jump tables and
+    /// other computed loads from code, which would otherwise read
+    /// unconstrained values.
+    pub fn set_resolve_limit(&mut self, limit: usize) {
+        self.resolve_limit = Some(limit)
     }
 
     pub fn write_byte(&mut self, address: Address, byte: u8) {
@@ -216,7 +559,7 @@ impl<B: BV> Memory<B> {
     /// Panics if the number of bytes to read is concrete but does not fit
     /// in a u32, which should never be the case.
     pub fn read(
-        &self,
+        &mut self,
         read_kind: Val<B>,
         address: Val<B>,
         bytes: Val<B>,
@@ -228,42 +571,183 @@ impl<B: BV> Memory<B> {
             let bytes = u32::try_from(bytes).expect("Bytes did not fit in u32 in memory read");
 
             if let Val::Bits(concrete_addr) = address {
-                for region in &self.regions {
-                    match region {
-                        Region::Constrained(range, generator) if range.contains(&concrete_addr.lower_u64()) => {
-                            return read_constrained(
-                                range,
-                                generator.as_ref(),
-                                read_kind,
-                                concrete_addr.lower_u64(),
-                                bytes,
-                                solver,
-                            )
-                        }
+                if let Some(name) = self.stop_addresses.get(&concrete_addr.lower_u64()) {
+                    return Err(ExecError::Stopped(name.clone()));
+                }
+                if let Some(name) = self.probe_addresses.get(&concrete_addr.lower_u64()) {
+                    log!(log::PROBE, &format!("Read from {} (0x{:x})", name, concrete_addr.lower_u64()))
+                }
+                // Device reads never see writes remembered in
+                // sequential mode, as device writes go straight to the
+                // device.
+                if self.regions.iter().any(
+                    |region| matches!(region, Region::Device(range, _) if range.contains(&concrete_addr.lower_u64())),
+                ) {
+                    return self.read_regions(read_kind, address, bytes, solver);
+                }
+            }
 
-                        Region::Symbolic(range) if range.contains(&concrete_addr.lower_u64()) => {
-                            return self.read_symbolic(read_kind, address, bytes, solver)
-                        }
+            match self.sequential.take() {
+                Some(sequential) => {
+                    let result = sequential.read(self, read_kind, address, bytes, solver);
+                    self.sequential = Some(sequential);
+                    result
+                }
+                None => self.read_regions(read_kind, address, bytes, solver),
+            }
+        } else {
+            Err(ExecError::SymbolicLength("read_symbolic"))
+        }
+    }
 
-                        Region::SymbolicCode(range) if range.contains(&concrete_addr.lower_u64()) => {
-                            return self.read_symbolic(read_kind, address, bytes, solver)
-                        }
+    /// Read from the memory regions, ignoring any writes remembered
+    /// in sequential mode.
+    fn read_regions(
+        &mut self,
+        read_kind: Val<B>,
+        address: Val<B>,
+        bytes: u32,
+        solver: &mut Solver<B>,
+    ) -> Result<Val<B>, ExecError> {
+        let (value, record) = self.read_regions_value(&address, bytes, solver)?;
+        if record {
+            solver.add_event(Event::ReadMem { value: value.clone(), read_kind, address, bytes });
+        }
+        Ok(value)
+    }
 
-                        Region::Concrete(range, contents) if range.contains(&concrete_addr.lower_u64()) => {
-                            return read_concrete(contents, read_kind, concrete_addr.lower_u64(), bytes, solver)
-                        }
+    /// Read the value in the memory regions without adding a ReadMem
+    /// event to the trace. Also returns whether the read should be
+    /// recorded as a ReadMem event, which is not the case for device
+    /// reads, as devices record their own events.
+    fn read_regions_value(
+        &mut self,
+        address: &Val<B>,
+        bytes: u32,
+        solver: &mut Solver<B>,
+    ) -> Result<(Val<B>, bool), ExecError> {
+        if let Val::Bits(concrete_addr) = address {
+            for region in &mut self.regions {
+                match region {
+                    Region::Constrained(range, generator) if range.contains(&concrete_addr.lower_u64()) => {
+                        let value =
+                            read_constrained(range, generator.as_ref(), concrete_addr.lower_u64(), bytes, solver)?;
+                        return Ok((value, true));
+                    }
 
-                        _ => continue,
+                    Region::Symbolic(range) if range.contains(&concrete_addr.lower_u64()) => {
+                        return Ok((read_symbolic(bytes, solver), true))
+                    }
+
+                    Region::SymbolicCode(range) if range.contains(&concrete_addr.lower_u64()) => {
+                        return Ok((read_symbolic(bytes, solver), true))
                     }
-                }
 
-                self.read_symbolic(read_kind, address, bytes, solver)
-            } else {
-                self.read_symbolic(read_kind, address, bytes, solver)
+                    Region::Concrete(range, contents) if range.contains(&concrete_addr.lower_u64()) => {
+                        return Ok((read_concrete(contents, concrete_addr.lower_u64(), bytes)?, true))
+                    }
+
+                    Region::Device(range, device) if range.contains(&concrete_addr.lower_u64()) => {
+                        let offset = concrete_addr.lower_u64() - range.start;
+                        if concrete_addr.lower_u64() + bytes as u64 > range.end {
+                            return Err(ExecError::BadRead);
+                        }
+                        log!(log::MEMORY, &format!("Read device: 0x{:x} + 0x{:x}", range.start, offset));
+                        return Ok((device.read(offset, bytes, solver)?, false));
+                    }
+
+                    _ => continue,
+                }
             }
+
+            Ok((read_symbolic(bytes, solver), true))
         } else {
-            Err(ExecError::SymbolicLength("read_symbolic"))
+            if let Some(limit) = self.resolve_limit {
+                if let Some(value) = self.read_resolved(address, bytes, limit, solver)? {
+                    return Ok((value, true));
+                }
+            }
+            Ok((read_symbolic(bytes, solver), true))
+        }
+    }
+
+    /// Find the concrete values a symbolic address can take, giving
+    /// up if there are more than `limit` of them.
+    fn feasible_addresses(
+        address: &Exp,
+        limit: usize,
+        solver: &mut Solver<B>,
+    ) -> Result<Option<Vec<(Address, u32)>>, ExecError> {
+        let mut addresses = Vec::new();
+        let mut excluded = Exp::Bool(true);
+        while addresses.len() <= limit {
+            if !solver.check_sat_with(&excluded).is_sat()? {
+                return Ok(Some(addresses));
+            }
+            match Model::new(solver).get_exp(address)? {
+                Some(Exp::Bits64(addr, width)) => {
+                    addresses.push((addr, width));
+                    excluded = Exp::And(
+                        Box::new(excluded),
+                        Box::new(Exp::Neq(Box::new(address.clone()), Box::new(Exp::Bits64(addr, width)))),
+                    )
+                }
+                // The address is unconstrained by the model
+                _ => return Ok(None),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Read from a symbolic address by resolving it against the
+    /// concrete regions of memory, returning `None` if the address
+    /// can take too many values or any of them is not concrete.
+    fn read_resolved(
+        &self,
+        address: &Val<B>,
+        bytes: u32,
+        limit: usize,
+        solver: &mut Solver<B>,
+    ) -> Result<Option<Val<B>>, ExecError> {
+        let address_exp = crate::primop::smt_value(address)?;
+        let addresses = match Self::feasible_addresses(&address_exp, limit, solver)? {
+            Some(addresses) if !addresses.is_empty() => addresses,
+            _ => return Ok(None),
+        };
+
+        let mut values: Vec<(Address, u32, B)> = Vec::new();
+        for (addr, width) in addresses {
+            let contents = self.regions.iter().find_map(|region| match region {
+                Region::Concrete(range, contents) if range.contains(&addr) && addr + bytes as u64 <= range.end => {
+                    Some(contents)
+                }
+                _ => None,
+            });
+            match contents.and_then(|contents| concrete_bytes(contents, addr, bytes)) {
+                Some(value) => values.push((addr, width, value)),
+                None => return Ok(None),
+            }
         }
+
+        log!(log::MEMORY, &format!("Read resolved: {} possible addresses", values.len()));
+
+        let value = if let [(_, _, value)] = values.as_slice() {
+            Val::Bits(*value)
+        } else {
+            // The addresses are exhaustive, so the last one needs no test
+            let (_, _, last) = values.pop().unwrap();
+            let mut exp = crate::primop::smt_value(&Val::Bits(last))?;
+            for (addr, width, value) in values {
+                exp = Exp::Ite(
+                    Box::new(Exp::Eq(Box::new(address_exp.clone()), Box::new(Exp::Bits64(addr, width)))),
+                    Box::new(crate::primop::smt_value(&Val::Bits(value))?),
+                    Box::new(exp),
+                )
+            }
+            Val::Symbolic(solver.define_const(exp))
+        };
+
+        Ok(Some(value))
     }
 
     pub fn write(
@@ -275,37 +759,77 @@ impl<B: BV> Memory<B> {
     ) -> Result<Val<B>, ExecError> {
         log!(log::MEMORY, &format!("Write: {:?} {:?} {:?}", write_kind, address, data));
 
-        if let Val::Bits(_) = address {
-            self.write_symbolic(write_kind, address, data, solver)
+        if let Val::Bits(concrete_addr) = address {
+            let concrete_addr = concrete_addr.lower_u64();
+            for region in &mut self.regions {
+                match region {
+                    Region::Device(range, device) if range.contains(&concrete_addr) => {
+                        let offset = concrete_addr - range.start;
+                        let data_length = crate::primop::length_bits(&data, solver)?;
+                        if data_length % 8 != 0 {
+                            return Err(ExecError::Type("write device"));
+                        }
+                        if concrete_addr + (data_length / 8) as u64 > range.end {
+                            return Err(ExecError::BadWrite);
+                        }
+                        log!(log::MEMORY, &format!("Write device: 0x{:x} + 0x{:x}", range.start, offset));
+                        device.write(offset, data, solver)?;
+                        return Ok(Val::Bool(true));
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        if let Some(sequential) = &mut self.sequential {
+            sequential.write(&address, &data, solver)?;
+            // Writes in sequential mode always succeed
+            if let Val::Symbolic(v) = self.write_symbolic(write_kind, address, data, solver)? {
+                solver.add(Def::Assert(Exp::Var(v)))
+            }
+            Ok(Val::Bool(true))
         } else {
             self.write_symbolic(write_kind, address, data, solver)
         }
     }
 
-    /// The simplest read is to symbolically read a memory location. In
-    /// that case we just return a fresh SMT bitvector of the appropriate
-    /// size, and add a ReadMem event to the trace. For this we need the
-    /// number of bytes to be non-symbolic.
-    fn read_symbolic(
-        &self,
-        read_kind: Val<B>,
-        address: Val<B>,
-        bytes: u32,
-        solver: &mut Solver<B>,
-    ) -> Result<Val<B>, ExecError> {
+    /// Read the tag for the granule containing an address, adding a
+    /// ReadTag event to the trace. In sequential mode this sees the
+    /// earlier tag writes in the same path, otherwise the tag is
+    /// symbolic, except for addresses outside any tagged region,
+    /// which always have a cleared tag.
+    pub fn read_tag(&self, address: Val<B>, solver: &mut Solver<B>) -> Result<Val<B>, ExecError> {
         use crate::smt::smtlib::*;
 
-        let value = solver.fresh();
-        solver.add(Def::DeclareConst(value, Ty::BitVec(8 * bytes)));
-        match &self.client_info {
-            Some(c) => c.symbolic_read(&self.regions, solver, &Val::Symbolic(value), &read_kind, &address, bytes),
-            None => (),
+        let value = match &self.tags {
+            Some(tags) if self.sequential.is_some() => tags.read(&address, solver)?,
+            Some(tags) if matches!(&address, Val::Bits(addr) if !tags.is_tagged(addr.lower_u64())) => tags.cleared(),
+            _ => {
+                let value = solver.fresh();
+                let ty = match self.tags.as_ref().and_then(|tags| tags.width) {
+                    Some(width) => Ty::BitVec(width),
+                    None => Ty::Bool,
+                };
+                solver.add(Def::DeclareConst(value, ty));
+                Val::Symbolic(value)
+            }
         };
-        solver.add_event(Event::ReadMem { value: Val::Symbolic(value), read_kind, address, bytes });
 
-        log!(log::MEMORY, &format!("Read symbolic: {}", value));
+        log!(log::MEMORY, &format!("Read tag: {:?} {:?}", address, value));
+        solver.add_event(Event::ReadTag { value: value.clone(), address });
+        Ok(value)
+    }
 
-        Ok(Val::Symbolic(value))
+    /// Write the tag for the granule containing an address, adding a
+    /// WriteTag event to the trace.
+    pub fn write_tag(&mut self, address: Val<B>, tag: Val<B>, solver: &mut Solver<B>) -> Result<Val<B>, ExecError> {
+        log!(log::MEMORY, &format!("Write tag: {:?} {:?}", address, tag));
+
+        if let (Some(tags), Some(_)) = (&mut self.tags, &self.sequential) {
+            tags.write(&address, tag.clone())?
+        }
+        solver.add_event(Event::WriteTag { address, tag });
+        Ok(Val::Unit)
     }
 
     /// `write_symbolic` just adds a WriteMem event to the trace,
@@ -331,10 +855,6 @@ impl<B: BV> Memory<B> {
 
         let value = solver.fresh();
         solver.add(Def::DeclareConst(value, Ty::Bool));
-        match &mut self.client_info {
-            Some(c) => c.symbolic_write(&self.regions, solver, value, &write_kind, &address, &data, bytes),
-            None => (),
-        };
         solver.add_event(Event::WriteMem { value, write_kind, address, data, bytes });
 
         Ok(Val::Symbolic(value))
@@ -406,35 +926,38 @@ fn reverse_endianness(bytes: &mut [u8]) {
     }
 }
 
+/// The simplest read is to symbolically read a memory location. In
+/// that case we just return a fresh SMT bitvector of the appropriate
+/// size. For this we need the number of bytes to be non-symbolic.
+fn read_symbolic<B: BV>(bytes: u32, solver: &mut Solver<B>) -> Val<B> {
+    use crate::smt::smtlib::*;
+
+    let value = solver.fresh();
+    solver.add(Def::DeclareConst(value, Ty::BitVec(8 * bytes)));
+
+    log!(log::MEMORY, &format!("Read symbolic: {}", value));
+
+    Val::Symbolic(value)
+}
+
 fn read_constrained<B: BV>(
     range: &Range<Address>,
     generator: &(dyn Fn(&mut Solver<B>) -> Sym),
-    read_kind: Val<B>,
     address: Address,
     bytes: u32,
     solver: &mut Solver<B>,
 ) -> Result<Val<B>, ExecError> {
     let region = generator(solver);
     if address == range.start && address + bytes as u64 == range.end {
-        solver.add_event(Event::ReadMem {
-            value: Val::Symbolic(region),
-            read_kind,
-            address: Val::Bits(B::from_u64(address)),
-            bytes,
-        });
         Ok(Val::Symbolic(region))
     } else {
         Err(ExecError::BadRead)
     }
 }
 
-fn read_concrete<B: BV>(
-    region: &HashMap<Address, u8>,
-    read_kind: Val<B>,
-    address: Address,
-    bytes: u32,
-    solver: &mut Solver<B>,
-) -> Result<Val<B>, ExecError> {
+/// Read bytes from a concrete region as a little-endian bitvector,
+/// or `None` if the read is too large.
+fn concrete_bytes<B: BV>(region: &HashMap<Address, u8>, address: Address, bytes: u32) -> Option<B> {
     let mut byte_vec: Vec<u8> = Vec::with_capacity(bytes as usize);
     for i in address..(address + u64::from(bytes)) {
         byte_vec.push(*region.get(&i).unwrap_or(&0))
@@ -444,12 +967,263 @@ fn read_concrete<B: BV>(
 
     if byte_vec.len() <= 8 {
         log!(log::MEMORY, &format!("Read concrete: {:?}", byte_vec));
-
-        let value = Val::Bits(B::from_bytes(&byte_vec));
-        solver.add_event(Event::ReadMem { value, read_kind, address: Val::Bits(B::from_u64(address)), bytes });
-        Ok(Val::Bits(B::from_bytes(&byte_vec)))
+        Some(B::from_bytes(&byte_vec))
     } else {
         // TODO: Handle reads > 64 bits
-        Err(ExecError::BadRead)
+        None
+    }
+}
+
+fn read_concrete<B: BV>(region: &HashMap<Address, u8>, address: Address, bytes: u32) -> Result<Val<B>, ExecError> {
+    Ok(Val::Bits(concrete_bytes(region, address, bytes).ok_or(ExecError::BadRead)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::concrete::bitvector64::B64;
+    use crate::smt::smtlib::Ty;
+    use crate::smt::{Config, Context, SmtResult};
+
+    #[test]
+    fn sequential_concrete() {
+        let cfg = Config::new();
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let mut memory = Memory::new();
+        memory.add_symbolic_region(0x0..0x1000);
+        memory.set_sequential();
+
+        let addr = |a| Val::Bits(B64::from_u64(a));
+        memory.write(Val::Unit, addr(0x100), Val::Bits(B64::new(0xdead_beef, 32)), &mut solver).unwrap();
+        memory.write(Val::Unit, addr(0x101), Val::Bits(B64::new(0x42, 8)), &mut solver).unwrap();
+
+        let value = memory.read(Val::Unit, addr(0x100), Val::I128(4), &mut solver).unwrap();
+        assert_eq!(value.as_bits(), Some(&B64::new(0xdead_42ef, 32)));
+        let value = memory.read(Val::Unit, addr(0x102), Val::I128(2), &mut solver).unwrap();
+        assert_eq!(value.as_bits(), Some(&B64::new(0xdead, 16)));
+
+        // Bytes which were not written are read from the underlying region
+        let value = memory.read(Val::Unit, addr(0xfe), Val::I128(4), &mut solver).unwrap();
+        assert!(value.is_symbolic())
+    }
+
+    /// A device with a counter that is incremented by each read, and
+    /// which records each write to it as a `Device` event.
+    #[derive(Clone)]
+    struct Counter {
+        count: u64,
+    }
+
+    impl<B: BV> Device<B> for Counter {
+        fn read(&mut self, _: Address, bytes: u32, _: &mut Solver<B>) -> Result<Val<B>, ExecError> {
+            self.count += 1;
+            Ok(Val::Bits(B::new(self.count, bytes * 8)))
+        }
+
+        fn write(&mut self, _: Address, data: Val<B>, solver: &mut Solver<B>) -> Result<(), ExecError> {
+            solver.add_event(Event::Device { name: "counter".to_string(), value: data });
+            Ok(())
+        }
+
+        fn clone_device(&self) -> Box<dyn Device<B>> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn device_region() {
+        let cfg = Config::new();
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let mut memory = Memory::new();
+        memory.add_device_region(0x1000..0x1008, Box::new(Counter { count: 0 }));
+
+        let addr = |a| Val::Bits(B64::from_u64(a));
+        let value = memory.read(Val::Unit, addr(0x1000), Val::I128(4), &mut solver).unwrap();
+        assert_eq!(value.as_bits(), Some(&B64::new(1, 32)));
+
+        // Each copy of memory has its own device state
+        let mut forked = memory.clone();
+        let value = forked.read(Val::Unit, addr(0x1004), Val::I128(4), &mut solver).unwrap();
+        assert_eq!(value.as_bits(), Some(&B64::new(2, 32)));
+        let value = memory.read(Val::Unit, addr(0x1004), Val::I128(4), &mut solver).unwrap();
+        assert_eq!(value.as_bits(), Some(&B64::new(2, 32)));
+
+        assert!(memory.read(Val::Unit, addr(0x1006), Val::I128(4), &mut solver).is_err());
+
+        memory.write(Val::Unit, addr(0x1000), Val::Bits(B64::new(0x41, 8)), &mut solver).unwrap();
+        assert!(solver.trace().to_vec().iter().any(|ev| matches!(ev, Event::Device { .. })));
+
+        // Writes must also fit within the device region
+        memory.write(Val::Unit, addr(0x1004), Val::Bits(B64::new(0x41, 32)), &mut solver).unwrap();
+        assert!(memory.write(Val::Unit, addr(0x1006), Val::Bits(B64::new(0x41, 32)), &mut solver).is_err())
+    }
+
+    #[test]
+    fn sequential_device_region() {
+        let cfg = Config::new();
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let mut memory = Memory::new();
+        memory.add_device_region(0x1000..0x1008, Box::new(Counter { count: 0 }));
+        memory.set_sequential();
+
+        // A write to a symbolic address could alias any address, but
+        // not the device
+        let a = solver.fresh();
+        solver.add(Def::DeclareConst(a, Ty::BitVec(64)));
+        memory.write(Val::Unit, Val::Symbolic(a), Val::Bits(B64::new(0xab, 8)), &mut solver).unwrap();
+
+        let addr = |a| Val::Bits(B64::from_u64(a));
+        let value = memory.read(Val::Unit, addr(0x1000), Val::I128(4), &mut solver).unwrap();
+        assert_eq!(value.as_bits(), Some(&B64::new(1, 32)));
+        assert!(!solver.trace().to_vec().iter().any(|ev| matches!(ev, Event::ReadMem { .. })))
+    }
+
+    #[test]
+    fn sequential_tags() {
+        let cfg = Config::new();
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let mut memory = Memory::new();
+        memory.add_symbolic_region(0x0..0x1000);
+        memory.add_tagged_region(0x0..0x1000, 16, None);
+        memory.set_sequential();
+
+        let addr = |a| Val::Bits(B64::from_u64(a));
+        let is_set = |tag: Val<B64>| matches!(tag, Val::Bool(true));
+
+        assert!(!is_set(memory.read_tag(addr(0x100), &mut solver).unwrap()));
+        memory.write_tag(addr(0x100), Val::Bool(true), &mut solver).unwrap();
+        // Tags apply to the whole granule
+        assert!(is_set(memory.read_tag(addr(0x10f), &mut solver).unwrap()));
+        assert!(!is_set(memory.read_tag(addr(0x110), &mut solver).unwrap()));
+
+        // Writes outside tagged regions are dropped
+        memory.write_tag(addr(0x2000), Val::Bool(true), &mut solver).unwrap();
+        assert!(!is_set(memory.read_tag(addr(0x2000), &mut solver).unwrap()));
+
+        // A write to a symbolic address may clear the tag
+        let a = solver.fresh();
+        solver.add(Def::DeclareConst(a, Ty::BitVec(64)));
+        memory.write_tag(Val::Symbolic(a), Val::Bool(false), &mut solver).unwrap();
+        match memory.read_tag(addr(0x100), &mut solver).unwrap() {
+            Val::Symbolic(v) => {
+                solver.add(Def::Assert(Exp::Eq(Box::new(Exp::Var(a)), Box::new(Exp::Bits64(0x108, 64)))));
+                solver.add(Def::Assert(Exp::Var(v)));
+                assert!(solver.check_sat() == SmtResult::Unsat)
+            }
+            _ => panic!("Expected symbolic tag"),
+        }
+    }
+
+    #[test]
+    fn resolved_read() {
+        let cfg = Config::new();
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let mut memory = Memory::new();
+        let contents = (0..0x10).map(|i| (0x100 + i, i as u8)).collect();
+        memory.add_concrete_region(0x100..0x110, contents);
+        memory.set_resolve_limit(4);
+
+        // The address is either 0x100 or 0x104
+        let a = solver.fresh();
+        solver.add(Def::DeclareConst(a, Ty::BitVec(64)));
+        let is = |addr| Exp::Eq(Box::new(Exp::Var(a)), Box::new(Exp::Bits64(addr, 64)));
+        solver.add(Def::Assert(Exp::Or(Box::new(is(0x100)), Box::new(is(0x104)))));
+
+        match memory.read(Val::Unit, Val::Symbolic(a), Val::I128(4), &mut solver).unwrap() {
+            Val::Symbolic(v) => {
+                let value = Exp::Eq(Box::new(Exp::Var(v)), Box::new(Exp::Bits64(0x0706_0504, 32)));
+                assert!(solver.check_sat_with(&value) == SmtResult::Sat);
+                solver.add(Def::Assert(is(0x100)));
+                assert!(solver.check_sat_with(&value) == SmtResult::Unsat)
+            }
+            _ => panic!("Expected symbolic read"),
+        }
+
+        // An unconstrained address is read symbolically
+        let b = solver.fresh();
+        solver.add(Def::DeclareConst(b, Ty::BitVec(64)));
+        let value = memory.read(Val::Unit, Val::Symbolic(b), Val::I128(4), &mut solver).unwrap();
+        assert!(value.is_symbolic())
+    }
+
+    #[test]
+    fn sequential_wide_read() {
+        let cfg = Config::new();
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let mut memory = Memory::new();
+        memory.add_symbolic_region(0x0..0x1000);
+        memory.set_sequential();
+
+        let addr = |a| Val::Bits(B64::from_u64(a));
+        memory.write(Val::Unit, addr(0x100), Val::Bits(B64::new(0x0123_4567_89ab_cdef, 64)), &mut solver).unwrap();
+        memory.write(Val::Unit, addr(0x108), Val::Bits(B64::new(0xfedc_ba98_7654_3210, 64)), &mut solver).unwrap();
+
+        // Every byte is concrete, but 16 bytes do not fit in a B64
+        let value = memory.read(Val::Unit, addr(0x100), Val::I128(16), &mut solver).unwrap();
+        assert!(value.is_symbolic());
+        let value_exp = crate::primop::smt_value(&value).unwrap();
+        let half = |hi, lo, bits| {
+            Exp::Eq(Box::new(Exp::Extract(hi, lo, Box::new(value_exp.clone()))), Box::new(Exp::Bits64(bits, 64)))
+        };
+        let expected =
+            Exp::And(Box::new(half(63, 0, 0x0123_4567_89ab_cdef)), Box::new(half(127, 64, 0xfedc_ba98_7654_3210)));
+        assert!(solver.check_sat_with(&Exp::Not(Box::new(expected))) == SmtResult::Unsat)
+    }
+
+    #[test]
+    fn sequential_symbolic() {
+        let cfg = Config::new();
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let mut memory = Memory::new();
+        memory.set_sequential();
+
+        let a = solver.fresh();
+        solver.add(Def::DeclareConst(a, Ty::BitVec(64)));
+        memory.write(Val::Unit, Val::Symbolic(a), Val::Bits(B64::new(0xab, 8)), &mut solver).unwrap();
+
+        match memory.read(Val::Unit, Val::Symbolic(a), Val::I128(1), &mut solver).unwrap() {
+            Val::Symbolic(v) => {
+                solver.add(Def::Assert(Exp::Neq(Box::new(Exp::Var(v)), Box::new(Exp::Bits64(0xab, 8)))));
+                assert!(solver.check_sat() == SmtResult::Unsat)
+            }
+            _ => panic!("Expected symbolic read"),
+        }
+    }
+
+    #[test]
+    fn sequential_partial_overlap() {
+        let cfg = Config::new();
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let mut memory = Memory::new();
+        let contents = (0..0x10).map(|i| (0x100 + i, i as u8)).collect();
+        memory.add_concrete_region(0x100..0x110, contents);
+        memory.set_sequential();
+
+        let addr = |a| Val::Bits(B64::from_u64(a));
+        memory.write(Val::Unit, addr(0x105), Val::Bits(B64::new(0xabcd, 16)), &mut solver).unwrap();
+
+        // Only the middle two bytes were written, so the rest come from the region
+        let value = memory.read(Val::Unit, addr(0x104), Val::I128(4), &mut solver).unwrap();
+        let value_exp = crate::primop::smt_value(&value).unwrap();
+        let expected = Exp::Eq(Box::new(value_exp), Box::new(Exp::Bits64(0x07ab_cd04, 32)));
+        assert!(solver.check_sat_with(&Exp::Not(Box::new(expected))) == SmtResult::Unsat);
+
+        // The trace records the combined value, and only once
+        let reads: Vec<Val<B64>> = solver
+            .trace()
+            .to_vec()
+            .iter()
+            .filter_map(|ev| if let Event::ReadMem { value, .. } = ev { Some(value.clone()) } else { None })
+            .collect();
+        assert_eq!(reads.len(), 1);
+        assert_eq!(format!("{:?}", reads[0]), format!("{:?}", value))
     }
 }
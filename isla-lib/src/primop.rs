@@ -1775,7 +1775,7 @@ fn choice<B: BV>(xs: Val<B>, solver: &mut Solver<B>) -> Result<Val<B>, ExecError
 }
 
 fn read_mem<B: BV>(args: Vec<Val<B>>, solver: &mut Solver<B>, frame: &mut LocalFrame<B>) -> Result<Val<B>, ExecError> {
-    frame.memory().read(args[0].clone(), args[2].clone(), args[3].clone(), solver)
+    frame.memory_mut().read(args[0].clone(), args[2].clone(), args[3].clone(), solver)
 }
 
 fn bad_read<B: BV>(_: Val<B>, _: &mut Solver<B>) -> Result<Val<B>, ExecError> {
@@ -1790,6 +1790,14 @@ fn bad_write<B: BV>(_: Val<B>, _: &mut Solver<B>) -> Result<Val<B>, ExecError> {
     Err(ExecError::BadWrite)
 }
 
+fn read_tag<B: BV>(args: Vec<Val<B>>, solver: &mut Solver<B>, frame: &mut LocalFrame<B>) -> Result<Val<B>, ExecError> {
+    frame.memory().read_tag(args[0].clone(), solver)
+}
+
+fn write_tag<B: BV>(args: Vec<Val<B>>, solver: &mut Solver<B>, frame: &mut LocalFrame<B>) -> Result<Val<B>, ExecError> {
+    frame.memory_mut().write_tag(args[0].clone(), args[1].clone(), solver)
+}
+
 fn write_mem_ea<B: BV>(
     _: Vec<Val<B>>,
     _solver: &mut Solver<B>,
@@ -2087,6 +2095,10 @@ pub fn variadic_primops<B: BV>() -> HashMap<String, Variadic<B>> {
     primops.insert("platform_read_mem".to_string(), read_mem as Variadic<B>);
     primops.insert("platform_write_mem".to_string(), write_mem as Variadic<B>);
     primops.insert("platform_write_mem_ea".to_string(), write_mem_ea as Variadic<B>);
+    primops.insert("platform_read_tag".to_string(), read_tag as Variadic<B>);
+    primops.insert("platform_write_tag".to_string(), write_tag as Variadic<B>);
+    primops.insert("read_tag_bool".to_string(), read_tag as Variadic<B>);
+    primops.insert("write_tag_bool".to_string(), write_tag as Variadic<B>);
     primops.insert("platform_cache_maintenance".to_string(), cache_maintenance as Variadic<B>);
     primops.insert("elf_entry".to_string(), elf_entry as Variadic<B>);
     primops.insert("ite".to_string(), ite as Variadic<B>);
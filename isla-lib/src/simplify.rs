@@ -29,6 +29,7 @@
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 use std::borrow::Borrow;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::io::Write;
 
@@ -36,7 +37,7 @@ use crate::concrete::{write_bits64, BV};
 use crate::ir::{Name, Symtab, Val, HAVE_EXCEPTION};
 use crate::smt::smtlib::*;
 use crate::smt::Event::*;
-use crate::smt::{Accessor, Event, Sym};
+use crate::smt::{Accessor, Event, ExceptionKind, Sym};
 use crate::zencode;
 
 /// `renumber_event` Renumbers all the symbolic variables in an event such that multiple event
@@ -50,7 +51,9 @@ pub fn renumber_event<B>(event: &mut Event<B>, i: u32, total: u32) {
     match event {
         Smt(def) => renumber_def(def, i, total),
         Fork(_, v, _) | Sleeping(v) => *v = Sym { id: (v.id * total) + i },
-        ReadReg(_, _, value) | WriteReg(_, _, value) | Instr(value) => renumber_val(value, i, total),
+        ReadReg(_, _, value) | WriteReg(_, _, value) | Instr(value) | Device { value, .. } => {
+            renumber_val(value, i, total)
+        }
         Branch { address } => renumber_val(address, i, total),
         Barrier { barrier_kind } => renumber_val(barrier_kind, i, total),
         ReadMem { value, read_kind, address, bytes: _ } => {
@@ -68,7 +71,15 @@ pub fn renumber_event<B>(event: &mut Event<B>, i: u32, total: u32) {
             renumber_val(cache_op_kind, i, total);
             renumber_val(address, i, total);
         }
-        Cycle | SleepRequest | WakeupRequest | MarkReg { .. } => (),
+        ReadTag { value, address } => {
+            renumber_val(value, i, total);
+            renumber_val(address, i, total);
+        }
+        WriteTag { address, tag } => {
+            renumber_val(address, i, total);
+            renumber_val(tag, i, total);
+        }
+        Cycle | SleepRequest | WakeupRequest | Exception(_) | MarkReg { .. } => (),
     }
 }
 
@@ -308,7 +319,7 @@ impl EventReferences {
 }
 
 #[allow(clippy::unneeded_field_pattern)]
-fn calculate_uses<B, E: Borrow<Event<B>>>(events: &Vec<E>) -> HashMap<Sym, u32> {
+fn calculate_uses<B, E: Borrow<Event<B>>>(events: &[E]) -> HashMap<Sym, u32> {
     let mut uses: HashMap<Sym, u32> = HashMap::new();
 
     for event in events.iter().rev() {
@@ -338,15 +349,25 @@ fn calculate_uses<B, E: Borrow<Event<B>>>(events: &Vec<E>) -> HashMap<Sym, u32>
                 uses_in_value(&mut uses, cache_op_kind);
                 uses_in_value(&mut uses, address)
             }
+            ReadTag { value, address } => {
+                uses_in_value(&mut uses, value);
+                uses_in_value(&mut uses, address)
+            }
+            WriteTag { address, tag } => {
+                uses_in_value(&mut uses, address);
+                uses_in_value(&mut uses, tag)
+            }
             Fork(_, sym, _) => {
                 uses.insert(*sym, uses.get(&sym).unwrap_or(&0) + 1);
             }
             Cycle => (),
             Instr(val) => uses_in_value(&mut uses, val),
+            Device { value, .. } => uses_in_value(&mut uses, value),
             Sleeping(sym) => {
                 uses.insert(*sym, uses.get(&sym).unwrap_or(&0) + 1);
             }
             MarkReg { .. } => (),
+            Exception(_) => (),
             WakeupRequest => (),
             SleepRequest => (),
         }
@@ -405,6 +426,463 @@ pub fn remove_unused<B: BV, E: Borrow<Event<B>>>(events: &mut Vec<E>) {
     }
 }
 
+/// Widths and types of the variables defined so far in a trace, used
+/// by the bitvector simplifications which need to know the size of
+/// sub-expressions.
+#[derive(Default)]
+struct TypeContext {
+    vars: HashMap<Sym, Ty>,
+    funs: HashMap<Sym, (Vec<Ty>, Ty)>,
+}
+
+impl TypeContext {
+    fn add_def(&mut self, def: &Def) {
+        match def {
+            Def::DeclareConst(v, ty) => {
+                self.vars.insert(*v, ty.clone());
+            }
+            Def::DeclareFun(v, arg_tys, result_ty) => {
+                self.funs.insert(*v, (arg_tys.clone(), result_ty.clone()));
+            }
+            Def::DefineConst(v, exp) => {
+                if let Some(ty) = exp.infer(&self.vars, &self.funs) {
+                    self.vars.insert(*v, ty);
+                }
+            }
+            Def::DefineEnum(_, _) | Def::Assert(_) => (),
+        }
+    }
+
+    fn width(&self, exp: &Exp) -> Option<u32> {
+        match exp.infer(&self.vars, &self.funs) {
+            Some(Ty::BitVec(sz)) => Some(sz),
+            _ => None,
+        }
+    }
+}
+
+fn mask(len: u32) -> u64 {
+    if len >= 64 {
+        u64::MAX
+    } else {
+        (1 << len) - 1
+    }
+}
+
+fn bits64(bits: u64, len: u32) -> Exp {
+    Exp::Bits64(bits & mask(len), len)
+}
+
+/// Returns the value and length of a bitvector literal that fits in 64 bits
+fn literal(exp: &Exp) -> Option<(u64, u32)> {
+    match exp {
+        Exp::Bits64(bits, len) => Some((*bits, *len)),
+        Exp::Bits(bv) if bv.len() <= 64 => Some((
+            bv.iter().enumerate().fold(0, |acc, (i, bit)| if *bit { acc | (1 << i) } else { acc }),
+            bv.len() as u32,
+        )),
+        _ => None,
+    }
+}
+
+fn is_literal(exp: &Exp) -> bool {
+    matches!(exp, Exp::Bits64(_, _) | Exp::Bits(_) | Exp::Bool(_) | Exp::Enum(_))
+}
+
+fn signed(bits: u64, len: u32) -> i64 {
+    if len == 0 || len >= 64 {
+        bits as i64
+    } else {
+        ((bits << (64 - len)) as i64) >> (64 - len)
+    }
+}
+
+fn fold_bvbinop(exp: &Exp, a: u64, b: u64, len: u32) -> Option<Exp> {
+    use Exp::*;
+    Some(match exp {
+        Bvand(_, _) => bits64(a & b, len),
+        Bvor(_, _) => bits64(a | b, len),
+        Bvxor(_, _) => bits64(a ^ b, len),
+        Bvnand(_, _) => bits64(!(a & b), len),
+        Bvnor(_, _) => bits64(!(a | b), len),
+        Bvxnor(_, _) => bits64(!(a ^ b), len),
+        Bvadd(_, _) => bits64(a.wrapping_add(b), len),
+        Bvsub(_, _) => bits64(a.wrapping_sub(b), len),
+        Bvmul(_, _) => bits64(a.wrapping_mul(b), len),
+        Bvudiv(_, _) => bits64(a.checked_div(b).unwrap_or(u64::MAX), len),
+        Bvurem(_, _) => bits64(a.checked_rem(b).unwrap_or(a), len),
+        Bvshl(_, _) => bits64(if b >= u64::from(len) { 0 } else { a << b }, len),
+        Bvlshr(_, _) => bits64(if b >= u64::from(len) { 0 } else { a >> b }, len),
+        Bvashr(_, _) => bits64((signed(a, len) >> std::cmp::min(b, 63)) as u64, len),
+        Bvult(_, _) => Bool(a < b),
+        Bvule(_, _) => Bool(a <= b),
+        Bvugt(_, _) => Bool(a > b),
+        Bvuge(_, _) => Bool(a >= b),
+        Bvslt(_, _) => Bool(signed(a, len) < signed(b, len)),
+        Bvsle(_, _) => Bool(signed(a, len) <= signed(b, len)),
+        Bvsgt(_, _) => Bool(signed(a, len) > signed(b, len)),
+        Bvsge(_, _) => Bool(signed(a, len) >= signed(b, len)),
+        _ => return None,
+    })
+}
+
+/// Perform a single simplification step at the root of an
+/// expression, assuming that all its sub-expressions have already
+/// been simplified. Returns `None` if no rule applies.
+fn simplify_step(exp: &Exp, tcx: &TypeContext) -> Option<Exp> {
+    use Exp::*;
+    match exp {
+        Not(exp) => match &**exp {
+            Bool(b) => Some(Bool(!b)),
+            Not(exp) => Some((**exp).clone()),
+            _ => None,
+        },
+
+        Eq(lhs, rhs) | Neq(lhs, rhs) => {
+            let equal = if lhs == rhs {
+                Some(true)
+            } else if let (Some(lhs), Some(rhs)) = (literal(lhs), literal(rhs)) {
+                Some(lhs == rhs)
+            } else if is_literal(lhs) && is_literal(rhs) {
+                Some(false)
+            } else {
+                None
+            }?;
+            Some(Bool(if let Eq(_, _) = exp { equal } else { !equal }))
+        }
+
+        And(lhs, rhs) => match (&**lhs, &**rhs) {
+            (Bool(true), other) | (other, Bool(true)) => Some(other.clone()),
+            (Bool(false), _) | (_, Bool(false)) => Some(Bool(false)),
+            _ if lhs == rhs => Some((**lhs).clone()),
+            _ => None,
+        },
+
+        Or(lhs, rhs) => match (&**lhs, &**rhs) {
+            (Bool(false), other) | (other, Bool(false)) => Some(other.clone()),
+            (Bool(true), _) | (_, Bool(true)) => Some(Bool(true)),
+            _ if lhs == rhs => Some((**lhs).clone()),
+            _ => None,
+        },
+
+        Bvnot(exp) => match (&**exp, literal(exp)) {
+            (_, Some((bits, len))) => Some(bits64(!bits, len)),
+            (Bvnot(exp), _) => Some((**exp).clone()),
+            _ => None,
+        },
+
+        Bvneg(exp) => literal(exp).map(|(bits, len)| bits64(bits.wrapping_neg(), len)),
+
+        Bvand(lhs, rhs)
+        | Bvor(lhs, rhs)
+        | Bvxor(lhs, rhs)
+        | Bvnand(lhs, rhs)
+        | Bvnor(lhs, rhs)
+        | Bvxnor(lhs, rhs)
+        | Bvadd(lhs, rhs)
+        | Bvsub(lhs, rhs)
+        | Bvmul(lhs, rhs)
+        | Bvudiv(lhs, rhs)
+        | Bvurem(lhs, rhs)
+        | Bvshl(lhs, rhs)
+        | Bvlshr(lhs, rhs)
+        | Bvashr(lhs, rhs)
+        | Bvult(lhs, rhs)
+        | Bvule(lhs, rhs)
+        | Bvugt(lhs, rhs)
+        | Bvuge(lhs, rhs)
+        | Bvslt(lhs, rhs)
+        | Bvsle(lhs, rhs)
+        | Bvsgt(lhs, rhs)
+        | Bvsge(lhs, rhs) => match (literal(lhs), literal(rhs)) {
+            (Some((a, len)), Some((b, _))) => fold_bvbinop(exp, a, b, len),
+            (_, Some((0, _))) => match exp {
+                Bvand(_, _) => Some((**rhs).clone()),
+                Bvor(_, _) | Bvxor(_, _) | Bvadd(_, _) | Bvsub(_, _) | Bvshl(_, _) | Bvlshr(_, _) | Bvashr(_, _) => {
+                    Some((**lhs).clone())
+                }
+                _ => None,
+            },
+            (Some((0, _)), _) => match exp {
+                Bvand(_, _) => Some((**lhs).clone()),
+                Bvor(_, _) | Bvxor(_, _) | Bvadd(_, _) => Some((**rhs).clone()),
+                _ => None,
+            },
+            (_, Some((bits, len))) if bits == mask(len) => match exp {
+                Bvand(_, _) => Some((**lhs).clone()),
+                Bvor(_, _) => Some((**rhs).clone()),
+                _ => None,
+            },
+            (Some((bits, len)), _) if bits == mask(len) => match exp {
+                Bvand(_, _) => Some((**rhs).clone()),
+                Bvor(_, _) => Some((**lhs).clone()),
+                _ => None,
+            },
+            _ => None,
+        },
+
+        Extract(hi, lo, exp) => {
+            if let Some((bits, _)) = literal(exp) {
+                return Some(bits64(bits >> lo, (hi - lo) + 1));
+            }
+            if *lo == 0 && tcx.width(exp) == Some(hi + 1) {
+                return Some((**exp).clone());
+            }
+            match &**exp {
+                Extract(_, inner_lo, exp) => Some(Extract(hi + inner_lo, lo + inner_lo, exp.clone())),
+                Concat(upper, lower) => {
+                    let lower_width = tcx.width(lower)?;
+                    if *hi < lower_width {
+                        Some(simplify_exp(Extract(*hi, *lo, lower.clone()), tcx))
+                    } else if *lo >= lower_width {
+                        Some(simplify_exp(Extract(hi - lower_width, lo - lower_width, upper.clone()), tcx))
+                    } else {
+                        Some(Concat(
+                            Box::new(simplify_exp(Extract(hi - lower_width, 0, upper.clone()), tcx)),
+                            Box::new(simplify_exp(Extract(lower_width - 1, *lo, lower.clone()), tcx)),
+                        ))
+                    }
+                }
+                ZeroExtend(_, exp) => {
+                    let width = tcx.width(exp)?;
+                    if *hi < width {
+                        Some(simplify_exp(Extract(*hi, *lo, exp.clone()), tcx))
+                    } else if *lo >= width {
+                        Some(bits64(0, (hi - lo) + 1))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            }
+        }
+
+        ZeroExtend(0, exp) | SignExtend(0, exp) => Some((**exp).clone()),
+
+        ZeroExtend(ext, exp) => match literal(exp) {
+            Some((bits, len)) if len + ext <= 64 => Some(bits64(bits, len + ext)),
+            _ => None,
+        },
+
+        SignExtend(ext, exp) => match literal(exp) {
+            Some((bits, len)) if len + ext <= 64 => Some(bits64(signed(bits, len) as u64, len + ext)),
+            _ => None,
+        },
+
+        Concat(lhs, rhs) => match (literal(lhs), literal(rhs)) {
+            (Some((upper, upper_len)), Some((lower, lower_len))) if upper_len + lower_len <= 64 => {
+                let upper = if lower_len >= 64 { 0 } else { upper << lower_len };
+                Some(bits64(upper | lower, upper_len + lower_len))
+            }
+            _ => None,
+        },
+
+        Ite(cond, then_exp, else_exp) => match (&**cond, &**then_exp, &**else_exp) {
+            (Bool(true), _, _) => Some((**then_exp).clone()),
+            (Bool(false), _, _) => Some((**else_exp).clone()),
+            (_, Bool(true), Bool(false)) => Some((**cond).clone()),
+            (_, Bool(false), Bool(true)) => Some(Not(cond.clone())),
+            _ if then_exp == else_exp => Some((**then_exp).clone()),
+            _ => None,
+        },
+
+        _ => None,
+    }
+}
+
+/// Simplify an SMT expression bottom-up using constant folding and
+/// some local bitvector rewrites, such as extracts of concatenations
+/// and zero extensions, and redundant if-then-else expressions.
+fn simplify_exp(mut exp: Exp, tcx: &TypeContext) -> Exp {
+    exp.modify(
+        &(|exp| {
+            while let Some(simpler) = simplify_step(exp, tcx) {
+                *exp = simpler
+            }
+        }),
+    );
+    exp
+}
+
+fn substitute_exp(exp: &mut Exp, subst: &HashMap<Sym, Exp>) {
+    exp.modify(
+        &(|exp| {
+            if let Exp::Var(v) = exp {
+                if let Some(replacement) = subst.get(v) {
+                    *exp = replacement.clone()
+                }
+            }
+        }),
+    )
+}
+
+fn substitute_sym(v: &mut Sym, subst: &HashMap<Sym, Exp>) {
+    if let Some(Exp::Var(replacement)) = subst.get(v) {
+        *v = *replacement
+    }
+}
+
+/// Substitute into a value. Variables can be replaced by other
+/// variables, or by literals which can be represented as values.
+fn substitute_val<B: BV>(val: &mut Val<B>, subst: &HashMap<Sym, Exp>) {
+    use Val::*;
+    match val {
+        Symbolic(v) => match subst.get(v) {
+            Some(Exp::Var(replacement)) => *v = *replacement,
+            Some(Exp::Bits64(bits, len)) if *len <= B::MAX_WIDTH => *val = Bits(B::new(*bits, *len)),
+            Some(Exp::Bool(b)) => *val = Bool(*b),
+            _ => (),
+        },
+        I64(_) | I128(_) | Bool(_) | Bits(_) | Enum(_) | String(_) | Unit | Ref(_) | Poison => (),
+        List(vals) | Vector(vals) => vals.iter_mut().for_each(|val| substitute_val(val, subst)),
+        Struct(fields) => fields.iter_mut().for_each(|(_, val)| substitute_val(val, subst)),
+        Ctor(_, val) => substitute_val(val, subst),
+    }
+}
+
+fn substitute_event<B: BV>(event: &mut Event<B>, subst: &HashMap<Sym, Exp>) {
+    match event {
+        Smt(Def::DefineConst(_, exp)) | Smt(Def::Assert(exp)) => substitute_exp(exp, subst),
+        Smt(_) => (),
+        Fork(_, v, _) | Sleeping(v) => substitute_sym(v, subst),
+        ReadReg(_, _, value) | WriteReg(_, _, value) | Instr(value) | Device { value, .. } => {
+            substitute_val(value, subst)
+        }
+        Branch { address } => substitute_val(address, subst),
+        Barrier { barrier_kind } => substitute_val(barrier_kind, subst),
+        ReadMem { value, read_kind, address, .. } => {
+            substitute_val(value, subst);
+            substitute_val(read_kind, subst);
+            substitute_val(address, subst)
+        }
+        WriteMem { value, write_kind, address, data, .. } => {
+            substitute_sym(value, subst);
+            substitute_val(write_kind, subst);
+            substitute_val(address, subst);
+            substitute_val(data, subst)
+        }
+        CacheOp { cache_op_kind, address } => {
+            substitute_val(cache_op_kind, subst);
+            substitute_val(address, subst)
+        }
+        ReadTag { value, address } => {
+            substitute_val(value, subst);
+            substitute_val(address, subst)
+        }
+        WriteTag { address, tag } => {
+            substitute_val(address, subst);
+            substitute_val(tag, subst)
+        }
+        Cycle | SleepRequest | WakeupRequest | Exception(_) | MarkReg { .. } => (),
+    }
+}
+
+/// `propagate_constants` simplifies every expression in the trace,
+/// and substitutes definitions which simplify to a literal or another
+/// variable into their uses. Chains of definitions like `(define-const
+/// v2 (bvadd v1 #x1))` where `v1` is constant are folded into a single
+/// literal. Like the other passes, the events should be in the order
+/// produced by `Trace::to_vec`, i.e. most recent first.
+pub fn propagate_constants<B: BV>(events: &mut Vec<Event<B>>) {
+    let mut tcx = TypeContext::default();
+    let mut subst: HashMap<Sym, Exp> = HashMap::new();
+    let mut trivial = vec![false; events.len()];
+
+    for (i, event) in events.iter_mut().enumerate().rev() {
+        substitute_event(event, &subst);
+        match event {
+            Smt(Def::DefineConst(v, exp)) => {
+                *exp = simplify_exp(exp.clone(), &tcx);
+                if is_literal(exp) || matches!(exp, Exp::Var(_)) {
+                    subst.insert(*v, exp.clone());
+                }
+            }
+            Smt(Def::Assert(exp)) => {
+                *exp = simplify_exp(exp.clone(), &tcx);
+                trivial[i] = *exp == Exp::Bool(true)
+            }
+            _ => (),
+        }
+        if let Smt(def) = event {
+            tcx.add_def(def)
+        }
+    }
+
+    let mut i = 0;
+    events.retain(|_| (!trivial[i], i += 1).0)
+}
+
+/// `eliminate_common_subexpressions` replaces every definition whose
+/// expression is syntactically identical to an earlier definition with
+/// a reference to that earlier variable.
+pub fn eliminate_common_subexpressions<B: BV>(events: &mut Vec<Event<B>>) {
+    let mut seen: HashMap<Exp, Sym> = HashMap::new();
+    let mut subst: HashMap<Sym, Exp> = HashMap::new();
+    let mut duplicate = vec![false; events.len()];
+
+    for (i, event) in events.iter_mut().enumerate().rev() {
+        substitute_event(event, &subst);
+        if let Smt(Def::DefineConst(v, exp)) = event {
+            match seen.get(exp) {
+                Some(original) => {
+                    subst.insert(*v, Exp::Var(*original));
+                    duplicate[i] = true
+                }
+                None => {
+                    seen.insert(exp.clone(), *v);
+                }
+            }
+        }
+    }
+
+    let mut i = 0;
+    events.retain(|_| (!duplicate[i], i += 1).0)
+}
+
+/// `inline_single_use` substitutes definitions which are used exactly
+/// once, within another SMT definition or assertion, into that use.
+/// Definitions referred to from other events, such as register writes,
+/// are kept so the event can still name them.
+pub fn inline_single_use<B: BV>(events: &mut Vec<Event<B>>) {
+    let uses = calculate_uses(events);
+
+    let mut smt_uses: HashMap<Sym, u32> = HashMap::new();
+    for event in events.iter() {
+        if let Smt(Def::DefineConst(_, exp)) | Smt(Def::Assert(exp)) = event {
+            uses_in_exp(&mut smt_uses, exp)
+        }
+    }
+
+    let mut subst: HashMap<Sym, Exp> = HashMap::new();
+    let mut inlined = vec![false; events.len()];
+
+    for (i, event) in events.iter_mut().enumerate().rev() {
+        if let Smt(Def::DefineConst(_, exp)) | Smt(Def::Assert(exp)) = event {
+            substitute_exp(exp, &subst)
+        }
+        if let Smt(Def::DefineConst(v, exp)) = event {
+            if uses.get(v) == Some(&1) && smt_uses.get(v) == Some(&1) {
+                subst.insert(*v, exp.clone());
+                inlined[i] = true
+            }
+        }
+    }
+
+    let mut i = 0;
+    events.retain(|_| (!inlined[i], i += 1).0)
+}
+
+/// Apply all the simplification passes, removing any definitions
+/// which become unused.
+pub fn simplify<B: BV>(events: &mut Vec<Event<B>>) {
+    propagate_constants(events);
+    eliminate_common_subexpressions(events);
+    remove_unused(events);
+    inline_single_use(events);
+    propagate_constants(events);
+    remove_unused(events)
+}
+
 fn accessor_to_string(acc: &[Accessor], symtab: &Symtab) -> String {
     acc.iter()
         .map(|elem| elem.to_string(symtab))
@@ -424,18 +902,36 @@ pub struct WriteOpts {
     pub just_smt: bool,
     /// Print the sizes of enumerations declared during symbolic
     /// evaluation.
-    pub define_enum: bool
+    pub define_enum: bool,
+    /// Print the trace as human-readable pseudocode rather than
+    /// S-expressions. The other options only affect how variables
+    /// and enumeration members are named.
+    pub pseudocode: bool,
 }
 
 impl WriteOpts {
     pub fn smtlib() -> Self {
-        WriteOpts { variable_prefix: "v".to_string(), enum_prefix: "e".to_string(), types: true, just_smt: true, define_enum: false }
+        WriteOpts {
+            variable_prefix: "v".to_string(),
+            enum_prefix: "e".to_string(),
+            types: true,
+            just_smt: true,
+            define_enum: false,
+            pseudocode: false,
+        }
     }
 }
 
 impl Default for WriteOpts {
     fn default() -> Self {
-        WriteOpts { variable_prefix: "v".to_string(), enum_prefix: "e".to_string(), types: false, just_smt: false, define_enum: true }
+        WriteOpts {
+            variable_prefix: "v".to_string(),
+            enum_prefix: "e".to_string(),
+            types: false,
+            just_smt: false,
+            define_enum: true,
+            pseudocode: false,
+        }
     }
 }
 
@@ -574,12 +1070,393 @@ fn write_binop(
     write!(buf, ")")
 }
 
+fn pseudo_bits(bits: u64, len: u32) -> String {
+    if len.is_multiple_of(4) {
+        format!("0x{:0width$X}", bits, width = (len / 4) as usize)
+    } else {
+        format!("0b{:0width$b}", bits, width = len as usize)
+    }
+}
+
+fn is_infix(exp: &Exp) -> bool {
+    use Exp::*;
+    matches!(
+        exp,
+        Eq(_, _)
+            | Neq(_, _)
+            | And(_, _)
+            | Or(_, _)
+            | Bvand(_, _)
+            | Bvor(_, _)
+            | Bvxor(_, _)
+            | Bvadd(_, _)
+            | Bvsub(_, _)
+            | Bvmul(_, _)
+            | Bvult(_, _)
+            | Bvslt(_, _)
+            | Bvule(_, _)
+            | Bvsle(_, _)
+            | Bvuge(_, _)
+            | Bvsge(_, _)
+            | Bvugt(_, _)
+            | Bvsgt(_, _)
+            | Concat(_, _)
+            | Ite(_, _, _)
+    )
+}
+
+fn pseudo_ty(ty: &Ty) -> String {
+    match ty {
+        Ty::BitVec(sz) => format!("bits({})", sz),
+        Ty::Bool => "boolean".to_string(),
+        _ => format!("{}", ty),
+    }
+}
+
+/// State for rendering a trace as pseudocode. Definitions used once
+/// are inlined at their use, while those used more than once are
+/// bound to a variable where they are defined, so the output stays
+/// the same size as the trace. A symbolic variable is printed as the
+/// register or memory location that currently holds it. If a location
+/// is overwritten while its old value is still needed, the old value
+/// is first saved into a variable.
+struct Pseudocode<'a, 'ir> {
+    defs: HashMap<Sym, Exp>,
+    shared: HashSet<Sym>,
+    tcx: TypeContext,
+    enums: Vec<usize>,
+    holders: HashMap<Sym, String>,
+    evicted: HashSet<Sym>,
+    needed: RefCell<HashSet<Sym>>,
+    symtab: &'a Symtab<'ir>,
+    opts: &'a WriteOpts,
+}
+
+impl<'a, 'ir> Pseudocode<'a, 'ir> {
+    fn resolve<'b>(&'b self, exp: &'b Exp) -> &'b Exp {
+        match exp {
+            Exp::Var(v) if !self.holders.contains_key(v) && !self.shared.contains(v) => match self.defs.get(v) {
+                Some(def) => self.resolve(def),
+                None => exp,
+            },
+            _ => exp,
+        }
+    }
+
+    fn operand(&self, exp: &Exp) -> String {
+        if is_infix(self.resolve(exp)) {
+            format!("({})", self.exp(exp))
+        } else {
+            self.exp(exp)
+        }
+    }
+
+    fn infix(&self, op: &str, lhs: &Exp, rhs: &Exp) -> String {
+        format!("{} {} {}", self.operand(lhs), op, self.operand(rhs))
+    }
+
+    fn call(&self, f: &str, args: &[&Exp]) -> String {
+        let args: Vec<String> = args.iter().map(|arg| self.exp(arg)).collect();
+        format!("{}({})", f, args.join(", "))
+    }
+
+    fn extend(&self, f: &str, ext: u32, exp: &Exp) -> String {
+        match self.tcx.width(exp) {
+            Some(width) => format!("{}({}, {})", f, self.exp(exp), width + ext),
+            None => format!("{}({}, +{})", f, self.exp(exp), ext),
+        }
+    }
+
+    fn var(&self, v: Sym) -> String {
+        format!("{}{}", self.opts.variable_prefix, v)
+    }
+
+    fn exp(&self, exp: &Exp) -> String {
+        use Exp::*;
+        match exp {
+            Var(v) => match (self.holders.get(v), self.defs.get(v)) {
+                (Some(location), _) => location.clone(),
+                (None, Some(_)) if self.shared.contains(v) => self.var(*v),
+                (None, Some(def)) => self.exp(def),
+                (None, None) => {
+                    if self.evicted.contains(v) {
+                        self.needed.borrow_mut().insert(*v);
+                    }
+                    self.var(*v)
+                }
+            },
+            Bits(bv) => match literal(exp) {
+                Some((bits, len)) => pseudo_bits(bits, len),
+                None => {
+                    let mut buf = Vec::new();
+                    write_bits(&mut buf, bv).unwrap();
+                    String::from_utf8(buf).unwrap()
+                }
+            },
+            Bits64(bits, len) => pseudo_bits(*bits, *len),
+            Enum(e) => format!("{}{}_{}", self.opts.enum_prefix, self.enums[e.enum_id], e.member),
+            Bool(b) => format!("{}", b),
+            Eq(lhs, rhs) => self.infix("==", lhs, rhs),
+            Neq(lhs, rhs) => self.infix("!=", lhs, rhs),
+            And(lhs, rhs) => self.infix("&&", lhs, rhs),
+            Or(lhs, rhs) => self.infix("||", lhs, rhs),
+            Not(exp) => format!("!{}", self.operand(exp)),
+            Bvnot(exp) => self.call("NOT", &[exp]),
+            Bvand(lhs, rhs) => self.infix("AND", lhs, rhs),
+            Bvor(lhs, rhs) => self.infix("OR", lhs, rhs),
+            Bvxor(lhs, rhs) => self.infix("EOR", lhs, rhs),
+            Bvnand(lhs, rhs) => format!("NOT({})", self.infix("AND", lhs, rhs)),
+            Bvnor(lhs, rhs) => format!("NOT({})", self.infix("OR", lhs, rhs)),
+            Bvxnor(lhs, rhs) => format!("NOT({})", self.infix("EOR", lhs, rhs)),
+            Bvneg(exp) => format!("-{}", self.operand(exp)),
+            Bvadd(lhs, rhs) => self.infix("+", lhs, rhs),
+            Bvsub(lhs, rhs) => self.infix("-", lhs, rhs),
+            Bvmul(lhs, rhs) => self.infix("*", lhs, rhs),
+            Bvudiv(lhs, rhs) => self.call("udiv", &[lhs, rhs]),
+            Bvsdiv(lhs, rhs) => self.call("sdiv", &[lhs, rhs]),
+            Bvurem(lhs, rhs) => self.call("urem", &[lhs, rhs]),
+            Bvsrem(lhs, rhs) => self.call("srem", &[lhs, rhs]),
+            Bvsmod(lhs, rhs) => self.call("smod", &[lhs, rhs]),
+            Bvult(lhs, rhs) => format!("{} < {}", self.call("UInt", &[lhs]), self.call("UInt", &[rhs])),
+            Bvule(lhs, rhs) => format!("{} <= {}", self.call("UInt", &[lhs]), self.call("UInt", &[rhs])),
+            Bvugt(lhs, rhs) => format!("{} > {}", self.call("UInt", &[lhs]), self.call("UInt", &[rhs])),
+            Bvuge(lhs, rhs) => format!("{} >= {}", self.call("UInt", &[lhs]), self.call("UInt", &[rhs])),
+            Bvslt(lhs, rhs) => format!("{} < {}", self.call("SInt", &[lhs]), self.call("SInt", &[rhs])),
+            Bvsle(lhs, rhs) => format!("{} <= {}", self.call("SInt", &[lhs]), self.call("SInt", &[rhs])),
+            Bvsgt(lhs, rhs) => format!("{} > {}", self.call("SInt", &[lhs]), self.call("SInt", &[rhs])),
+            Bvsge(lhs, rhs) => format!("{} >= {}", self.call("SInt", &[lhs]), self.call("SInt", &[rhs])),
+            Extract(hi, lo, exp) if hi == lo => format!("{}<{}>", self.operand(exp), hi),
+            Extract(hi, lo, exp) => format!("{}<{}:{}>", self.operand(exp), hi, lo),
+            ZeroExtend(ext, exp) => self.extend("zero_extend", *ext, exp),
+            SignExtend(ext, exp) => self.extend("sign_extend", *ext, exp),
+            Bvshl(lhs, rhs) => self.call("LSL", &[lhs, rhs]),
+            Bvlshr(lhs, rhs) => self.call("LSR", &[lhs, rhs]),
+            Bvashr(lhs, rhs) => self.call("ASR", &[lhs, rhs]),
+            Concat(lhs, rhs) => self.infix(":", lhs, rhs),
+            Ite(cond, then_exp, else_exp) => {
+                format!("if {} then {} else {}", self.exp(cond), self.operand(then_exp), self.operand(else_exp))
+            }
+            App(f, args) => {
+                let args: Vec<&Exp> = args.iter().collect();
+                self.call(&self.var(*f), &args)
+            }
+            Select(array, index) => format!("{}[{}]", self.operand(array), self.exp(index)),
+            Store(array, index, val) => self.call("store", &[array, index, val]),
+        }
+    }
+
+    fn val<B: BV>(&self, val: &Val<B>) -> String {
+        match val {
+            Val::Symbolic(v) => self.exp(&Exp::Var(*v)),
+            Val::Bits(bv) if bv.len() <= 64 => pseudo_bits(bv.lower_u64(), bv.len()),
+            Val::I64(n) => format!("{}", n),
+            Val::I128(n) => format!("{}", n),
+            Val::List(vals) | Val::Vector(vals) => {
+                let vals: Vec<String> = vals.iter().map(|val| self.val(val)).collect();
+                format!("[{}]", vals.join(", "))
+            }
+            Val::Struct(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, val)| format!("{} = {}", zencode::decode(self.symtab.to_str(*field)), self.val(val)))
+                    .collect();
+                format!("{{ {} }}", fields.join(", "))
+            }
+            Val::Ctor(ctor, val) => format!("{}({})", zencode::decode(self.symtab.to_str(*ctor)), self.val(val)),
+            _ => val.to_string(self.symtab),
+        }
+    }
+
+    /// Record that the symbolic variables in a value are held in a
+    /// location, named by `path`.
+    fn hold<B: BV>(&mut self, path: String, val: &Val<B>) {
+        match val {
+            Val::Symbolic(v) => {
+                self.holders.entry(*v).or_insert(path);
+            }
+            Val::List(vals) | Val::Vector(vals) => {
+                vals.iter().enumerate().for_each(|(i, val)| self.hold(format!("{}[{}]", path, i), val))
+            }
+            Val::Struct(fields) => fields.iter().for_each(|(field, val)| {
+                self.hold(format!("{}.{}", path, zencode::decode(self.symtab.to_str(*field))), val)
+            }),
+            Val::Ctor(_, val) => self.hold(path, val),
+            _ => (),
+        }
+    }
+
+    /// Forget about all the variables held in locations which overlap
+    /// `path`, returning those which are needed later along with the
+    /// location they were held in.
+    fn release(&mut self, path: &str) -> Vec<(Sym, String)> {
+        let overlaps = |location: &str| {
+            let (shorter, longer) = if location.len() < path.len() { (location, path) } else { (path, location) };
+            longer.starts_with(shorter)
+                && (longer.len() == shorter.len() || longer[shorter.len()..].starts_with(['.', '[']))
+        };
+        let released: Vec<(Sym, String)> =
+            self.holders.iter().filter(|(_, location)| overlaps(location)).map(|(v, l)| (*v, l.clone())).collect();
+        let needed = self.needed.borrow();
+        let mut saves = Vec::new();
+        for (v, location) in released {
+            self.holders.remove(&v);
+            self.evicted.insert(v);
+            if needed.contains(&v) {
+                saves.push((v, location))
+            }
+        }
+        saves.sort_by_key(|(v, _)| v.id);
+        saves
+    }
+
+    fn register(&self, reg: Name, accessor: &[Accessor]) -> String {
+        let mut path = zencode::decode(self.symtab.to_str(reg));
+        for Accessor::Field(field) in accessor {
+            path.push('.');
+            path.push_str(&zencode::decode(self.symtab.to_str(*field)))
+        }
+        path
+    }
+
+    fn memory<B: BV>(&self, address: &Val<B>, bytes: u32) -> String {
+        format!("Mem[{}, {}]", self.val(address), bytes)
+    }
+
+    fn write_statements<B: BV>(
+        &mut self,
+        buf: &mut dyn Write,
+        events: &[Event<B>],
+        read_values: &HashSet<Sym>,
+    ) -> std::io::Result<()> {
+        let uses = calculate_uses(events);
+
+        for event in events {
+            match event {
+                Smt(Def::DeclareConst(v, ty)) if !read_values.contains(v) => {
+                    writeln!(buf, "{} {} = UNKNOWN;", pseudo_ty(ty), self.var(*v))?
+                }
+                Smt(Def::DefineConst(v, exp)) if self.shared.contains(v) => {
+                    let ty = match self.tcx.vars.get(v) {
+                        Some(ty) => pseudo_ty(ty),
+                        None => "let".to_string(),
+                    };
+                    writeln!(buf, "{} {} = {};", ty, self.var(*v), self.exp(exp))?
+                }
+                Smt(Def::Assert(exp)) => writeln!(buf, "assume({});", self.exp(exp))?,
+                Smt(_) => (),
+                Fork(n, _, loc) => writeln!(buf, "// branch {} \"{}\"", n, loc)?,
+                ReadReg(reg, accessor, value) => self.hold(self.register(*reg, accessor), value),
+                WriteReg(reg, accessor, value) => {
+                    let path = self.register(*reg, accessor);
+                    let value_str = self.val(value);
+                    for (v, location) in self.release(&path) {
+                        writeln!(buf, "{} = {};", self.var(v), location)?
+                    }
+                    writeln!(buf, "{} = {};", path, value_str)?;
+                    self.hold(path, value)
+                }
+                ReadMem { value, address, bytes, .. } => {
+                    let location = self.memory(address, *bytes);
+                    match value {
+                        Val::Symbolic(v) if uses.get(v) == Some(&1) => writeln!(buf, "_ = {};", location)?,
+                        _ => self.hold(location, value),
+                    }
+                }
+                WriteMem { address, data, bytes, .. } => {
+                    let location = self.memory(address, *bytes);
+                    let data_str = self.val(data);
+                    // Any memory location may alias the one being written
+                    for (v, location) in self.release("Mem") {
+                        writeln!(buf, "{} = {};", self.var(v), location)?
+                    }
+                    writeln!(buf, "{} = {};", location, data_str)?
+                }
+                Branch { address } => writeln!(buf, "BranchTo({});", self.val(address))?,
+                Barrier { barrier_kind } => writeln!(buf, "Barrier({});", self.val(barrier_kind))?,
+                CacheOp { cache_op_kind, address } => {
+                    writeln!(buf, "CacheOp({}, {});", self.val(cache_op_kind), self.val(address))?
+                }
+                ReadTag { value, address } => writeln!(buf, "{} = ReadTag({});", self.val(value), self.val(address))?,
+                WriteTag { address, tag } => writeln!(buf, "WriteTag({}, {});", self.val(address), self.val(tag))?,
+                MarkReg { reg, mark } => writeln!(buf, "// mark {} \"{}\"", self.register(*reg, &[]), mark)?,
+                Exception(ExceptionKind::Taken) => writeln!(buf, "TakeException();")?,
+                Exception(ExceptionKind::Return) => writeln!(buf, "ExceptionReturn();")?,
+                Device { name, value } => writeln!(buf, "Device(\"{}\", {});", name, self.val(value))?,
+                Cycle => (),
+                Instr(value) => writeln!(buf, "// instruction {}", self.val(value))?,
+                Sleeping(v) => writeln!(buf, "// sleeping {}", self.exp(&Exp::Var(*v)))?,
+                SleepRequest => writeln!(buf, "// sleep request")?,
+                WakeupRequest => writeln!(buf, "// wakeup request")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write a trace as C-like pseudocode using ASL-style operators, for
+/// example `R1 = zero_extend(Mem[R2 + 0x8, 4], 64);`. SMT definitions
+/// are inlined into the statements that use them, so this is most
+/// readable after the trace has been simplified.
+fn write_pseudocode<B: BV>(
+    buf: &mut dyn Write,
+    events: &[Event<B>],
+    symtab: &Symtab,
+    opts: &WriteOpts,
+) -> std::io::Result<()> {
+    let mut pc = Pseudocode {
+        defs: HashMap::new(),
+        shared: HashSet::new(),
+        tcx: TypeContext::default(),
+        enums: Vec::new(),
+        holders: HashMap::new(),
+        evicted: HashSet::new(),
+        needed: RefCell::new(HashSet::new()),
+        symtab,
+        opts,
+    };
+
+    let uses = calculate_uses(events);
+    let mut read_values = HashSet::new();
+    for event in events {
+        match event {
+            Smt(def) => {
+                pc.tcx.add_def(def);
+                match def {
+                    Def::DefineConst(v, exp) => {
+                        let atomic =
+                            matches!(exp, Exp::Var(_) | Exp::Bits(_) | Exp::Bits64(_, _) | Exp::Enum(_) | Exp::Bool(_));
+                        if !atomic && matches!(uses.get(v), Some(n) if *n > 1) {
+                            pc.shared.insert(*v);
+                        }
+                        pc.defs.insert(*v, exp.clone());
+                    }
+                    Def::DefineEnum(_, size) => pc.enums.push(*size),
+                    _ => (),
+                }
+            }
+            ReadReg(_, _, value) | ReadMem { value, .. } => read_values.extend(value.symbolic_variables()),
+            _ => (),
+        }
+    }
+
+    // The first pass finds which overwritten values are used later,
+    // so the second pass knows which ones to save.
+    pc.write_statements(&mut std::io::sink(), events, &read_values)?;
+    pc.holders.clear();
+    pc.evicted.clear();
+    pc.write_statements(buf, events, &read_values)
+}
+
 pub fn write_events_with_opts<B: BV>(
     buf: &mut dyn Write,
     events: &[Event<B>],
     symtab: &Symtab,
     opts: &WriteOpts,
 ) -> std::io::Result<()> {
+    if opts.pseudocode {
+        return write_pseudocode(buf, events, symtab, opts);
+    }
+
     let mut tcx: HashMap<Sym, Ty> = HashMap::new();
     let mut ftcx: HashMap<Sym, (Vec<Ty>, Ty)> = HashMap::new();
     let mut enums: Vec<usize> = Vec::new();
@@ -671,6 +1548,14 @@ pub fn write_events_with_opts<B: BV>(
                 write!(buf, "\n  (cache-op {} {})", cache_op_kind.to_string(symtab), address.to_string(symtab))
             }
 
+            ReadTag { value, address } => {
+                write!(buf, "\n  (read-tag {} {})", value.to_string(symtab), address.to_string(symtab))
+            }
+
+            WriteTag { address, tag } => {
+                write!(buf, "\n  (write-tag {} {})", address.to_string(symtab), tag.to_string(symtab))
+            }
+
             WriteReg(n, acc, v) => write!(
                 buf,
                 "\n  (write-reg |{}| {} {})",
@@ -697,6 +1582,12 @@ pub fn write_events_with_opts<B: BV>(
                 write!(buf, "\n  (mark-reg |{}| \"{}\")", zencode::decode(symtab.to_str(*reg)), mark)
             }
 
+            Exception(ExceptionKind::Taken) => write!(buf, "\n  (take-exception)"),
+
+            Exception(ExceptionKind::Return) => write!(buf, "\n  (exception-return)"),
+
+            Device { name, value } => write!(buf, "\n  (device \"{}\" {})", name, value.to_string(symtab)),
+
             Cycle => write!(buf, "\n  (cycle)"),
 
             Instr(value) => write!(buf, "\n  (instr {})", value.to_string(symtab)),
@@ -717,3 +1608,159 @@ pub fn write_events_with_opts<B: BV>(
 pub fn write_events<B: BV>(buf: &mut dyn Write, events: &[Event<B>], symtab: &Symtab) {
     write_events_with_opts(buf, events, symtab, &WriteOpts::default()).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::concrete::bitvector64::B64;
+
+    fn var(id: u32) -> Exp {
+        Exp::Var(Sym::from_u32(id))
+    }
+
+    fn define(id: u32, exp: Exp) -> Event<B64> {
+        Smt(Def::DefineConst(Sym::from_u32(id), exp))
+    }
+
+    fn declare(id: u32, sz: u32) -> Event<B64> {
+        Smt(Def::DeclareConst(Sym::from_u32(id), Ty::BitVec(sz)))
+    }
+
+    fn write_reg(id: u32) -> Event<B64> {
+        WriteReg(crate::ir::RETURN, Vec::new(), Val::Symbolic(Sym::from_u32(id)))
+    }
+
+    // The passes expect the most recent event first
+    fn run<F: Fn(&mut Vec<Event<B64>>)>(pass: F, mut events: Vec<Event<B64>>) -> Vec<Event<B64>> {
+        events.reverse();
+        pass(&mut events);
+        events.reverse();
+        events
+    }
+
+    fn defined(event: &Event<B64>) -> Option<&Exp> {
+        match event {
+            Smt(Def::DefineConst(_, exp)) => Some(exp),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn extract_concat() {
+        use Exp::*;
+        let tcx = {
+            let mut tcx = TypeContext::default();
+            tcx.vars.insert(Sym::from_u32(0), Ty::BitVec(32));
+            tcx.vars.insert(Sym::from_u32(1), Ty::BitVec(32));
+            tcx
+        };
+        let concat = Concat(Box::new(var(0)), Box::new(var(1)));
+        assert_eq!(simplify_exp(Extract(31, 0, Box::new(concat.clone())), &tcx), var(1));
+        assert_eq!(simplify_exp(Extract(63, 32, Box::new(concat.clone())), &tcx), var(0));
+        assert_eq!(
+            simplify_exp(Extract(39, 24, Box::new(concat)), &tcx),
+            Concat(Box::new(Extract(7, 0, Box::new(var(0)))), Box::new(Extract(31, 24, Box::new(var(1)))))
+        );
+        assert_eq!(simplify_exp(Extract(63, 32, Box::new(ZeroExtend(32, Box::new(var(0))))), &tcx), Bits64(0, 32));
+        assert_eq!(simplify_exp(ZeroExtend(32, Box::new(Bits64(0xFF, 8))), &tcx), Bits64(0xFF, 40));
+        assert_eq!(simplify_exp(SignExtend(8, Box::new(Bits64(0x80, 8))), &tcx), Bits64(0xFF80, 16));
+    }
+
+    #[test]
+    fn redundant_ite() {
+        use Exp::*;
+        let tcx = TypeContext::default();
+        let cond = Eq(Box::new(var(0)), Box::new(var(1)));
+        assert_eq!(simplify_exp(Ite(Box::new(cond.clone()), Box::new(var(2)), Box::new(var(2))), &tcx), var(2));
+        assert_eq!(simplify_exp(Ite(Box::new(cond.clone()), Box::new(Bool(true)), Box::new(Bool(false))), &tcx), cond);
+        assert_eq!(
+            simplify_exp(
+                Ite(Box::new(Eq(Box::new(var(3)), Box::new(var(3)))), Box::new(var(1)), Box::new(var(2))),
+                &tcx
+            ),
+            var(1)
+        );
+    }
+
+    #[test]
+    fn constant_chain() {
+        use Exp::*;
+        let events = vec![
+            define(0, Bits64(1, 64)),
+            define(1, Bvadd(Box::new(var(0)), Box::new(Bits64(2, 64)))),
+            define(2, Extract(7, 0, Box::new(var(1)))),
+            write_reg(2),
+        ];
+        let events = run(simplify, events);
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            WriteReg(_, _, Val::Bits(bv)) => assert_eq!(*bv, B64::new(3, 8)),
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn common_subexpressions() {
+        use Exp::*;
+        let events = vec![
+            declare(0, 64),
+            define(1, Bvadd(Box::new(var(0)), Box::new(Bits64(4, 64)))),
+            define(2, Bvadd(Box::new(var(0)), Box::new(Bits64(4, 64)))),
+            write_reg(1),
+            write_reg(2),
+        ];
+        let events = run(eliminate_common_subexpressions, events);
+        assert_eq!(events.len(), 4);
+        match &events[3] {
+            WriteReg(_, _, Val::Symbolic(v)) => assert_eq!(*v, Sym::from_u32(1)),
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn single_use() {
+        use Exp::*;
+        let events = vec![
+            declare(0, 64),
+            define(1, Bvnot(Box::new(var(0)))),
+            define(2, Bvadd(Box::new(var(1)), Box::new(var(0)))),
+            write_reg(2),
+        ];
+        let events = run(inline_single_use, events);
+        assert_eq!(events.len(), 3);
+        assert_eq!(defined(&events[1]), Some(&Bvadd(Box::new(Bvnot(Box::new(var(0)))), Box::new(var(0)))))
+    }
+
+    // Write a trace as pseudocode, with the final value of `v` written to R0
+    fn pseudocode(mut events: Vec<Event<B64>>, v: u32) -> String {
+        let mut symtab = Symtab::new();
+        let r0 = symtab.intern("zR0");
+        events.push(WriteReg(r0, Vec::new(), Val::Symbolic(Sym::from_u32(v))));
+        let opts = WriteOpts { pseudocode: true, ..WriteOpts::default() };
+        let mut buf = Vec::new();
+        write_events_with_opts(&mut buf, &events, &symtab, &opts).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn pseudocode_inline() {
+        use Exp::*;
+        let events = vec![declare(0, 64), define(1, Bvadd(Box::new(var(0)), Box::new(Bits64(0xFF, 16))))];
+        assert_eq!(pseudocode(events, 1), "bits(64) v0 = UNKNOWN;\nR0 = v0 + 0x00FF;\n")
+    }
+
+    #[test]
+    fn pseudocode_shared() {
+        use Exp::*;
+        // Each definition uses the previous one twice, so inlining
+        // them all would double the output at each step
+        let mut events = vec![declare(0, 64)];
+        for i in 1..=32 {
+            events.push(define(i, Bvadd(Box::new(var(i - 1)), Box::new(var(i - 1)))))
+        }
+        let output = pseudocode(events, 32);
+        assert!(output.contains("bits(64) v1 = v0 + v0;\n"));
+        assert!(output.ends_with("bits(64) v31 = v30 + v30;\nR0 = v31 + v31;\n"));
+        assert_eq!(output.lines().count(), 33)
+    }
+}
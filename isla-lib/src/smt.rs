@@ -116,7 +116,7 @@ pub mod smtlib {
         }
     }
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
     pub enum Exp {
         Var(Sym),
         Bits(Vec<bool>),
@@ -347,6 +347,14 @@ impl Accessor {
     }
 }
 
+/// Whether an exception event enters or returns from an exception
+/// handler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExceptionKind {
+    Taken,
+    Return,
+}
+
 #[derive(Clone, Debug)]
 pub enum Event<B> {
     Smt(Def),
@@ -358,6 +366,10 @@ pub enum Event<B> {
     Branch { address: Val<B> },
     Barrier { barrier_kind: Val<B> },
     CacheOp { cache_op_kind: Val<B>, address: Val<B> },
+    ReadTag { value: Val<B>, address: Val<B> },
+    WriteTag { address: Val<B>, tag: Val<B> },
+    Exception(ExceptionKind),
+    Device { name: String, value: Val<B> },
     MarkReg { reg: Name, mark: String },
     Cycle,
     Instr(Val<B>),
@@ -437,6 +449,14 @@ impl<B: BV> Event<B> {
         }
     }
 
+    pub fn is_exception(&self) -> bool {
+        matches!(self, Event::Exception(_))
+    }
+
+    pub fn is_tag(&self) -> bool {
+        matches!(self, Event::ReadTag { .. } | Event::WriteTag { .. })
+    }
+
     pub fn is_memory_read(&self) -> bool {
         match self {
             Event::ReadMem { .. } => true,
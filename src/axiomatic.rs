@@ -30,7 +30,8 @@
 use crossbeam::queue::SegQueue;
 use crossbeam::thread;
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
 use std::error::Error;
 use std::ffi::OsStr;
 use std::fmt;
@@ -40,12 +41,15 @@ use std::path::{Path, PathBuf};
 use std::process::{self, Command};
 use std::time::Instant;
 
+use isla_axiomatic::axiomatic::model::Model;
 use isla_axiomatic::cat_config::tcx_from_config;
-use isla_axiomatic::graph::{graph_from_z3_output, Graph};
-use isla_axiomatic::litmus::Litmus;
-use isla_axiomatic::run_litmus;
+use isla_axiomatic::graph::{graph_from_violation, graph_from_z3_output, Graph};
+use isla_axiomatic::litmus::{Litmus, Loc, Quantifier};
+use isla_axiomatic::run_litmus::{self, Explanation};
 use isla_cat::cat;
-use isla_lib::concrete::bitvector64::B64;
+use isla_lib::cache::Cache;
+use isla_lib::concrete::{bitvector64::B64, BV};
+use isla_lib::config::ISAConfig;
 use isla_lib::init::{initialize_architecture, Initialized};
 use isla_lib::ir::*;
 use isla_lib::log;
@@ -145,6 +149,17 @@ fn isla_main() -> i32 {
         "Open graphviz dot files in default image viewer. Implies --temp-dot unless --dot is set.",
     );
     opts.optopt("", "refs", "references to compare output with", "<path>");
+    opts.optflag("", "outcomes", "Enumerate every final state allowed by the model, like herd's States output");
+    opts.optflag(
+        "",
+        "symbolic",
+        "Decide each test with a single SMT query, letting the solver choose the path through each thread",
+    );
+    opts.optflag(
+        "",
+        "explain",
+        "Report which checks in the model forbid each candidate. With --dot, draws the violations.",
+    );
     opts.optopt(
         "",
         "cache",
@@ -172,6 +187,7 @@ fn isla_main() -> i32 {
         eprintln!("Invalid cache directory");
         return 1;
     }
+    let cache = Cache::new(cache, &arch_hash);
 
     let dot_path = match matches.opt_str("dot").map(PathBuf::from) {
         Some(path) => {
@@ -190,6 +206,9 @@ fn isla_main() -> i32 {
         }
     };
     let view = matches.opt_present("view");
+    let outcomes = matches.opt_present("outcomes");
+    let explain = matches.opt_present("explain");
+    let symbolic = matches.opt_present("symbolic");
 
     let timeout: Option<u64> = match matches.opt_get("timeout") {
         Ok(timeout) => timeout,
@@ -275,27 +294,7 @@ fn isla_main() -> i32 {
 
             scope.spawn(move |_| {
                 for (i, litmus_file) in GroupIndex::new(tests, group_id, thread_groups).enumerate() {
-                    let litmus = if litmus_file.extension() == Some(OsStr::new("litmus")) {
-                        let output = Command::new("isla-litmus")
-                            .arg(litmus_file)
-                            .output()
-                            .expect("Failed to invoke isla-litmus");
-
-                        if output.status.success() {
-                            String::from_utf8_lossy(&output.stdout).to_string()
-                        } else {
-                            eprintln!(
-                                "Failed to translate litmus file: {}\n{}",
-                                litmus_file.display(),
-                                String::from_utf8_lossy(&output.stderr)
-                            );
-                            continue;
-                        }
-                    } else {
-                        fs::read_to_string(&litmus_file).expect("Failed to read test file")
-                    };
-
-                    let litmus = match Litmus::parse(&litmus, &shared_state.symtab, isa_config) {
+                    let litmus = match Litmus::from_file(litmus_file, &shared_state.symtab, isa_config) {
                         Ok(litmus) => litmus,
                         Err(msg) => {
                             eprintln!("Failed to parse litmus file: {}\n{}", litmus_file.display(), msg);
@@ -304,9 +303,190 @@ fn isla_main() -> i32 {
                     };
 
                     let now = Instant::now();
+
+                    if outcomes {
+                        let outcome_queue = SegQueue::new();
+                        let run_info = run_litmus::outcomes_per_candidate::<B64, _, Infallible>(
+                            threads_per_test,
+                            timeout,
+                            &litmus,
+                            !use_ifetch,
+                            cat,
+                            regs.clone(),
+                            lets.clone(),
+                            shared_state,
+                            isa_config,
+                            cache,
+                            &|_, _, outcomes| {
+                                outcome_queue.push(outcomes);
+                                Ok(())
+                            },
+                        );
+
+                        if let Err(err) = run_info {
+                            eprintln!("{}", err);
+                            print_results(&litmus.name, litmus.quantifier, now, &[Error], None);
+                            continue;
+                        }
+
+                        let mut states = HashSet::new();
+                        while let Ok(outcomes) = outcome_queue.pop() {
+                            states.extend(outcomes)
+                        }
+                        print_states(&litmus, &shared_state.symtab, isa_config, now, states);
+                        continue;
+                    }
+
+                    if symbolic {
+                        let result_queue = SegQueue::new();
+                        let run_info = run_litmus::smt_output_symbolic::<B64, _, Infallible>(
+                            &format!("g{}t{}", group_id, i),
+                            threads_per_test,
+                            timeout,
+                            &litmus,
+                            !use_ifetch,
+                            cat,
+                            regs.clone(),
+                            lets.clone(),
+                            shared_state,
+                            isa_config,
+                            cache,
+                            &|symbolic, exec, _, z3_output| {
+                                if let Some(model) = z3_output.strip_prefix("sat") {
+                                    let events: Vec<&str> = exec.events.iter().map(|ev| ev.name.as_ref()).collect();
+                                    let chosen = Model::<B64>::parse(&events, model)
+                                        .ok_or_else(|| "Failed to parse model".to_string())
+                                        .and_then(|mut model| {
+                                            symbolic.chosen_paths(&mut model).map_err(|err| err.to_string())
+                                        });
+                                    match chosen {
+                                        Ok(chosen) => result_queue.push((Allowed(None), Some(chosen))),
+                                        Err(err) => {
+                                            eprintln!("Failed to read the chosen candidate from the model: {}", err);
+                                            result_queue.push((Error, None))
+                                        }
+                                    }
+                                } else if z3_output.starts_with("unsat") {
+                                    result_queue.push((Forbidden, None))
+                                } else {
+                                    result_queue.push((Error, None))
+                                }
+                                Ok(())
+                            },
+                        );
+
+                        let ref_result = refs.get(&litmus.name);
+                        if let Err(err) = run_info {
+                            eprintln!("{}", err);
+                            print_results(&litmus.name, litmus.quantifier, now, &[Error], ref_result);
+                            continue;
+                        }
+
+                        let mut results = Vec::new();
+                        while let Ok((result, chosen)) = result_queue.pop() {
+                            if let Some(chosen) = chosen {
+                                let paths: Vec<String> = chosen
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(tid, path)| format!("thread {} path {}", tid, path))
+                                    .collect();
+                                println!("{} allowed by candidate: {}", litmus.name, paths.join(", "))
+                            }
+                            results.push(result)
+                        }
+                        print_results(&litmus.name, litmus.quantifier, now, &results, ref_result);
+                        continue;
+                    }
+
+                    if explain {
+                        let explanation_queue = SegQueue::new();
+                        let run_info = run_litmus::explain_per_candidate::<B64, _, Infallible>(
+                            threads_per_test,
+                            timeout,
+                            &litmus,
+                            !use_ifetch,
+                            cat,
+                            regs.clone(),
+                            lets.clone(),
+                            shared_state,
+                            isa_config,
+                            cache,
+                            dot_path.is_some(),
+                            &|candidate, exec, footprints, explanation| {
+                                let graph = match (dot_path, &explanation) {
+                                    (Some(_), Explanation::Forbidden { relaxed, witness: Some(witness), .. }) => {
+                                        graph_from_violation(
+                                            exec,
+                                            footprints,
+                                            witness,
+                                            &litmus,
+                                            cat,
+                                            use_ifetch,
+                                            shared_state,
+                                            isa_config,
+                                            relaxed,
+                                        )
+                                        .map_err(|err| eprintln!("Failed to generate graph: {}", err))
+                                        .ok()
+                                    }
+                                    _ => None,
+                                };
+                                explanation_queue.push((candidate, explanation, graph));
+                                Ok(())
+                            },
+                        );
+
+                        let run_info = match run_info {
+                            Ok(run_info) => run_info,
+                            Err(err) => {
+                                eprintln!("{}", err);
+                                print_results(&litmus.name, litmus.quantifier, now, &[Error], None);
+                                continue;
+                            }
+                        };
+
+                        let mut explanations = Vec::new();
+                        while let Ok(explanation) = explanation_queue.pop() {
+                            explanations.push(explanation)
+                        }
+                        explanations.sort_by_key(|(candidate, _, _)| *candidate);
+
+                        let mut results = Vec::new();
+                        for (candidate, explanation, graph) in explanations {
+                            let i = candidate + 1;
+                            match explanation {
+                                Explanation::Allowed => {
+                                    println!("{} candidate {}: allowed", litmus.name, i);
+                                    results.push(Allowed(None))
+                                }
+                                Explanation::Unreachable => {
+                                    println!("{} candidate {}: final state unreachable", litmus.name, i);
+                                    results.push(Forbidden)
+                                }
+                                Explanation::Forbidden { violated, .. } => {
+                                    println!("{} candidate {}: forbidden by {}", litmus.name, i, violated.join(", "));
+                                    results.push(Forbidden)
+                                }
+                            }
+                            if let (Some(dot_path), Some(graph)) = (dot_path, graph) {
+                                let dot_file = dot_path.join(format!("{}_forbidden_{}.dot", litmus.name, i));
+                                std::fs::write(&dot_file, graph.to_string()).expect("Failed to write dot file");
+                            }
+                        }
+                        if run_info.pruned > 0 {
+                            println!(
+                                "{} {} candidates pruned: a read value cannot be written",
+                                litmus.name, run_info.pruned
+                            );
+                            results.extend((0..run_info.pruned).map(|_| Forbidden))
+                        }
+                        print_results(&litmus.name, litmus.quantifier, now, &results, refs.get(&litmus.name));
+                        continue;
+                    }
+
                     let result_queue = SegQueue::new();
 
-                    let run_info = run_litmus::smt_output_per_candidate::<B64, _, _, ()>(
+                    let run_info = run_litmus::smt_output_per_candidate::<B64, _, ()>(
                         &format!("g{}t{}", group_id, i),
                         threads_per_test,
                         timeout,
@@ -321,7 +501,16 @@ fn isla_main() -> i32 {
                         &|exec, footprints, z3_output| {
                             if z3_output.starts_with("sat") {
                                 let graph = if dot_path.is_some() {
-                                    match graph_from_z3_output(exec, footprints, z3_output, &litmus, &cat, use_ifetch) {
+                                    match graph_from_z3_output(
+                                        exec,
+                                        footprints,
+                                        z3_output,
+                                        &litmus,
+                                        cat,
+                                        use_ifetch,
+                                        shared_state,
+                                        isa_config,
+                                    ) {
                                         Ok(graph) => Some(Box::new(graph)),
                                         Err(err) => {
                                             eprintln!("Failed to generate graph: {}", err);
@@ -343,17 +532,24 @@ fn isla_main() -> i32 {
 
                     let ref_result = refs.get(&litmus.name);
 
-                    if run_info.is_err() {
-                        print_results(&litmus.name, now, &[Error], ref_result);
-                        continue;
-                    }
+                    let run_info = match run_info {
+                        Ok(run_info) => run_info,
+                        Err(_) => {
+                            print_results(&litmus.name, litmus.quantifier, now, &[Error], ref_result);
+                            continue;
+                        }
+                    };
 
                     let mut results: Vec<AxResult> = Vec::new();
                     while let Ok(result) = result_queue.pop() {
                         results.push(result)
                     }
+                    // Pruned candidates are forbidden by every model
+                    for _ in 0..run_info.pruned {
+                        results.push(Forbidden)
+                    }
 
-                    print_results(&litmus.name, now, &results, ref_result);
+                    print_results(&litmus.name, litmus.quantifier, now, &results, ref_result);
 
                     if let Some(dot_path) = dot_path {
                         for (i, allowed) in results.iter().filter(|result| result.is_allowed()).enumerate() {
@@ -385,7 +581,13 @@ fn isla_main() -> i32 {
     0
 }
 
-fn print_results(name: &str, start_time: Instant, results: &[AxResult], expected: Option<&AxResult>) {
+fn print_results(
+    name: &str,
+    quantifier: Quantifier,
+    start_time: Instant,
+    results: &[AxResult],
+    expected: Option<&AxResult>,
+) {
     if results.is_empty() {
         let prefix = format!("{} no executions {}", name, start_time.elapsed().as_millis());
         println!("{:.<100} \x1b[95m\x1b[1merror\x1b[0m", prefix);
@@ -402,17 +604,25 @@ fn print_results(name: &str, start_time: Instant, results: &[AxResult], expected
 
     let count = format!("{} of {}", results.iter().filter(|result| result.is_allowed()).count(), results.len());
 
+    // Like herd, report whether the final condition holds under its quantifier
+    let condition = if got.is_error() {
+        format!("{} ?", quantifier)
+    } else {
+        format!("{} {}", quantifier, if quantifier.holds(got.is_allowed()) { "Ok" } else { "No" })
+    };
+
     let prefix = if let Some(reference) = expected {
         format!(
-            "{} {} ({}) reference: {} {}ms ",
+            "{} {} ({}) {} reference: {} {}ms ",
             name,
             got.short_name(),
             count,
+            condition,
             reference.short_name(),
             start_time.elapsed().as_millis()
         )
     } else {
-        format!("{} {} ({}) {}ms ", name, got.short_name(), count, start_time.elapsed().as_millis())
+        format!("{} {} ({}) {} {}ms ", name, got.short_name(), count, condition, start_time.elapsed().as_millis())
     };
 
     let result = if let Some(reference) = expected {
@@ -430,6 +640,38 @@ fn print_results(name: &str, start_time: Instant, results: &[AxResult], expected
     println!("{:.<100} {}", prefix, result)
 }
 
+/// Print the allowed final states of a litmus test in the same
+/// format as herd.
+fn print_states(
+    litmus: &Litmus<B64>,
+    symtab: &Symtab,
+    isa_config: &ISAConfig<B64>,
+    start_time: Instant,
+    states: HashSet<Vec<B64>>,
+) {
+    let locs = litmus.final_assertion.locations();
+    let mut states: Vec<Vec<B64>> = states.into_iter().collect();
+    states.sort_by_key(|state| state.iter().map(|bv| bv.lower_u64()).collect::<Vec<_>>());
+
+    let mut observed = false;
+    let mut output = format!("Test {} {}\nStates {}\n", litmus.name, litmus.quantifier.herd_kind(), states.len());
+    for state in &states {
+        for (loc, value) in locs.iter().zip(state.iter()) {
+            output.push_str(&format!(
+                "{}={}; ",
+                loc.name(&litmus.symbolic_addrs, symtab, isa_config),
+                value.lower_u64()
+            ))
+        }
+        output.push('\n');
+        let value = |loc: &Loc| locs.iter().position(|l| *l == loc).map(|i| state[i]);
+        observed |= litmus.final_assertion.eval(&value).unwrap_or(false)
+    }
+    output.push_str(if litmus.quantifier.holds(observed) { "Ok\n" } else { "No\n" });
+    output.push_str(&format!("Time {} {}ms", litmus.name, start_time.elapsed().as_millis()));
+    println!("{}", output)
+}
+
 #[derive(Debug)]
 pub enum AtLineError {
     NoParse(usize, String),
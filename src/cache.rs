@@ -0,0 +1,157 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use getopts::Options;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::process::exit;
+
+use isla_lib::cache::{hash_arch_options, Cache, CacheEntry, EntryStatus};
+
+fn print_usage(opts: &Options, code: i32) -> ! {
+    let brief = "Usage: isla-cache <list|verify|prune> --cache <path> [options]\n\n\
+                 list    show all entries in the cache\n\
+                 verify  check every entry, exiting with an error if any are invalid\n\
+                 prune   remove invalid entries and left over temporary files";
+    eprint!("{}", opts.usage(brief));
+    exit(code)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut opts = Options::new();
+    opts.reqopt("", "cache", "cache directory", "<path>");
+    opts.optopt("A", "arch", "architecture file, used to compute the architecture hash", "<file>");
+    opts.optopt("C", "config", "architecture config file, used to compute the architecture hash", "<file>");
+    opts.optmulti("R", "register", "register assignment, used to compute the architecture hash", "<register>=<value>");
+    opts.optmulti("L", "linearize", "linearized function, used to compute the architecture hash", "<id>");
+    opts.optopt("", "arch-hash", "architecture hash as a hexadecimal string", "<hash>");
+    opts.optflag("", "other-architectures", "when pruning, also remove entries for other architectures");
+    opts.optflag("h", "help", "print this help message");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}", f);
+            print_usage(&opts, 1)
+        }
+    };
+    if matches.opt_present("help") {
+        print_usage(&opts, 0)
+    }
+
+    let command = match matches.free.as_slice() {
+        [command] => command.clone(),
+        _ => print_usage(&opts, 1),
+    };
+
+    let arch_hash = if let Some(arch) = matches.opt_str("arch") {
+        // As in opts::parse and opts::parse_with_arch, the contents
+        // of the architecture and config files are followed by any
+        // options which modify the architecture
+        let mut hasher = Sha256::new();
+        for file in Some(arch).iter().chain(matches.opt_str("config").iter()) {
+            match fs::read_to_string(file) {
+                Ok(contents) => hasher.input(&contents),
+                Err(e) => {
+                    eprintln!("Could not read {}: {}", file, e);
+                    exit(1)
+                }
+            }
+        }
+        hash_arch_options(&mut hasher, &matches.opt_strs("register"), &matches.opt_strs("linearize"));
+        Some(format!("{:x}", hasher.result()))
+    } else {
+        matches.opt_str("arch-hash")
+    };
+
+    let cache = Cache::for_maintenance(matches.opt_str("cache").unwrap(), arch_hash.as_deref());
+    if !cache.dir().is_dir() {
+        eprintln!("Invalid cache directory");
+        exit(1)
+    }
+
+    let describe = |entry: &CacheEntry| {
+        let key = entry.key.as_deref().unwrap_or("?");
+        let arch_hash = entry.arch_hash.as_deref().map(|hash| &hash[..std::cmp::min(16, hash.len())]).unwrap_or("?");
+        let version = entry.version.map(|v| v.to_string()).unwrap_or_else(|| "?".to_string());
+        format!("{} arch:{} version:{} size:{}", key, arch_hash, version, entry.size)
+    };
+
+    match command.as_str() {
+        "list" | "verify" => {
+            let entries = match cache.entries() {
+                Ok(entries) => entries,
+                Err(e) => {
+                    eprintln!("Failed to read cache: {}", e);
+                    exit(1)
+                }
+            };
+            let mut invalid = 0;
+            for entry in &entries {
+                // Without an architecture hash, entries for any
+                // architecture are considered valid
+                let status = match entry.status {
+                    EntryStatus::OtherArchitecture if arch_hash.is_none() => EntryStatus::Valid,
+                    ref status => status.clone(),
+                };
+                if status != EntryStatus::Valid {
+                    invalid += 1
+                }
+                if command == "list" || status != EntryStatus::Valid {
+                    println!("{} ({})", describe(entry), status)
+                }
+            }
+            eprintln!("{} entries, {} invalid", entries.len(), invalid);
+            if command == "verify" && invalid > 0 {
+                exit(1)
+            }
+        }
+        "prune" => {
+            let other_architectures = matches.opt_present("other-architectures");
+            if other_architectures && arch_hash.is_none() {
+                eprintln!("--other-architectures requires --arch or --arch-hash");
+                exit(1)
+            }
+            match cache.prune(other_architectures) {
+                Ok(removed) => {
+                    for entry in &removed {
+                        println!("removed {} ({})", describe(entry), entry.status)
+                    }
+                    eprintln!("{} entries removed", removed.len())
+                }
+                Err(e) => {
+                    eprintln!("Failed to prune cache: {}", e);
+                    exit(1)
+                }
+            }
+        }
+        _ => print_usage(&opts, 1),
+    }
+}
@@ -0,0 +1,651 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! `isla-equivalence` checks whether two instructions (or two short
+//! sequences of instructions) have the same effect on registers and
+//! memory. Both sides are executed from a shared symbolic initial
+//! register state, and for every pair of feasible paths we ask the
+//! solver whether the final register values or memory writes can
+//! differ. If they can the model is printed as a counterexample.
+//!
+//! Memory is treated as an unknown function of the address, so reads
+//! of the same width from the same address on either side return the
+//! same value. Reads do not observe earlier writes within the same
+//! sequence, and both sides must perform the same sequence of writes.
+
+use crossbeam::queue::SegQueue;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::process::exit;
+use std::sync::Arc;
+
+use isla_lib::concrete::{bitvector64::B64, BV};
+use isla_lib::error::ExecError;
+use isla_lib::executor;
+use isla_lib::executor::{Backtrace, LocalFrame};
+use isla_lib::init::{initialize_architecture, Initialized};
+use isla_lib::ir::*;
+use isla_lib::primop::smt_value;
+use isla_lib::smt::smtlib::{Def, Exp};
+use isla_lib::smt::{checkpoint, Checkpoint, Config, Context, Event, Model, SmtResult, Solver};
+use isla_lib::zencode;
+
+mod opts;
+use opts::{parse_opcode, CommonOpts};
+
+fn main() {
+    let code = isla_main();
+    unsafe { isla_lib::smt::finalize_solver() };
+    exit(code)
+}
+
+fn isla_main() -> i32 {
+    let mut opts = opts::common_opts();
+    opts.reqopt("", "left", "first instruction sequence, separated by `;`", "<instructions>");
+    opts.reqopt("", "right", "second instruction sequence, separated by `;`", "<instructions>");
+    opts.optopt("e", "endianness", "instruction encoding endianness (little default)", "big/little");
+    opts.optflag("x", "hex", "parse instructions as hexadecimal opcodes, rather than assembly");
+    opts.optmulti("", "ignore", "ignore the final value of a register", "<register>");
+
+    let mut hasher = Sha256::new();
+    let (matches, arch) = opts::parse(&mut hasher, &opts);
+    let CommonOpts { num_threads, mut arch, symtab, isa_config } =
+        opts::parse_with_arch(&mut hasher, &opts, &matches, &arch);
+
+    let Initialized { regs, lets, shared_state } =
+        initialize_architecture(&mut arch, symtab, &isa_config, AssertionMode::Optimistic);
+
+    let little_endian = match matches.opt_str("endianness").as_deref() {
+        Some("little") | None => true,
+        Some("big") => false,
+        Some(_) => {
+            eprintln!("--endianness argument must be one of either `big` or `little`");
+            exit(1)
+        }
+    };
+
+    let hex = matches.opt_present("hex");
+    let parse_sequence = |sequence: String| -> Vec<B64> {
+        sequence
+            .split(';')
+            .map(str::trim)
+            .filter(|instr| !instr.is_empty())
+            .map(|instr| match parse_opcode(instr, hex, little_endian, &isa_config) {
+                Ok(opcode) => opcode,
+                Err(msg) => {
+                    eprintln!("{}", msg);
+                    exit(1)
+                }
+            })
+            .collect()
+    };
+    let left = parse_sequence(matches.opt_str("left").unwrap());
+    let right = parse_sequence(matches.opt_str("right").unwrap());
+
+    let mut ignored = isa_config.ignored_registers.clone();
+    for reg in matches.opt_strs("ignore") {
+        match shared_state.symtab.get(&zencode::encode(&reg)) {
+            Some(reg) => {
+                ignored.insert(reg);
+            }
+            None => {
+                eprintln!("Register {} does not exist in the specified architecture", reg);
+                return 1;
+            }
+        }
+    }
+
+    // Make every register we can represent symbolically an explicit
+    // variable, so both sides start from the same initial state.
+    let mut initial_regs = regs.clone();
+    let initial_checkpoint = {
+        let ctx = Context::new(Config::new());
+        let mut solver = Solver::<B64>::new(&ctx);
+        for uval in initial_regs.values_mut() {
+            if let UVal::Uninit(ty) = uval {
+                match executor::symbolic(ty, &shared_state, &mut solver) {
+                    Ok(Val::Poison) | Err(_) => (),
+                    Ok(value) => *uval = UVal::Init(value),
+                }
+            }
+        }
+        checkpoint(&mut solver)
+    };
+
+    let left_paths =
+        match execute_sequence(num_threads, &left, (initial_regs.clone(), initial_checkpoint), &lets, &shared_state) {
+            Ok(paths) => paths,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                return 1;
+            }
+        };
+
+    for (left_regs, left_checkpoint) in left_paths {
+        let left_len = trace_events(&left_checkpoint).len();
+        let right_paths = match execute_sequence(
+            num_threads,
+            &right,
+            (initial_regs.clone(), left_checkpoint),
+            &lets,
+            &shared_state,
+        ) {
+            Ok(paths) => paths,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                return 1;
+            }
+        };
+
+        for (right_regs, right_checkpoint) in right_paths {
+            let comparison = Comparison {
+                initial_regs: &initial_regs,
+                left_regs: &left_regs,
+                right_regs: &right_regs,
+                left_len,
+                ignored: &ignored,
+                symtab: &shared_state.symtab,
+            };
+            match comparison.check(right_checkpoint) {
+                Ok(None) => (),
+                Ok(Some(counterexample)) => {
+                    println!("not equivalent");
+                    print!("{}", counterexample);
+                    return 1;
+                }
+                Err(msg) => {
+                    eprintln!("{}", msg);
+                    return 1;
+                }
+            }
+        }
+    }
+
+    println!("equivalent");
+    0
+}
+
+/// The final register state and solver state of a path.
+type Path<'ir> = (Bindings<'ir, B64>, Checkpoint<B64>);
+
+type PathQueue<'ir> = SegQueue<Result<Path<'ir>, String>>;
+
+fn path_collector<'ir>(
+    _: usize,
+    _: usize,
+    result: Result<(Val<B64>, LocalFrame<'ir, B64>), (ExecError, Backtrace)>,
+    _: &SharedState<'ir, B64>,
+    mut solver: Solver<B64>,
+    collected: &PathQueue<'ir>,
+) {
+    match result {
+        Ok((_, frame)) => collected.push(Ok((frame.regs().clone(), checkpoint(&mut solver)))),
+        Err((ExecError::Dead, _)) => (),
+        Err((err, _)) => collected.push(Err(format!("Error {:?}", err))),
+    }
+}
+
+/// Runs each opcode in turn, starting each instruction from the final
+/// register state and solver state of every path through the
+/// previous one.
+fn execute_sequence<'ir>(
+    num_threads: usize,
+    opcodes: &[B64],
+    initial: Path<'ir>,
+    lets: &Bindings<'ir, B64>,
+    shared_state: &SharedState<'ir, B64>,
+) -> Result<Vec<Path<'ir>>, String> {
+    let function_id = shared_state.symtab.lookup("zisla_footprint");
+    let (args, _, instrs) = shared_state.functions.get(&function_id).unwrap();
+
+    let mut paths = vec![initial];
+    for opcode in opcodes {
+        let tasks = paths
+            .iter()
+            .enumerate()
+            .map(|(i, (regs, checkpoint))| {
+                LocalFrame::new(function_id, args, Some(&[Val::Bits(*opcode)]), instrs)
+                    .add_lets(lets)
+                    .add_regs(regs)
+                    .task_with_checkpoint(i, checkpoint.clone())
+            })
+            .collect();
+
+        let queue = Arc::new(SegQueue::new());
+        executor::start_multi(num_threads, None, tasks, shared_state, queue.clone(), &path_collector);
+
+        paths = Vec::new();
+        loop {
+            match queue.pop() {
+                Ok(Ok(path)) => paths.push(path),
+                Ok(Err(msg)) => return Err(msg),
+                Err(_) => break,
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// Returns the events in a checkpoint in the order they occurred.
+fn trace_events(checkpoint: &Checkpoint<B64>) -> Vec<Event<B64>> {
+    match checkpoint.trace() {
+        Some(trace) => trace.to_vec().drain(..).rev().cloned().collect(),
+        None => Vec::new(),
+    }
+}
+
+struct Access {
+    address: Val<B64>,
+    value: Val<B64>,
+    bytes: u32,
+}
+
+fn memory_accesses(events: &[Event<B64>]) -> (Vec<Access>, Vec<Access>) {
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    for event in events {
+        match event {
+            Event::ReadMem { value, address, bytes, .. } => {
+                reads.push(Access { address: address.clone(), value: value.clone(), bytes: *bytes })
+            }
+            Event::WriteMem { address, data, bytes, .. } => {
+                writes.push(Access { address: address.clone(), value: data.clone(), bytes: *bytes })
+            }
+            _ => (),
+        }
+    }
+    (reads, writes)
+}
+
+fn smt_exp(value: &Val<B64>) -> Result<Exp, String> {
+    smt_value(value).map_err(|err| format!("Cannot compare value {:?}: {:?}", value, err))
+}
+
+/// A location which may hold different values at the end of the two
+/// sequences, along with the SMT expressions for each side.
+struct Difference {
+    location: String,
+    left: Exp,
+    right: Exp,
+}
+
+impl Difference {
+    fn neq(&self) -> Exp {
+        Exp::Neq(Box::new(self.left.clone()), Box::new(self.right.clone()))
+    }
+}
+
+/// Compares two values, adding a `Difference` for every component
+/// that can be compared by the solver. Components that differ
+/// structurally, such as union constructors, are returned as an error
+/// string describing the location.
+fn compare_values(
+    location: String,
+    left: &Val<B64>,
+    right: &Val<B64>,
+    differences: &mut Vec<Difference>,
+    symtab: &Symtab,
+) -> Result<(), String> {
+    use Val::*;
+    match (left, right) {
+        (Poison, _) | (_, Poison) | (Unit, Unit) => Ok(()),
+        (Struct(left_fields), Struct(right_fields)) => {
+            for (field, left_value) in left_fields {
+                let field_location = format!("{}.{}", location, zencode::decode(symtab.to_str(*field)));
+                match right_fields.get(field) {
+                    Some(right_value) => compare_values(field_location, left_value, right_value, differences, symtab)?,
+                    None => return Err(field_location),
+                }
+            }
+            Ok(())
+        }
+        (Vector(left_values), Vector(right_values)) | (List(left_values), List(right_values)) => {
+            if left_values.len() != right_values.len() {
+                return Err(location);
+            }
+            for (i, (left_value, right_value)) in left_values.iter().zip(right_values.iter()).enumerate() {
+                compare_values(format!("{}[{}]", location, i), left_value, right_value, differences, symtab)?
+            }
+            Ok(())
+        }
+        (Ctor(left_ctor, left_value), Ctor(right_ctor, right_value)) if left_ctor == right_ctor => {
+            compare_values(location, left_value, right_value, differences, symtab)
+        }
+        (String(left_str), String(right_str)) if left_str == right_str => Ok(()),
+        (Ref(left_reg), Ref(right_reg)) if left_reg == right_reg => Ok(()),
+        (Ctor(_, _), _) | (String(_), _) | (Ref(_), _) | (_, Ctor(_, _)) | (_, String(_)) | (_, Ref(_)) => {
+            Err(location)
+        }
+        _ => {
+            differences.push(Difference { location, left: smt_exp(left)?, right: smt_exp(right)? });
+            Ok(())
+        }
+    }
+}
+
+/// Compares the final register state of two paths. A register which
+/// is initialized on one side but not on the other is reported as a
+/// mismatch, as is any register whose values differ structurally.
+fn compare_registers(
+    left_regs: &Bindings<B64>,
+    right_regs: &Bindings<B64>,
+    ignored: &HashSet<Name>,
+    differences: &mut Vec<Difference>,
+    symtab: &Symtab,
+) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    let regs: HashSet<Name> = left_regs.keys().chain(right_regs.keys()).copied().collect();
+    for reg in regs {
+        if ignored.contains(&reg) {
+            continue;
+        }
+        let location = zencode::decode(symtab.to_str(reg));
+        match (left_regs.get(&reg), right_regs.get(&reg)) {
+            (Some(UVal::Init(left_value)), Some(UVal::Init(right_value))) => {
+                if let Err(location) = compare_values(location, left_value, right_value, differences, symtab) {
+                    mismatches.push(location)
+                }
+            }
+            (Some(UVal::Init(_)), _) => mismatches.push(format!("{} is uninitialized on the right", location)),
+            (_, Some(UVal::Init(_))) => mismatches.push(format!("{} is uninitialized on the left", location)),
+            _ => (),
+        }
+    }
+    mismatches.sort();
+    mismatches
+}
+
+fn exp_to_string(exp: &Exp) -> String {
+    match exp {
+        Exp::Bits64(bits, len) => format!("{}", B64::new(*bits, *len)),
+        Exp::Bits(bits) => {
+            let mut s = "#b".to_string();
+            bits.iter().rev().for_each(|bit| s.push(if *bit { '1' } else { '0' }));
+            s
+        }
+        Exp::Bool(b) => format!("{}", b),
+        _ => format!("{:?}", exp),
+    }
+}
+
+fn model_value(model: &mut Model<B64>, value: &Val<B64>, symtab: &Symtab) -> String {
+    match value {
+        Val::Symbolic(v) => match model.get_var(*v) {
+            Ok(Some(exp)) => exp_to_string(&exp),
+            _ => "?".to_string(),
+        },
+        Val::Struct(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(field, value)| {
+                    format!("{} = {}", zencode::decode(symtab.to_str(*field)), model_value(model, value, symtab))
+                })
+                .collect();
+            format!("struct {{ {} }}", fields.join(", "))
+        }
+        Val::Vector(values) | Val::List(values) => {
+            let values: Vec<String> = values.iter().map(|value| model_value(model, value, symtab)).collect();
+            format!("[{}]", values.join(", "))
+        }
+        _ => match smt_value(value) {
+            Ok(exp) => exp_to_string(&exp),
+            Err(_) => format!("{:?}", value),
+        },
+    }
+}
+
+fn model_exp(model: &mut Model<B64>, exp: &Exp) -> String {
+    match model.get_exp(exp) {
+        Ok(Some(exp)) => exp_to_string(&exp),
+        _ => "?".to_string(),
+    }
+}
+
+struct Comparison<'a, 'ir> {
+    initial_regs: &'a Bindings<'ir, B64>,
+    left_regs: &'a Bindings<'ir, B64>,
+    right_regs: &'a Bindings<'ir, B64>,
+    left_len: usize,
+    ignored: &'a HashSet<Name>,
+    symtab: &'a Symtab<'ir>,
+}
+
+impl<'a, 'ir> Comparison<'a, 'ir> {
+    /// Checks a single pair of paths. The checkpoint contains the
+    /// events of the left path followed by those of the right path,
+    /// the first `left_len` events being from the left. Returns a
+    /// description of a counterexample if the paths can disagree.
+    fn check(&self, right_checkpoint: Checkpoint<B64>) -> Result<Option<String>, String> {
+        let events = trace_events(&right_checkpoint);
+        let (left_events, right_events) = events.split_at(self.left_len);
+
+        let mut differences = Vec::new();
+        let mut mismatches =
+            compare_registers(self.left_regs, self.right_regs, self.ignored, &mut differences, self.symtab);
+
+        let (left_reads, left_writes) = memory_accesses(left_events);
+        let (right_reads, right_writes) = memory_accesses(right_events);
+
+        if left_writes.len() != right_writes.len() {
+            mismatches.push(format!("{} memory writes vs {}", left_writes.len(), right_writes.len()))
+        } else {
+            for (i, (left_write, right_write)) in left_writes.iter().zip(right_writes.iter()).enumerate() {
+                if left_write.bytes != right_write.bytes {
+                    mismatches.push(format!("write {} is {} bytes vs {}", i, left_write.bytes, right_write.bytes));
+                    continue;
+                }
+                differences.push(Difference {
+                    location: format!("write {} address", i),
+                    left: smt_exp(&left_write.address)?,
+                    right: smt_exp(&right_write.address)?,
+                });
+                differences.push(Difference {
+                    location: format!("write {} data", i),
+                    left: smt_exp(&left_write.value)?,
+                    right: smt_exp(&right_write.value)?,
+                });
+            }
+        }
+
+        let cfg = Config::new();
+        cfg.set_param_value("model", "true");
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::from_checkpoint(&ctx, right_checkpoint);
+
+        // Reads of the same width from the same address see the same
+        // initial memory value
+        for left_read in &left_reads {
+            for right_read in right_reads.iter().filter(|read| read.bytes == left_read.bytes) {
+                solver.add(Def::Assert(Exp::Or(
+                    Box::new(Exp::Neq(Box::new(smt_exp(&left_read.address)?), Box::new(smt_exp(&right_read.address)?))),
+                    Box::new(Exp::Eq(Box::new(smt_exp(&left_read.value)?), Box::new(smt_exp(&right_read.value)?))),
+                )))
+            }
+        }
+
+        if mismatches.is_empty() {
+            let any_difference = differences
+                .iter()
+                .map(Difference::neq)
+                .fold(Exp::Bool(false), |acc, neq| Exp::Or(Box::new(acc), Box::new(neq)));
+            solver.add(Def::Assert(any_difference))
+        }
+
+        match solver.check_sat() {
+            SmtResult::Unsat => return Ok(None),
+            SmtResult::Unknown => return Err("Solver returned unknown".to_string()),
+            SmtResult::Sat => (),
+        }
+
+        let mut model = Model::new(&solver);
+        let mut counterexample = String::new();
+
+        counterexample.push_str("initial registers:\n");
+        let mut seen = HashSet::new();
+        for event in events.iter() {
+            if let Event::ReadReg(reg, _, _) | Event::WriteReg(reg, _, _) = event {
+                if !seen.insert(*reg) {
+                    continue;
+                }
+                if let Some(UVal::Init(value)) = self.initial_regs.get(reg) {
+                    counterexample.push_str(&format!(
+                        "  {} = {}\n",
+                        zencode::decode(self.symtab.to_str(*reg)),
+                        model_value(&mut model, value, self.symtab)
+                    ))
+                }
+            }
+        }
+
+        counterexample.push_str("initial memory:\n");
+        for read in left_reads.iter().chain(right_reads.iter()) {
+            counterexample.push_str(&format!(
+                "  [{}] ({} bytes) = {}\n",
+                model_value(&mut model, &read.address, self.symtab),
+                read.bytes,
+                model_value(&mut model, &read.value, self.symtab)
+            ))
+        }
+
+        counterexample.push_str("differences:\n");
+        for mismatch in &mismatches {
+            counterexample.push_str(&format!("  {}\n", mismatch))
+        }
+        for difference in &differences {
+            if let Ok(Some(Exp::Bool(true))) = model.get_exp(&difference.neq()) {
+                counterexample.push_str(&format!(
+                    "  {}: {} vs {}\n",
+                    difference.location,
+                    model_exp(&mut model, &difference.left),
+                    model_exp(&mut model, &difference.right)
+                ))
+            }
+        }
+
+        Ok(Some(counterexample))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use isla_lib::smt::Sym;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_compare_registers() {
+        let mut symtab = Symtab::new();
+        let x = symtab.intern("zX");
+        let y = symtab.intern("zY");
+        let z = symtab.intern("zZ");
+        let ty = Ty::Bits(64);
+
+        let bits = |n| UVal::Init(Val::Bits(B64::new(n, 64)));
+        let mut left: Bindings<B64> = HashMap::new();
+        let mut right: Bindings<B64> = HashMap::new();
+        left.insert(x, bits(1));
+        right.insert(x, bits(2));
+        left.insert(y, bits(1));
+        right.insert(y, UVal::Uninit(&ty));
+        right.insert(z, bits(3));
+
+        let mut differences = Vec::new();
+        let mismatches = compare_registers(&left, &right, &HashSet::new(), &mut differences, &symtab);
+        assert_eq!(mismatches, vec!["Y is uninitialized on the right", "Z is uninitialized on the left"]);
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].location, "X");
+
+        let mut ignored = HashSet::new();
+        ignored.insert(y);
+        ignored.insert(z);
+        let mut differences = Vec::new();
+        assert!(compare_registers(&left, &right, &ignored, &mut differences, &symtab).is_empty());
+        assert_eq!(differences.len(), 1)
+    }
+
+    #[test]
+    fn test_compare_values() {
+        let mut symtab = Symtab::new();
+        let f = symtab.intern("zf");
+        let some = symtab.intern("zSome");
+        let none = symtab.intern("zNone");
+
+        let mut left_fields = HashMap::new();
+        left_fields.insert(f, Val::Bits(B64::new(1, 8)));
+        let mut right_fields = HashMap::new();
+        right_fields.insert(f, Val::Symbolic(Sym::from_u32(0)));
+
+        let mut differences = Vec::new();
+        assert!(compare_values(
+            "R".to_string(),
+            &Val::Struct(left_fields),
+            &Val::Struct(right_fields),
+            &mut differences,
+            &symtab
+        )
+        .is_ok());
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].location, "R.f");
+
+        let left = Val::Ctor(some, Box::new(Val::Unit));
+        let right = Val::Ctor(none, Box::new(Val::Unit));
+        assert_eq!(compare_values("R".to_string(), &left, &right, &mut differences, &symtab), Err("R".to_string()))
+    }
+
+    #[test]
+    fn test_comparison_check() {
+        let mut symtab = Symtab::new();
+        let x = symtab.intern("zX");
+
+        let ctx = Context::new(Config::new());
+        let mut solver = Solver::<B64>::new(&ctx);
+        let v = solver.declare_const(isla_lib::smt::smtlib::Ty::BitVec(64));
+        let checkpoint = checkpoint(&mut solver);
+
+        let mut initial: Bindings<B64> = HashMap::new();
+        initial.insert(x, UVal::Init(Val::Symbolic(v)));
+        let mut zero: Bindings<B64> = HashMap::new();
+        zero.insert(x, UVal::Init(Val::Bits(B64::zeros(64))));
+        let ignored = HashSet::new();
+
+        let same = Comparison {
+            initial_regs: &initial,
+            left_regs: &initial,
+            right_regs: &initial,
+            left_len: 0,
+            ignored: &ignored,
+            symtab: &symtab,
+        };
+        assert!(same.check(checkpoint.clone()).unwrap().is_none());
+
+        let different = Comparison { right_regs: &zero, ..same };
+        let counterexample = different.check(checkpoint).unwrap().unwrap();
+        assert!(counterexample.contains("X: "))
+    }
+}
@@ -36,11 +36,13 @@ use std::sync::Arc;
 use std::time::Instant;
 
 use isla_lib::concrete::bitvector129::B129;
+use isla_lib::elf_loader::Elf;
 use isla_lib::executor;
 use isla_lib::executor::LocalFrame;
 use isla_lib::init::{initialize_architecture, Initialized};
 use isla_lib::ir::*;
 use isla_lib::lexer::Lexer;
+use isla_lib::memory::Memory;
 use isla_lib::value_parser::ValParser;
 use isla_lib::zencode;
 use isla_lib::{simplify, simplify::WriteOpts};
@@ -62,6 +64,11 @@ fn isla_main() -> i32 {
     opts.optflag("", "optimistic", "assume assertions succeed");
     opts.optflag("t", "traces", "print execution traces");
     opts.optflag("s", "simplify", "simplify function traces");
+    opts.optopt("", "elf", "load an ELF file into memory", "<file>");
+    opts.optmulti("", "stop-symbol", "stop execution when an ELF symbol is fetched or read", "<symbol>");
+    opts.optmulti("", "probe-symbol", "trace reads of an ELF symbol", "<symbol>");
+    opts.optflag("", "sequential", "reads see earlier writes, as when running a single thread");
+    opts.optopt("", "resolve-reads", "resolve reads from symbolic addresses with at most <n> values", "<n>");
 
     let mut hasher = Sha256::new();
     let (matches, arch) = opts::parse::<B129>(&mut hasher, &opts);
@@ -77,7 +84,7 @@ fn isla_main() -> i32 {
     let assertion_mode =
         if matches.opt_present("optimistic") { AssertionMode::Optimistic } else { AssertionMode::Pessimistic };
 
-    let Initialized { regs, lets, shared_state } =
+    let Initialized { regs, mut lets, shared_state } =
         initialize_architecture(&mut arch, symtab, &isa_config, assertion_mode);
 
     let function_id = shared_state.symtab.lookup(&function_name);
@@ -97,6 +104,52 @@ fn isla_main() -> i32 {
             return 1;
         }
     }
+    if let Some(file) = matches.opt_str("elf") {
+        let elf = match Elf::from_file(&file) {
+            Ok(elf) => elf,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        };
+        let mut memory = Memory::new();
+        elf.load(&mut memory);
+        elf.set_entry(&mut lets);
+        for (symbol, stop) in matches
+            .opt_strs("stop-symbol")
+            .iter()
+            .map(|symbol| (symbol, true))
+            .chain(matches.opt_strs("probe-symbol").iter().map(|symbol| (symbol, false)))
+        {
+            match elf.symbol(symbol) {
+                Some(address) if stop => memory.add_stop_address(address, symbol.to_string()),
+                Some(address) => memory.add_probe_address(address, symbol.to_string()),
+                None => {
+                    eprintln!("Symbol {} not found in {}", symbol, file);
+                    return 1;
+                }
+            }
+        }
+        memory.log();
+        frame.set_memory(memory);
+    } else if matches.opt_present("stop-symbol") || matches.opt_present("probe-symbol") {
+        eprintln!("--stop-symbol and --probe-symbol require an ELF file given with --elf");
+        return 1;
+    }
+
+    if matches.opt_present("sequential") {
+        frame.memory_mut().set_sequential()
+    }
+    if let Some(limit) = matches.opt_str("resolve-reads") {
+        match limit.parse::<usize>() {
+            Ok(limit) => frame.memory_mut().set_resolve_limit(limit),
+            Err(e) => {
+                eprintln!("Bad limit for --resolve-reads {}: {}", limit, e);
+                return 1;
+            }
+        }
+    }
+
     let task = frame.add_lets(&lets).add_regs(&regs).task(0);
 
     let queue = Arc::new(SegQueue::new());
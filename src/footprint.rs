@@ -28,13 +28,22 @@
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 use crossbeam::queue::SegQueue;
+use serde::Serialize;
 use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
 use std::process::exit;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Instant;
 
-use isla_axiomatic::litmus::assemble_instruction;
+use isla_axiomatic::footprint_analysis::{
+    dependencies, opcode_footprints, opcode_footprints_partial, Dependencies, EncodingPattern, FootprintSummary,
+};
+use isla_lib::cache::Cache;
 use isla_lib::concrete::{bitvector64::B64, BV};
+use isla_lib::config::ISAConfig;
 use isla_lib::executor;
 use isla_lib::executor::LocalFrame;
 use isla_lib::init::{initialize_architecture, Initialized};
@@ -43,7 +52,7 @@ use isla_lib::{simplify, simplify::WriteOpts};
 use isla_lib::smt::Event;
 
 mod opts;
-use opts::CommonOpts;
+use opts::{parse_opcode, CommonOpts};
 
 fn main() {
     let code = isla_main();
@@ -53,15 +62,20 @@ fn main() {
 
 fn isla_main() -> i32 {
     let mut opts = opts::common_opts();
-    opts.reqopt("i", "instruction", "display footprint of instruction", "<instruction>");
+    opts.optmulti("i", "instruction", "display footprint of instruction (can be repeated)", "<instruction>");
     opts.optopt("e", "endianness", "instruction encoding endianness (little default)", "big/little");
     opts.optflag("x", "hex", "parse instruction as hexadecimal opcode, rather than assembly");
     opts.optflag("s", "simplify", "simplify instruction footprint");
+    opts.optflag("p", "pseudocode", "print instruction footprint as pseudocode");
+    opts.optflag("j", "json", "print footprint summaries and the dependencies between instructions as JSON");
+    opts.optopt("", "database", "directory of precomputed footprints to use and extend", "<directory>");
+    opts.optmulti("", "pattern", "compute footprints for all opcodes matching an encoding pattern", "<pattern>");
 
     let mut hasher = Sha256::new();
     let (matches, arch) = opts::parse(&mut hasher, &opts);
     let CommonOpts { num_threads, mut arch, symtab, isa_config } =
         opts::parse_with_arch(&mut hasher, &opts, &matches, &arch);
+    let arch_hash = hasher.result();
 
     let Initialized { regs, lets, shared_state } =
         initialize_architecture(&mut arch, symtab, &isa_config, AssertionMode::Optimistic);
@@ -75,42 +89,207 @@ fn isla_main() -> i32 {
         }
     };
 
-    let instruction = matches.opt_str("instruction").unwrap();
-
-    let opcode = if matches.opt_present("hex") {
-        match u32::from_str_radix(&instruction, 16) {
-            Ok(opcode) => opcode.to_le_bytes(),
-            Err(e) => {
-                eprintln!("Could not parse instruction: {}", e);
-                exit(1)
+    let database = match matches.opt_str("database").map(PathBuf::from) {
+        Some(database) => {
+            if fs::create_dir_all(&database).is_err() || !database.is_dir() {
+                eprintln!("Invalid footprint database directory {}", database.display());
+                return 1;
             }
+            Some(Cache::new(database, &arch_hash))
         }
-    } else {
-        match assemble_instruction(&instruction, &isa_config) {
-            Ok(bytes) => {
-                let mut opcode: [u8; 4] = Default::default();
-                opcode.copy_from_slice(&bytes);
-                opcode
+        None => None,
+    };
+
+    let patterns = matches.opt_strs("pattern");
+    if !patterns.is_empty() {
+        let database = match database {
+            Some(database) => database,
+            None => {
+                eprintln!("--pattern requires a --database directory to store footprints");
+                return 1;
             }
+        };
+        let mut encodings = Vec::new();
+        for pattern in &patterns {
+            match EncodingPattern::from_str(pattern) {
+                Ok(encoding) if encoding.width() == 32 => encodings.push(encoding),
+                Ok(_) => {
+                    eprintln!("Encoding pattern {} must be 32 bits long", pattern);
+                    return 1;
+                }
+                Err(msg) => {
+                    eprintln!("{}", msg);
+                    return 1;
+                }
+            }
+        }
+        return build_database(&encodings, &database, num_threads, &regs, &lets, &shared_state, &isa_config);
+    }
+
+    let instructions = matches.opt_strs("instruction");
+    if instructions.is_empty() {
+        eprintln!("No instructions given");
+        return 1;
+    }
+
+    let mut opcodes = Vec::new();
+    for instruction in &instructions {
+        match parse_opcode(instruction, matches.opt_present("hex"), little_endian, &isa_config) {
+            Ok(opcode) => opcodes.push(opcode),
             Err(msg) => {
                 eprintln!("{}", msg);
                 return 1;
             }
         }
+    }
+
+    if matches.opt_present("json") {
+        let footprints = match opcode_footprints(
+            num_threads,
+            &opcodes,
+            &lets,
+            &regs,
+            &shared_state,
+            &isa_config,
+            database.as_ref(),
+        ) {
+            Ok(footprints) => footprints,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        };
+        let output = JsonOutput {
+            footprints: opcodes
+                .iter()
+                .map(|opcode| JsonFootprint {
+                    opcode: format!("{:#010x}", opcode.bits),
+                    footprint: footprints[opcode].summary(&shared_state.symtab),
+                })
+                .collect(),
+            dependencies: dependencies(&opcodes, &footprints),
+        };
+        let stdout = std::io::stdout();
+        serde_json::to_writer_pretty(stdout.lock(), &output).unwrap();
+        println!();
+        return 0;
+    }
+
+    for opcode in opcodes {
+        let code = print_footprint(opcode, &matches, num_threads, &regs, &lets, &shared_state);
+        if code != 0 {
+            return code;
+        }
+    }
+    0
+}
+
+/// How many opcodes to symbolically execute at once when building a
+/// footprint database.
+const DATABASE_CHUNK_SIZE: usize = 1024;
+
+/// Computes the footprint of every opcode matching the encoding
+/// patterns and stores them in `database`, keyed by opcode. Opcodes
+/// already in the database are skipped, so an interrupted run can be
+/// resumed. Opcodes which cannot be analysed (e.g. because they cause
+/// an error during execution) are listed in a `failures` file in the
+/// database directory, which is replaced on every run.
+fn build_database<'ir>(
+    encodings: &[EncodingPattern],
+    database: &Cache,
+    num_threads: usize,
+    regs: &Bindings<'ir, B64>,
+    lets: &Bindings<'ir, B64>,
+    shared_state: &SharedState<'ir, B64>,
+    isa_config: &ISAConfig<B64>,
+) -> i32 {
+    let total: u128 = encodings.iter().map(EncodingPattern::count).sum();
+    eprintln!("Computing footprints for at most {} opcodes", total);
+
+    // Skip opcodes that were already matched by an earlier pattern
+    let mut opcodes = encodings.iter().enumerate().flat_map(|(i, encoding)| {
+        encoding.opcodes().filter(move |opcode| !encodings[..i].iter().any(|earlier| earlier.matches(*opcode)))
+    });
+
+    let mut failures = Vec::new();
+    let mut done = 0;
+    let now = Instant::now();
+
+    loop {
+        let chunk: Vec<B64> = opcodes.by_ref().take(DATABASE_CHUNK_SIZE).map(|opcode| B64::new(opcode, 32)).collect();
+        if chunk.is_empty() {
+            break;
+        }
+
+        match opcode_footprints_partial(num_threads, &chunk, lets, regs, shared_state, isa_config, Some(database)) {
+            Ok((_, mut chunk_failures)) => {
+                chunk_failures.sort_by_key(|(opcode, _)| opcode.bits);
+                for (opcode, msg) in chunk_failures {
+                    failures.push(format!("{:#010x}: {}", opcode.bits, msg))
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        }
+
+        done += chunk.len();
+        eprintln!("{} opcodes done, {} failed ({}s)", done, failures.len(), now.elapsed().as_secs());
+    }
+
+    // Failed opcodes are not cached, so they are retried (and
+    // reported again) by every run. Any list from an earlier run is
+    // therefore replaced.
+    let failures_file = database.dir().join("failures");
+    let result = if failures.is_empty() {
+        match fs::remove_file(&failures_file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    } else {
+        let mut contents = failures.join("\n");
+        contents.push('\n');
+        fs::write(&failures_file, contents)
     };
+    if let Err(e) = result {
+        eprintln!("Failed to write list of failed opcodes: {}", e);
+        return 1;
+    }
+    0
+}
+
+#[derive(Serialize)]
+struct JsonFootprint {
+    opcode: String,
+    footprint: FootprintSummary,
+}
+
+#[derive(Serialize)]
+struct JsonOutput {
+    footprints: Vec<JsonFootprint>,
+    dependencies: Dependencies,
+}
 
-    let opcode = B64::from_u32(if little_endian { u32::from_le_bytes(opcode) } else { u32::from_be_bytes(opcode) });
+fn print_footprint<'ir>(
+    opcode: B64,
+    matches: &getopts::Matches,
+    num_threads: usize,
+    regs: &Bindings<'ir, B64>,
+    lets: &Bindings<'ir, B64>,
+    shared_state: &SharedState<'ir, B64>,
+) -> i32 {
     eprintln!("opcode: {:#010x}", opcode.bits);
 
     let function_id = shared_state.symtab.lookup("zisla_footprint");
     let (args, _, instrs) = shared_state.functions.get(&function_id).unwrap();
     let task =
-        LocalFrame::new(function_id, args, Some(&[Val::Bits(opcode)]), instrs).add_lets(&lets).add_regs(&regs).task(0);
+        LocalFrame::new(function_id, args, Some(&[Val::Bits(opcode)]), instrs).add_lets(lets).add_regs(regs).task(0);
 
     let queue = Arc::new(SegQueue::new());
 
     let now = Instant::now();
-    executor::start_multi(num_threads, None, vec![task], &shared_state, queue.clone(), &executor::trace_collector);
+    executor::start_multi(num_threads, None, vec![task], shared_state, queue.clone(), &executor::trace_collector);
     eprintln!("Execution took: {}ms", now.elapsed().as_millis());
 
     loop {
@@ -118,13 +297,14 @@ fn isla_main() -> i32 {
             Ok(Ok((_, mut events))) => {
                 if matches.opt_present("simplify") {
                     simplify::hide_initialization(&mut events);
-                    simplify::remove_unused(&mut events);
+                    simplify::simplify(&mut events);
                 }
                 let events: Vec<Event<B64>> = events.drain(..).rev().collect();
                 let stdout = std::io::stdout();
                 let mut handle = stdout.lock();
                 let write_opts = WriteOpts {
                     define_enum: !matches.opt_present("simplify"),
+                    pseudocode: matches.opt_present("pseudocode"),
                     ..WriteOpts::default()
                 };
                 simplify::write_events_with_opts(&mut handle, &events, &shared_state.symtab, &write_opts).unwrap();
@@ -0,0 +1,99 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2020 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use getopts::Options;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+
+use isla_axiomatic::litmus::cycle;
+use isla_lib::config::CycleTemplates;
+
+fn print_usage(opts: &Options, code: i32) -> ! {
+    let brief = "Usage: isla-generate --config <file> [options] <cycle>\n\n\
+                 Generate a litmus test from a relaxation cycle, e.g.\n\
+                 isla-generate -C configs/aarch64.toml 'PodWW Rfe PodRR Fre'";
+    eprint!("{}", opts.usage(brief));
+    exit(code)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut opts = Options::new();
+    opts.reqopt("C", "config", "architecture config file containing the instruction templates", "<file>");
+    opts.optopt("n", "name", "name of the generated test", "<name>");
+    opts.optopt("o", "output", "directory to write the test to, rather than printing it", "<dir>");
+    opts.optflag("h", "help", "print this help message");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}", f);
+            print_usage(&opts, 1)
+        }
+    };
+    if matches.opt_present("help") {
+        print_usage(&opts, 0)
+    }
+    if matches.free.is_empty() {
+        print_usage(&opts, 1)
+    }
+    // Allow the cycle to be given either as one argument or as
+    // separate arguments for each edge
+    let cycle = matches.free.join(" ");
+
+    let config_file = matches.opt_str("config").unwrap();
+    let templates =
+        match fs::read_to_string(&config_file).map_err(|e| e.to_string()).and_then(|c| CycleTemplates::parse(&c)) {
+            Ok(templates) => templates,
+            Err(e) => {
+                eprintln!("Could not read templates from {}: {}", config_file, e);
+                exit(1)
+            }
+        };
+
+    let generated = match cycle::generate(matches.opt_str("name").as_deref(), &cycle, &templates) {
+        Ok(generated) => generated,
+        Err(e) => {
+            eprintln!("Could not generate test for {}: {}", cycle, e);
+            exit(1)
+        }
+    };
+
+    if let Some(dir) = matches.opt_str("output") {
+        let path: PathBuf = [dir, format!("{}.toml", generated.name)].iter().collect();
+        if let Err(e) = fs::write(&path, &generated.toml) {
+            eprintln!("Could not write {}: {}", path.display(), e);
+            exit(1)
+        }
+        println!("{}: {}", path.display(), generated.assertion)
+    } else {
+        print!("{}", generated.toml)
+    }
+}
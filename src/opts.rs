@@ -35,6 +35,7 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::process::exit;
 
+use isla_axiomatic::litmus::assemble_instruction;
 use isla_lib::concrete::BV;
 use isla_lib::config::ISAConfig;
 use isla_lib::ir;
@@ -267,5 +268,35 @@ pub fn parse_with_arch<'ir, B: BV>(
         }
     });
 
+    // Register assignments and linearization change the results of
+    // anything we cache, so they are part of the architecture hash
+    isla_lib::cache::hash_arch_options(hasher, &matches.opt_strs("register"), &matches.opt_strs("linearize"));
+
     CommonOpts { num_threads, arch, symtab, isa_config }
 }
+
+/// Parses a single instruction, given either as assembly or (with
+/// `hex`) as a hexadecimal opcode, into a 32-bit opcode.
+#[allow(dead_code)]
+pub fn parse_opcode<B: BV>(
+    instr: &str,
+    hex: bool,
+    little_endian: bool,
+    isa_config: &ISAConfig<B>,
+) -> Result<B, String> {
+    let opcode = if hex {
+        match u32::from_str_radix(instr, 16) {
+            Ok(opcode) => opcode.to_le_bytes(),
+            Err(e) => return Err(format!("Could not parse instruction {}: {}", instr, e)),
+        }
+    } else {
+        let bytes = assemble_instruction(instr, isa_config)?;
+        let mut opcode: [u8; 4] = Default::default();
+        if bytes.len() != opcode.len() {
+            return Err(format!("Instruction {} did not assemble to a 4 byte opcode", instr));
+        }
+        opcode.copy_from_slice(&bytes);
+        opcode
+    };
+    Ok(B::from_u32(if little_endian { u32::from_le_bytes(opcode) } else { u32::from_be_bytes(opcode) }))
+}
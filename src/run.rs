@@ -0,0 +1,237 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2020 Alasdair Armstrong
+// Copyright (c) 2020 Brian Campbell
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::process::exit;
+
+use isla_lib::concrete::{bitvector64::B64, BV};
+use isla_lib::driver::{run_program, StopConditions, StopReason};
+use isla_lib::elf_loader::Elf;
+use isla_lib::executor::LocalFrame;
+use isla_lib::init::{initialize_architecture, Initialized};
+use isla_lib::ir::*;
+use isla_lib::memory::{Address, Memory};
+use isla_lib::smt::Event;
+use isla_lib::zencode;
+use isla_lib::{simplify, simplify::WriteOpts};
+
+mod opts;
+use opts::CommonOpts;
+
+fn main() {
+    let code = isla_main();
+    unsafe { isla_lib::smt::finalize_solver() };
+    exit(code)
+}
+
+fn parse_address(address: &str, elf: Option<&Elf>) -> Result<Address, String> {
+    if let Some(hex) = address.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).map_err(|e| format!("Bad address {}: {}", address, e))
+    } else if let Ok(address) = address.parse::<u64>() {
+        Ok(address)
+    } else {
+        elf.and_then(|elf| elf.symbol(address)).ok_or_else(|| format!("Unknown address or symbol {}", address))
+    }
+}
+
+fn isla_main() -> i32 {
+    let mut opts = opts::common_opts();
+    opts.optflag("", "optimistic", "assume assertions succeed");
+    opts.optflag("t", "traces", "print execution traces");
+    opts.optflag("s", "simplify", "simplify traces");
+    opts.optopt("", "elf", "load an ELF file into memory", "<file>");
+    opts.optopt("", "binary", "load a raw binary into memory", "<file>");
+    opts.optopt("", "base", "address to load a raw binary at (default 0x0)", "<address>");
+    opts.optopt("", "pc", "initial program counter (default ELF entry point or binary base)", "<address>");
+    opts.optopt("", "step", "instruction step function (default isla_footprint)", "<function>");
+    opts.optmulti("", "stop-address", "stop before fetching from an address or ELF symbol", "<address>");
+    opts.optmulti("", "stop-function", "stop when a Sail function is called", "<function>");
+    opts.optopt("", "limit", "stop after executing this many instructions", "<n>");
+    opts.optflag("", "sequential", "reads see earlier writes, as when running a single thread");
+    opts.optopt("", "resolve-reads", "resolve reads from symbolic addresses with at most <n> values", "<n>");
+
+    let mut hasher = Sha256::new();
+    let (matches, arch) = opts::parse::<B64>(&mut hasher, &opts);
+    let CommonOpts { num_threads: _, mut arch, symtab, isa_config } =
+        opts::parse_with_arch(&mut hasher, &opts, &matches, &arch);
+
+    let assertion_mode =
+        if matches.opt_present("optimistic") { AssertionMode::Optimistic } else { AssertionMode::Pessimistic };
+
+    let Initialized { mut regs, mut lets, shared_state } =
+        initialize_architecture(&mut arch, symtab, &isa_config, assertion_mode);
+
+    let step_name = zencode::encode(&matches.opt_str("step").unwrap_or_else(|| "isla_footprint".to_string()));
+    let step_function = match shared_state.symtab.get(&step_name) {
+        Some(id) => id,
+        None => {
+            eprintln!("Step function {} not found", zencode::decode(&step_name));
+            return 1;
+        }
+    };
+
+    let mut memory = Memory::new();
+    let mut elf = None;
+    let mut entry = None;
+    if let Some(file) = matches.opt_str("elf") {
+        match Elf::from_file(&file) {
+            Ok(loaded) => {
+                loaded.load(&mut memory);
+                loaded.set_entry(&mut lets);
+                entry = Some(loaded.entry());
+                elf = Some(loaded)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        }
+    }
+    if let Some(file) = matches.opt_str("binary") {
+        let base = match matches.opt_str("base").map(|base| parse_address(&base, None)).unwrap_or(Ok(0)) {
+            Ok(base) => base,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        };
+        let contents = match fs::read(&file) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", file, e);
+                return 1;
+            }
+        };
+        let bytes: HashMap<Address, u8> =
+            contents.iter().enumerate().map(|(i, byte)| (base + i as Address, *byte)).collect();
+        memory.add_concrete_region(base..(base + contents.len() as Address), bytes);
+        entry = entry.or(Some(base))
+    }
+    memory.log();
+    if matches.opt_present("sequential") {
+        memory.set_sequential()
+    }
+    if let Some(limit) = matches.opt_str("resolve-reads") {
+        match limit.parse::<usize>() {
+            Ok(limit) => memory.set_resolve_limit(limit),
+            Err(e) => {
+                eprintln!("Bad limit for --resolve-reads {}: {}", limit, e);
+                return 1;
+            }
+        }
+    }
+
+    let entry = match matches.opt_str("pc").map(|pc| parse_address(&pc, elf.as_ref())) {
+        Some(Ok(pc)) => pc,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+        None => match entry {
+            Some(entry) => entry,
+            None => {
+                eprintln!("No initial program counter, use --elf, --binary, or --pc");
+                return 1;
+            }
+        },
+    };
+    regs.insert(isa_config.pc, UVal::Init(Val::Bits(B64::from_u64(entry))));
+
+    let mut stop = StopConditions::default();
+    for address in matches.opt_strs("stop-address") {
+        match parse_address(&address, elf.as_ref()) {
+            Ok(address) => {
+                stop.addresses.insert(address);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        }
+    }
+    for function in matches.opt_strs("stop-function") {
+        match shared_state.symtab.get(&zencode::encode(&function)) {
+            Some(id) => {
+                stop.functions.insert(id);
+            }
+            None => {
+                eprintln!("Function {} not found", function);
+                return 1;
+            }
+        }
+    }
+    if let Some(limit) = matches.opt_str("limit") {
+        match limit.parse::<usize>() {
+            Ok(limit) => stop.limit = Some(limit),
+            Err(e) => {
+                eprintln!("Bad instruction limit {}: {}", limit, e);
+                return 1;
+            }
+        }
+    }
+
+    let (args, _, instrs) = shared_state.functions.get(&step_function).unwrap();
+    let mut frame = LocalFrame::new(step_function, args, None, instrs);
+    frame.add_lets(&lets).add_regs(&regs).set_memory(memory);
+
+    let paths = match run_program(step_function, frame, &stop, &shared_state, &isa_config) {
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    let stdout = std::io::stdout();
+    let mut handle = stdout.lock();
+    for mut path in paths {
+        let reason = match path.reason {
+            StopReason::Address(address) => format!("reached 0x{:x}", address),
+            StopReason::Limit => "instruction limit".to_string(),
+            StopReason::SymbolicPC => "symbolic program counter".to_string(),
+            StopReason::SymbolicOpcode(address) => format!("symbolic opcode at 0x{:x}", address),
+            StopReason::Stopped(name) => format!("stopped at {}", name),
+        };
+        writeln!(handle, "Path: {} instructions, {}", path.instructions, reason).unwrap();
+        if matches.opt_present("traces") {
+            if matches.opt_present("simplify") {
+                simplify::hide_initialization(&mut path.events);
+                simplify::remove_unused(&mut path.events);
+            }
+            let events: Vec<Event<B64>> = path.events.drain(..).rev().collect();
+            let write_opts = WriteOpts { define_enum: !matches.opt_present("simplify"), ..WriteOpts::default() };
+            simplify::write_events_with_opts(&mut handle, &events, &shared_state.symtab, &write_opts).unwrap();
+        }
+    }
+    0
+}
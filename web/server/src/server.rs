@@ -61,10 +61,6 @@ async fn spawn_worker_err(config: &Config, req: &Request) -> Option<String> {
     let mut command = Command::new(&config.worker);
     command.arg("--resources").arg(&config.resources).arg("--cache").arg(&config.cache);
 
-    if let Some(path) = &config.litmus_convert {
-        command.arg("--litmus-convert").arg(path);
-    }
-
     if let Some(value) = &config.ld_library_path {
         command.env("LD_LIBRARY_PATH", value);
     }
@@ -118,7 +114,6 @@ async fn spawn_worker((config, req_cache, req): (&Config, &ReqCache, Request)) -
 #[derive(Clone)]
 struct Config {
     worker: PathBuf,
-    litmus_convert: Option<String>,
     dist: PathBuf,
     resources: PathBuf,
     cache: PathBuf,
@@ -133,7 +128,6 @@ fn get_config() -> &'static Config {
     let args: Vec<String> = std::env::args().collect();
     let mut opts = Options::new();
     opts.reqopt("", "worker", "path to worker process", "<path>");
-    opts.optopt("", "litmus-convert", "path of .litmus to .toml converter", "<path>");
     opts.reqopt("", "dist", "path to static files", "<path>");
     opts.reqopt("", "resources", "path to resource files", "<path>");
     opts.reqopt("", "cache", "path to a cache directory", "<path>");
@@ -153,7 +147,6 @@ fn get_config() -> &'static Config {
 
     Box::leak(Box::new(Config {
         worker: PathBuf::from(matches.opt_str("worker").unwrap()),
-        litmus_convert: matches.opt_str("litmus-convert"),
         dist: PathBuf::from(matches.opt_str("dist").unwrap()),
         resources: PathBuf::from(matches.opt_str("resources").unwrap()),
         cache: PathBuf::from(matches.opt_str("cache").unwrap()),
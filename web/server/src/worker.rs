@@ -29,7 +29,7 @@
 
 use crossbeam::queue::SegQueue;
 use serde::de::DeserializeOwned;
-use std::borrow::Cow;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::error::Error;
@@ -37,7 +37,6 @@ use std::fmt;
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
-use std::process::Stdio;
 use std::time::Instant;
 
 use isla_cat::cat;
@@ -48,8 +47,8 @@ use isla_axiomatic::axiomatic::{AxEvent, Pairs};
 use isla_axiomatic::cat_config::tcx_from_config;
 use isla_axiomatic::litmus::Litmus;
 use isla_axiomatic::run_litmus;
-use isla_axiomatic::sandbox::SandboxedCommand;
 use isla_axiomatic::sexp::SexpVal;
+use isla_lib::cache::Cache;
 use isla_lib::concrete::{bitvector64::B64, BV};
 use isla_lib::config::ISAConfig;
 use isla_lib::init::{initialize_architecture, Initialized};
@@ -150,7 +149,6 @@ fn handle_request() -> Result<Response, Box<dyn Error>> {
     let mut opts = Options::new();
     opts.reqopt("", "resources", "path to resource files", "<path>");
     opts.reqopt("", "cache", "path to a cache directory", "<path>");
-    opts.optopt("", "litmus-convert", "path of .litmus to .toml file converter", "<path>");
 
     let matches = opts.parse(&args[1..])?;
 
@@ -184,44 +182,34 @@ fn handle_request() -> Result<Response, Box<dyn Error>> {
     let symtab_file = resources.join(format!("{}.symtab", req.arch));
     let ir_file = resources.join(format!("{}.irx", req.arch));
 
-    let strings: Vec<String> = bincode::deserialize(&fs::read(&symtab_file)?)?;
+    let mut hasher = Sha256::new();
+
+    let symtab_contents = fs::read(&symtab_file)?;
+    hasher.input(&symtab_contents);
+    let strings: Vec<String> = bincode::deserialize(&symtab_contents)?;
     let symtab = Symtab::from_raw_table(&strings);
 
-    let mut ir: Vec<Def<Name, B64>> =
-        ir_serialize::deserialize(&fs::read(&ir_file)?).expect("Failed to deserialize IR");
+    let ir_contents = fs::read(&ir_file)?;
+    hasher.input(&ir_contents);
+    let mut ir: Vec<Def<Name, B64>> = ir_serialize::deserialize(&ir_contents).expect("Failed to deserialize IR");
+
+    let config_contents = fs::read_to_string(&config_file)?;
+    hasher.input(&config_contents);
+    let isa_config: ISAConfig<B64> = ISAConfig::parse_in_dir(&config_contents, &resources, &symtab)?;
 
-    let isa_config: ISAConfig<B64> = ISAConfig::parse(&fs::read_to_string(&config_file)?, &symtab)?;
+    let cache = Cache::new(cache, &hasher.result());
 
     eprintln!("Loaded architecture in: {}ms", now.elapsed().as_millis());
 
-    let litmus_text = if req.litmus_format == "toml" {
-        Cow::Borrowed(req.litmus.as_str())
+    let litmus = if req.litmus_format == "toml" {
+        Litmus::parse(&req.litmus, &symtab, &isa_config)
     } else if req.litmus_format == "litmus" {
-        if let Some(converter_path) = matches.opt_str("litmus-convert").map(PathBuf::from) {
-            let mut converter = SandboxedCommand::new(converter_path)
-                .arg("--stdin")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()?;
-
-            let stdin = converter.stdin.as_mut().unwrap();
-            stdin.write_all(req.litmus.as_bytes())?;
-
-            let output = converter.wait_with_output()?;
-
-            if output.status.success() {
-                Cow::Owned(dbg!(String::from_utf8_lossy(&output.stdout).to_string()))
-            } else {
-                panic!(".litmus converter failed!")
-            }
-        } else {
-            panic!(".litmus file given with no converter!")
-        }
+        Litmus::parse_herd(&req.litmus, &symtab, &isa_config)
     } else {
         return Ok(Response::Error { message: format!("Unrecognised litmus file format") });
     };
 
-    let litmus = match Litmus::parse(&litmus_text, &symtab, &isa_config) {
+    let litmus = match litmus {
         Ok(litmus) => litmus,
         Err(e) => return Ok(Response::Error { message: format!("Failed to process litmus file:\n{}\n", e) }),
     };